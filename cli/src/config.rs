@@ -1,33 +1,969 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
+    fmt::Display,
     fs::File,
     io::{BufReader, Read},
+    ops::Range,
     path::PathBuf,
 };
 
 use anyhow::Result;
-use myaku::{GitRepository, MetricConfig};
+use myaku::{
+    validate_metrics, CommitHash, GitRepository, Glob, HttpsCredentials, MetricConfig,
+    MetricConfigError, Quarantine, RetryPolicy, TreeLimits,
+};
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use toml::Spanned;
+
+use crate::output::OutputConfig;
+use crate::presets;
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Config declares neither a reference nor any repositories")]
+    MissingReference,
+
+    #[error("{0} has to be set per repository in configs with several repositories")]
+    SharedOption(&'static str),
+
+    #[error("Presets are defined, but neither used by repositories nor by use_presets")]
+    PresetsWithoutRepositories,
+
+    #[error("Repository {url} uses unknown preset {preset}")]
+    UnknownPreset { url: String, preset: String },
+
+    #[error("Repository {url} is declared more than once for the same branch")]
+    DuplicateRepository { url: String },
+
+    #[error("Config declares {0} repositories, but this command works on a single one")]
+    MultipleRepositories(usize),
+
+    #[error("Commits are quarantined for unknown metric {0}")]
+    UnknownQuarantinedMetric(String),
+
+    #[error("Environment variable {0} of the HTTPS token is not set")]
+    MissingTokenVariable(String),
+
+    #[error("Only one of https_token and credential_helper can be set")]
+    ConflictingHttpsCredentials,
+
+    #[error("Config contains invalid metrics\n{}", format_diagnostics(.0))]
+    InvalidMetrics(Vec<Diagnostic>),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// Problem of a metric in a config file
+#[derive(Debug, Serialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+
+    /// Path of the metric in the config, e.g. `presets.rust.loc`
+    pub metric: String,
+
+    /// Option of the metric the problem was found in
+    pub field: &'static str,
+
+    /// Position of the option in the config file, starting at 1
+    pub line: usize,
+    pub column: usize,
+
+    /// Message of the problem and all of its causes
+    pub message: String,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+
+        write!(
+            f,
+            "{}:{}: {} in {}.{}: {}",
+            self.line, self.column, severity, self.metric, self.field, self.message
+        )
+    }
+}
+
+fn format_diagnostics(diagnostics: &[Diagnostic]) -> String {
+    diagnostics
+        .iter()
+        .map(|diagnostic| format!("  {diagnostic}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
 
+/// Options of each metric with their location in the config file
+type MetricSpans = HashMap<String, Spanned<HashMap<String, Spanned<toml::Value>>>>;
+
+#[derive(Deserialize)]
+struct ConfigSpans {
+    #[serde(default)]
+    metrics: MetricSpans,
+    #[serde(default)]
+    presets: HashMap<String, MetricSpans>,
+    #[serde(default)]
+    repositories: Vec<RepositorySpans>,
+}
+
+#[derive(Deserialize)]
+struct RepositorySpans {
+    #[serde(default)]
+    metrics: MetricSpans,
+}
+
+/// Username sent with HTTPS tokens if none is configured, accepted by GitHub and most other hosts
+const DEFAULT_HTTPS_USERNAME: &str = "x-access-token";
+
+/// Token for HTTPS remotes, either given directly or by the name of an environment variable
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum TokenSource {
+    Value(String),
+    Env { env: String },
+}
+
+impl std::fmt::Debug for TokenSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Value(_) => f.write_str("Value(<redacted>)"),
+            Self::Env { env } => f.debug_struct("Env").field("env", env).finish(),
+        }
+    }
+}
+
+impl TokenSource {
+    fn resolve(&self) -> Result<String, ConfigError> {
+        match self {
+            Self::Value(token) => Ok(token.clone()),
+            Self::Env { env } => {
+                std::env::var(env).map_err(|_| ConfigError::MissingTokenVariable(env.clone()))
+            }
+        }
+    }
+}
+
+/// Config of a single repository
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Config {
     pub reference: GitRepository,
     pub metrics: HashMap<String, MetricConfig>,
 
     pub repository_path: Option<PathBuf>,
+    pub object_store_path: Option<PathBuf>,
+    pub exclude_paths: Option<Vec<Glob>>,
     pub cache_path: Option<PathBuf>,
     pub output_path: Option<PathBuf>,
+    pub worktree_path: Option<PathBuf>,
+    pub retry_policy: Option<RetryPolicy>,
+    pub tree_limits: Option<TreeLimits>,
+
+    /// Appends the duration, cache hit ratio and scanned bytes of every run to the output
+    pub meta_metrics: Option<bool>,
+
+    /// Keeps a cache per branch, which takes values of commits shared with other branches from
+    /// their caches
+    pub isolate_branch_cache: Option<bool>,
+
+    /// Parquet metric tables reference commits by integer ids instead of their hashes, which
+    /// `commits.parquet` maps to the hashes
+    pub commit_ids: Option<bool>,
+
+    /// Format, compression, layout and auxiliary files of the output
+    pub output: Option<OutputConfig>,
+
+    /// CSV or JSON file of external events, e.g. deployments or incidents, written to the output
+    /// to correlate them with the metrics
+    pub events: Option<PathBuf>,
+
+    /// Private key in the OpenSSH format used to clone and fetch the repository
+    pub ssh_key_path: Option<PathBuf>,
+
+    /// Token used to clone and fetch the repository over HTTPS
+    pub https_token: Option<TokenSource>,
+    pub https_username: Option<String>,
+
+    /// Credential helper asked instead of the ones of the git config, in the format of git's
+    /// `credential.helper` option
+    pub credential_helper: Option<String>,
+
+    /// Commits skipped by the collector of a metric, keyed by metric name
+    #[serde(default)]
+    pub quarantine: HashMap<String, Vec<CommitHash>>,
 }
 
 impl Config {
+    /// Reads a config file declaring exactly one repository
     pub fn from_file(path: &PathBuf) -> Result<Config> {
+        Ok(ConfigFile::from_file(path)?.into_single()?)
+    }
+
+    /// Credentials for HTTPS remotes, reading the token from the environment if configured so
+    pub fn https_credentials(&self) -> Result<Option<HttpsCredentials>, ConfigError> {
+        match (&self.https_token, &self.credential_helper) {
+            (Some(_), Some(_)) => Err(ConfigError::ConflictingHttpsCredentials),
+            (Some(token), None) => Ok(Some(HttpsCredentials::Token {
+                username: self
+                    .https_username
+                    .clone()
+                    .unwrap_or(DEFAULT_HTTPS_USERNAME.to_string()),
+                token: token.resolve()?,
+            })),
+            (None, Some(helper)) => Ok(Some(HttpsCredentials::Helper(helper.clone()))),
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// Quarantined commits keyed by the collector ids of their metrics
+    pub fn resolve_quarantine(&self) -> Result<Quarantine, ConfigError> {
+        let mut quarantine = Quarantine::default();
+
+        for (metric_name, commits) in &self.quarantine {
+            let Some(metric) = self.metrics.get(metric_name) else {
+                return Err(ConfigError::UnknownQuarantinedMetric(metric_name.clone()));
+            };
+
+            let collector_id = metric.collector.id();
+
+            for commit_hash in commits {
+                quarantine.insert(collector_id.clone(), commit_hash.clone());
+            }
+        }
+
+        Ok(quarantine)
+    }
+}
+
+/// Contents of a config file, either a single repository or a list of `repositories`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ConfigFile {
+    pub reference: Option<GitRepository>,
+    #[serde(default)]
+    pub metrics: HashMap<String, MetricConfig>,
+
+    pub repository_path: Option<PathBuf>,
+    pub object_store_path: Option<PathBuf>,
+
+    /// Used by all repositories which don't set their own
+    pub exclude_paths: Option<Vec<Glob>>,
+
+    pub cache_path: Option<PathBuf>,
+    pub output_path: Option<PathBuf>,
+
+    /// Directory the temporary worktrees are created in, e.g. on a local disk while the
+    /// repository, cache and output are on a network share
+    pub worktree_path: Option<PathBuf>,
+
+    /// Used by all repositories which don't set their own
+    pub retry_policy: Option<RetryPolicy>,
+
+    /// Used by all repositories which don't set their own
+    pub tree_limits: Option<TreeLimits>,
+
+    /// Used by all repositories which don't set their own
+    pub meta_metrics: Option<bool>,
+
+    /// Used by all repositories which don't set their own
+    pub isolate_branch_cache: Option<bool>,
+
+    /// Used by all repositories which don't set their own
+    pub commit_ids: Option<bool>,
+
+    /// Used by all repositories which don't set their own
+    pub output: Option<OutputConfig>,
+
+    /// Used by all repositories which don't set their own
+    pub events: Option<PathBuf>,
+
+    /// Used by all repositories which don't set their own
+    pub ssh_key_path: Option<PathBuf>,
+
+    /// Used by all repositories which don't set their own token or credential helper
+    pub https_token: Option<TokenSource>,
+    pub https_username: Option<String>,
+    pub credential_helper: Option<String>,
+
+    #[serde(default)]
+    pub quarantine: HashMap<String, Vec<CommitHash>>,
+
+    /// Presets whose metrics are collected in configs of a single repository, its own metrics
+    /// replace those of the same name
+    #[serde(default)]
+    pub use_presets: Vec<String>,
+
+    /// Named sets of metrics shared between repositories, these take precedence over the
+    /// built-in presets of the same name
+    #[serde(default)]
+    pub presets: HashMap<String, HashMap<String, MetricConfig>>,
+
+    #[serde(default)]
+    pub repositories: Vec<RepositoryConfig>,
+
+    /// Problems of the metrics which don't prevent collecting them
+    #[serde(skip)]
+    pub warnings: Vec<Diagnostic>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RepositoryConfig {
+    #[serde(flatten)]
+    pub reference: GitRepository,
+
+    /// Presets whose metrics are collected, metrics of later presets and the repository's own
+    /// ones replace those of the same name
+    #[serde(default)]
+    pub presets: Vec<String>,
+    #[serde(default)]
+    pub metrics: HashMap<String, MetricConfig>,
+
+    pub repository_path: Option<PathBuf>,
+    pub object_store_path: Option<PathBuf>,
+    pub exclude_paths: Option<Vec<Glob>>,
+    pub cache_path: Option<PathBuf>,
+    pub output_path: Option<PathBuf>,
+    pub worktree_path: Option<PathBuf>,
+    pub retry_policy: Option<RetryPolicy>,
+    pub tree_limits: Option<TreeLimits>,
+    pub meta_metrics: Option<bool>,
+    pub isolate_branch_cache: Option<bool>,
+    pub commit_ids: Option<bool>,
+    pub output: Option<OutputConfig>,
+    pub events: Option<PathBuf>,
+    pub ssh_key_path: Option<PathBuf>,
+    pub https_token: Option<TokenSource>,
+    pub https_username: Option<String>,
+    pub credential_helper: Option<String>,
+    #[serde(default)]
+    pub quarantine: HashMap<String, Vec<CommitHash>>,
+}
+
+/// Metrics of the given presets, metrics of later presets replace those of the same name
+fn resolve_presets(
+    defined_presets: &HashMap<String, HashMap<String, MetricConfig>>,
+    url: &str,
+    presets: &[String],
+) -> Result<HashMap<String, MetricConfig>, ConfigError> {
+    let mut metrics = HashMap::new();
+
+    for preset in presets {
+        let preset_metrics = match defined_presets.get(preset) {
+            Some(preset_metrics) => preset_metrics.clone(),
+            None => presets::builtin(preset).ok_or_else(|| ConfigError::UnknownPreset {
+                url: url.to_string(),
+                preset: preset.clone(),
+            })?,
+        };
+
+        metrics.extend(preset_metrics);
+    }
+
+    Ok(metrics)
+}
+
+/// Line and column of the start of the span, both starting at 1
+fn position(source: &str, span: &Range<usize>) -> (usize, usize) {
+    let before = &source[..span.start];
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+
+    (
+        before.matches('\n').count() + 1,
+        before[line_start..].chars().count() + 1,
+    )
+}
+
+impl ConfigFile {
+    pub fn from_file(path: &PathBuf) -> Result<ConfigFile> {
         let file = File::open(path)?;
         let mut buf_reader = BufReader::new(file);
         let mut contents = String::new();
         buf_reader.read_to_string(&mut contents)?;
 
-        let config: Config = toml::from_str(&contents)?;
+        ConfigFile::parse(&contents)
+    }
+
+    /// Parses the contents of a config file, failing with all problems of its metrics if any of
+    /// them is invalid
+    pub fn parse(source: &str) -> Result<ConfigFile> {
+        let mut config: ConfigFile = toml::from_str(source)?;
+
+        let diagnostics = config.validate(source)?;
+
+        if diagnostics
+            .iter()
+            .any(|diagnostic| diagnostic.severity == Severity::Error)
+        {
+            return Err(ConfigError::InvalidMetrics(diagnostics).into());
+        }
+
+        config.warnings = diagnostics;
 
         Ok(config)
     }
+
+    /// Validates all metrics, including the ones of presets and repositories, before any
+    /// repository is cloned
+    fn validate(&self, source: &str) -> Result<Vec<Diagnostic>, toml::de::Error> {
+        let spans: ConfigSpans = toml::from_str(source)?;
+
+        let mut diagnostics = vec![];
+
+        let mut check =
+            |path: &str, metrics: &HashMap<String, MetricConfig>, spans: &MetricSpans| {
+                // Checks of single metrics, followed by the ones comparing them with each other
+                let mut errors: Vec<(String, MetricConfigError)> = metrics
+                    .iter()
+                    .flat_map(|(metric_name, metric)| {
+                        metric
+                            .validate()
+                            .into_iter()
+                            .map(|error| (metric_name.clone(), error))
+                    })
+                    .collect();
+                errors.extend(validate_metrics(metrics));
+
+                for (metric_name, error) in errors {
+                    let span = spans.get(&metric_name).map_or(0..0, |metric| {
+                        metric
+                            .get_ref()
+                            .get(error.field())
+                            .map_or(metric.span(), Spanned::span)
+                    });
+
+                    let (line, column) = position(source, &span);
+
+                    diagnostics.push(Diagnostic {
+                        severity: if error.is_warning() {
+                            Severity::Warning
+                        } else {
+                            Severity::Error
+                        },
+                        metric: format!("{path}.{metric_name}"),
+                        field: error.field(),
+                        line,
+                        column,
+                        message: format!("{:#}", anyhow::Error::from(error)),
+                    });
+                }
+            };
+
+        check("metrics", &self.metrics, &spans.metrics);
+
+        for (preset_name, metrics) in &self.presets {
+            let empty = MetricSpans::new();
+            let preset_spans = spans.presets.get(preset_name).unwrap_or(&empty);
+            check(&format!("presets.{preset_name}"), metrics, preset_spans);
+        }
+
+        for (index, repository) in self.repositories.iter().enumerate() {
+            check(
+                &format!("repositories[{index}].metrics"),
+                &repository.metrics,
+                &spans.repositories[index].metrics,
+            );
+        }
+
+        diagnostics.sort_by_key(|diagnostic| (diagnostic.line, diagnostic.column));
+
+        Ok(diagnostics)
+    }
+
+    /// One config per declared repository, with the metrics of its presets resolved
+    pub fn into_configs(self) -> Result<Vec<Config>, ConfigError> {
+        if self.repositories.is_empty() {
+            if !self.presets.is_empty() && self.use_presets.is_empty() {
+                return Err(ConfigError::PresetsWithoutRepositories);
+            }
+
+            let reference = self.reference.ok_or(ConfigError::MissingReference)?;
+
+            let mut metrics = resolve_presets(&self.presets, &reference.url, &self.use_presets)?;
+            metrics.extend(self.metrics);
+
+            return Ok(vec![Config {
+                reference,
+                metrics,
+                repository_path: self.repository_path,
+                object_store_path: self.object_store_path,
+                exclude_paths: self.exclude_paths,
+                cache_path: self.cache_path,
+                output_path: self.output_path,
+                worktree_path: self.worktree_path,
+                retry_policy: self.retry_policy,
+                tree_limits: self.tree_limits,
+                meta_metrics: self.meta_metrics,
+                isolate_branch_cache: self.isolate_branch_cache,
+                commit_ids: self.commit_ids,
+                output: self.output,
+                events: self.events,
+                ssh_key_path: self.ssh_key_path,
+                https_token: self.https_token,
+                https_username: self.https_username,
+                credential_helper: self.credential_helper,
+                quarantine: self.quarantine,
+            }]);
+        }
+
+        // Sharing these between repositories would mix up their data
+        let shared_options = [
+            ("reference", self.reference.is_some()),
+            ("metrics", !self.metrics.is_empty()),
+            ("use_presets", !self.use_presets.is_empty()),
+            ("repository_path", self.repository_path.is_some()),
+            ("object_store_path", self.object_store_path.is_some()),
+            ("cache_path", self.cache_path.is_some()),
+            ("output_path", self.output_path.is_some()),
+            ("worktree_path", self.worktree_path.is_some()),
+            ("quarantine", !self.quarantine.is_empty()),
+        ];
+
+        if let Some((option, _)) = shared_options.iter().find(|(_, is_set)| *is_set) {
+            return Err(ConfigError::SharedOption(option));
+        }
+
+        let mut seen = HashSet::new();
+
+        self.repositories
+            .into_iter()
+            .map(|repository| {
+                let url = repository.reference.url.clone();
+
+                if !seen.insert((url.clone(), repository.reference.branch.clone())) {
+                    return Err(ConfigError::DuplicateRepository { url });
+                }
+
+                let mut metrics = resolve_presets(&self.presets, &url, &repository.presets)?;
+                metrics.extend(repository.metrics);
+
+                // A token or helper of the repository replaces both of the shared ones
+                let (https_token, credential_helper) =
+                    if repository.https_token.is_some() || repository.credential_helper.is_some() {
+                        (repository.https_token, repository.credential_helper)
+                    } else {
+                        (self.https_token.clone(), self.credential_helper.clone())
+                    };
+
+                Ok(Config {
+                    reference: repository.reference,
+                    metrics,
+                    repository_path: repository.repository_path,
+                    object_store_path: repository.object_store_path,
+                    exclude_paths: repository
+                        .exclude_paths
+                        .or_else(|| self.exclude_paths.clone()),
+                    cache_path: repository.cache_path,
+                    output_path: repository.output_path,
+                    worktree_path: repository.worktree_path,
+                    retry_policy: repository.retry_policy.or(self.retry_policy),
+                    tree_limits: repository.tree_limits.or(self.tree_limits),
+                    meta_metrics: repository.meta_metrics.or(self.meta_metrics),
+                    isolate_branch_cache: repository
+                        .isolate_branch_cache
+                        .or(self.isolate_branch_cache),
+                    commit_ids: repository.commit_ids.or(self.commit_ids),
+                    output: repository.output.or_else(|| self.output.clone()),
+                    events: repository.events.or_else(|| self.events.clone()),
+                    ssh_key_path: repository
+                        .ssh_key_path
+                        .or_else(|| self.ssh_key_path.clone()),
+                    https_token,
+                    https_username: repository
+                        .https_username
+                        .or_else(|| self.https_username.clone()),
+                    credential_helper,
+                    quarantine: repository.quarantine,
+                })
+            })
+            .collect()
+    }
+
+    /// Config of the only declared repository, for commands working on a single one
+    pub fn into_single(self) -> Result<Config, ConfigError> {
+        let mut configs = self.into_configs()?;
+
+        if configs.len() > 1 {
+            return Err(ConfigError::MultipleRepositories(configs.len()));
+        }
+
+        Ok(configs.remove(0))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::output::{OutputCompression, OutputLayout, OutputType};
+    use rstest::rstest;
+
+    #[test]
+    fn test_config_corpus_parses() {
+        // The execution graphs of the corpus are snapshot tested in the library
+        let dir = PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/../lib/tests/configs"));
+
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+
+            if path.extension().is_some_and(|ext| ext == "toml") {
+                Config::from_file(&path)
+                    .unwrap_or_else(|e| panic!("{} no longer parses: {e}", path.display()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_repositories_resolve_presets() {
+        let config: ConfigFile = toml::from_str(
+            r#"
+            exclude_paths = ["vendor/**"]
+
+            [presets.rust.loc]
+            collector = "total-loc"
+            frequency = "per-commit"
+
+            [presets.rust.deps]
+            collector = "total-cargo-deps"
+            frequency = "per-commit"
+
+            [[repositories]]
+            url = "https://github.com/user/a"
+            presets = ["rust"]
+            exclude_paths = []
+
+            [repositories.metrics.loc]
+            collector = "total-loc"
+            frequency = "yearly"
+
+            [[repositories]]
+            url = "https://github.com/user/b"
+            branch = "main"
+            presets = ["rust"]
+            "#,
+        )
+        .unwrap();
+
+        let configs = config.into_configs().unwrap();
+
+        assert_eq!(configs.len(), 2);
+
+        // Own metrics replace the ones of the presets
+        assert_eq!(configs[0].metrics.len(), 2);
+        assert_eq!(
+            configs[0].metrics["loc"].frequency,
+            myaku::Frequency::Yearly
+        );
+        assert_eq!(configs[0].exclude_paths.as_ref().unwrap().len(), 0);
+
+        assert_eq!(configs[1].reference.branch.as_deref(), Some("main"));
+        assert_eq!(
+            configs[1].metrics["loc"].frequency,
+            myaku::Frequency::PerCommit
+        );
+        assert_eq!(configs[1].exclude_paths.as_ref().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_builtin_presets() {
+        let config: ConfigFile = toml::from_str(
+            r#"
+            use_presets = ["rust-basics"]
+
+            [reference]
+            url = "https://github.com/user/a"
+
+            [metrics.loc]
+            collector = "total-loc"
+            frequency = "per-commit"
+            "#,
+        )
+        .unwrap();
+
+        let config = config.into_single().unwrap();
+
+        assert!(config.metrics.contains_key("deps"));
+        assert_eq!(config.metrics["loc"].frequency, myaku::Frequency::PerCommit);
+
+        // Presets of the config shadow the built-in ones
+        let config: ConfigFile = toml::from_str(
+            r#"
+            [presets.rust-basics.deps]
+            collector = "total-cargo-deps"
+            frequency = "yearly"
+
+            [[repositories]]
+            url = "https://github.com/user/a"
+            presets = ["rust-basics"]
+            "#,
+        )
+        .unwrap();
+
+        let configs = config.into_configs().unwrap();
+
+        assert_eq!(configs[0].metrics.len(), 1);
+    }
+
+    #[rstest]
+    #[case(
+        "[[repositories]]\nurl = \"a\"\npresets = [\"rust\"]",
+        "Repository a uses unknown preset rust"
+    )]
+    #[case(
+        "cache_path = \"cache\"\n[[repositories]]\nurl = \"a\"",
+        "cache_path has to be set per repository in configs with several repositories"
+    )]
+    #[case(
+        "worktree_path = \"/tmp/worktree\"\n[[repositories]]\nurl = \"a\"",
+        "worktree_path has to be set per repository in configs with several repositories"
+    )]
+    #[case(
+        "[[repositories]]\nurl = \"a\"\n[[repositories]]\nurl = \"a\"",
+        "Repository a is declared more than once for the same branch"
+    )]
+    #[case(
+        "[reference]\nurl = \"a\"\n[presets.rust]",
+        "Presets are defined, but neither used by repositories nor by use_presets"
+    )]
+    #[case(
+        "use_presets = [\"rust\"]\n[reference]\nurl = \"a\"",
+        "Repository a uses unknown preset rust"
+    )]
+    #[case(
+        "[quarantine]\nloc = []\n[[repositories]]\nurl = \"a\"",
+        "quarantine has to be set per repository in configs with several repositories"
+    )]
+    #[case("", "Config declares neither a reference nor any repositories")]
+    fn test_invalid_repositories(#[case] config: &str, #[case] expected: &str) {
+        let config: ConfigFile = toml::from_str(config).unwrap();
+
+        assert_eq!(config.into_configs().unwrap_err().to_string(), expected);
+    }
+
+    #[test]
+    fn test_single_repository_commands_reject_several_repositories() {
+        let config: ConfigFile =
+            toml::from_str("[[repositories]]\nurl = \"a\"\n[[repositories]]\nurl = \"b\"").unwrap();
+
+        assert!(matches!(
+            config.into_single(),
+            Err(ConfigError::MultipleRepositories(2))
+        ));
+    }
+
+    #[test]
+    fn test_quarantine_is_keyed_by_collector() {
+        let config: ConfigFile = toml::from_str(
+            r#"
+            [reference]
+            url = "https://github.com/user/a"
+
+            [metrics.loc]
+            collector = "total-loc"
+            frequency = "per-commit"
+
+            [quarantine]
+            loc = ["1f8e488"]
+            "#,
+        )
+        .unwrap();
+
+        let mut config = config.into_single().unwrap();
+        let quarantine = config.resolve_quarantine().unwrap();
+
+        assert!(quarantine.contains(
+            &config.metrics["loc"].collector.id(),
+            &CommitHash("1f8e488".to_string())
+        ));
+
+        config.quarantine.insert("deps".to_string(), vec![]);
+        assert!(matches!(
+            config.resolve_quarantine(),
+            Err(ConfigError::UnknownQuarantinedMetric(metric)) if metric == "deps"
+        ));
+    }
+
+    #[test]
+    fn test_https_token_is_shared_and_read_from_environment() {
+        let config: ConfigFile = toml::from_str(
+            r#"
+            https_token = { env = "MYAKU_TEST_HTTPS_TOKEN" }
+
+            [[repositories]]
+            url = "https://github.com/user/a"
+
+            [[repositories]]
+            url = "https://github.com/user/b"
+            https_token = "ghp_b"
+            https_username = "user"
+            "#,
+        )
+        .unwrap();
+
+        let configs = config.into_configs().unwrap();
+
+        std::env::remove_var("MYAKU_TEST_HTTPS_TOKEN");
+        assert!(matches!(
+            configs[0].https_credentials(),
+            Err(ConfigError::MissingTokenVariable(env)) if env == "MYAKU_TEST_HTTPS_TOKEN"
+        ));
+
+        std::env::set_var("MYAKU_TEST_HTTPS_TOKEN", "ghp_a");
+        let credentials = configs[0].https_credentials().unwrap();
+        assert!(matches!(
+            credentials,
+            Some(HttpsCredentials::Token { username, token })
+                if username == "x-access-token" && token == "ghp_a"
+        ));
+
+        let credentials = configs[1].https_credentials().unwrap();
+        assert!(matches!(
+            credentials,
+            Some(HttpsCredentials::Token { username, token })
+                if username == "user" && token == "ghp_b"
+        ));
+        assert!(!format!("{:?}", configs[1]).contains("ghp_b"));
+    }
+
+    #[test]
+    fn test_credential_helper_replaces_shared_token() {
+        let config: ConfigFile = toml::from_str(
+            r#"
+            https_token = "ghp_a"
+
+            [[repositories]]
+            url = "https://github.com/user/a"
+
+            [[repositories]]
+            url = "https://git.example.com/user/b"
+            credential_helper = "store --file /run/secrets/git-credentials"
+            "#,
+        )
+        .unwrap();
+
+        let configs = config.into_configs().unwrap();
+
+        assert!(matches!(
+            configs[0].https_credentials().unwrap(),
+            Some(HttpsCredentials::Token { token, .. }) if token == "ghp_a"
+        ));
+        assert!(matches!(
+            configs[1].https_credentials().unwrap(),
+            Some(HttpsCredentials::Helper(helper))
+                if helper == "store --file /run/secrets/git-credentials"
+        ));
+
+        let config: ConfigFile = toml::from_str(
+            r#"
+            https_token = "ghp_a"
+            credential_helper = "store"
+
+            [reference]
+            url = "https://github.com/user/a"
+            "#,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            config.into_single().unwrap().https_credentials(),
+            Err(ConfigError::ConflictingHttpsCredentials)
+        ));
+    }
+
+    #[test]
+    fn test_output_section_is_shared() {
+        let config: ConfigFile = toml::from_str(
+            r#"
+            [output]
+            compression = "zstd"
+            layout = "long"
+            tags = false
+
+            [[repositories]]
+            url = "https://github.com/user/a"
+
+            [[repositories]]
+            url = "https://github.com/user/b"
+            output = { format = "json", manifest = true }
+            "#,
+        )
+        .unwrap();
+
+        let configs = config.into_configs().unwrap();
+
+        assert_eq!(
+            configs[0].output,
+            Some(OutputConfig {
+                compression: Some(OutputCompression::Zstd),
+                layout: Some(OutputLayout::Long),
+                tags: Some(false),
+                ..OutputConfig::default()
+            })
+        );
+        assert_eq!(
+            configs[1].output,
+            Some(OutputConfig {
+                format: Some(OutputType::Json),
+                manifest: Some(true),
+                ..OutputConfig::default()
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_reports_all_invalid_metrics() {
+        let source = r#"
+[presets.rust.todo]
+collector = "pattern-occurences"
+pattern = "todo("
+frequency = "monthly"
+
+[[repositories]]
+url = "https://github.com/user/a"
+presets = ["rust"]
+
+[repositories.metrics.warnings]
+collector = "build-warnings"
+command = []
+frequency = "monthly"
+"#;
+
+        let error = ConfigFile::parse(source).unwrap_err();
+        let Some(ConfigError::InvalidMetrics(diagnostics)) = error.downcast_ref::<ConfigError>()
+        else {
+            panic!("unexpected error {error}");
+        };
+
+        let locations: Vec<_> = diagnostics
+            .iter()
+            .map(|d| (d.line, d.column, d.metric.as_str(), d.field))
+            .collect();
+
+        assert_eq!(
+            locations,
+            [
+                (4, 11, "presets.rust.todo", "pattern"),
+                (13, 11, "repositories[0].metrics.warnings", "command"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_keeps_warnings() {
+        let config = ConfigFile::parse(
+            "[reference]\nurl = \"a\"\n[metrics.churn]\ncollector = \"cumulative-diff-stat\"\nfrequency = \"yearly\"",
+        )
+        .unwrap();
+
+        assert_eq!(config.warnings.len(), 1);
+        assert_eq!(config.warnings[0].severity, Severity::Warning);
+        assert_eq!(
+            config.warnings[0].to_string(),
+            "5:13: warning in metrics.churn.frequency: Totals only include the sampled commits unless collected per-commit"
+        );
+    }
 }
@@ -0,0 +1,143 @@
+use std::path::PathBuf;
+
+use myaku::CollectionProcessError;
+use serde::Serialize;
+
+use crate::config::{ConfigError, Diagnostic};
+use crate::ssh::SshKeyError;
+
+/// Failure of a command, written to stderr with `--error-format json`
+#[derive(Debug, Serialize)]
+pub struct ErrorReport<'a> {
+    /// Stable, machine-readable identifier of the kind of failure
+    pub code: &'static str,
+
+    pub message: String,
+
+    /// Messages of the errors which caused this one, outermost first
+    pub causes: Vec<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collector_id: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit_hash: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+
+    /// All problems of the metrics of an invalid config
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    pub diagnostics: &'a [Diagnostic],
+}
+
+impl<'a> From<&'a anyhow::Error> for ErrorReport<'a> {
+    fn from(error: &'a anyhow::Error) -> Self {
+        let mut report = ErrorReport {
+            code: "internal",
+            message: error.to_string(),
+            causes: error.chain().skip(1).map(ToString::to_string).collect(),
+            collector_id: None,
+            commit_hash: None,
+            path: None,
+            diagnostics: &[],
+        };
+
+        if error.downcast_ref::<toml::de::Error>().is_some() {
+            report.code = "invalid_config";
+        }
+
+        if let Some(error) = error.downcast_ref::<ConfigError>() {
+            report.code = "invalid_config";
+
+            if let ConfigError::InvalidMetrics(diagnostics) = error {
+                report.diagnostics = diagnostics;
+            }
+        }
+
+        if let Some(error) = error.downcast_ref::<SshKeyError>() {
+            report.code = "invalid_ssh_key";
+            report.path = Some(error.path().to_path_buf());
+        }
+
+        let Some(error) = error.downcast_ref::<CollectionProcessError>() else {
+            return report;
+        };
+
+        report.code = error.code();
+
+        match error {
+            CollectionProcessError::Task {
+                collector_id,
+                commit_hash,
+                ..
+            } => {
+                report.collector_id = Some(collector_id.clone());
+                report.commit_hash = Some(commit_hash.to_string());
+            }
+            CollectionProcessError::UnknownTask {
+                collector_config,
+                commit_hash,
+            }
+            | CollectionProcessError::MissingDependency {
+                collector_config,
+                commit_hash,
+            } => {
+                report.collector_id = Some(collector_config.id());
+                report.commit_hash = Some(commit_hash.to_string());
+            }
+            CollectionProcessError::HeadMoved { actual, .. } => {
+                report.commit_hash = Some(actual.to_string());
+            }
+            CollectionProcessError::IO { path, .. } => {
+                report.path = Some(path.clone());
+            }
+            CollectionProcessError::Cache(e) => {
+                report.path = Some(e.path().to_path_buf());
+            }
+            _ => {}
+        }
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use myaku::CollectionProcessError;
+    use serde_json::json;
+
+    use super::ErrorReport;
+
+    #[test]
+    fn test_error_report_carries_code_context_and_causes() {
+        let error = anyhow::Error::from(CollectionProcessError::IO {
+            path: PathBuf::from(".myaku/worktree/example"),
+            source: std::io::Error::other("disk full"),
+        });
+
+        let report = serde_json::to_value(ErrorReport::from(&error)).unwrap();
+
+        assert_eq!(
+            report,
+            json!({
+                "code": "io",
+                "message": "Could not create directory .myaku/worktree/example",
+                "causes": ["disk full"],
+                "path": ".myaku/worktree/example",
+            })
+        );
+    }
+
+    #[test]
+    fn test_error_report_of_unknown_error() {
+        let error = anyhow::anyhow!("Invalid state");
+
+        let report = ErrorReport::from(&error);
+
+        assert_eq!(report.code, "internal");
+        assert!(report.causes.is_empty());
+    }
+}
@@ -1,35 +1,51 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs::OpenOptions;
 use std::io::{self, Read};
 use std::os::fd::AsRawFd;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
-use std::{io::Write, time::Duration};
+use std::{
+    io::Write,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
-use anyhow::{Ok, Result};
+use anyhow::{Context, Ok, Result};
 use clap::{Parser, Subcommand};
 use console::{colors_enabled, style, Term};
-use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use indicatif::{HumanBytes, ProgressBar, ProgressDrawTarget, ProgressStyle};
 use myaku::{
-    Cache, CollectorConfig, CollectorValue, FileCache, GitRepository, Initial, MetricConfig,
-    RepositoryHandle,
+    attribute_events, read_events, BranchCache, Cache, CollectorConfig, CollectorValue, CommitHash,
+    FileCache, Frequency, GitRepository, Initial, MetricConfig, Quarantine, RepositoryHandle,
+    RetryPolicy, ThresholdLevel, TreeLimits,
+};
+use output::{
+    Catalog, Dataset, JsonOutput, Manifest, OutputObj, OutputType, ParquetOutput, Retention,
+    RunMetrics, OUTPUT_ROOT,
 };
-use output::{JsonOutput, OutputObj, ParquetOutput};
 use polars::prelude::*;
 use serde::Serialize;
+use ssh_key::PrivateKey;
 use tracing::debug;
 use tracing_subscriber::fmt::format::FmtSpan;
 use tracing_subscriber::EnvFilter;
 use tracing_subscriber::{prelude::*, registry::Registry};
 
-use crate::config::Config;
+use crate::config::{Config, ConfigFile};
+use crate::error::ErrorReport;
 use crate::output::Output;
 
 mod config;
+mod error;
 mod output;
+mod presets;
+mod quarantine;
+mod ssh;
 mod util;
+#[cfg(feature = "grpc")]
+mod worker;
 
 // TODO: Add debug / verbosity flag
 
@@ -47,14 +63,18 @@ struct Cli {
     #[arg(long)]
     /// Enable tracing
     trace: bool,
+
+    #[arg(long, default_value_t, value_enum, global = true)]
+    /// Format of errors written to stderr
+    error_format: ErrorFormat,
 }
 
-#[derive(Clone, Debug, Default, clap::ValueEnum, Serialize)]
+#[derive(Clone, Copy, Debug, Default, clap::ValueEnum, Serialize)]
 #[serde(rename_all = "kebab-case")]
-enum OutputType {
-    Json,
+enum ErrorFormat {
     #[default]
-    Parquet,
+    Human,
+    Json,
 }
 
 #[derive(Subcommand)]
@@ -82,8 +102,22 @@ enum Commands {
         #[arg(short, long, action = clap::ArgAction::SetTrue, requires = "offline")]
         ignore_mismatched_repo_url: bool,
 
-        #[arg(long, default_value_t, value_enum)]
-        output: OutputType,
+        /// Skip tasks failing in this run in later runs
+        #[arg(long, action = clap::ArgAction::SetTrue)]
+        quarantine_failing: bool,
+
+        /// Private key used to clone and fetch all repositories, instead of the ones of the config
+        #[arg(long, value_name = "FILE")]
+        ssh_key: Option<PathBuf>,
+
+        /// File listing the commits to collect, one hash per line, instead of sampling them by
+        /// the frequencies of the metrics
+        #[arg(long, value_name = "FILE")]
+        commits: Option<PathBuf>,
+
+        /// Format of the output, instead of the one of the `[output]` section of the config
+        #[arg(long, value_enum)]
+        output: Option<OutputType>,
     },
     /// Request a singular metric
     Query {
@@ -118,6 +152,67 @@ enum Commands {
         #[arg(short, long, action = clap::ArgAction::SetTrue, requires = "offline")]
         ignore_mismatched_repo_url: bool,
     },
+    /// Collect a single task of the execution graph and write it to the cache
+    RunTask {
+        #[arg(short, long, value_name = "FILE")]
+        config: PathBuf,
+
+        #[arg(long, value_name = "JSON")]
+        /// Collector config of the task, e.g. '{"collector":"total-loc"}'
+        collector: String,
+
+        #[arg(long, value_name = "HASH")]
+        /// Full hash of the commit to collect
+        commit: String,
+
+        #[arg(short, long, action = clap::ArgAction::SetTrue)]
+        offline: bool,
+
+        #[arg(short, long, action = clap::ArgAction::SetTrue, requires = "offline")]
+        ignore_mismatched_repo_url: bool,
+    },
+    /// Compare the execution graphs of two configs of the same repository, e.g. to review the cost
+    /// of a config change before merging it
+    PlanDiff {
+        /// Config before the change
+        #[arg(long, value_name = "FILE")]
+        old: PathBuf,
+
+        /// Config after the change, whose repository settings decide the commits of both graphs
+        #[arg(long, value_name = "FILE")]
+        new: PathBuf,
+
+        #[arg(short, long, action = clap::ArgAction::SetTrue)]
+        offline: bool,
+
+        #[arg(short, long, action = clap::ArgAction::SetTrue, requires = "offline")]
+        ignore_mismatched_repo_url: bool,
+    },
+    /// Rewrite the parquet outputs of a config, dropping values which are no longer needed and
+    /// merging their files
+    CompactOutput {
+        #[arg(short, long, value_name = "FILE")]
+        config: PathBuf,
+
+        /// Drop values of commits older than this many days
+        #[arg(long, value_name = "DAYS")]
+        max_age: Option<u32>,
+
+        /// Keep only one value per period, e.g. `weekly`
+        #[arg(long, value_parser = parse_frequency)]
+        frequency: Option<Frequency>,
+    },
+    /// Run as a remote collection worker serving gRPC requests
+    #[cfg(feature = "grpc")]
+    Worker {
+        #[arg(long, default_value = "127.0.0.1:50051")]
+        listen: std::net::SocketAddr,
+    },
+}
+
+fn parse_frequency(value: &str) -> Result<Frequency, String> {
+    serde_json::from_value(serde_json::Value::String(value.to_string()))
+        .map_err(|_| format!("unknown frequency {value}"))
 }
 
 #[derive(Debug)]
@@ -152,15 +247,12 @@ impl Write for EmptyTermTarget {
     }
 }
 
-#[allow(clippy::too_many_lines)]
-fn collect(
+/// Clones or fetches the reference repository, depending on the state of the reference directory
+fn refresh_repository(
     mut term: &Term,
-    repository_name: &str,
     process: myaku::CollectionProcess,
-    output: Option<&OutputObj>,
     offline: bool,
-    disable_cache: bool,
-) -> Result<myaku::PostCollection> {
+) -> Result<myaku::IdleWithoutCommits> {
     macro_rules! info {
         ($($arg:tt)*) => {{
             tracing::info!($($arg)*);
@@ -171,11 +263,6 @@ fn collect(
         }};
     }
 
-    info!(
-        "Collecting metrics for {}",
-        style(&repository_name).underlined()
-    )?;
-
     let process = match process {
         myaku::CollectionProcess::IdleWithoutCommits(process) => {
             info!("Repository already exists in reference directory")?;
@@ -192,7 +279,9 @@ fn collect(
             }
 
             info!("Refreshing repository")?;
-            let process = process.fetch()?;
+            let pb = clone_progress_bar(term);
+            let process = process.fetch(|progress| report_clone_progress(&pb, progress))?;
+            pb.finish_and_clear();
             term.clear_last_lines(1)?;
             info!("Refreshed repository successfully")?;
             process
@@ -211,59 +300,310 @@ fn collect(
                 &process.repository_path.display()
             )?;
 
-            let pb = ProgressBar::with_draw_target(
-                Some(1000),
-                ProgressDrawTarget::term(term.clone(), 20),
-            );
-            let style =
-                ProgressStyle::with_template(" {spinner} [{elapsed_precise}] [{bar:40}] {msg}")
-                    .expect("Failed to create progress style")
-                    .progress_chars("#>-");
-            pb.set_style(style);
-            pb.enable_steady_tick(Duration::from_millis(100));
-            pb.set_message("Initializing");
-            let process = process.clone(|progress| match progress {
-                myaku::CloneProgress::EnumeratingObjects => {
-                    pb.set_message("Enumerating objects");
-                }
-                myaku::CloneProgress::CountingObjects { finished, total } => {
-                    pb.set_message(format!("Counting objects [{finished}, {total}]"));
-                    pb.set_length(*total as u64);
-                    pb.set_position(*finished as u64);
-                }
-                myaku::CloneProgress::CompressingObjects { finished, total } => {
-                    pb.set_message(format!("Compressing objects [{finished}, {total}]",));
-                    pb.set_length(*total as u64);
-                    pb.set_position(*finished as u64);
-                }
-                myaku::CloneProgress::ReceivingObjects { finished, total } => {
-                    pb.set_message(format!("Receiving objects [{finished}, {total}]"));
-                    pb.set_length(*total as u64);
-                    pb.set_position(*finished as u64);
-                }
-                myaku::CloneProgress::ResolvingDeltas { finished, total } => {
-                    pb.set_message(format!("Resolving deltas [{finished}, {total}]",));
-                    pb.set_length(*total as u64);
-                    pb.set_position(*finished as u64);
-                }
-            })?;
+            let pb = clone_progress_bar(term);
+            let process = process.clone(|progress| report_clone_progress(&pb, progress))?;
             pb.finish_and_clear();
             term.clear_last_lines(1)?;
-            info!(
-                "Successfully cloned repository into {}",
-                &process.get_repository_path().display()
-            )?;
+            if let Some(summary) = &process.clone_summary {
+                info!(
+                    "Successfully cloned repository into {} ({} objects, {}) in {:.2}s",
+                    &process.get_repository_path().display(),
+                    summary.objects,
+                    HumanBytes(summary.bytes),
+                    summary.duration.as_secs_f64()
+                )?;
+            }
 
             process
         }
         _ => return Err(anyhow::anyhow!("Invalid state")),
     };
 
+    Ok(process)
+}
+
+/// Progress bar showing the phases of a clone or fetch below the last line of the terminal
+fn clone_progress_bar(term: &Term) -> ProgressBar {
+    let pb = ProgressBar::with_draw_target(Some(1000), ProgressDrawTarget::term(term.clone(), 20));
+    let style = ProgressStyle::with_template(" {spinner} [{elapsed_precise}] [{bar:40}] {msg}")
+        .expect("Failed to create progress style")
+        .progress_chars("#>-");
+    pb.set_style(style);
+    pb.enable_steady_tick(Duration::from_millis(100));
+    pb.set_message("Initializing");
+    pb
+}
+
+fn report_clone_progress(pb: &ProgressBar, progress: &myaku::CloneProgress) {
+    match progress {
+        myaku::CloneProgress::EnumeratingObjects => {
+            pb.set_message("Enumerating objects");
+        }
+        myaku::CloneProgress::CountingObjects { finished, total } => {
+            pb.set_message(format!("Counting objects [{finished}, {total}]"));
+            pb.set_length(*total as u64);
+            pb.set_position(*finished as u64);
+        }
+        myaku::CloneProgress::CompressingObjects { finished, total } => {
+            pb.set_message(format!("Compressing objects [{finished}, {total}]",));
+            pb.set_length(*total as u64);
+            pb.set_position(*finished as u64);
+        }
+        myaku::CloneProgress::ReceivingObjects { finished, total } => {
+            pb.set_message(format!("Receiving objects [{finished}, {total}]"));
+            pb.set_length(*total as u64);
+            pb.set_position(*finished as u64);
+        }
+        myaku::CloneProgress::ResolvingDeltas { finished, total } => {
+            pb.set_message(format!("Resolving deltas [{finished}, {total}]",));
+            pb.set_length(*total as u64);
+            pb.set_position(*finished as u64);
+        }
+    }
+}
+
+/// Number of tasks, cached tasks and tasks with a checkout per collector, labeled with the names
+/// of the metrics collecting it or its kind for collectors only other collectors depend on
+fn summarize_planned_tasks(
+    tasks: &[myaku::PlannedTask],
+    metrics: &HashMap<String, MetricConfig>,
+) -> BTreeMap<String, (usize, usize, usize)> {
+    let mut summary: BTreeMap<String, (usize, usize, usize)> = BTreeMap::new();
+
+    for task in tasks {
+        let mut metric_names: Vec<&str> = metrics
+            .iter()
+            .filter(|(_, metric)| metric.collector == task.collector_config)
+            .map(|(metric_name, _)| metric_name.as_str())
+            .collect();
+        metric_names.sort_unstable();
+
+        let label = if metric_names.is_empty() {
+            let kind = serde_json::to_value(&task.collector_config)
+                .ok()
+                .and_then(|value| value["collector"].as_str().map(str::to_string))
+                .unwrap_or_else(|| task.collector_config.id());
+            format!("{kind} (dependency)")
+        } else {
+            metric_names.join(", ")
+        };
+
+        let entry = summary.entry(label).or_default();
+        entry.0 += 1;
+        entry.1 += usize::from(task.cached);
+        entry.2 += usize::from(task.requires_checkout);
+    }
+
+    summary
+}
+
+#[derive(Clone, Debug, Default)]
+struct TaskCounts {
+    metrics: usize,
+    finished_metrics: usize,
+    precomputed: usize,
+    fresh: usize,
+    reused: usize,
+    retried: usize,
+    quarantined: usize,
+    too_large: usize,
+    failed: usize,
+    failed_by_code: BTreeMap<&'static str, usize>,
+    failed_tasks: Vec<(String, CommitHash)>,
+    task_duration: Duration,
+    max_task_duration: Duration,
+    scanned_bytes: u64,
+}
+
+impl TaskCounts {
+    fn to_run_metrics(&self, started_at: SystemTime, head: &CommitHash) -> RunMetrics {
+        let hits = self.reused + self.precomputed;
+        let total = hits + self.fresh + self.failed;
+
+        RunMetrics {
+            started_at: started_at
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            head: head.0.clone(),
+            duration_ms: as_millis(started_at.elapsed().unwrap_or_default()),
+            metric_count: self.metrics as u64,
+            fresh_task_count: self.fresh as u64,
+            reused_task_count: self.reused as u64,
+            precomputed_task_count: self.precomputed as u64,
+            failed_task_count: self.failed as u64,
+            quarantined_task_count: self.quarantined as u64,
+            skipped_task_count: self.too_large as u64,
+            #[allow(clippy::cast_precision_loss)]
+            cache_hit_ratio: (total > 0).then(|| hits as f64 / total as f64),
+            task_duration_ms: as_millis(self.task_duration),
+            max_task_duration_ms: as_millis(self.max_task_duration),
+            scanned_bytes: self.scanned_bytes,
+        }
+    }
+}
+
+fn as_millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+/// Runs a collection step sending progress to the given channel, while every progress event is
+/// handled on a separate thread. Returns once all events were handled.
+fn with_progress<T>(
+    mut on_progress: impl FnMut(myaku::ExecutionProgressCallbackState) + Send + 'static,
+    step: impl FnOnce(std::sync::mpsc::Sender<myaku::ExecutionProgressCallbackState>) -> T,
+) -> Result<T> {
+    let (tx, rx) = std::sync::mpsc::channel::<myaku::ExecutionProgressCallbackState>();
+
+    let reader = std::thread::spawn(move || {
+        while let Result::Ok(state) = rx.recv() {
+            on_progress(state);
+        }
+    });
+
+    let result = step(tx);
+
+    reader
+        .join()
+        .map_err(|_| anyhow::anyhow!("Cannot join reader"))?;
+
+    Ok(result)
+}
+
+/// Collection of a single repository set up from its config, the same way for the `collect`
+/// command and the workers
+struct RepositoryCollection {
+    initial: Initial,
+    worktree_dir: PathBuf,
+    cache_directory: PathBuf,
+
+    /// Number of values taken from the caches of other branches, if branch caches are isolated
+    shared_value_count: Option<Arc<AtomicU64>>,
+}
+
+impl RepositoryCollection {
+    /// Resolves the reference directory, credentials, cache, worktree and quarantine of a
+    /// repository. Tasks quarantined by previous runs are kept next to the values cached by them.
+    fn prepare(
+        config: Config,
+        repository_name: &str,
+        ssh_key: Option<PrivateKey>,
+        disable_cache: bool,
+        offline: bool,
+        measure_scanned_bytes: bool,
+    ) -> Result<Self> {
+        let reference_dir = get_repository_path(repository_name, Some(&config))?;
+
+        let mut quarantine = config.resolve_quarantine()?;
+
+        let https_credentials = if offline {
+            None
+        } else {
+            config.https_credentials()?
+        };
+
+        let cache_directory = get_cache_path(&config, repository_name);
+        let (cache, shared_value_count) = open_cache(&config, &cache_directory)?;
+
+        let worktree_dir = config
+            .worktree_path
+            .unwrap_or(PathBuf::from(format!(".myaku/worktree/{repository_name}")));
+
+        quarantine.extend(quarantine::load(&Self::quarantine_path(&cache_directory))?);
+
+        let initial = Initial {
+            metrics: config.metrics,
+
+            reference: config.reference,
+
+            repository_path: reference_dir,
+            cache,
+
+            ssh_key,
+            https_credentials,
+
+            object_store_path: config.object_store_path,
+            exclude_paths: config.exclude_paths,
+
+            disable_cache,
+
+            offline,
+
+            retry_policy: config.retry_policy.unwrap_or_default(),
+
+            quarantine,
+
+            tree_limits: config.tree_limits.unwrap_or_default(),
+
+            measure_scanned_bytes,
+        };
+
+        Ok(Self {
+            initial,
+            worktree_dir,
+            cache_directory,
+            shared_value_count,
+        })
+    }
+
+    /// File of the tasks quarantined by previous runs in a cache directory
+    fn quarantine_path(cache_directory: &Path) -> PathBuf {
+        cache_directory.join("quarantine.json")
+    }
+}
+
+#[allow(clippy::too_many_lines, clippy::too_many_arguments)]
+fn collect(
+    mut term: &Term,
+    repository_name: &str,
+    process: myaku::CollectionProcess,
+    worktree_dir: PathBuf,
+    output: Option<&OutputObj>,
+    offline: bool,
+    disable_cache: bool,
+    // Failing tasks are added to this quarantine file, so later runs skip them
+    quarantine_path: Option<&Path>,
+) -> Result<(myaku::PostCollection, RunMetrics)> {
+    macro_rules! info {
+        ($($arg:tt)*) => {{
+            tracing::info!($($arg)*);
+            writeln!(
+                &mut term,
+                $($arg)*
+            )
+        }};
+    }
+
+    let started_at = SystemTime::now();
+
+    info!(
+        "Collecting metrics for {}",
+        style(&repository_name).underlined()
+    )?;
+
+    let process = refresh_repository(term, process, offline)?;
+
     info!("Collecting commit information")?;
     let process = process.collect_commits()?;
     term.clear_last_lines(1)?;
     info!("Collected commit information")?;
 
+    let summary = process.preflight_summary(true);
+    if let Some((first, last)) = summary.date_range {
+        info!(
+            "Found {} commits from {} to {}",
+            summary.commit_count,
+            first.format("%Y-%m-%d"),
+            last.format("%Y-%m-%d")
+        )?;
+    }
+    for (metric_name, count) in &summary.sampled_commits {
+        info!(
+            "  {} ({}): {} commits",
+            metric_name, process.metrics[metric_name].frequency, count
+        )?;
+    }
+
     if let Some(output) = output {
         if !disable_cache {
             output.load()?;
@@ -271,7 +611,18 @@ fn collect(
             // Fill storage from previous output
             for commit in &process.commits {
                 for (metric_name, metric_config) in &process.metrics {
+                    if !metric_config.is_cached()
+                        || !process.branches.contains(metric_config, &commit.id)
+                    {
+                        continue;
+                    }
+
                     if let Some(value) = output.get_metric(metric_name, &commit.id)? {
+                        // Commits skipped by earlier runs are tried again with the current limits
+                        if matches!(value, CollectorValue::Skipped(_)) {
+                            continue;
+                        }
+
                         process
                             .storage
                             .insert((metric_config.collector.clone(), commit.id.clone()), value);
@@ -293,7 +644,7 @@ fn collect(
     info!("Built execution graph")?;
 
     info!("Collecting data points")?;
-    let (process, fresh_task_count, reused_task_count, metric_count, duration_in_secs) = {
+    let (process, counts, quarantined, duration_in_secs) = {
         let pb = ProgressBar::with_draw_target(Some(1), ProgressDrawTarget::term(term.clone(), 20));
         let style = ProgressStyle::with_template(" {spinner} [{elapsed_precise}] [{bar:40}] {msg}")
             .expect("Failed to create progress style")
@@ -301,122 +652,479 @@ fn collect(
         pb.set_style(style);
         pb.enable_steady_tick(Duration::from_millis(100));
 
-        let (tx, rx) = std::sync::mpsc::channel::<myaku::ExecutionProgressCallbackState>();
+        let counts = Arc::new(Mutex::new(TaskCounts::default()));
 
-        let metric_count = Arc::new(Mutex::new(0_usize));
-        let fresh_task_count = Arc::new(Mutex::new(0_usize));
-        let reused_task_count = Arc::new(Mutex::new(0_usize));
+        // Progress events only carry collector ids, failures are reported with the metric name
+        let collector_names: HashMap<String, String> = process
+            .metrics
+            .iter()
+            .map(|(metric_name, metric)| (metric.collector.id(), metric_name.clone()))
+            .collect();
 
         let movable_pb = pb.clone();
-        let movable_metric_count = metric_count.clone();
-        let movable_fresh_task_count = fresh_task_count.clone();
-        let movable_reused_task_count = reused_task_count.clone();
-
-        let reader = std::thread::spawn(move || {
-            let pb = movable_pb;
-            let metric_count = movable_metric_count;
-            let fresh_task_count = movable_fresh_task_count;
-            let reused_task_count = movable_reused_task_count;
-
-            while let Result::Ok(state) = rx.recv() {
-                match state {
-                    myaku::ExecutionProgressCallbackState::Initial {
-                        task_count,
-                        metric_count: mcount,
-                    } => {
-                        let mut metric_count_lock =
-                            metric_count.lock().expect("Failed to lock metric count");
-                        *metric_count_lock = mcount;
-                        drop(metric_count_lock);
-                        pb.set_length(task_count as u64);
-                    }
-                    myaku::ExecutionProgressCallbackState::Reused {
-                        collector_config,
+        let movable_counts = counts.clone();
+
+        let on_progress = move |state| {
+            let pb = &movable_pb;
+            let mut counts = movable_counts.lock().expect("Failed to lock task counts");
+
+            match state {
+                myaku::ExecutionProgressCallbackState::Initial {
+                    metric_count,
+                    task_count,
+                    precomputed_task_count,
+                    quarantined_task_count,
+                    skipped_task_count,
+                } => {
+                    counts.metrics = metric_count;
+                    counts.precomputed = precomputed_task_count;
+                    counts.quarantined = quarantined_task_count;
+                    counts.too_large = skipped_task_count;
+                    pb.set_length(task_count as u64);
+                    return;
+                }
+                myaku::ExecutionProgressCallbackState::Reused {
+                    collector_id,
+                    commit_hash,
+                } => {
+                    debug!("Found data from previous run for collector {} and commit {}, skipping collection", collector_id, commit_hash);
+                    counts.reused += 1;
+                    pb.inc(1);
+                }
+                myaku::ExecutionProgressCallbackState::New {
+                    duration,
+                    scanned_bytes,
+                    ..
+                } => {
+                    counts.fresh += 1;
+                    counts.task_duration += duration;
+                    counts.max_task_duration = counts.max_task_duration.max(duration);
+                    counts.scanned_bytes += scanned_bytes.unwrap_or(0);
+                    pb.inc(1);
+                }
+                myaku::ExecutionProgressCallbackState::TaskRetried {
+                    collector_id,
+                    commit_hash,
+                    attempt,
+                    code,
+                    error,
+                } => {
+                    tracing::warn!(
+                        "Attempt {} to collect {} for commit {} failed ({}), retrying: {}",
+                        attempt,
+                        collector_names.get(&collector_id).unwrap_or(&collector_id),
                         commit_hash,
-                    } => {
-                        debug!("Found data from previous run for collector {:?} and commit {}, skipping collection", collector_config, commit_hash);
-                        let mut reused_task_count_lock = reused_task_count
-                            .lock()
-                            .expect("Failed to lock reused task count");
-                        *reused_task_count_lock += 1;
-                        drop(reused_task_count_lock);
-                    }
-                    myaku::ExecutionProgressCallbackState::New {
-                        collector_config: _,
-                        commit_hash: _,
-                    } => {
-                        let mut fresh_task_count_lock = fresh_task_count
-                            .lock()
-                            .expect("Failed to lock fresh task count");
-                        *fresh_task_count_lock += 1;
-                        drop(fresh_task_count_lock);
-                    }
-                    myaku::ExecutionProgressCallbackState::Finished => {}
+                        code,
+                        error
+                    );
+                    counts.retried += 1;
                 }
-
-                let reused_task_count_lock = reused_task_count
-                    .lock()
-                    .expect("Failed to lock reused task count");
-                let reused_task_count = *reused_task_count_lock;
-                drop(reused_task_count_lock);
-
-                let fresh_task_count_lock = fresh_task_count
-                    .lock()
-                    .expect("Failed to lock fresh task count");
-                let fresh_task_count = *fresh_task_count_lock;
-                drop(fresh_task_count_lock);
-
-                pb.inc(1);
-                pb.set_message(format!(
-                    "{} collected ({} reused)",
-                    fresh_task_count + reused_task_count,
-                    reused_task_count
-                ));
+                myaku::ExecutionProgressCallbackState::TaskFailed {
+                    collector_id,
+                    commit_hash,
+                    code,
+                    error,
+                } => {
+                    tracing::error!(
+                        "Failed to collect {} for commit {} ({}): {}",
+                        collector_names.get(&collector_id).unwrap_or(&collector_id),
+                        commit_hash,
+                        code,
+                        error
+                    );
+                    counts.failed += 1;
+                    *counts.failed_by_code.entry(code).or_default() += 1;
+                    counts.failed_tasks.push((collector_id, commit_hash));
+                    pb.inc(1);
+                }
+                myaku::ExecutionProgressCallbackState::MetricFinished {
+                    metric_name,
+                    latest_value,
+                } => {
+                    debug!(
+                        "Finished metric {} with latest value {:?}",
+                        metric_name, latest_value
+                    );
+                    counts.finished_metrics += 1;
+                }
+                _ => return,
             }
-        });
-
-        let worktree_dir = PathBuf::from(format!(".myaku/worktree/{repository_name}"));
 
-        let process = process.collect_metrics(Some(tx), worktree_dir)?;
+            pb.set_message(format!(
+                "{} collected ({} reused, {} failed), {}/{} metrics finished",
+                counts.fresh + counts.reused,
+                counts.reused,
+                counts.failed,
+                counts.finished_metrics,
+                counts.metrics
+            ));
+        };
 
-        reader
-            .join()
-            .map_err(|_| anyhow::anyhow!("Cannot join reader"))?;
+        let result = with_progress(on_progress, |tx| {
+            process.collect_metrics(Some(tx), worktree_dir)
+        })?;
 
         pb.finish_and_clear();
-        let metric_count = *metric_count.lock().expect("Failed to lock metric count");
-        let reused_task_count = *reused_task_count
-            .lock()
-            .expect("Failed to lock reused task count");
-        let fresh_task_count = *fresh_task_count
-            .lock()
-            .expect("Failed to lock fresh task count");
+        let counts = counts.lock().expect("Failed to lock task counts").clone();
+
+        let quarantined = match quarantine_path {
+            Some(quarantine_path) => quarantine::record(quarantine_path, &counts.failed_tasks)?,
+            None => 0,
+        };
+
+        let process = match result {
+            Result::Ok(process) => process,
+            Err(e) => {
+                term.clear_last_lines(1)?;
+
+                if counts.retried > 0 {
+                    info!("Retried {} failed attempts", counts.retried)?;
+                }
+
+                if quarantined > 0 {
+                    info!("Quarantined {} failing tasks", quarantined)?;
+                }
+
+                if counts.failed > 0 {
+                    let failures = counts
+                        .failed_by_code
+                        .iter()
+                        .map(|(code, count)| format!("{count} {code}"))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+
+                    info!(
+                        "Failed to collect {} data points ({})",
+                        counts.failed, failures
+                    )?;
+                }
+
+                return Err(e.into());
+            }
+        };
 
         let duration_in_secs = pb.elapsed().as_secs_f32();
 
-        (
-            process,
-            fresh_task_count,
-            reused_task_count,
-            metric_count,
-            duration_in_secs,
-        )
+        (process, counts, quarantined, duration_in_secs)
     };
     term.clear_last_lines(1)?;
     info!(
-        "Collected {} data points for {} metrics in {:.2}s ({} reused)",
-        fresh_task_count + reused_task_count,
-        metric_count,
+        "Collected {} data points for {} metrics in {:.2}s ({} reused, {} precomputed)",
+        counts.fresh + counts.reused + counts.precomputed,
+        counts.metrics,
         duration_in_secs,
-        reused_task_count
+        counts.reused,
+        counts.precomputed
     )?;
 
+    if counts.retried > 0 {
+        info!("Retried {} failed attempts", counts.retried)?;
+    }
+
+    if counts.quarantined > 0 {
+        info!("Skipped {} quarantined tasks", counts.quarantined)?;
+    }
+
+    if counts.too_large > 0 {
+        info!(
+            "Skipped {} tasks of commits exceeding the tree limits",
+            counts.too_large
+        )?;
+    }
+
+    if quarantined > 0 {
+        info!("Quarantined {} failing tasks", quarantined)?;
+    }
+
     info!("Writing data to cache")?;
     let process = process.write_to_cache()?;
     term.clear_last_lines(1)?;
     info!("Wrote data to cache")?;
 
-    Ok(process)
+    let run = counts.to_run_metrics(started_at, &process.head);
+
+    Ok((process, run))
+}
+
+/// Collects all metrics of a single repository, returns false if it was not collected
+#[allow(
+    clippy::too_many_lines,
+    clippy::too_many_arguments,
+    clippy::fn_params_excessive_bools
+)]
+fn collect_repository(
+    mut term: &Term,
+    config: Config,
+    ssh_key: Option<PrivateKey>,
+    output_type: Option<OutputType>,
+    disable_cache: bool,
+    offline: bool,
+    ignore_mismatched_repo_url: bool,
+    quarantine_failing: bool,
+    should_render_colors: bool,
+) -> Result<bool> {
+    macro_rules! error {
+        ($($arg:tt)*) => {{
+            tracing::error!($($arg)*);
+
+            if !should_render_colors {
+                writeln!(&mut term, $($arg)*)
+            } else {
+                writeln!(&mut term, "{}", style(format!($($arg)*)).red().bold())
+            }
+        }};
+    }
+
+    macro_rules! info {
+        ($($arg:tt)*) => {{
+            tracing::info!($($arg)*);
+            writeln!(
+                &mut term,
+                $($arg)*
+            )
+        }};
+    }
+
+    if config.metrics.is_empty() {
+        error!(
+            "No metrics configured for {}, please add some to your config file",
+            config.reference.url
+        )?;
+        return Ok(false);
+    }
+
+    let Some(repository_name) = util::get_repository_name_from_url(&config.reference.url) else {
+        error!(
+            "Cannot determine repository name from URL: {}",
+            config.reference.url
+        )?;
+        return Ok(false);
+    };
+
+    // Read before collecting, so a broken file doesn't waste a run
+    let events = config.events.as_deref().map(read_events).transpose()?;
+
+    let Some(output_dir) = get_output_path(&config) else {
+        error!(
+            "Cannot determine output directory from URL: {}",
+            config.reference.url
+        )?;
+        return Ok(false);
+    };
+
+    // Recorded in the catalog, after the config was handed to the collection
+    let url = config.reference.url.clone();
+    let branch = config.reference.branch.clone();
+    let metric_names: BTreeSet<String> = config.metrics.keys().cloned().collect();
+
+    let output_config = config.output.clone().unwrap_or_default();
+    let output_type = output_type.or(output_config.format).unwrap_or_default();
+    let commit_ids = config.commit_ids.unwrap_or_default();
+
+    let mut output: OutputObj = match output_type {
+        OutputType::Json => OutputObj::Json(
+            JsonOutput::new(&output_dir).with_transforms(output_config.transforms.clone()),
+        ),
+        OutputType::Parquet => OutputObj::Parquet(
+            ParquetOutput::new(&output_dir)
+                .with_commit_ids(commit_ids)
+                .with_compression(output_config.compression.unwrap_or_default())
+                .with_layout(output_config.layout.unwrap_or_default())
+                .with_transforms(output_config.transforms.clone()),
+        ),
+    };
+
+    let meta_metrics = config.meta_metrics.unwrap_or_default();
+
+    let RepositoryCollection {
+        initial,
+        worktree_dir,
+        cache_directory,
+        shared_value_count,
+    } = RepositoryCollection::prepare(
+        config,
+        &repository_name,
+        ssh_key,
+        disable_cache,
+        offline,
+        meta_metrics,
+    )?;
+    let quarantine_path = RepositoryCollection::quarantine_path(&cache_directory);
+
+    let process = initial.initialize(ignore_mismatched_repo_url)?;
+
+    let (process, run) = collect(
+        term,
+        &repository_name,
+        process,
+        worktree_dir,
+        Some(&output),
+        offline,
+        disable_cache,
+        quarantine_failing.then_some(quarantine_path.as_path()),
+    )?;
+
+    info!("Writing data to output")?;
+    let write_commits = output_config.commits.unwrap_or(true);
+    if write_commits {
+        output.set_commits(&process.commits)?;
+    }
+    let write_tags = output_config.tags.unwrap_or(true) && process.tags.is_some();
+    if let Some(tags) = process.tags.as_ref().filter(|_| write_tags) {
+        output.set_commit_tags(tags)?;
+    }
+    let write_events = events.is_some();
+    if let Some(mut events) = events {
+        attribute_events(&mut events, &process.commits);
+        output.set_events(&events)?;
+    }
+    for (metric_name, values) in process.metric_values() {
+        for (commit, value) in &values {
+            output.set_metric(metric_name, commit, value)?;
+        }
+    }
+    for metric_name in process.metrics.keys() {
+        output.set_commits_represented(metric_name, &process.commits_represented(metric_name))?;
+    }
+    let write_commit_samples = output_config.commit_samples.unwrap_or(true);
+    if write_commit_samples {
+        output.set_commit_samples(&process.commit_samples())?;
+    }
+    let projections = process.campaign_projections()?;
+    let write_campaigns = !projections.is_empty();
+    if write_campaigns {
+        output.set_campaigns(&projections)?;
+    }
+    if meta_metrics {
+        output.add_run(&run)?;
+    }
+    output.flush()?;
+
+    if output_config.manifest.unwrap_or_default() {
+        let is_parquet = output_type == OutputType::Parquet;
+
+        Manifest {
+            format: output_type,
+            compression: is_parquet.then(|| output_config.compression.unwrap_or_default()),
+            layout: is_parquet.then(|| output_config.layout.unwrap_or_default()),
+            commit_ids: is_parquet && commit_ids,
+            commits: write_commits,
+            tags: write_tags,
+            commit_samples: write_commit_samples,
+            events: write_events,
+            campaigns: write_campaigns,
+            metrics: metric_names.iter().cloned().collect(),
+            head: process.head.0.clone(),
+        }
+        .write(&output_dir)?;
+    }
+
+    let shared_value_count =
+        shared_value_count.map(|shared_value_count| shared_value_count.load(Ordering::Relaxed));
+    if let Some(shared_value_count) = shared_value_count {
+        info!(
+            "Reused {} values of commits shared with other branches",
+            shared_value_count
+        )?;
+    }
+
+    let dataset = Dataset {
+        url,
+        branch,
+        resolved_branch: process.branches.default.clone(),
+        path: output_dir,
+        format: output_type.name().to_string(),
+        metrics: metric_names.into_iter().collect(),
+        head: process.head.0.clone(),
+        updated_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+        cache_path: shared_value_count.is_some().then_some(cache_directory),
+        shared_value_count,
+    };
+    Catalog::record(Path::new(OUTPUT_ROOT), dataset)?;
+
+    term.clear_last_lines(1)?;
+    info!("Wrote data to output")?;
+
+    for projection in &projections {
+        info!("Campaign: {}", projection)?;
+    }
+
+    // Checked after writing the output, so the values exceeding a threshold can be inspected
+    let violations = process.threshold_violations()?;
+    for violation in &violations {
+        match violation.level {
+            ThresholdLevel::Warn => info!("Warning: {}", violation)?,
+            ThresholdLevel::Fail => error!("Threshold exceeded: {}", violation)?,
+        }
+    }
+    drop(process);
+
+    Ok(!violations
+        .iter()
+        .any(|violation| violation.level == ThresholdLevel::Fail))
+}
+
+/// Output directory of a repository, namespaced by its URL and branch below [`OUTPUT_ROOT`]
+/// unless configured
+fn get_output_path(config: &Config) -> Option<PathBuf> {
+    match &config.output_path {
+        Some(path) => Some(path.clone()),
+        None => output::namespace(&config.reference.url, config.reference.branch.as_deref())
+            .map(|namespace| Path::new(OUTPUT_ROOT).join(namespace)),
+    }
+}
+
+/// Directory of the caches of the branches of a repository with isolated branch caches
+const BRANCH_CACHE_DIR: &str = "branches";
+
+/// Cache directory of a repository, with a directory per branch below it if its branch caches
+/// are isolated
+fn get_cache_path(config: &Config, repository_name: &str) -> PathBuf {
+    let root = config
+        .cache_path
+        .clone()
+        .unwrap_or(PathBuf::from(format!(".myaku/cache/{repository_name}")));
+
+    if config.isolate_branch_cache.unwrap_or_default() {
+        root.join(BRANCH_CACHE_DIR)
+            .join(output::branch_directory(config.reference.branch.as_deref()))
+    } else {
+        root
+    }
+}
+
+/// Cache of a repository and the number of values it took from the caches of other branches
+type OpenedCache = (Box<dyn Cache>, Option<Arc<AtomicU64>>);
+
+/// Opens the cache in the given directory. Isolated branch caches also look up the values of the
+/// caches of the other branches, counting the ones they take over.
+fn open_cache(config: &Config, cache_directory: &Path) -> Result<OpenedCache> {
+    let own = FileCache::new(cache_directory);
+    if !config.isolate_branch_cache.unwrap_or_default() {
+        return Ok((Box::new(own), None));
+    }
+
+    let mut others = vec![];
+    if let Some(branches_directory) = cache_directory.parent().filter(|path| path.is_dir()) {
+        for entry in std::fs::read_dir(branches_directory)? {
+            let path = entry?.path();
+            if path.is_dir() && path != cache_directory {
+                others.push(path);
+            }
+        }
+    }
+    others.sort();
+
+    let cache = BranchCache::new(
+        own,
+        others.iter().map(|path| FileCache::new(path)).collect(),
+    );
+    let shared_value_count = cache.shared_value_count();
+
+    Ok((Box::new(cache), Some(shared_value_count)))
 }
 
 fn get_repository_path(repository_name: &str, config: Option<&Config>) -> Result<PathBuf> {
@@ -434,7 +1142,19 @@ fn get_repository_path(repository_name: &str, config: Option<&Config>) -> Result
 #[tracing::instrument]
 fn main() -> Result<ExitCode> {
     let cli = Cli::parse();
+    let error_format = cli.error_format;
 
+    match run(&cli) {
+        Err(e) if matches!(error_format, ErrorFormat::Json) => {
+            eprintln!("{}", serde_json::to_string(&ErrorReport::from(&e))?);
+            Ok(ExitCode::from(1))
+        }
+        result => result,
+    }
+}
+
+#[allow(clippy::too_many_lines)]
+fn run(cli: &Cli) -> Result<ExitCode> {
     let should_render_fancy_output = !cli.trace;
     let should_render_colors = colors_enabled() && !cli.no_color;
 
@@ -500,94 +1220,87 @@ fn main() -> Result<ExitCode> {
             output: output_type,
             offline,
             ignore_mismatched_repo_url,
+            quarantine_failing,
+            ssh_key: ssh_key_path,
+            commits: commits_path,
         }) => {
-            let config = config::Config::from_file(config_path)?;
+            let config_file = ConfigFile::from_file(config_path)?;
+
+            let commits = commits_path
+                .as_ref()
+                .map(|path| {
+                    std::fs::read_to_string(path)
+                        .with_context(|| format!("Could not read {}", path.display()))
+                })
+                .transpose()?
+                .map(|contents| util::parse_commits(&contents));
 
             info!(
                 "Loaded config from {}",
                 style(&config_path.display()).underlined()
             )?;
 
-            if config.metrics.is_empty() {
-                error!("No metrics configured, please add some to your config file")?;
-                return Ok(ExitCode::from(1));
+            for warning in &config_file.warnings {
+                info!("{}:{}", config_path.display(), warning)?;
             }
 
-            let Some(repository_name) = util::get_repository_name_from_url(&config.reference.url)
-            else {
-                error!(
-                    "Cannot determine repository name from URL: {}",
-                    config.reference.url
-                )?;
-                return Ok(ExitCode::from(1));
-            };
-
-            let reference_dir = get_repository_path(&repository_name, Some(&config))?;
+            let configs = config_file.into_configs()?;
 
-            let output_dir = config
-                .output_path
-                .unwrap_or(PathBuf::from(format!(".myaku/output/{repository_name}")));
+            let repository_count = configs.len();
+            let mut failed_count = 0;
 
-            let mut output: OutputObj = match output_type {
-                OutputType::Json => OutputObj::Json(JsonOutput::new(&output_dir)),
-                OutputType::Parquet => OutputObj::Parquet(ParquetOutput::new(&output_dir)),
-            };
+            // Repositories usually share a key, so its passphrase is only asked for once
+            let mut ssh_keys: HashMap<PathBuf, PrivateKey> = HashMap::new();
 
-            let cache_directory = config
-                .cache_path
-                .unwrap_or(PathBuf::from(format!(".myaku/cache/{repository_name}")));
+            for mut config in configs {
+                let url = config.reference.url.clone();
 
-            let cache = FileCache::new(&cache_directory);
-            let cache: Box<dyn Cache> = Box::new(cache);
-
-            let process = Initial {
-                metrics: config.metrics,
-
-                reference: config.reference,
-
-                repository_path: reference_dir.clone(),
-                cache,
-
-                ssh_key: None,
-
-                disable_cache: *disable_cache,
+                if let Some(commits) = &commits {
+                    config.reference.commits = Some(commits.clone());
+                }
 
-                offline: *offline,
+                let ssh_key = match ssh_key_path.as_ref().or(config.ssh_key_path.as_ref()) {
+                    Some(path) if !*offline => {
+                        if !ssh_keys.contains_key(path) {
+                            ssh_keys.insert(path.clone(), ssh::load(path, Some(&term))?);
+                        }
+                        ssh_keys.get(path).cloned()
+                    }
+                    _ => None,
+                };
+
+                match collect_repository(
+                    &term,
+                    config,
+                    ssh_key,
+                    *output_type,
+                    *disable_cache,
+                    *offline,
+                    *ignore_mismatched_repo_url,
+                    *quarantine_failing,
+                    should_render_colors,
+                ) {
+                    Result::Ok(true) => {}
+                    Result::Ok(false) => failed_count += 1,
+                    // Repositories of the same config are collected independently of each other
+                    Err(e) if repository_count > 1 => {
+                        error!("Failed to collect {}: {:#}", url, e)?;
+                        failed_count += 1;
+                    }
+                    Err(e) => return Err(e),
+                }
             }
-            .initialize(*ignore_mismatched_repo_url)?;
-
-            let process = collect(
-                &term,
-                &repository_name,
-                process,
-                Some(&output),
-                *offline,
-                *disable_cache,
-            )?;
 
-            info!("Writing data to output")?;
-            output.set_commits(&process.commits)?;
-            if let Some(tags) = &process.tags {
-                output.set_commit_tags(tags)?;
-            }
-            for e in &process.storage {
-                let (collector, commit) = e.key();
-                let value = e.value();
-                let metric_names = process
-                    .metrics
-                    .iter()
-                    .filter(|(_, metric_config)| &metric_config.collector == collector)
-                    .map(|(metric_name, _)| metric_name)
-                    .collect::<Vec<&String>>();
-
-                for metric_name in metric_names {
-                    output.set_metric(metric_name, commit, value)?;
+            if failed_count > 0 {
+                if repository_count > 1 {
+                    error!(
+                        "Failed to collect {} of {} repositories",
+                        failed_count, repository_count
+                    )?;
                 }
+
+                return Ok(ExitCode::from(1));
             }
-            output.flush()?;
-            term.clear_last_lines(1)?;
-            info!("Wrote data to output")?;
-            drop(process);
         }
         Some(Commands::Query {
             query,
@@ -605,6 +1318,20 @@ fn main() -> Result<ExitCode> {
                     let reference = GitRepository {
                         url: url.clone(),
                         branch: repository_branch.clone(),
+                        since: None,
+                        until: None,
+                        max_commits: None,
+                        include_authors: None,
+                        exclude_authors: None,
+                        merge_commits: None,
+                        commits: None,
+                        from: None,
+                        to: None,
+                        paths: None,
+                        first_parent: None,
+                        clone_depth: None,
+                        partial_clone: None,
+                        submodules: None,
                     };
 
                     // TODO: Check if the path exists and is a git repository matching the URL and branch
@@ -615,6 +1342,20 @@ fn main() -> Result<ExitCode> {
                     let reference = GitRepository {
                         url: url.clone(),
                         branch: repository_branch.clone(),
+                        since: None,
+                        until: None,
+                        max_commits: None,
+                        include_authors: None,
+                        exclude_authors: None,
+                        merge_commits: None,
+                        commits: None,
+                        from: None,
+                        to: None,
+                        paths: None,
+                        first_parent: None,
+                        clone_depth: None,
+                        partial_clone: None,
+                        submodules: None,
                     };
 
                     (reference, None)
@@ -627,6 +1368,20 @@ fn main() -> Result<ExitCode> {
                     let reference = GitRepository {
                         url: remote_url,
                         branch: None,
+                        since: None,
+                        until: None,
+                        max_commits: None,
+                        include_authors: None,
+                        exclude_authors: None,
+                        merge_commits: None,
+                        commits: None,
+                        from: None,
+                        to: None,
+                        paths: None,
+                        first_parent: None,
+                        clone_depth: None,
+                        partial_clone: None,
+                        submodules: None,
                     };
 
                     (reference, Some(path.clone()))
@@ -662,8 +1417,17 @@ fn main() -> Result<ExitCode> {
                     metrics.insert(
                         "total-loc-over-time".to_string(),
                         MetricConfig {
-                            collector: myaku::CollectorConfig::TotalLoc,
+                            collector: myaku::CollectorConfig::TotalLoc {
+                                scope: Box::default(),
+                            },
                             frequency: myaku::Frequency::PerCommit,
+                            branch: None,
+                            cache: None,
+                            thresholds: None,
+                            campaign: None,
+                            align_to_calendar: None,
+                            phase_offset: None,
+                            max_concurrency: None,
                         },
                     );
                 }
@@ -674,8 +1438,17 @@ fn main() -> Result<ExitCode> {
                             collector: myaku::CollectorConfig::TotalPatternOccurences {
                                 pattern: pattern.clone(),
                                 files: None,
+                                redact: None,
+                                scope: Box::default(),
                             },
                             frequency: myaku::Frequency::PerCommit,
+                            branch: None,
+                            cache: None,
+                            thresholds: None,
+                            campaign: None,
+                            align_to_calendar: None,
+                            phase_offset: None,
+                            max_concurrency: None,
                         },
                     );
                 }
@@ -690,20 +1463,34 @@ fn main() -> Result<ExitCode> {
                 cache,
 
                 ssh_key: None,
+                https_credentials: None,
+
+                object_store_path: None,
+                exclude_paths: None,
 
                 disable_cache: *disable_cache,
 
                 offline: *offline,
+
+                retry_policy: RetryPolicy::default(),
+
+                quarantine: Quarantine::default(),
+
+                tree_limits: TreeLimits::default(),
+
+                measure_scanned_bytes: false,
             }
             .initialize(*ignore_mismatched_repo_url)?;
 
-            let process = collect(
+            let (process, _) = collect(
                 &term,
                 &repository_name,
                 process,
+                PathBuf::from(format!(".myaku/worktree/{repository_name}")),
                 None,
                 *offline,
                 *disable_cache,
+                None,
             )?;
 
             let mut commit_hashes = vec![];
@@ -716,9 +1503,12 @@ fn main() -> Result<ExitCode> {
                     for commit in &process.commits {
                         commit_hashes.push(commit.id.0.clone());
                         commit_dates.push(commit.time.timestamp());
-                        let loc_value = process
-                            .storage
-                            .get(&(CollectorConfig::TotalLoc, commit.id.clone()));
+                        let loc_value = process.storage.get(&(
+                            CollectorConfig::TotalLoc {
+                                scope: Box::default(),
+                            },
+                            commit.id.clone(),
+                        ));
 
                         let Some(loc_value) = loc_value else {
                             error!("Missing LOC value for commit {}", commit.id)?;
@@ -755,6 +1545,8 @@ fn main() -> Result<ExitCode> {
                             CollectorConfig::TotalPatternOccurences {
                                 pattern: pattern.clone(),
                                 files: None,
+                                redact: None,
+                                scope: Box::default(),
                             },
                             commit.id.clone(),
                         ));
@@ -799,6 +1591,241 @@ fn main() -> Result<ExitCode> {
             term.clear_last_lines(1)?;
             info!("Wrote output to {}", file.display())?;
         }
+        Some(Commands::RunTask {
+            config: config_path,
+            collector,
+            commit,
+            offline,
+            ignore_mismatched_repo_url,
+        }) => {
+            let config = config::Config::from_file(config_path)?;
+
+            let collector: CollectorConfig = serde_json::from_str(collector)?;
+            let commit = CommitHash(commit.clone());
+
+            let Some(repository_name) = util::get_repository_name_from_url(&config.reference.url)
+            else {
+                error!(
+                    "Cannot determine repository name from URL: {}",
+                    config.reference.url
+                )?;
+                return Ok(ExitCode::from(1));
+            };
+
+            let reference_dir = get_repository_path(&repository_name, Some(&config))?;
+
+            let https_credentials = if *offline {
+                None
+            } else {
+                config.https_credentials()?
+            };
+
+            let cache_directory = get_cache_path(&config, &repository_name);
+            let (cache, _) = open_cache(&config, &cache_directory)?;
+
+            let worktree_dir = config
+                .worktree_path
+                .unwrap_or(PathBuf::from(format!(".myaku/worktree/{repository_name}")));
+
+            let process = Initial {
+                metrics: config.metrics,
+
+                reference: config.reference,
+
+                repository_path: reference_dir.clone(),
+                cache,
+
+                ssh_key: match &config.ssh_key_path {
+                    Some(path) if !*offline => Some(ssh::load(path, Some(&term))?),
+                    _ => None,
+                },
+                https_credentials,
+
+                object_store_path: config.object_store_path,
+                exclude_paths: config.exclude_paths,
+
+                disable_cache: false,
+
+                offline: *offline,
+
+                retry_policy: config.retry_policy.unwrap_or_default(),
+
+                // The task is requested explicitly, so it is collected even if it is quarantined
+                // or its commit exceeds the tree limits
+                quarantine: Quarantine::default(),
+
+                tree_limits: TreeLimits::default(),
+
+                measure_scanned_bytes: false,
+            }
+            .initialize(*ignore_mismatched_repo_url)?;
+
+            let process = refresh_repository(&term, process, *offline)?;
+
+            let process = process.collect_commits()?.prepare_for_collection(true)?;
+
+            info!("Collecting {:?} at commit {}", collector, commit)?;
+            let process = process.collect_task(&collector, &commit, &worktree_dir)?;
+            term.clear_last_lines(1)?;
+            info!("Collected {:?} at commit {}", collector, commit)?;
+            drop(process);
+        }
+        Some(Commands::PlanDiff {
+            old: old_path,
+            new: new_path,
+            offline,
+            ignore_mismatched_repo_url,
+        }) => {
+            let old_config = config::Config::from_file(old_path)?;
+            let config = config::Config::from_file(new_path)?;
+
+            let Some(repository_name) = util::get_repository_name_from_url(&config.reference.url)
+            else {
+                error!(
+                    "Cannot determine repository name from URL: {}",
+                    config.reference.url
+                )?;
+                return Ok(ExitCode::from(1));
+            };
+
+            let reference_dir = get_repository_path(&repository_name, Some(&config))?;
+
+            let https_credentials = if *offline {
+                None
+            } else {
+                config.https_credentials()?
+            };
+
+            let cache_directory = get_cache_path(&config, &repository_name);
+            let (cache, _) = open_cache(&config, &cache_directory)?;
+
+            let old_metrics = old_config.metrics;
+            let new_metrics = config.metrics.clone();
+
+            let process = Initial {
+                metrics: config.metrics,
+
+                reference: config.reference,
+
+                repository_path: reference_dir,
+                cache,
+
+                ssh_key: match &config.ssh_key_path {
+                    Some(path) if !*offline => Some(ssh::load(path, Some(&term))?),
+                    _ => None,
+                },
+                https_credentials,
+
+                object_store_path: config.object_store_path,
+                exclude_paths: config.exclude_paths,
+
+                disable_cache: false,
+
+                offline: *offline,
+
+                retry_policy: config.retry_policy.unwrap_or_default(),
+                quarantine: Quarantine::default(),
+                tree_limits: TreeLimits::default(),
+
+                measure_scanned_bytes: false,
+            }
+            .initialize(*ignore_mismatched_repo_url)?;
+
+            let process = refresh_repository(&term, process, *offline)?;
+
+            info!("Collecting commit information")?;
+            let process = process.collect_commits()?;
+            term.clear_last_lines(1)?;
+
+            info!("Building execution graphs")?;
+            let old_plan = process.execution_plan(&old_metrics, true)?;
+            let new_plan = process.execution_plan(&new_metrics, true)?;
+            term.clear_last_lines(1)?;
+
+            let diff = old_plan.diff(&new_plan);
+
+            info!(
+                "Execution graph of {} at {} commits: {} tasks before, {} after",
+                style(&repository_name).underlined(),
+                process.commits.len(),
+                old_plan.tasks.len(),
+                new_plan.tasks.len()
+            )?;
+
+            for (sign, tasks, metrics) in [
+                ("+", &diff.added, &new_metrics),
+                ("-", &diff.removed, &old_metrics),
+            ] {
+                for (label, (count, cached, checkouts)) in summarize_planned_tasks(tasks, metrics) {
+                    info!(
+                        "  {} {}: {} tasks, {} cached, {} with a checkout",
+                        sign, label, count, cached, checkouts
+                    )?;
+                }
+            }
+
+            let uncached = diff.uncached_added().count();
+            let checkouts = diff
+                .uncached_added()
+                .filter(|task| task.requires_checkout)
+                .count();
+            info!(
+                "Estimated extra work: {} tasks to collect, {} of them with a checkout; {} tasks removed",
+                uncached,
+                checkouts,
+                diff.removed.len()
+            )?;
+        }
+        Some(Commands::CompactOutput {
+            config: config_path,
+            max_age,
+            frequency,
+        }) => {
+            let configs = ConfigFile::from_file(config_path)?.into_configs()?;
+
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            let retention = Retention {
+                max_age: max_age.map(|days| i64::from(days) * 24 * 60 * 60),
+                frequency: frequency.clone(),
+                now: i64::try_from(now)?,
+            };
+
+            for config in configs {
+                let Some(output_dir) = get_output_path(&config) else {
+                    error!(
+                        "Cannot determine output directory from URL: {}",
+                        config.reference.url
+                    )?;
+                    return Ok(ExitCode::from(1));
+                };
+
+                info!("Compacting {}", output_dir.display())?;
+                let compacted = output::compact(&output_dir, &retention)?;
+                term.clear_last_lines(1)?;
+
+                for file in &compacted {
+                    info!(
+                        "Compacted {} from {} to {} rows{}",
+                        file.path.display(),
+                        file.rows_before,
+                        file.rows_after,
+                        if file.merged_files > 0 {
+                            format!(", merged {} files", file.merged_files)
+                        } else {
+                            String::new()
+                        }
+                    )?;
+                }
+            }
+        }
+        #[cfg(feature = "grpc")]
+        Some(Commands::Worker { listen }) => {
+            info!("Starting worker on {}", listen)?;
+            worker::serve(*listen)?;
+        }
         None => {}
     }
 
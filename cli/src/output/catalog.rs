@@ -0,0 +1,183 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::util;
+
+/// Directory the outputs of all repositories are written to, unless they set an output path
+pub const OUTPUT_ROOT: &str = ".myaku/output";
+
+/// Index of the outputs below [`OUTPUT_ROOT`]
+pub const CATALOG_FILE: &str = "catalog.json";
+
+/// Directory name of the outputs of a repository without a configured branch
+const DEFAULT_BRANCH_DIR: &str = "HEAD";
+
+#[derive(Error, Debug)]
+pub enum CatalogError {
+    #[error("IO error: {0}")]
+    IO(#[from] std::io::Error),
+
+    #[error("Could not parse catalog {path}")]
+    Parse {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    #[error("Serde JSON error: {0}")]
+    SerdeJson(#[from] serde_json::Error),
+}
+
+/// Output of a single branch of a repository
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Dataset {
+    pub url: String,
+
+    /// Branch configured for the repository, missing if its default branch is collected
+    pub branch: Option<String>,
+
+    /// Branch which was collected, the default branch of the remote if none is configured
+    pub resolved_branch: String,
+
+    /// Directory of the output, relative to the catalog if it is written below it
+    pub path: PathBuf,
+
+    pub format: String,
+    pub metrics: Vec<String>,
+
+    /// Commit the branch pointed to when the output was written
+    pub head: String,
+
+    /// Time of the last write in seconds since the unix epoch
+    pub updated_at: u64,
+
+    /// Absolute path of the cache of the branch, if caches are isolated per branch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_path: Option<PathBuf>,
+
+    /// Values the last collection took from the caches of other branches
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shared_value_count: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct Catalog {
+    pub datasets: Vec<Dataset>,
+}
+
+impl Catalog {
+    /// Reads the catalog in the given root, which is empty if none was written yet
+    pub fn load(root: &Path) -> Result<Self, CatalogError> {
+        let path = root.join(CATALOG_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        serde_json::from_str(&fs::read_to_string(&path)?)
+            .map_err(|source| CatalogError::Parse { path, source })
+    }
+
+    /// Writes the catalog next to itself first, so readers never see a partially written one
+    pub fn save(&self, root: &Path) -> Result<(), CatalogError> {
+        fs::create_dir_all(root)?;
+
+        let path = root.join(CATALOG_FILE);
+        let temporary_path = path.with_extension("json.tmp");
+        fs::write(&temporary_path, serde_json::to_string_pretty(self)?)?;
+        fs::rename(temporary_path, path)?;
+
+        Ok(())
+    }
+
+    /// Adds a dataset, replacing the one of the same repository and branch
+    pub fn upsert(&mut self, dataset: Dataset) {
+        self.datasets
+            .retain(|other| other.url != dataset.url || other.branch != dataset.branch);
+        self.datasets.push(dataset);
+        self.datasets.sort_by(|a, b| a.path.cmp(&b.path));
+    }
+
+    /// Records a dataset in the catalog of the given root. Outputs outside of the root are
+    /// recorded with their absolute path.
+    pub fn record(root: &Path, mut dataset: Dataset) -> Result<(), CatalogError> {
+        dataset.path = match dataset.path.strip_prefix(root) {
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => std::path::absolute(&dataset.path)?,
+        };
+        dataset.cache_path = dataset.cache_path.map(std::path::absolute).transpose()?;
+
+        let mut catalog = Self::load(root)?;
+        catalog.upsert(dataset);
+        catalog.save(root)
+    }
+}
+
+/// Directory of the outputs of a branch below [`OUTPUT_ROOT`], e.g. `github.com/user/repo/main`
+pub fn namespace(url: &str, branch: Option<&str>) -> Option<PathBuf> {
+    let repository = util::normalize_url(url)?;
+
+    Some(PathBuf::from(repository).join(branch_directory(branch)))
+}
+
+/// Name of the directory of a branch. Slashes of branch names are escaped, so every branch is a
+/// single directory.
+pub fn branch_directory(branch: Option<&str>) -> String {
+    branch.map_or(DEFAULT_BRANCH_DIR.to_string(), |branch| {
+        branch.replace('%', "%25").replace('/', "%2F")
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use super::*;
+
+    #[test]
+    fn test_datasets_are_recorded_once_per_branch() {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .subsec_nanos();
+        let root = std::env::temp_dir().join(format!("myaku-catalog-{nanos}"));
+
+        let url = "git@github.com:user/repo.git";
+        let dataset = |branch: Option<&str>, head: &str| Dataset {
+            url: url.to_string(),
+            branch: branch.map(str::to_string),
+            resolved_branch: branch.unwrap_or("main").to_string(),
+            path: root.join(namespace(url, branch).unwrap()),
+            format: "parquet".to_string(),
+            metrics: vec!["loc".to_string()],
+            head: head.to_string(),
+            updated_at: 0,
+            cache_path: None,
+            shared_value_count: None,
+        };
+
+        Catalog::record(&root, dataset(None, "a")).unwrap();
+        Catalog::record(&root, dataset(Some("release/1.0"), "b")).unwrap();
+        Catalog::record(&root, dataset(None, "c")).unwrap();
+
+        let catalog = Catalog::load(&root).unwrap();
+        let datasets: Vec<(&Path, &str)> = catalog
+            .datasets
+            .iter()
+            .map(|dataset| (dataset.path.as_path(), dataset.head.as_str()))
+            .collect();
+        assert_eq!(
+            datasets,
+            vec![
+                (Path::new("github.com/user/repo/HEAD"), "c"),
+                (Path::new("github.com/user/repo/release%2F1.0"), "b"),
+            ]
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,429 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+
+use arrow::{
+    array::{Array, BooleanArray, RecordBatch, StringArray, UInt64Array},
+    compute::{concat_batches, filter_record_batch},
+};
+
+use myaku::{sample_commits, CommitInfo, Frequency, PhaseOffset};
+use parquet::{
+    arrow::{arrow_reader::ParquetRecordBatchReaderBuilder, ArrowWriter},
+    basic::Compression,
+    file::properties::WriterProperties,
+};
+use thiserror::Error;
+
+use super::parquet::commit_id;
+
+/// Rows per row group of compacted files, much larger than the ones written by single runs
+const ROW_GROUP_SIZE: usize = 1024 * 1024;
+
+#[derive(Error, Debug)]
+pub enum CompactionError {
+    #[error("IO error: {0}")]
+    IO(#[from] std::io::Error),
+
+    #[error("Parquet error: {0}")]
+    Parquet(#[from] parquet::errors::ParquetError),
+
+    #[error("Arrow error: {0}")]
+    Arrow(#[from] arrow::error::ArrowError),
+
+    #[error("Serde Arrow error: {0}")]
+    SerdeArrow(#[from] serde_arrow::Error),
+
+    #[error("No commits.parquet in output {0}, it has to be written by collect --output parquet")]
+    MissingCommits(PathBuf),
+
+    #[error("File {0} has no commit column")]
+    MissingCommitColumn(PathBuf),
+}
+
+/// Values kept by a compaction, the latest value of a metric is always kept
+#[derive(Debug, Default)]
+pub struct Retention {
+    /// Values of commits older than this many seconds are dropped
+    pub max_age: Option<i64>,
+
+    /// Only one value per period is kept, like when collecting with this frequency
+    pub frequency: Option<Frequency>,
+
+    /// Current time in seconds since the unix epoch, which the age is relative to
+    pub now: i64,
+}
+
+/// Rows of a single file before and after compacting it
+#[derive(Debug)]
+pub struct CompactedFile {
+    pub path: PathBuf,
+    pub rows_before: usize,
+    pub rows_after: usize,
+
+    /// Files which were merged into this one and removed
+    pub merged_files: usize,
+}
+
+/// Rewrites the parquet files of every metric of an output written by `collect --output parquet`.
+///
+/// Parts next to a `data.parquet` are merged into it, keeping the values of `data.parquet` for
+/// commits contained in several files.
+pub fn compact(base: &Path, retention: &Retention) -> Result<Vec<CompactedFile>, CompactionError> {
+    let commits_path = base.join("commits.parquet");
+    if !commits_path.exists() {
+        return Err(CompactionError::MissingCommits(base.to_path_buf()));
+    }
+
+    let mut commits: Vec<CommitInfo> = Vec::new();
+    for batch in ParquetRecordBatchReaderBuilder::try_new(File::open(&commits_path)?)?.build()? {
+        commits.extend(serde_arrow::from_record_batch::<Vec<CommitInfo>>(&batch?)?);
+    }
+    commits.sort_by_key(|commit| commit.time);
+
+    let metrics_dir = base.join("metrics");
+    if !metrics_dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut metric_dirs = fs::read_dir(&metrics_dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<PathBuf>, _>>()?;
+    metric_dirs.retain(|path| path.is_dir());
+    metric_dirs.sort();
+
+    let mut compacted = vec![];
+    for metric_dir in metric_dirs {
+        let mut parts = fs::read_dir(&metric_dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<PathBuf>, _>>()?;
+        parts.retain(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "parquet")
+                && !path.ends_with("data.parquet")
+                && !path.ends_with("skipped.parquet")
+        });
+        parts.sort();
+
+        let data_path = metric_dir.join("data.parquet");
+        let mut data_files: Vec<PathBuf> = data_path
+            .exists()
+            .then_some(data_path.clone())
+            .into_iter()
+            .collect();
+        data_files.extend(parts);
+
+        if !data_files.is_empty() {
+            compacted.push(compact_files(&data_files, &data_path, &commits, retention)?);
+        }
+
+        let skipped_path = metric_dir.join("skipped.parquet");
+        if skipped_path.exists() {
+            compacted.push(compact_files(
+                std::slice::from_ref(&skipped_path),
+                &skipped_path,
+                &commits,
+                retention,
+            )?);
+        }
+    }
+
+    Ok(compacted)
+}
+
+fn compact_files(
+    files: &[PathBuf],
+    target: &Path,
+    commits: &[CommitInfo],
+    retention: &Retention,
+) -> Result<CompactedFile, CompactionError> {
+    let mut batches = vec![];
+    for file in files {
+        for batch in ParquetRecordBatchReaderBuilder::try_new(File::open(file)?)?.build()? {
+            batches.push(batch?);
+        }
+    }
+
+    let schema = batches[0].schema();
+    let batch = concat_batches(&schema, &batches)?;
+
+    let commit_column = commit_column(&batch, commits)
+        .ok_or_else(|| CompactionError::MissingCommitColumn(files[0].clone()))?;
+
+    let present: HashSet<&str> = commit_column.iter().flatten().map(String::as_str).collect();
+    let kept = retained_commits(&present, commits, retention);
+
+    let mut seen = HashSet::new();
+    let mask: BooleanArray = commit_column
+        .iter()
+        .map(|commit| {
+            Some(
+                commit
+                    .as_deref()
+                    .is_some_and(|commit| kept.contains(commit) && seen.insert(commit)),
+            )
+        })
+        .collect();
+
+    let compacted = filter_record_batch(&batch, &mask)?;
+
+    // Written next to the target first, so a failure leaves the previous files intact
+    let temporary_path = target.with_extension("parquet.tmp");
+    let mut writer = ArrowWriter::try_new(
+        File::create(&temporary_path)?,
+        compacted.schema(),
+        Some(
+            WriterProperties::builder()
+                .set_compression(Compression::SNAPPY)
+                .set_max_row_group_size(ROW_GROUP_SIZE)
+                .build(),
+        ),
+    )?;
+    writer.write(&compacted)?;
+    writer.close()?;
+
+    fs::rename(&temporary_path, target)?;
+    for file in files {
+        if file != target {
+            fs::remove_file(file)?;
+        }
+    }
+
+    Ok(CompactedFile {
+        path: target.to_path_buf(),
+        rows_before: batch.num_rows(),
+        rows_after: compacted.num_rows(),
+        merged_files: files.len() - 1,
+    })
+}
+
+/// Commit hashes of the rows, either from the `commit` column or resolved from the `commit_id`
+/// column of outputs written with `commit_ids`. Ids missing in `commits.parquet` are kept as they
+/// are, which never matches a hash.
+fn commit_column(batch: &RecordBatch, commits: &[CommitInfo]) -> Option<Vec<Option<String>>> {
+    if let Some(column) = batch.column_by_name("commit") {
+        let hashes = column.as_any().downcast_ref::<StringArray>()?;
+
+        return Some(hashes.iter().map(|hash| hash.map(str::to_string)).collect());
+    }
+
+    let ids = batch
+        .column_by_name("commit_id")?
+        .as_any()
+        .downcast_ref::<UInt64Array>()?;
+
+    let hashes: HashMap<u64, &str> = commits
+        .iter()
+        .filter_map(|commit| Some((commit_id(&commit.id).ok()?, commit.id.0.as_str())))
+        .collect();
+
+    Some(
+        ids.iter()
+            .map(|id| {
+                id.map(|id| {
+                    hashes
+                        .get(&id)
+                        .map_or_else(|| id.to_string(), |hash| (*hash).to_string())
+                })
+            })
+            .collect(),
+    )
+}
+
+/// Commits of the given ones whose values are kept. Commits missing in `commits.parquet` are
+/// kept, as their age is unknown.
+fn retained_commits<'a>(
+    present: &HashSet<&'a str>,
+    commits: &[CommitInfo],
+    retention: &Retention,
+) -> HashSet<&'a str> {
+    let known: Vec<CommitInfo> = commits
+        .iter()
+        .filter(|commit| present.contains(commit.id.0.as_str()))
+        .cloned()
+        .collect();
+
+    let Some(latest) = known.last() else {
+        return present.clone();
+    };
+
+    let sampled: Vec<&CommitInfo> = match &retention.frequency {
+        Some(frequency) => sample_commits(&known, frequency, false, PhaseOffset::default(), true)
+            .into_iter()
+            .map(|(commit, _)| commit)
+            .collect(),
+        None => known.iter().collect(),
+    };
+
+    let known_commits: HashSet<&str> = known.iter().map(|commit| commit.id.0.as_str()).collect();
+    let mut retained: HashSet<&'a str> = present
+        .iter()
+        .filter(|commit| !known_commits.contains(*commit))
+        .copied()
+        .collect();
+
+    for commit in sampled {
+        let is_recent = retention
+            .max_age
+            .is_none_or(|max_age| commit.time.timestamp() >= retention.now - max_age);
+
+        if is_recent || commit.id == latest.id {
+            if let Some(commit) = present.get(commit.id.0.as_str()) {
+                retained.insert(commit);
+            }
+        }
+    }
+
+    retained
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use myaku::{values::TotalFileCountValue, CollectorValue};
+    use serde_json::json;
+
+    use super::*;
+    use crate::output::{Output, ParquetOutput};
+
+    const DAY: i64 = 24 * 60 * 60;
+
+    #[test]
+    fn test_compact_merges_and_drops_values() {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .subsec_nanos();
+        let base = std::env::temp_dir().join(format!("myaku-compact-{nanos}"));
+
+        let start = 1_700_000_000;
+        let commits: Vec<CommitInfo> = [("a", start), ("b", start + 60), ("c", start + 2 * DAY)]
+            .iter()
+            .map(|(id, time)| {
+                let author = json!({ "name": null, "email": null });
+                serde_json::from_value(json!({
+                    "id": id, "author": author, "committer": author, "message": null, "time": time
+                }))
+                .unwrap()
+            })
+            .collect();
+
+        let mut output = ParquetOutput::new(&base);
+        output.set_commits(&commits).unwrap();
+        for commit in &commits {
+            let value = CollectorValue::TotalFileCount(TotalFileCountValue {
+                total_file_count: 1,
+            });
+            output.set_metric("files", &commit.id, &value).unwrap();
+        }
+        output.flush().unwrap();
+
+        let metric_dir = base.join("metrics").join("files");
+        fs::copy(
+            metric_dir.join("data.parquet"),
+            metric_dir.join("part.parquet"),
+        )
+        .unwrap();
+
+        // Commit b was made on the same day as a
+        let retention = Retention {
+            frequency: Some(Frequency::Daily),
+            ..Retention::default()
+        };
+        let compacted = compact(&base, &retention).unwrap();
+        assert_eq!(compacted.len(), 1);
+        assert_eq!(compacted[0].rows_before, 6);
+        assert_eq!(compacted[0].rows_after, 2);
+        assert_eq!(compacted[0].merged_files, 1);
+        assert!(!metric_dir.join("part.parquet").exists());
+
+        // The latest value is kept even if it is older than the maximal age
+        let retention = Retention {
+            max_age: Some(DAY),
+            frequency: None,
+            now: start + 10 * DAY,
+        };
+        let compacted = compact(&base, &retention).unwrap();
+        assert_eq!(compacted[0].rows_after, 1);
+
+        let file = File::open(metric_dir.join("data.parquet")).unwrap();
+        let batch = ParquetRecordBatchReaderBuilder::try_new(file)
+            .unwrap()
+            .build()
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        let commit_column = batch.column_by_name("commit").unwrap();
+        let commits = commit_column
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(commits.value(0), "c");
+
+        fs::remove_dir_all(base).unwrap();
+    }
+
+    #[test]
+    fn test_compact_output_with_commit_ids() {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .subsec_nanos();
+        let base = std::env::temp_dir().join(format!("myaku-compact-ids-{nanos}"));
+
+        let start = 1_700_000_000;
+        let commits: Vec<CommitInfo> = [("a1", start), ("b2", start + 60), ("c3", start + 2 * DAY)]
+            .iter()
+            .map(|(id, time)| {
+                let author = json!({ "name": null, "email": null });
+                serde_json::from_value(json!({
+                    "id": id, "author": author, "committer": author, "message": null, "time": time
+                }))
+                .unwrap()
+            })
+            .collect();
+
+        let mut output = ParquetOutput::new(&base).with_commit_ids(true);
+        output.set_commits(&commits).unwrap();
+        for commit in &commits {
+            let value = CollectorValue::TotalFileCount(TotalFileCountValue {
+                total_file_count: 1,
+            });
+            output.set_metric("files", &commit.id, &value).unwrap();
+        }
+        output.flush().unwrap();
+
+        // Commit b2 was made on the same day as a1
+        let retention = Retention {
+            frequency: Some(Frequency::Daily),
+            ..Retention::default()
+        };
+        let compacted = compact(&base, &retention).unwrap();
+        assert_eq!(compacted[0].rows_before, 3);
+        assert_eq!(compacted[0].rows_after, 2);
+
+        let file = File::open(base.join("metrics/files/data.parquet")).unwrap();
+        let batch = ParquetRecordBatchReaderBuilder::try_new(file)
+            .unwrap()
+            .build()
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        let ids = batch
+            .column_by_name("commit_id")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .unwrap();
+        let mut ids: Vec<u64> = ids.values().to_vec();
+        ids.sort_unstable();
+        assert_eq!(ids, [0xa1, 0xc3]);
+
+        fs::remove_dir_all(base).unwrap();
+    }
+}
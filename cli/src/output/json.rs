@@ -1,13 +1,16 @@
 use std::{
+    collections::{BTreeMap, HashMap},
     fs::{self, File},
     io::{BufReader, Read, Write},
     path::{Path, PathBuf},
 };
 
-use myaku::{CollectorValue, CommitHash, CommitInfo, CommitTagInfo};
+use myaku::{
+    CampaignProjection, CollectorValue, CommitHash, CommitInfo, CommitSample, CommitTagInfo, Event,
+};
 use thiserror::Error;
 
-use super::Output;
+use super::{Output, RunMetrics, ValueTransform};
 
 #[derive(Error, Debug)]
 pub enum JsonOutputError {
@@ -24,6 +27,9 @@ pub enum JsonOutputError {
 #[derive(Debug)]
 pub struct JsonOutput {
     base: PathBuf,
+
+    // Key: Metric name
+    transforms: BTreeMap<String, ValueTransform>,
 }
 
 impl JsonOutput {
@@ -31,8 +37,15 @@ impl JsonOutput {
     pub fn new(base: &Path) -> Self {
         Self {
             base: base.to_path_buf(),
+            transforms: BTreeMap::new(),
         }
     }
+
+    #[must_use]
+    pub fn with_transforms(mut self, transforms: BTreeMap<String, ValueTransform>) -> Self {
+        self.transforms = transforms;
+        self
+    }
 }
 
 impl JsonOutput {
@@ -44,6 +57,13 @@ impl JsonOutput {
         self.get_metric_dir(metric_name)
             .join(Path::new(&format!("{commit}.json")))
     }
+
+    /// Kept apart from the values, every file in the directory of a metric is a value
+    fn get_commits_represented_file(&self, metric_name: &str) -> PathBuf {
+        self.base
+            .join("commits_represented")
+            .join(Path::new(&format!("{metric_name}.json")))
+    }
 }
 
 impl Output for JsonOutput {
@@ -56,7 +76,8 @@ impl Output for JsonOutput {
     ) -> Result<Option<CollectorValue>, Self::Error> {
         let file_path = self.get_metric_file(metric_name, commit);
 
-        if !file_path.exists() {
+        // Transformed values can't be read back, they are taken from the cache instead
+        if !file_path.exists() || self.transforms.contains_key(metric_name) {
             return Ok(None);
         }
 
@@ -101,6 +122,30 @@ impl Output for JsonOutput {
         Ok(())
     }
 
+    fn set_events(&mut self, events: &[Event]) -> Result<(), Self::Error> {
+        let file_path: PathBuf = self.base.join("events.json");
+
+        fs::create_dir_all(&self.base)?;
+
+        let mut file = File::create(file_path)?;
+        let contents: String = serde_json::to_string(&events)?;
+        file.write_all(contents.as_bytes())?;
+
+        Ok(())
+    }
+
+    fn set_campaigns(&mut self, projections: &[CampaignProjection]) -> Result<(), Self::Error> {
+        let file_path: PathBuf = self.base.join("campaigns.json");
+
+        fs::create_dir_all(&self.base)?;
+
+        let mut file = File::create(file_path)?;
+        let contents: String = serde_json::to_string(&projections)?;
+        file.write_all(contents.as_bytes())?;
+
+        Ok(())
+    }
+
     fn set_metric(
         &mut self,
         metric_name: &str,
@@ -113,9 +158,69 @@ impl Output for JsonOutput {
             fs::create_dir_all(parent)?;
         }
 
-        let contents = serde_json::to_string(value)?;
+        let contents = match self.transforms.get(metric_name) {
+            Some(transform) if !matches!(value, CollectorValue::Skipped(_)) => {
+                let mut json = serde_json::to_value(value)?;
+                transform.apply_to_json(&mut json);
+                serde_json::to_string(&json)?
+            }
+            _ => serde_json::to_string(value)?,
+        };
+
+        let mut file = File::create(file_path)?;
+        file.write_all(contents.as_bytes())?;
+
+        Ok(())
+    }
+
+    fn set_commit_samples(&mut self, samples: &[CommitSample]) -> Result<(), Self::Error> {
+        let file_path: PathBuf = self.base.join("commit_samples.json");
+
+        fs::create_dir_all(&self.base)?;
+
+        let mut file = File::create(file_path)?;
+        let contents: String = serde_json::to_string(&samples)?;
+        file.write_all(contents.as_bytes())?;
+
+        Ok(())
+    }
+
+    fn set_commits_represented(
+        &mut self,
+        metric_name: &str,
+        commits_represented: &HashMap<CommitHash, usize>,
+    ) -> Result<(), Self::Error> {
+        let file_path = self.get_commits_represented_file(metric_name);
+
+        if let Some(parent) = file_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let commits_represented: BTreeMap<&str, usize> = commits_represented
+            .iter()
+            .map(|(commit, count)| (commit.0.as_str(), *count))
+            .collect();
+        let contents = serde_json::to_string(&commits_represented)?;
+
+        let mut file = File::create(file_path)?;
+        file.write_all(contents.as_bytes())?;
+
+        Ok(())
+    }
+
+    fn add_run(&mut self, run: &RunMetrics) -> Result<(), Self::Error> {
+        let file_path: PathBuf = self.base.join("runs.json");
+
+        let mut runs: Vec<RunMetrics> = if file_path.exists() {
+            serde_json::from_reader(BufReader::new(File::open(&file_path)?))?
+        } else {
+            fs::create_dir_all(&self.base)?;
+            Vec::new()
+        };
+        runs.push(run.clone());
 
         let mut file = File::create(file_path)?;
+        let contents: String = serde_json::to_string(&runs)?;
         file.write_all(contents.as_bytes())?;
 
         Ok(())
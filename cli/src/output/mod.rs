@@ -1,10 +1,181 @@
+mod catalog;
+mod compact;
 mod json;
 mod parquet;
+mod transform;
+
+pub use catalog::{branch_directory, namespace, Catalog, Dataset, OUTPUT_ROOT};
+pub use compact::{compact, Retention};
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
 
 pub use json::{JsonOutput, JsonOutputError};
-use myaku::{CollectorValue, CommitHash, CommitInfo, CommitTagInfo};
+use myaku::{
+    CampaignProjection, CollectorValue, CommitHash, CommitInfo, CommitSample, CommitTagInfo, Event,
+};
 pub use parquet::{ParquetOutput, ParquetOutputError};
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
+pub use transform::ValueTransform;
+
+/// File describing an output, written with `manifest = true`
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputType {
+    Json,
+    #[default]
+    Parquet,
+}
+
+impl OutputType {
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        match self {
+            OutputType::Json => "json",
+            OutputType::Parquet => "parquet",
+        }
+    }
+}
+
+/// Compression codec of parquet files
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputCompression {
+    Uncompressed,
+    #[default]
+    Snappy,
+    Gzip,
+    Lz4,
+    Zstd,
+    Brotli,
+}
+
+impl From<OutputCompression> for ::parquet::basic::Compression {
+    fn from(value: OutputCompression) -> Self {
+        match value {
+            OutputCompression::Uncompressed => Self::UNCOMPRESSED,
+            OutputCompression::Snappy => Self::SNAPPY,
+            OutputCompression::Gzip => Self::GZIP(::parquet::basic::GzipLevel::default()),
+            OutputCompression::Lz4 => Self::LZ4_RAW,
+            OutputCompression::Zstd => Self::ZSTD(::parquet::basic::ZstdLevel::default()),
+            OutputCompression::Brotli => Self::BROTLI(::parquet::basic::BrotliLevel::default()),
+        }
+    }
+}
+
+/// Shape of the parquet metric tables
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputLayout {
+    /// One row per commit and one column per field of the values
+    #[default]
+    Wide,
+
+    /// One row per commit and numeric field, with the name of the field in `field` and its value
+    /// in `value`, so metrics with different fields can be stacked into one table. Fields which
+    /// aren't numbers, e.g. lists of files, are left out.
+    Long,
+}
+
+/// `[output]` section of the config
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutputConfig {
+    /// Format used unless `--output` is passed, parquet by default
+    pub format: Option<OutputType>,
+
+    /// Codec of the parquet files, snappy by default
+    pub compression: Option<OutputCompression>,
+
+    /// Layout of the parquet metric tables, wide by default
+    pub layout: Option<OutputLayout>,
+
+    /// Whether the commits are written, true by default. Compacting an output requires them.
+    pub commits: Option<bool>,
+
+    /// Whether the tags of the commits are written, true by default
+    pub tags: Option<bool>,
+
+    /// Whether the table mapping every commit to the sampled commit of each metric representing
+    /// it is written, true by default
+    pub commit_samples: Option<bool>,
+
+    /// Whether a manifest describing the output is written next to it, false by default
+    pub manifest: Option<bool>,
+
+    /// Changes to the values of metrics before they are written, by metric name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub transforms: BTreeMap<String, ValueTransform>,
+}
+
+/// Describes how an output was written, for readers which don't know its config
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub format: OutputType,
+
+    /// Only set for parquet outputs
+    pub compression: Option<OutputCompression>,
+    pub layout: Option<OutputLayout>,
+    pub commit_ids: bool,
+
+    /// Auxiliary files contained in the output
+    pub commits: bool,
+    pub tags: bool,
+    pub commit_samples: bool,
+    pub events: bool,
+    #[serde(default)]
+    pub campaigns: bool,
+
+    pub metrics: Vec<String>,
+
+    /// Commit the branch pointed to when the output was written
+    pub head: String,
+}
+
+impl Manifest {
+    pub fn write(&self, base: &Path) -> std::io::Result<()> {
+        std::fs::create_dir_all(base)?;
+
+        let contents = serde_json::to_string_pretty(self).map_err(std::io::Error::other)?;
+        std::fs::write(base.join(MANIFEST_FILE), contents)
+    }
+}
+
+/// Operational metrics of a single collection run, to track its cost as the history grows
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RunMetrics {
+    /// Start of the collection in seconds since the unix epoch
+    pub started_at: u64,
+
+    /// Commit the branch of the reference pointed to
+    pub head: String,
+
+    pub duration_ms: u64,
+
+    pub metric_count: u64,
+    pub fresh_task_count: u64,
+    pub reused_task_count: u64,
+    pub precomputed_task_count: u64,
+    pub failed_task_count: u64,
+    pub quarantined_task_count: u64,
+    pub skipped_task_count: u64,
+
+    /// Share of the values which were reused from the cache or the previous output, missing if
+    /// there were no tasks at all
+    pub cache_hit_ratio: Option<f64>,
+
+    /// Time spent on all fresh tasks, which exceeds the duration as tasks run in parallel
+    pub task_duration_ms: u64,
+    pub max_task_duration_ms: u64,
+
+    /// Size of the files read by tasks going through all files of their commit
+    pub scanned_bytes: u64,
+}
 
 pub trait Output: core::fmt::Debug {
     type Error;
@@ -13,6 +184,12 @@ pub trait Output: core::fmt::Debug {
 
     fn set_commit_tags(&mut self, commit_tags: &[CommitTagInfo]) -> Result<(), Self::Error>;
 
+    /// External events of the `events` file of the config
+    fn set_events(&mut self, events: &[Event]) -> Result<(), Self::Error>;
+
+    /// Projections of the metrics with a campaign, see [`myaku::PostCollection::campaign_projections`]
+    fn set_campaigns(&mut self, projections: &[CampaignProjection]) -> Result<(), Self::Error>;
+
     fn get_metric(
         &self,
         metric_name: &str,
@@ -25,6 +202,20 @@ pub trait Output: core::fmt::Debug {
         value: &CollectorValue,
     ) -> Result<(), Self::Error>;
 
+    /// Number of commits the values of a metric at its sampled commits stand for, see
+    /// [`myaku::PostCollection::commits_represented`]
+    fn set_commits_represented(
+        &mut self,
+        metric_name: &str,
+        commits_represented: &HashMap<CommitHash, usize>,
+    ) -> Result<(), Self::Error>;
+
+    /// Sampled commits of the metrics representing every commit of their branches
+    fn set_commit_samples(&mut self, samples: &[CommitSample]) -> Result<(), Self::Error>;
+
+    /// Appends the metrics of a collection run to the ones of previous runs
+    fn add_run(&mut self, run: &RunMetrics) -> Result<(), Self::Error>;
+
     fn load(&self) -> Result<(), Self::Error>;
     fn flush(&self) -> Result<(), Self::Error>;
 }
@@ -72,6 +263,20 @@ impl Output for OutputObj {
         }
     }
 
+    fn set_events(&mut self, events: &[Event]) -> Result<(), Self::Error> {
+        match self {
+            Self::Json(output) => output.set_events(events).map_err(|e| e.into()),
+            Self::Parquet(output) => output.set_events(events).map_err(|e| e.into()),
+        }
+    }
+
+    fn set_campaigns(&mut self, projections: &[CampaignProjection]) -> Result<(), Self::Error> {
+        match self {
+            Self::Json(output) => output.set_campaigns(projections).map_err(|e| e.into()),
+            Self::Parquet(output) => output.set_campaigns(projections).map_err(|e| e.into()),
+        }
+    }
+
     fn get_metric(
         &self,
         metric_name: &str,
@@ -99,6 +304,35 @@ impl Output for OutputObj {
         }
     }
 
+    fn set_commit_samples(&mut self, samples: &[CommitSample]) -> Result<(), Self::Error> {
+        match self {
+            Self::Json(output) => output.set_commit_samples(samples).map_err(|e| e.into()),
+            Self::Parquet(output) => output.set_commit_samples(samples).map_err(|e| e.into()),
+        }
+    }
+
+    fn set_commits_represented(
+        &mut self,
+        metric_name: &str,
+        commits_represented: &HashMap<CommitHash, usize>,
+    ) -> Result<(), Self::Error> {
+        match self {
+            Self::Json(output) => output
+                .set_commits_represented(metric_name, commits_represented)
+                .map_err(|e| e.into()),
+            Self::Parquet(output) => output
+                .set_commits_represented(metric_name, commits_represented)
+                .map_err(|e| e.into()),
+        }
+    }
+
+    fn add_run(&mut self, run: &RunMetrics) -> Result<(), Self::Error> {
+        match self {
+            Self::Json(output) => output.add_run(run).map_err(|e| e.into()),
+            Self::Parquet(output) => output.add_run(run).map_err(|e| e.into()),
+        }
+    }
+
     fn load(&self) -> Result<(), Self::Error> {
         match self {
             Self::Json(output) => output.load().map_err(|e| e.into()),
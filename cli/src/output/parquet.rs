@@ -1,24 +1,38 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     fs::{self, File},
     path::{Path, PathBuf},
     sync::Arc,
 };
 
 use arrow::{
-    array::{Array, ArrayRef, RecordBatch, StringArray},
-    datatypes::{Field, FieldRef, Schema},
+    array::{new_empty_array, Array, ArrayRef, AsArray, RecordBatch, StringArray, UInt64Array},
+    compute::{cast, concat},
+    datatypes::{DataType, Field, FieldRef, Schema},
 };
 use myaku::{
-    ChangedFilesLocValue, ChangedFilesValue, CollectorValue, CommitHash, CommitInfo, CommitTagInfo,
-    FileListValue, LocValue, PatternOccurencesValue, TotalCargoDependenciesValue,
-    TotalDiffStatValue, TotalFileCountValue, TotalLocValue, TotalPatternOccurencesValue,
+    values::{
+        AuthorDomainValue, AuthorDomainsValue, BuildWarningsValue, ChangedFilesLocValue,
+        ChangedFilesValue, CiWorkflowsValue, CognitiveComplexityValue, CommentDensityValue,
+        ConfigFilesValue, ContainerConfigValue, CoverageReportValue, CumulativeDiffStatValue,
+        ExpressionValue, FileLengthValue, FileListValue, FormattingChangesValue,
+        HighEntropyStringsValue, ImportCouplingValue, LocValue, MaintainabilityIndexValue,
+        PatternOccurencesValue, RollingDiffStatValue, SecretsValue, ShellcheckValue, SkippedValue,
+        TotalCargoDependenciesValue, TotalCargoFeaturesValue, TotalDiffStatValue,
+        TotalFileCountValue, TotalLocValue, TotalPatternOccurencesValue,
+        TotalSymlinksAndSubmodulesValue, TotalWorkspaceMembersValue,
+    },
+    CampaignProjection, CollectorValue, CommitHash, CommitInfo, CommitSample, CommitTagInfo, Event,
+    COLLECTOR_METADATA_KEY,
+};
+use parquet::{
+    arrow::{arrow_reader::ParquetRecordBatchReaderBuilder, ArrowWriter},
+    file::properties::WriterProperties,
 };
-use parquet::{arrow::ArrowWriter, basic::Compression, file::properties::WriterProperties};
 use serde_arrow::schema::{SchemaLike, TracingOptions};
 use thiserror::Error;
 
-use super::Output;
+use super::{Output, OutputCompression, OutputLayout, RunMetrics, ValueTransform};
 
 #[derive(Error, Debug)]
 pub enum ParquetOutputError {
@@ -31,6 +45,9 @@ pub enum ParquetOutputError {
     #[error("Parquet error: {0}")]
     Parquet(#[from] parquet::errors::ParquetError),
 
+    #[error("Arrow error: {0}")]
+    Arrow(#[from] arrow::error::ArrowError),
+
     #[error("Could not serialize as arrow record batch: {0}")]
     RecordBatchConversion(#[from] RecordBatchConversionError),
 }
@@ -41,6 +58,16 @@ pub struct ParquetOutput {
 
     // Key: Metric name
     metrics: HashMap<String, HashMap<CommitHash, CollectorValue>>,
+    commits_represented: HashMap<String, HashMap<CommitHash, usize>>,
+
+    /// Metric tables reference commits by [`commit_id`] instead of their hash
+    commit_ids: bool,
+
+    compression: OutputCompression,
+    layout: OutputLayout,
+
+    // Key: Metric name
+    transforms: BTreeMap<String, ValueTransform>,
 }
 
 impl ParquetOutput {
@@ -49,8 +76,43 @@ impl ParquetOutput {
         Self {
             base: base.to_path_buf(),
             metrics: HashMap::default(),
+            commits_represented: HashMap::default(),
+            commit_ids: false,
+            compression: OutputCompression::default(),
+            layout: OutputLayout::default(),
+            transforms: BTreeMap::default(),
         }
     }
+
+    #[must_use]
+    pub fn with_commit_ids(mut self, commit_ids: bool) -> Self {
+        self.commit_ids = commit_ids;
+        self
+    }
+
+    #[must_use]
+    pub fn with_compression(mut self, compression: OutputCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    #[must_use]
+    pub fn with_layout(mut self, layout: OutputLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    #[must_use]
+    pub fn with_transforms(mut self, transforms: BTreeMap<String, ValueTransform>) -> Self {
+        self.transforms = transforms;
+        self
+    }
+}
+
+/// Stable integer id of a commit, the first 64 bits of its hash. Metric tables store it instead of
+/// the hash with `commit_ids` enabled, `commits.parquet` maps it to the hash.
+pub fn commit_id(commit: &CommitHash) -> Result<u64, std::num::ParseIntError> {
+    u64::from_str_radix(commit.0.get(..16).unwrap_or(&commit.0), 16)
 }
 
 impl ParquetOutput {
@@ -62,9 +124,14 @@ impl ParquetOutput {
         self.get_metric_dir(metric_name).join("data.parquet")
     }
 
-    fn get_writer_props() -> WriterProperties {
+    /// Commits skipped by the collector are written separately, their values differ in type
+    fn get_skipped_file(&self, metric_name: &str) -> PathBuf {
+        self.get_metric_dir(metric_name).join("skipped.parquet")
+    }
+
+    fn get_writer_props(&self) -> WriterProperties {
         WriterProperties::builder()
-            .set_compression(Compression::SNAPPY)
+            .set_compression(self.compression.into())
             .build()
     }
 }
@@ -99,13 +166,19 @@ impl Output for ParquetOutput {
                 .from_type_budget(1000),
         )?;
 
-        let batch = serde_arrow::to_record_batch(&fields, &commits)?;
+        let mut batch = serde_arrow::to_record_batch(&fields, &commits)?;
 
-        let mut writer = ArrowWriter::try_new(
-            file,
-            batch.schema(),
-            Some(ParquetOutput::get_writer_props()),
-        )?;
+        if self.commit_ids {
+            let ids = commits
+                .iter()
+                .map(|commit| commit_id(&commit.id))
+                .collect::<Result<UInt64Array, _>>()
+                .map_err(RecordBatchConversionError::from)?;
+
+            batch = with_column(&batch, "commit_id", Arc::new(ids), false)?;
+        }
+
+        let mut writer = ArrowWriter::try_new(file, batch.schema(), Some(self.get_writer_props()))?;
 
         writer.write(&batch)?;
         writer.close()?;
@@ -131,11 +204,55 @@ impl Output for ParquetOutput {
 
         let batch = serde_arrow::to_record_batch(&fields, &commit_tags)?;
 
-        let mut writer = ArrowWriter::try_new(
-            file,
-            batch.schema(),
-            Some(ParquetOutput::get_writer_props()),
+        let mut writer = ArrowWriter::try_new(file, batch.schema(), Some(self.get_writer_props()))?;
+
+        writer.write(&batch)?;
+        writer.close()?;
+
+        Ok(())
+    }
+
+    fn set_events(&mut self, events: &[Event]) -> Result<(), Self::Error> {
+        let file_path: PathBuf = self.base.join("events.parquet");
+
+        fs::create_dir_all(&self.base)?;
+
+        let file = File::create(file_path)?;
+
+        let fields = Vec::<FieldRef>::from_type::<Event>(
+            TracingOptions::default()
+                .map_as_struct(false)
+                .enums_without_data_as_strings(true)
+                .from_type_budget(1000),
+        )?;
+
+        let batch = serde_arrow::to_record_batch(&fields, &events)?;
+
+        let mut writer = ArrowWriter::try_new(file, batch.schema(), Some(self.get_writer_props()))?;
+
+        writer.write(&batch)?;
+        writer.close()?;
+
+        Ok(())
+    }
+
+    fn set_campaigns(&mut self, projections: &[CampaignProjection]) -> Result<(), Self::Error> {
+        let file_path: PathBuf = self.base.join("campaigns.parquet");
+
+        fs::create_dir_all(&self.base)?;
+
+        let file = File::create(file_path)?;
+
+        // Traced from the projections, as dates can't be traced from their type
+        let fields = Vec::<FieldRef>::from_samples(
+            &projections,
+            TracingOptions::default()
+                .allow_null_fields(true)
+                .guess_dates(true),
         )?;
+        let batch = serde_arrow::to_record_batch(&fields, &projections)?;
+
+        let mut writer = ArrowWriter::try_new(file, batch.schema(), Some(self.get_writer_props()))?;
 
         writer.write(&batch)?;
         writer.close()?;
@@ -154,31 +271,132 @@ impl Output for ParquetOutput {
         Ok(())
     }
 
+    fn set_commit_samples(&mut self, samples: &[CommitSample]) -> Result<(), Self::Error> {
+        let file_path: PathBuf = self.base.join("commit_samples.parquet");
+
+        fs::create_dir_all(&self.base)?;
+
+        let metrics = StringArray::from_iter_values(samples.iter().map(|sample| &sample.metric));
+
+        let commit_columns: [(&str, ArrayRef); 2] = if self.commit_ids {
+            let ids = |commit: fn(&CommitSample) -> &CommitHash| {
+                samples
+                    .iter()
+                    .map(|sample| commit_id(commit(sample)))
+                    .collect::<Result<UInt64Array, _>>()
+                    .map_err(RecordBatchConversionError::from)
+            };
+
+            [
+                ("commit_id", Arc::new(ids(|sample| &sample.commit)?)),
+                (
+                    "sampled_commit_id",
+                    Arc::new(ids(|sample| &sample.sampled_commit)?),
+                ),
+            ]
+        } else {
+            let hashes = |commit: fn(&CommitSample) -> &CommitHash| {
+                StringArray::from_iter_values(samples.iter().map(|sample| &commit(sample).0))
+            };
+
+            [
+                ("commit", Arc::new(hashes(|sample| &sample.commit))),
+                (
+                    "sampled_commit",
+                    Arc::new(hashes(|sample| &sample.sampled_commit)),
+                ),
+            ]
+        };
+
+        let mut fields = vec![Field::new("metric", DataType::Utf8, false)];
+        let mut columns: Vec<ArrayRef> = vec![Arc::new(metrics)];
+        for (name, column) in commit_columns {
+            fields.push(Field::new(name, column.data_type().clone(), false));
+            columns.push(column);
+        }
+
+        let batch = RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)?;
+
+        let mut writer = ArrowWriter::try_new(
+            File::create(file_path)?,
+            batch.schema(),
+            Some(self.get_writer_props()),
+        )?;
+
+        writer.write(&batch)?;
+        writer.close()?;
+
+        Ok(())
+    }
+
+    fn set_commits_represented(
+        &mut self,
+        metric_name: &str,
+        commits_represented: &HashMap<CommitHash, usize>,
+    ) -> Result<(), Self::Error> {
+        self.commits_represented
+            .insert(metric_name.to_string(), commits_represented.clone());
+        Ok(())
+    }
+
     fn flush(&self) -> Result<(), Self::Error> {
+        let none = HashMap::new();
+
         for (metric_name, values) in &self.metrics {
-            if values.is_empty() {
-                continue;
-            }
+            let (skipped, values): (HashMap<_, _>, HashMap<_, _>) = values
+                .iter()
+                .map(|(commit, value)| (commit.clone(), value.clone()))
+                .partition(|(_, value)| matches!(value, CollectorValue::Skipped(_)));
 
-            let file_path = self.get_metric_file(metric_name);
+            let commits_represented = self.commits_represented.get(metric_name).unwrap_or(&none);
 
-            if let Some(parent) = file_path.parent() {
-                fs::create_dir_all(parent)?;
-            }
+            self.write_values(
+                &self.get_metric_file(metric_name),
+                &values,
+                commits_represented,
+                self.layout,
+                self.transforms.get(metric_name),
+            )?;
 
-            let file = File::create(file_path)?;
+            // Skip reasons aren't numbers, so skipped commits are always listed in the wide layout
+            self.write_values(
+                &self.get_skipped_file(metric_name),
+                &skipped,
+                commits_represented,
+                OutputLayout::Wide,
+                None,
+            )?;
+        }
 
-            let record_batch = values_to_record_batch(values)?;
+        Ok(())
+    }
 
-            let mut writer = ArrowWriter::try_new(
-                file,
-                record_batch.schema(),
-                Some(ParquetOutput::get_writer_props()),
-            )?;
+    fn add_run(&mut self, run: &RunMetrics) -> Result<(), Self::Error> {
+        let file_path: PathBuf = self.base.join("runs.parquet");
 
-            writer.write(&record_batch)?;
-            writer.close()?;
+        let mut runs: Vec<RunMetrics> = Vec::new();
+        if file_path.exists() {
+            let reader =
+                ParquetRecordBatchReaderBuilder::try_new(File::open(&file_path)?)?.build()?;
+            for batch in reader {
+                runs.extend(serde_arrow::from_record_batch::<Vec<RunMetrics>>(&batch?)?);
+            }
+        } else {
+            fs::create_dir_all(&self.base)?;
         }
+        runs.push(run.clone());
+
+        let fields = Vec::<FieldRef>::from_type::<RunMetrics>(TracingOptions::default())?;
+        let batch = serde_arrow::to_record_batch(&fields, &runs)?;
+
+        let mut writer = ArrowWriter::try_new(
+            File::create(file_path)?,
+            batch.schema(),
+            Some(self.get_writer_props()),
+        )?;
+
+        writer.write(&batch)?;
+        writer.close()?;
 
         Ok(())
     }
@@ -189,6 +407,45 @@ impl Output for ParquetOutput {
     }
 }
 
+impl ParquetOutput {
+    fn write_values(
+        &self,
+        file_path: &Path,
+        values: &HashMap<CommitHash, CollectorValue>,
+        commits_represented: &HashMap<CommitHash, usize>,
+        layout: OutputLayout,
+        transform: Option<&ValueTransform>,
+    ) -> Result<(), ParquetOutputError> {
+        if values.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(parent) = file_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let file = File::create(file_path)?;
+
+        let record_batch =
+            values_to_record_batch(values, commits_represented, self.commit_ids, transform)?;
+
+        // Transformed values and the long layout don't match the values of the collector anymore
+        let record_batch = match (layout, transform, values.values().next()) {
+            (OutputLayout::Wide, None, Some(value)) => with_collector(record_batch, value)?,
+            (OutputLayout::Wide, _, _) => record_batch,
+            (OutputLayout::Long, _, _) => to_long_layout(&record_batch)?,
+        };
+
+        let mut writer =
+            ArrowWriter::try_new(file, record_batch.schema(), Some(self.get_writer_props()))?;
+
+        writer.write(&record_batch)?;
+        writer.close()?;
+
+        Ok(())
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum RecordBatchConversionError {
     #[error("No values to convert")]
@@ -197,11 +454,17 @@ pub enum RecordBatchConversionError {
     #[error("Expected all values to have the same type")]
     DifferingValueTypes,
 
+    #[error("Values of this collector can not be written as parquet yet")]
+    UnsupportedValueType,
+
     #[error("Serde Arrow error: {0}")]
     SerdeArrow(#[from] serde_arrow::Error),
 
     #[error("Arrow error: {0}")]
     Arrow(#[from] arrow::error::ArrowError),
+
+    #[error("Commit hash is not hexadecimal: {0}")]
+    CommitId(#[from] std::num::ParseIntError),
 }
 
 macro_rules! to_batch {
@@ -230,8 +493,16 @@ macro_rules! to_batch {
     }};
 }
 
+#[allow(clippy::too_many_lines)]
+/// Column of metric tables with the number of commits a sampled commit stands for, empty for
+/// values of commits the metric doesn't sample, e.g. ones sampled by metrics sharing its collector
+const COMMITS_REPRESENTED_COLUMN: &str = "commits_represented";
+
 fn values_to_record_batch(
     values: &HashMap<CommitHash, CollectorValue>,
+    commits_represented: &HashMap<CommitHash, usize>,
+    commit_ids: bool,
+    transform: Option<&ValueTransform>,
 ) -> Result<RecordBatch, RecordBatchConversionError> {
     let mut commits = Vec::new();
 
@@ -255,6 +526,9 @@ fn values_to_record_batch(
         CollectorValue::TotalCargoDependencies(_) => {
             to_batch!(values, commits, TotalCargoDependenciesValue)
         }
+        CollectorValue::TotalCargoFeatures(_) => {
+            to_batch!(values, commits, TotalCargoFeaturesValue)
+        }
         CollectorValue::TotalLoc(_) => {
             to_batch!(values, commits, TotalLocValue)
         }
@@ -270,32 +544,553 @@ fn values_to_record_batch(
         CollectorValue::ChangedFilesLoc(_) => {
             to_batch!(values, commits, ChangedFilesLocValue)
         }
+        CollectorValue::TotalWorkspaceMembers(_) => {
+            to_batch!(values, commits, TotalWorkspaceMembersValue)
+        }
+        CollectorValue::CiWorkflows(_) => {
+            to_batch!(values, commits, CiWorkflowsValue)
+        }
+        CollectorValue::ContainerConfig(_) => {
+            to_batch!(values, commits, ContainerConfigValue)
+        }
+        CollectorValue::TotalSymlinksAndSubmodules(_) => {
+            to_batch!(values, commits, TotalSymlinksAndSubmodulesValue)
+        }
+        CollectorValue::CoverageReport(_) => {
+            to_batch!(values, commits, CoverageReportValue)
+        }
+        CollectorValue::FileLength(_) => {
+            to_batch!(values, commits, FileLengthValue)
+        }
+        CollectorValue::CumulativeDiffStat(_) => {
+            to_batch!(values, commits, CumulativeDiffStatValue)
+        }
+        CollectorValue::RollingDiffStat(_) => {
+            to_batch!(values, commits, RollingDiffStatValue)
+        }
+        CollectorValue::BuildWarnings(_) => {
+            to_batch!(values, commits, BuildWarningsValue)
+        }
+        CollectorValue::FormattingChanges(_) => {
+            to_batch!(values, commits, FormattingChangesValue)
+        }
+        CollectorValue::ImportCoupling(_) => {
+            to_batch!(values, commits, ImportCouplingValue)
+        }
+        CollectorValue::ConfigFiles(_) => {
+            to_batch!(values, commits, ConfigFilesValue)
+        }
+        CollectorValue::Shellcheck(_) => {
+            to_batch!(values, commits, ShellcheckValue)
+        }
+        CollectorValue::CognitiveComplexity(_) => {
+            to_batch!(values, commits, CognitiveComplexityValue)
+        }
+        CollectorValue::CommentDensity(_) => {
+            to_batch!(values, commits, CommentDensityValue)
+        }
+        CollectorValue::MaintainabilityIndex(_) => {
+            to_batch!(values, commits, MaintainabilityIndexValue)
+        }
+        CollectorValue::Expression(_) => {
+            to_batch!(values, commits, ExpressionValue)
+        }
+        CollectorValue::AuthorDomain(_) => {
+            to_batch!(values, commits, AuthorDomainValue)
+        }
+        CollectorValue::AuthorDomains(_) => {
+            to_batch!(values, commits, AuthorDomainsValue)
+        }
+        CollectorValue::Secrets(_) => {
+            to_batch!(values, commits, SecretsValue)
+        }
+        CollectorValue::HighEntropyStrings(_) => {
+            to_batch!(values, commits, HighEntropyStringsValue)
+        }
+        CollectorValue::Skipped(_) => {
+            to_batch!(values, commits, SkippedValue)
+        }
+        _ => return Err(RecordBatchConversionError::UnsupportedValueType),
+    };
+
+    let batch = match transform {
+        Some(transform) => transform.apply_to_batch(&batch)?,
+        None => batch,
+    };
+
+    let commit_column: (&str, ArrayRef) = if commit_ids {
+        let ids = commits
+            .iter()
+            .map(commit_id)
+            .collect::<Result<UInt64Array, _>>()?;
+
+        ("commit_id", Arc::new(ids))
+    } else {
+        let hashes = StringArray::from(
+            commits
+                .iter()
+                .map(|c| c.to_string())
+                .collect::<Vec<String>>(),
+        );
+
+        ("commit", Arc::new(hashes))
+    };
+
+    let represented: UInt64Array = commits
+        .iter()
+        .map(|commit| commits_represented.get(commit).map(|count| *count as u64))
+        .collect();
+
+    let batch = with_column(&batch, commit_column.0, commit_column.1, true)?;
+    Ok(with_column(
+        &batch,
+        COMMITS_REPRESENTED_COLUMN,
+        Arc::new(represented),
+        false,
+    )?)
+}
+
+/// Unpivots a wide metric table, whose first column references the commits, into one row per
+/// commit and numeric field. Fields of nested values are named by their path, e.g. `totals.loc`.
+fn to_long_layout(batch: &RecordBatch) -> Result<RecordBatch, arrow::error::ArrowError> {
+    let schema = batch.schema();
+    let commit_field = schema.field(0);
+    let commit_column = batch.column(0);
+
+    // Repeated for every field of a commit like the commit itself
+    let represented_column = batch.column_by_name(COMMITS_REPRESENTED_COLUMN);
+
+    let mut numeric_columns: Vec<(String, ArrayRef)> = Vec::new();
+    for (field, column) in schema.fields().iter().zip(batch.columns()).skip(1) {
+        if field.name() != COMMITS_REPRESENTED_COLUMN {
+            collect_numeric_columns(field.name(), column, &mut numeric_columns);
+        }
+    }
+
+    let mut commits: Vec<ArrayRef> = Vec::new();
+    let mut represented: Vec<ArrayRef> = Vec::new();
+    let mut fields: Vec<ArrayRef> = Vec::new();
+    let mut values: Vec<ArrayRef> = Vec::new();
+    for (name, column) in numeric_columns {
+        commits.push(commit_column.clone());
+        represented.extend(represented_column.cloned());
+        fields.push(Arc::new(StringArray::from(vec![name; column.len()])));
+        values.push(cast(&column, &DataType::Float64)?);
+    }
+
+    let concat_or_empty = |arrays: &[ArrayRef], data_type: &DataType| {
+        if arrays.is_empty() {
+            return Ok(new_empty_array(data_type));
+        }
+
+        concat(
+            &arrays
+                .iter()
+                .map(AsRef::as_ref)
+                .collect::<Vec<&dyn Array>>(),
+        )
+    };
+
+    let mut schema_fields = vec![
+        commit_field.clone(),
+        Field::new("field", DataType::Utf8, false),
+        Field::new("value", DataType::Float64, true),
+    ];
+    let mut columns = vec![
+        concat_or_empty(&commits, commit_field.data_type())?,
+        concat_or_empty(&fields, &DataType::Utf8)?,
+        concat_or_empty(&values, &DataType::Float64)?,
+    ];
+
+    if represented_column.is_some() {
+        schema_fields.push(Field::new(
+            COMMITS_REPRESENTED_COLUMN,
+            DataType::UInt64,
+            true,
+        ));
+        columns.push(concat_or_empty(&represented, &DataType::UInt64)?);
+    }
+
+    RecordBatch::try_new(Arc::new(Schema::new(schema_fields)), columns)
+}
+
+fn collect_numeric_columns(name: &str, column: &ArrayRef, columns: &mut Vec<(String, ArrayRef)>) {
+    match column.data_type() {
+        DataType::Struct(_) => {
+            let column = column.as_struct();
+            for (field, child) in column.fields().iter().zip(column.columns()) {
+                collect_numeric_columns(&format!("{name}.{}", field.name()), child, columns);
+            }
+        }
+        data_type if data_type.is_numeric() || *data_type == DataType::Boolean => {
+            columns.push((name.to_string(), column.clone()));
+        }
+        _ => {}
+    }
+}
+
+/// Records the collector of the values in the schema, so [`myaku::load_output`] can read them
+fn with_collector(
+    batch: RecordBatch,
+    value: &CollectorValue,
+) -> Result<RecordBatch, arrow::error::ArrowError> {
+    let collector = serde_json::to_value(value)
+        .ok()
+        .and_then(|value| value.get("collector")?.as_str().map(ToString::to_string));
+
+    let Some(collector) = collector else {
+        return Ok(batch);
+    };
+
+    let schema = Schema::clone(&batch.schema()).with_metadata(HashMap::from([(
+        COLLECTOR_METADATA_KEY.to_string(),
+        collector,
+    )]));
+
+    batch.with_schema(Arc::new(schema))
+}
+
+/// Adds a column in front of the other columns of the batch or after them
+fn with_column(
+    batch: &RecordBatch,
+    name: &str,
+    array: ArrayRef,
+    first: bool,
+) -> Result<RecordBatch, arrow::error::ArrowError> {
+    let field = Arc::new(Field::new(
+        name,
+        array.data_type().clone(),
+        array.is_nullable(),
+    ));
+
+    let mut fields: Vec<FieldRef> = batch.schema().fields().to_vec();
+    let mut arrays: Vec<ArrayRef> = batch.columns().to_vec();
+
+    if first {
+        fields.insert(0, field);
+        arrays.insert(0, array);
+    } else {
+        fields.push(field);
+        arrays.push(array);
+    }
+
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashSet,
+        time::{SystemTime, UNIX_EPOCH},
     };
 
-    let commit_array = StringArray::from(
-        commits
+    use myaku::values::{SkipReason, TotalLocValue};
+    use parquet::basic::Compression;
+
+    use super::*;
+
+    #[test]
+    fn test_runs_are_appended() {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .subsec_nanos();
+        let base = std::env::temp_dir().join(format!("myaku-output-{nanos}"));
+
+        let run = RunMetrics {
+            started_at: 1_700_000_000,
+            head: "abc".to_string(),
+            duration_ms: 1200,
+            metric_count: 2,
+            fresh_task_count: 10,
+            reused_task_count: 5,
+            precomputed_task_count: 5,
+            failed_task_count: 0,
+            quarantined_task_count: 0,
+            skipped_task_count: 1,
+            cache_hit_ratio: Some(0.5),
+            task_duration_ms: 4000,
+            max_task_duration_ms: 900,
+            scanned_bytes: 1024,
+        };
+        let empty_run = RunMetrics {
+            cache_hit_ratio: None,
+            ..run.clone()
+        };
+
+        let mut output = ParquetOutput::new(&base);
+        output.add_run(&run).unwrap();
+        output.add_run(&empty_run).unwrap();
+
+        let file = File::open(base.join("runs.parquet")).unwrap();
+        let runs: Vec<RunMetrics> = ParquetRecordBatchReaderBuilder::try_new(file)
+            .unwrap()
+            .build()
+            .unwrap()
+            .flat_map(|batch| serde_arrow::from_record_batch::<Vec<_>>(&batch.unwrap()).unwrap())
+            .collect();
+
+        assert_eq!(runs, vec![run, empty_run]);
+
+        fs::remove_dir_all(base).unwrap();
+    }
+
+    #[test]
+    fn test_campaigns_are_written() {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .subsec_nanos();
+        let base = std::env::temp_dir().join(format!("myaku-output-campaigns-{nanos}"));
+
+        let projection = CampaignProjection {
+            metric: "deprecated-calls".to_string(),
+            target: 0.0,
+            commit: CommitHash("abc".to_string()),
+            value: 40.0,
+            change_per_day: -2.0,
+            projected_date: "2024-03-01".parse().ok(),
+        };
+        let stalled = CampaignProjection {
+            metric: "todos".to_string(),
+            change_per_day: 0.0,
+            projected_date: None,
+            ..projection.clone()
+        };
+
+        let mut output = ParquetOutput::new(&base);
+        output
+            .set_campaigns(&[projection.clone(), stalled.clone()])
+            .unwrap();
+
+        let file = File::open(base.join("campaigns.parquet")).unwrap();
+        let projections: Vec<CampaignProjection> = ParquetRecordBatchReaderBuilder::try_new(file)
+            .unwrap()
+            .build()
+            .unwrap()
+            .flat_map(|batch| serde_arrow::from_record_batch::<Vec<_>>(&batch.unwrap()).unwrap())
+            .collect();
+
+        assert_eq!(projections, vec![projection, stalled]);
+
+        fs::remove_dir_all(base).unwrap();
+    }
+
+    #[test]
+    fn test_written_values_can_be_loaded() {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .subsec_nanos();
+        let base = std::env::temp_dir().join(format!("myaku-output-load-{nanos}"));
+
+        let commits: Vec<CommitInfo> = ["0a", "0b", "0c"]
+            .iter()
+            .zip(1..)
+            .map(|(prefix, time)| {
+                serde_json::from_value(serde_json::json!({
+                    "id": prefix.repeat(20),
+                    "author": {"name": null, "email": "jane@example.com"},
+                    "committer": {"name": null, "email": "jane@example.com"},
+                    "message": null,
+                    "time": time,
+                }))
+                .unwrap()
+            })
+            .collect();
+
+        let mut output = ParquetOutput::new(&base).with_commit_ids(true);
+        output.set_commits(&commits).unwrap();
+        output
+            .set_commit_tags(&[CommitTagInfo {
+                name: "v1".to_string(),
+                commit: commits[2].id.clone(),
+            }])
+            .unwrap();
+
+        let values = [
+            CollectorValue::TotalLoc(TotalLocValue { loc: 10 }),
+            CollectorValue::TotalLoc(TotalLocValue { loc: 20 }),
+            CollectorValue::Skipped(SkippedValue {
+                reason: SkipReason::TooLarge,
+                file_count: 3,
+                total_bytes: 4,
+            }),
+        ];
+        for (commit, value) in commits.iter().zip(&values) {
+            output.set_metric("rust/loc", &commit.id, value).unwrap();
+        }
+        output
+            .set_commits_represented("rust/loc", &HashMap::from([(commits[1].id.clone(), 2)]))
+            .unwrap();
+        output.flush().unwrap();
+
+        let data = myaku::load_output(&base).unwrap();
+
+        assert_eq!(data.commits.len(), 3);
+        assert_eq!(data.tags.as_ref().unwrap()[0].commit, commits[2].id);
+        assert_eq!(
+            data.commits_represented["rust/loc"],
+            HashMap::from([(commits[1].id.clone(), 2)])
+        );
+
+        let series: Vec<(&CommitHash, &CollectorValue)> = data
+            .series("rust/loc")
             .into_iter()
-            .map(|c| c.to_string())
-            .collect::<Vec<String>>(),
-    );
+            .map(|(commit, value)| (&commit.id, value))
+            .collect();
+        assert!(matches!(
+            series.as_slice(),
+            [
+                (_, CollectorValue::TotalLoc(TotalLocValue { loc: 10 })),
+                (_, CollectorValue::TotalLoc(TotalLocValue { loc: 20 })),
+                (
+                    _,
+                    CollectorValue::Skipped(SkippedValue { file_count: 3, .. })
+                ),
+            ]
+        ));
+
+        fs::remove_dir_all(base).unwrap();
+    }
+
+    #[test]
+    fn test_long_layout_with_zstd() {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .subsec_nanos();
+        let base = std::env::temp_dir().join(format!("myaku-output-long-{nanos}"));
+
+        let mut output = ParquetOutput::new(&base)
+            .with_compression(OutputCompression::Zstd)
+            .with_layout(OutputLayout::Long);
+        for (commit, loc) in [("a", 10), ("b", 20)] {
+            let value = CollectorValue::TotalLoc(TotalLocValue { loc });
+            output
+                .set_metric("loc", &CommitHash(commit.to_string()), &value)
+                .unwrap();
+        }
+
+        // Only `a` is sampled by the metric, its value stands for two more skipped commits
+        output
+            .set_commits_represented("loc", &HashMap::from([(CommitHash("a".to_string()), 3)]))
+            .unwrap();
+        output.flush().unwrap();
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(
+            File::open(base.join("metrics/loc/data.parquet")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            reader.metadata().row_group(0).column(0).compression(),
+            Compression::ZSTD(parquet::basic::ZstdLevel::default())
+        );
+
+        let batches: Vec<RecordBatch> = reader.build().unwrap().map(Result::unwrap).collect();
+        let batch = arrow::compute::concat_batches(&batches[0].schema(), &batches).unwrap();
 
-    let commit_field = Field::new(
-        "commit",
-        commit_array.data_type().clone(),
-        commit_array.is_nullable(),
-    );
+        let column = |name: &str| batch.column_by_name(name).unwrap().clone();
+        let mut rows: Vec<(String, String, f64, Option<u64>)> = (0..batch.num_rows())
+            .map(|row| {
+                let represented = column("commits_represented");
+                let represented = represented.as_primitive::<arrow::datatypes::UInt64Type>();
 
-    let commit_fields: Vec<Arc<Field>> = vec![Arc::new(commit_field)];
-    let data_fields: Vec<Arc<Field>> = batch.schema().fields().to_vec();
-    let combined_fields: Vec<Arc<Field>> = [commit_fields, data_fields].concat();
+                (
+                    column("commit").as_string::<i32>().value(row).to_string(),
+                    column("field").as_string::<i32>().value(row).to_string(),
+                    column("value")
+                        .as_primitive::<arrow::datatypes::Float64Type>()
+                        .value(row),
+                    represented.is_valid(row).then(|| represented.value(row)),
+                )
+            })
+            .collect();
+        rows.sort_by(|a, b| a.0.cmp(&b.0));
 
-    let combined_schema = Schema::new(combined_fields);
+        assert_eq!(
+            rows,
+            [
+                ("a".to_string(), "loc".to_string(), 10.0, Some(3)),
+                ("b".to_string(), "loc".to_string(), 20.0, None)
+            ]
+        );
 
-    let commit_arrays: Vec<ArrayRef> = vec![Arc::new(commit_array)];
-    let data_arrays: Vec<ArrayRef> = batch.columns().to_vec();
-    let combined_arrays: Vec<ArrayRef> = [commit_arrays, data_arrays].concat();
+        fs::remove_dir_all(base).unwrap();
+    }
+
+    #[test]
+    fn test_commit_ids_shrink_metric_tables() {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .subsec_nanos();
 
-    let combined_batch = RecordBatch::try_new(Arc::new(combined_schema), combined_arrays)?;
+        // Hashes are random, so they barely compress
+        let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+        let commits: Vec<CommitHash> = (0..10_000)
+            .map(|_| {
+                let hash: String = (0..5)
+                    .map(|_| {
+                        state ^= state << 13;
+                        state ^= state >> 7;
+                        state ^= state << 17;
+                        format!("{:08x}", state as u32)
+                    })
+                    .collect();
+                CommitHash(hash)
+            })
+            .collect();
 
-    Ok(combined_batch)
+        let write = |commit_ids: bool| {
+            let base = std::env::temp_dir().join(format!("myaku-output-{nanos}-{commit_ids}"));
+
+            let mut output = ParquetOutput::new(&base).with_commit_ids(commit_ids);
+            for (index, commit) in commits.iter().enumerate() {
+                let value = CollectorValue::TotalLoc(TotalLocValue {
+                    loc: u32::try_from(index).unwrap(),
+                });
+                output.set_metric("loc", commit, &value).unwrap();
+            }
+            output.flush().unwrap();
+
+            let file_path = base.join("metrics/loc/data.parquet");
+            let size = fs::metadata(&file_path).unwrap().len();
+            let batches: Vec<RecordBatch> =
+                ParquetRecordBatchReaderBuilder::try_new(File::open(&file_path).unwrap())
+                    .unwrap()
+                    .build()
+                    .unwrap()
+                    .map(Result::unwrap)
+                    .collect();
+            let batch = arrow::compute::concat_batches(&batches[0].schema(), &batches).unwrap();
+
+            fs::remove_dir_all(base).unwrap();
+            (size, batch)
+        };
+
+        let (hash_size, hash_batch) = write(false);
+        let (id_size, id_batch) = write(true);
+
+        assert!(hash_batch.column_by_name("commit").is_some());
+        assert!(id_batch.column_by_name("commit").is_none());
+
+        let ids = id_batch
+            .column_by_name("commit_id")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .unwrap();
+        let expected: HashSet<u64> = commits.iter().map(|c| commit_id(c).unwrap()).collect();
+        assert_eq!(
+            ids.values().iter().copied().collect::<HashSet<_>>(),
+            expected
+        );
+
+        // About 480 KB with hashes and 160 KB with ids
+        assert!(
+            id_size * 2 < hash_size,
+            "{id_size} bytes with ids, {hash_size} bytes with hashes"
+        );
+    }
 }
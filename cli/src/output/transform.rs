@@ -0,0 +1,243 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
+
+use arrow::{
+    array::{
+        Array, ArrayRef, AsArray, GenericListArray, OffsetSizeTrait, RecordBatch, StructArray,
+    },
+    compute::kernels::arity::unary,
+    datatypes::{DataType, Field, FieldRef, Float64Type, Schema},
+    error::ArrowError,
+};
+use serde::{Deserialize, Serialize};
+
+/// Changes applied to the values of a metric right before they are written, e.g. to strip
+/// sensitive or large fields without changing the collector.
+///
+/// Fields are referenced by their path in the original value, nested fields separated by dots,
+/// e.g. `matches.submatches`. Lists are passed through, so a path applies to the field of every
+/// item. Paths which don't exist are ignored.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ValueTransform {
+    /// Fields left out of the output
+    #[serde(default)]
+    pub drop: BTreeSet<String>,
+
+    /// New names of fields, only the last segment of the path is renamed
+    #[serde(default)]
+    pub rename: BTreeMap<String, String>,
+
+    /// Number of decimal places of floating point fields
+    #[serde(default)]
+    pub round: BTreeMap<String, u32>,
+}
+
+fn join_path(parent: &str, name: &str) -> String {
+    if parent.is_empty() {
+        name.to_string()
+    } else {
+        format!("{parent}.{name}")
+    }
+}
+
+fn round(value: f64, places: u32) -> f64 {
+    let factor = 10f64.powi(i32::try_from(places).unwrap_or(i32::MAX));
+    (value * factor).round() / factor
+}
+
+impl ValueTransform {
+    /// Transforms a value serialized as JSON
+    pub fn apply_to_json(&self, value: &mut serde_json::Value) {
+        self.transform_json("", value);
+    }
+
+    fn transform_json(&self, path: &str, value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::Array(items) => {
+                for item in items {
+                    self.transform_json(path, item);
+                }
+            }
+            serde_json::Value::Object(fields) => {
+                let transformed = std::mem::take(fields)
+                    .into_iter()
+                    .filter_map(|(name, mut field)| {
+                        let path = join_path(path, &name);
+                        if self.drop.contains(&path) {
+                            return None;
+                        }
+
+                        self.transform_json(&path, &mut field);
+
+                        let name = self.rename.get(&path).cloned().unwrap_or(name);
+                        Some((name, field))
+                    })
+                    .collect();
+                *fields = transformed;
+            }
+            serde_json::Value::Number(number) if number.is_f64() => {
+                if let (Some(places), Some(float)) = (self.round.get(path), number.as_f64()) {
+                    if let Some(rounded) = serde_json::Number::from_f64(round(float, *places)) {
+                        *number = rounded;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Transforms the columns of a metric table holding the fields of its values
+    pub fn apply_to_batch(&self, batch: &RecordBatch) -> Result<RecordBatch, ArrowError> {
+        let (fields, columns) =
+            self.transform_fields("", batch.schema().fields(), batch.columns())?;
+
+        RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+    }
+
+    fn transform_fields(
+        &self,
+        parent: &str,
+        fields: &[FieldRef],
+        columns: &[ArrayRef],
+    ) -> Result<(Vec<FieldRef>, Vec<ArrayRef>), ArrowError> {
+        let mut transformed_fields = Vec::new();
+        let mut transformed_columns = Vec::new();
+
+        for (field, column) in fields.iter().zip(columns) {
+            let path = join_path(parent, field.name());
+            if self.drop.contains(&path) {
+                continue;
+            }
+
+            let column = self.transform_array(&path, column)?;
+            let name = self.rename.get(&path).unwrap_or(field.name());
+
+            transformed_fields.push(Arc::new(
+                Field::new(name, column.data_type().clone(), field.is_nullable())
+                    .with_metadata(field.metadata().clone()),
+            ));
+            transformed_columns.push(column);
+        }
+
+        Ok((transformed_fields, transformed_columns))
+    }
+
+    fn transform_array(&self, path: &str, array: &ArrayRef) -> Result<ArrayRef, ArrowError> {
+        Ok(match array.data_type() {
+            DataType::Float64 => match self.round.get(path) {
+                Some(places) => Arc::new(unary::<Float64Type, _, Float64Type>(
+                    array.as_primitive(),
+                    |value| round(value, *places),
+                )),
+                None => array.clone(),
+            },
+            DataType::Struct(fields) => {
+                let array = array.as_struct();
+                let (fields, columns) = self.transform_fields(path, fields, array.columns())?;
+
+                Arc::new(StructArray::try_new(
+                    fields.into(),
+                    columns,
+                    array.nulls().cloned(),
+                )?)
+            }
+            DataType::List(item) => self.transform_list(path, item, array.as_list::<i32>())?,
+            DataType::LargeList(item) => self.transform_list(path, item, array.as_list::<i64>())?,
+            _ => array.clone(),
+        })
+    }
+
+    fn transform_list<O: OffsetSizeTrait>(
+        &self,
+        path: &str,
+        item: &FieldRef,
+        array: &GenericListArray<O>,
+    ) -> Result<ArrayRef, ArrowError> {
+        let values = self.transform_array(path, array.values())?;
+        let item = item
+            .as_ref()
+            .clone()
+            .with_data_type(values.data_type().clone());
+
+        Ok(Arc::new(GenericListArray::<O>::try_new(
+            Arc::new(item),
+            array.offsets().clone(),
+            values,
+            array.nulls().cloned(),
+        )?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use arrow::array::Float64Array;
+    use serde_arrow::schema::{SchemaLike, TracingOptions};
+
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    struct Item {
+        text: String,
+        score: f64,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Value {
+        average: f64,
+        items: Vec<Item>,
+    }
+
+    fn transform() -> ValueTransform {
+        ValueTransform {
+            drop: BTreeSet::from(["items.text".to_string()]),
+            rename: BTreeMap::from([("average".to_string(), "mean".to_string())]),
+            round: BTreeMap::from([("average".to_string(), 1), ("items.score".to_string(), 0)]),
+        }
+    }
+
+    fn value() -> Value {
+        Value {
+            average: 1.26,
+            items: vec![Item {
+                text: "secret".to_string(),
+                score: 2.7,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_apply_to_json() {
+        let mut json = serde_json::to_value(value()).unwrap();
+        transform().apply_to_json(&mut json);
+
+        assert_eq!(
+            json,
+            serde_json::json!({"mean": 1.3, "items": [{"score": 3.0}]})
+        );
+    }
+
+    #[test]
+    fn test_apply_to_batch() {
+        let fields = Vec::<FieldRef>::from_type::<Value>(TracingOptions::default()).unwrap();
+        let batch = serde_arrow::to_record_batch(&fields, &[value()]).unwrap();
+
+        let batch = transform().apply_to_batch(&batch).unwrap();
+
+        let mean: &Float64Array = batch.column_by_name("mean").unwrap().as_primitive();
+        assert!((mean.value(0) - 1.3).abs() < f64::EPSILON);
+        assert!(batch.column_by_name("average").is_none());
+
+        let items = batch.column_by_name("items").unwrap();
+        let items = match items.data_type() {
+            DataType::LargeList(_) => items.as_list::<i64>().values().clone(),
+            _ => items.as_list::<i32>().values().clone(),
+        };
+        let items = items.as_struct();
+        assert!(items.column_by_name("text").is_none());
+        let score: &Float64Array = items.column_by_name("score").unwrap().as_primitive();
+        assert!((score.value(0) - 3.0).abs() < f64::EPSILON);
+    }
+}
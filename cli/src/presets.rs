@@ -0,0 +1,36 @@
+use std::collections::HashMap;
+
+use myaku::MetricConfig;
+
+/// Presets shipped with the CLI, selectable by name like the presets of a config file
+const BUILTIN_PRESETS: [(&str, &str); 2] = [
+    ("rust-basics", include_str!("../presets/rust-basics.toml")),
+    ("web-frontend", include_str!("../presets/web-frontend.toml")),
+];
+
+/// Metrics of the built-in preset with the given name
+pub fn builtin(name: &str) -> Option<HashMap<String, MetricConfig>> {
+    let (_, source) = BUILTIN_PRESETS.iter().find(|(preset, _)| *preset == name)?;
+
+    Some(toml::from_str(source).expect("Built-in presets are valid"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_builtin_presets_are_valid() {
+        for (name, _) in BUILTIN_PRESETS {
+            for (metric_name, metric) in builtin(name).unwrap() {
+                assert!(
+                    metric.validate().is_empty(),
+                    "{name}.{metric_name} is invalid: {:?}",
+                    metric.validate()
+                );
+            }
+        }
+
+        assert!(builtin("unknown").is_none());
+    }
+}
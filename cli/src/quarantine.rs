@@ -0,0 +1,35 @@
+use std::{fs, path::Path};
+
+use anyhow::Result;
+use myaku::{CommitHash, Quarantine};
+
+/// Reads the tasks quarantined by previous runs, an empty quarantine if there were none
+pub fn load(path: &Path) -> Result<Quarantine> {
+    if !path.exists() {
+        return Ok(Quarantine::default());
+    }
+
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+/// Adds the failed tasks to the quarantine file, returns the number of newly quarantined tasks
+pub fn record(path: &Path, failed_tasks: &[(String, CommitHash)]) -> Result<usize> {
+    let mut quarantine = load(path)?;
+
+    let added = failed_tasks
+        .iter()
+        .filter(|(collector_id, commit_hash)| {
+            quarantine.insert(collector_id.clone(), commit_hash.clone())
+        })
+        .count();
+
+    if added > 0 {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(path, serde_json::to_string_pretty(&quarantine)?)?;
+    }
+
+    Ok(added)
+}
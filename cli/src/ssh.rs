@@ -0,0 +1,132 @@
+use std::path::{Path, PathBuf};
+
+use console::Term;
+use ssh_key::PrivateKey;
+use thiserror::Error;
+
+/// Passphrase of an encrypted key, used instead of prompting for it
+pub const PASSPHRASE_ENV: &str = "MYAKU_SSH_KEY_PASSPHRASE";
+
+#[derive(Error, Debug)]
+pub enum SshKeyError {
+    #[error("Could not read SSH key {path}")]
+    Read {
+        path: PathBuf,
+        #[source]
+        source: ssh_key::Error,
+    },
+
+    #[error("SSH key {0} is encrypted, but no passphrase was given in {PASSPHRASE_ENV}")]
+    MissingPassphrase(PathBuf),
+
+    #[error("Could not read the passphrase of SSH key {path}")]
+    Prompt {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("Could not decrypt SSH key {path}, the passphrase might be wrong")]
+    Decrypt {
+        path: PathBuf,
+        #[source]
+        source: ssh_key::Error,
+    },
+}
+
+impl SshKeyError {
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Read { path, .. } | Self::Prompt { path, .. } | Self::Decrypt { path, .. } => {
+                path
+            }
+            Self::MissingPassphrase(path) => path,
+        }
+    }
+}
+
+/// Reads a private key in the OpenSSH format. The passphrase of an encrypted key is taken from
+/// [`PASSPHRASE_ENV`] or, if a terminal is given, prompted for.
+pub fn load(path: &Path, term: Option<&Term>) -> Result<PrivateKey, SshKeyError> {
+    let key = PrivateKey::read_openssh_file(path).map_err(|source| SshKeyError::Read {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    if !key.is_encrypted() {
+        return Ok(key);
+    }
+
+    let passphrase = match (std::env::var(PASSPHRASE_ENV), term) {
+        (Ok(passphrase), _) => passphrase,
+        (Err(_), Some(term)) if term.is_term() => {
+            let prompt = || -> std::io::Result<String> {
+                term.write_str(&format!("Passphrase for {}: ", path.display()))?;
+                let passphrase = term.read_secure_line()?;
+                term.clear_line()?;
+                Ok(passphrase)
+            };
+
+            prompt().map_err(|source| SshKeyError::Prompt {
+                path: path.to_path_buf(),
+                source,
+            })?
+        }
+        (Err(_), _) => return Err(SshKeyError::MissingPassphrase(path.to_path_buf())),
+    };
+
+    key.decrypt(passphrase)
+        .map_err(|source| SshKeyError::Decrypt {
+            path: path.to_path_buf(),
+            source,
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use ssh_key::{rand_core::OsRng, Algorithm, EcdsaCurve, LineEnding};
+
+    use super::*;
+
+    #[test]
+    fn test_load_encrypted_key() {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .subsec_nanos();
+        let path = std::env::temp_dir().join(format!("myaku-key-{nanos}"));
+
+        let key = PrivateKey::random(
+            &mut OsRng,
+            Algorithm::Ecdsa {
+                curve: EcdsaCurve::NistP256,
+            },
+        )
+        .unwrap();
+        key.encrypt(&mut OsRng, "secret")
+            .unwrap()
+            .write_openssh_file(&path, LineEnding::default())
+            .unwrap();
+
+        // Without a terminal to prompt on, the passphrase has to be given in the environment
+        std::env::remove_var(PASSPHRASE_ENV);
+        assert!(matches!(
+            load(&path, None),
+            Err(SshKeyError::MissingPassphrase(_))
+        ));
+
+        std::env::set_var(PASSPHRASE_ENV, "wrong");
+        assert!(matches!(
+            load(&path, None),
+            Err(SshKeyError::Decrypt { .. })
+        ));
+
+        std::env::set_var(PASSPHRASE_ENV, "secret");
+        assert_eq!(load(&path, None).unwrap(), key);
+
+        std::env::remove_var(PASSPHRASE_ENV);
+        std::fs::remove_file(path).unwrap();
+    }
+}
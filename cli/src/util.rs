@@ -17,6 +17,50 @@ pub fn get_repository_name_from_url(url: &str) -> Option<String> {
     re.captures(url).map(|caps| caps["main"].to_string())
 }
 
+/// Host and path of a repository URL without its scheme, user and `.git` suffix, e.g.
+/// `github.com/user/repo` for both `https://github.com/user/repo.git` and
+/// `git@github.com:user/repo`
+pub fn normalize_url(url: &str) -> Option<String> {
+    let url = url.trim().trim_end_matches('/');
+
+    let (authority, path) = match url.split_once("://") {
+        Some((_, rest)) => rest.split_once('/')?,
+        // The scp-like syntax of SSH URLs
+        None => url.split_once(':')?,
+    };
+
+    let host = authority.rsplit('@').next()?;
+    let host = host.split(':').next()?.to_lowercase();
+
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    let segments: Vec<&str> = path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect();
+
+    if host.is_empty()
+        || segments.is_empty()
+        || segments
+            .iter()
+            .any(|segment| *segment == "." || *segment == "..")
+    {
+        return None;
+    }
+
+    Some(format!("{host}/{}", segments.join("/")))
+}
+
+/// Commit hashes listed one per line, e.g. by `git rev-list`, ignoring empty lines and `#`
+/// comments
+pub fn parse_commits(contents: &str) -> Vec<myaku::CommitHash> {
+    contents
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+        .map(|hash| myaku::CommitHash(hash.to_string()))
+        .collect()
+}
+
 mod test {
     #[test]
     fn test_get_repository_name_from_url_case_https_github_com_user_repo() {
@@ -38,4 +82,34 @@ mod test {
         let expected = Some("user/repo".to_string());
         assert_eq!(&result, &expected);
     }
+
+    #[test]
+    fn test_parse_commits() {
+        let commits = super::parse_commits("# releases\n3f1a9b  # v1.0\n\n  c0ffee\n");
+        let hashes: Vec<&str> = commits.iter().map(|commit| commit.0.as_str()).collect();
+        assert_eq!(hashes, ["3f1a9b", "c0ffee"]);
+    }
+
+    #[test]
+    fn test_normalize_url() {
+        for url in [
+            "https://github.com/user/repo",
+            "https://token@GitHub.com/user/repo.git/",
+            "ssh://git@github.com:22/user/repo.git",
+            "git@github.com:user/repo.git",
+        ] {
+            assert_eq!(
+                super::normalize_url(url).as_deref(),
+                Some("github.com/user/repo"),
+                "{url}"
+            );
+        }
+
+        assert_eq!(
+            super::normalize_url("https://gitlab.com/group/subgroup/repo").as_deref(),
+            Some("gitlab.com/group/subgroup/repo")
+        );
+        assert_eq!(super::normalize_url("/home/user/repo"), None);
+        assert_eq!(super::normalize_url("https://github.com/../repo"), None);
+    }
 }
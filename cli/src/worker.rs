@@ -0,0 +1,398 @@
+//! gRPC service mode for running collections on remote workers.
+//!
+//! Every worker manages its own reference repositories, cache and worktrees below `.myaku`,
+//! exactly like the `collect` command does.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError, TryLockError};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use myaku::{CommitHash, ExecutionProgressCallbackState};
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::{Stream, StreamExt};
+use tonic::{Request, Response, Status};
+use tracing::{error, info};
+
+use crate::config::{Config, ConfigFile};
+use crate::error::ErrorReport;
+use crate::ssh;
+use crate::util;
+use crate::{with_progress, RepositoryCollection};
+
+mod proto {
+    tonic::include_proto!("myaku.worker");
+}
+
+use proto::progress_event::{
+    Event, Failed, Finished, Initial as InitialEvent, MetricFinished, Task, TaskRetried,
+};
+use proto::worker_server::{Worker, WorkerServer};
+use proto::{JobRequest, JobStatus, ProgressEvent, ResultsResponse, SubmitRequest, SubmitResponse};
+
+const EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+/// Finished jobs are forgotten after this time, their results have to be fetched before
+const FINISHED_JOB_TTL: Duration = Duration::from_secs(60 * 60);
+
+struct Job {
+    /// All events emitted so far, replayed to late subscribers
+    events: Vec<ProgressEvent>,
+
+    /// Dropped once the job completed, which ends all progress streams
+    sender: Option<broadcast::Sender<ProgressEvent>>,
+
+    status: JobStatus,
+    results: Option<String>,
+    error: Option<String>,
+    error_code: Option<String>,
+
+    finished_at: Option<Instant>,
+}
+
+impl Job {
+    fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+
+        Self {
+            events: Vec::new(),
+            sender: Some(sender),
+            status: JobStatus::Running,
+            results: None,
+            error: None,
+            error_code: None,
+            finished_at: None,
+        }
+    }
+
+    fn push(&mut self, event: Event) {
+        let event = ProgressEvent { event: Some(event) };
+
+        if let Some(sender) = &self.sender {
+            // Sending only fails if nobody is subscribed, the event is kept in the history anyway
+            let _ = sender.send(event.clone());
+        }
+
+        self.events.push(event);
+    }
+
+    fn complete(&mut self, result: Result<String>) {
+        match result {
+            Ok(results) => {
+                self.push(Event::Finished(Finished {}));
+                self.status = JobStatus::Succeeded;
+                self.results = Some(results);
+            }
+            Err(e) => {
+                let code = ErrorReport::from(&e).code.to_string();
+
+                self.push(Event::Failed(Failed {
+                    error: format!("{e:#}"),
+                    code: code.clone(),
+                }));
+                self.status = JobStatus::Failed;
+                self.error = Some(format!("{e:#}"));
+                self.error_code = Some(code);
+            }
+        }
+
+        self.sender = None;
+        self.finished_at = Some(Instant::now());
+    }
+
+    fn is_expired(&self) -> bool {
+        self.finished_at
+            .is_some_and(|finished_at| finished_at.elapsed() > FINISHED_JOB_TTL)
+    }
+}
+
+type Jobs = Arc<Mutex<HashMap<String, Job>>>;
+
+/// Locks per repository name, jobs of the same repository share its clone and worktrees so they
+/// run one after another
+type RepositoryLocks = Arc<Mutex<HashMap<String, Arc<Mutex<()>>>>>;
+
+#[derive(Default)]
+struct WorkerService {
+    jobs: Jobs,
+    repository_locks: RepositoryLocks,
+    next_job_id: AtomicU64,
+}
+
+fn progress_event(state: ExecutionProgressCallbackState) -> Option<Event> {
+    let task = |collector_id, commit_hash: CommitHash, reused, error, error_code| Task {
+        collector_id,
+        commit_hash: commit_hash.to_string(),
+        reused,
+        error,
+        error_code,
+    };
+
+    match state {
+        ExecutionProgressCallbackState::Initial {
+            metric_count,
+            task_count,
+            precomputed_task_count,
+            quarantined_task_count,
+            skipped_task_count,
+        } => Some(Event::Initial(InitialEvent {
+            metric_count: metric_count as u64,
+            task_count: task_count as u64,
+            precomputed_task_count: precomputed_task_count as u64,
+            quarantined_task_count: quarantined_task_count as u64,
+            skipped_task_count: skipped_task_count as u64,
+        })),
+        ExecutionProgressCallbackState::New {
+            collector_id,
+            commit_hash,
+            ..
+        } => Some(Event::Task(task(
+            collector_id,
+            commit_hash,
+            false,
+            None,
+            None,
+        ))),
+        ExecutionProgressCallbackState::Reused {
+            collector_id,
+            commit_hash,
+        } => Some(Event::Task(task(
+            collector_id,
+            commit_hash,
+            true,
+            None,
+            None,
+        ))),
+        ExecutionProgressCallbackState::TaskFailed {
+            collector_id,
+            commit_hash,
+            code,
+            error,
+        } => Some(Event::Task(task(
+            collector_id,
+            commit_hash,
+            false,
+            Some(error),
+            Some(code.to_string()),
+        ))),
+        ExecutionProgressCallbackState::TaskRetried {
+            collector_id,
+            commit_hash,
+            attempt,
+            code,
+            error,
+        } => Some(Event::TaskRetried(TaskRetried {
+            collector_id,
+            commit_hash: commit_hash.to_string(),
+            attempt,
+            error,
+            error_code: code.to_string(),
+        })),
+        ExecutionProgressCallbackState::MetricFinished {
+            metric_name,
+            latest_value,
+        } => Some(Event::MetricFinished(MetricFinished {
+            metric_name,
+            latest_value: latest_value.and_then(|value| serde_json::to_string(&value).ok()),
+        })),
+        // The job itself emits the finished event once the results are available
+        ExecutionProgressCallbackState::Finished => None,
+        // Events added later are not part of the protocol yet
+        _ => None,
+    }
+}
+
+fn run_job(
+    jobs: &Jobs,
+    repository_locks: &RepositoryLocks,
+    job_id: &str,
+    config: &str,
+    no_cache: bool,
+    offline: bool,
+) -> Result<String> {
+    let config: Config = ConfigFile::parse(config)?.into_single()?;
+
+    let repository_name =
+        util::get_repository_name_from_url(&config.reference.url).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Cannot determine repository name from URL: {}",
+                config.reference.url
+            )
+        })?;
+
+    let repository_lock = repository_locks
+        .lock()
+        .expect("Failed to lock repository locks")
+        .entry(repository_name.clone())
+        .or_default()
+        .clone();
+
+    // A panicked job poisons the lock, the next fetch recovers the repository from it anyway
+    let _repository_guard = match repository_lock.try_lock() {
+        Ok(guard) => guard,
+        Err(TryLockError::Poisoned(e)) => e.into_inner(),
+        Err(TryLockError::WouldBlock) => {
+            info!(
+                "Job {} waits for the running job of repository {}",
+                job_id, repository_name
+            );
+            repository_lock
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+        }
+    };
+
+    // Nobody could be asked for a passphrase, it has to be given in the environment
+    let ssh_key = match &config.ssh_key_path {
+        Some(path) if !offline => Some(ssh::load(path, None)?),
+        _ => None,
+    };
+
+    let RepositoryCollection {
+        initial,
+        worktree_dir,
+        ..
+    } = RepositoryCollection::prepare(config, &repository_name, ssh_key, no_cache, offline, false)?;
+
+    let jobs = jobs.clone();
+    let job_id = job_id.to_string();
+    let on_progress = move |state| {
+        let Some(event) = progress_event(state) else {
+            return;
+        };
+
+        let mut jobs = jobs.lock().expect("Failed to lock jobs");
+        if let Some(job) = jobs.get_mut(&job_id) {
+            job.push(event);
+        }
+    };
+
+    let process = with_progress(on_progress, |tx| initial.run(worktree_dir, Some(tx)))?;
+
+    Ok(process?.results_json()?)
+}
+
+type ProgressStream = Pin<Box<dyn Stream<Item = Result<ProgressEvent, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl Worker for WorkerService {
+    async fn submit(
+        &self,
+        request: Request<SubmitRequest>,
+    ) -> Result<Response<SubmitResponse>, Status> {
+        let SubmitRequest {
+            config,
+            no_cache,
+            offline,
+        } = request.into_inner();
+
+        let job_id = self.next_job_id.fetch_add(1, Ordering::Relaxed).to_string();
+
+        {
+            let mut jobs = self.jobs.lock().expect("Failed to lock jobs");
+            jobs.retain(|_, job| !job.is_expired());
+            jobs.insert(job_id.clone(), Job::new());
+        }
+
+        info!("Starting job {}", job_id);
+
+        let jobs = self.jobs.clone();
+        let repository_locks = self.repository_locks.clone();
+        let movable_job_id = job_id.clone();
+        tokio::task::spawn_blocking(move || {
+            let job_id = movable_job_id;
+            let result = run_job(
+                &jobs,
+                &repository_locks,
+                &job_id,
+                &config,
+                no_cache,
+                offline,
+            );
+
+            match &result {
+                Ok(_) => info!("Finished job {}", job_id),
+                Err(e) => error!("Job {} failed: {:#}", job_id, e),
+            }
+
+            let mut jobs = jobs.lock().expect("Failed to lock jobs");
+            if let Some(job) = jobs.get_mut(&job_id) {
+                job.complete(result);
+            }
+        });
+
+        Ok(Response::new(SubmitResponse { job_id }))
+    }
+
+    type StreamProgressStream = ProgressStream;
+
+    async fn stream_progress(
+        &self,
+        request: Request<JobRequest>,
+    ) -> Result<Response<Self::StreamProgressStream>, Status> {
+        let job_id = request.into_inner().job_id;
+
+        let jobs = self.jobs.lock().expect("Failed to lock jobs");
+        let Some(job) = jobs.get(&job_id) else {
+            return Err(Status::not_found(format!("Unknown job {job_id}")));
+        };
+
+        // History and subscription are taken under the same lock, so no event is missed or
+        // delivered twice
+        let history = tokio_stream::iter(job.events.clone()).map(Ok);
+        let live = job.sender.as_ref().map(broadcast::Sender::subscribe);
+        drop(jobs);
+
+        let stream: ProgressStream = match live {
+            Some(receiver) => {
+                Box::pin(history.chain(BroadcastStream::new(receiver).map(|event| {
+                    event.map_err(|BroadcastStreamRecvError::Lagged(skipped)| {
+                        Status::data_loss(format!("Skipped {skipped} progress events"))
+                    })
+                })))
+            }
+            None => Box::pin(history),
+        };
+
+        Ok(Response::new(stream))
+    }
+
+    async fn fetch_results(
+        &self,
+        request: Request<JobRequest>,
+    ) -> Result<Response<ResultsResponse>, Status> {
+        let job_id = request.into_inner().job_id;
+
+        let jobs = self.jobs.lock().expect("Failed to lock jobs");
+        let Some(job) = jobs.get(&job_id) else {
+            return Err(Status::not_found(format!("Unknown job {job_id}")));
+        };
+
+        Ok(Response::new(ResultsResponse {
+            status: job.status.into(),
+            results: job.results.clone(),
+            error: job.error.clone(),
+            error_code: job.error_code.clone(),
+        }))
+    }
+}
+
+pub fn serve(listen: SocketAddr) -> Result<()> {
+    let runtime = tokio::runtime::Runtime::new()?;
+
+    runtime.block_on(async {
+        info!("Listening on {}", listen);
+
+        tonic::transport::Server::builder()
+            .add_service(WorkerServer::new(WorkerService::default()))
+            .serve(listen)
+            .await
+    })?;
+
+    Ok(())
+}
@@ -0,0 +1,43 @@
+#ifndef MYAKU_H
+#define MYAKU_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define MYAKU_OK 0
+#define MYAKU_ERROR 1
+#define MYAKU_INVALID_ARGUMENT 2
+
+typedef struct MyakuHandle MyakuHandle;
+
+/* Receives a JSON encoded progress event. Invoked from a background thread. */
+typedef void (*myaku_progress_callback)(const char *event, void *user_data);
+
+MyakuHandle *myaku_init(void);
+
+/* Runs a collection for the JSON encoded config. The callback may be NULL. Results are
+   returned as JSON unless the config sets "result_format": "arrow_ipc". */
+int32_t myaku_run(MyakuHandle *handle, const char *config_json,
+                  myaku_progress_callback callback, void *user_data);
+
+/* Owned by the handle, valid until the next myaku_run or myaku_free. */
+const char *myaku_result_json(const MyakuHandle *handle);
+const char *myaku_last_error(const MyakuHandle *handle);
+
+/* Arrow IPC stream of the table "commits", "tags" or of a metric, named after it. Writes the
+   length of the stream to length, NULL if there is no such table. Owned by the handle, valid
+   until the next myaku_run or myaku_free. */
+const uint8_t *myaku_result_arrow(const MyakuHandle *handle, const char *table,
+                                  size_t *length);
+
+void myaku_free(MyakuHandle *handle);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* MYAKU_H */
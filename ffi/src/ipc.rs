@@ -0,0 +1,111 @@
+//! Results encoded as Arrow IPC streams, one per table.
+
+use std::{collections::HashMap, sync::Arc};
+
+use arrow::{
+    array::{RecordBatch, StringArray},
+    datatypes::{DataType, Field, FieldRef, Schema},
+    error::ArrowError,
+    ipc::writer::StreamWriter,
+};
+use myaku::{CommitInfo, CommitTagInfo, PostCollection};
+use serde_arrow::schema::{SchemaLike, TracingOptions};
+use serde_json::Value;
+use thiserror::Error;
+
+/// Name of the table of the commits, all other tables are named after their metric
+pub const COMMITS_TABLE: &str = "commits";
+
+/// Name of the table of the tags, only present if tags were collected
+pub const TAGS_TABLE: &str = "tags";
+
+#[derive(Error, Debug)]
+pub enum ArrowResultError {
+    #[error("Serde Arrow error: {0}")]
+    SerdeArrow(#[from] serde_arrow::Error),
+
+    #[error("Arrow error: {0}")]
+    Arrow(#[from] ArrowError),
+
+    #[error("Could not serialize value: {0}")]
+    SerdeJson(#[from] serde_json::Error),
+}
+
+fn type_options() -> TracingOptions {
+    TracingOptions::default()
+        .map_as_struct(false)
+        .enums_without_data_as_strings(true)
+        .from_type_budget(1000)
+}
+
+fn to_ipc_stream(batch: &RecordBatch) -> Result<Vec<u8>, ArrowError> {
+    let mut buffer = Vec::new();
+
+    let mut writer = StreamWriter::try_new(&mut buffer, &batch.schema())?;
+    writer.write(batch)?;
+    writer.finish()?;
+    drop(writer);
+
+    Ok(buffer)
+}
+
+/// Values of a metric with a `commit` column followed by the fields of its values. The schema is
+/// traced from the values, since the fields of some values depend on the config of the metric.
+fn metric_batch(rows: Vec<Value>) -> Result<RecordBatch, ArrowResultError> {
+    if rows.is_empty() {
+        let schema = Schema::new(vec![Field::new("commit", DataType::Utf8, false)]);
+        let commits: StringArray = Vec::<String>::new().into();
+
+        return Ok(RecordBatch::try_new(
+            Arc::new(schema),
+            vec![Arc::new(commits)],
+        )?);
+    }
+
+    let fields = Vec::<FieldRef>::from_samples(
+        &rows,
+        TracingOptions::default()
+            .coerce_numbers(true)
+            .allow_null_fields(true),
+    )?;
+
+    Ok(serde_arrow::to_record_batch(&fields, &rows)?)
+}
+
+/// Encodes the commits, the tags and the values of every metric as separate Arrow IPC streams
+pub fn tables(process: &PostCollection) -> Result<HashMap<String, Vec<u8>>, ArrowResultError> {
+    let mut tables = HashMap::new();
+
+    let fields = Vec::<FieldRef>::from_type::<CommitInfo>(type_options())?;
+    let batch = serde_arrow::to_record_batch(&fields, &process.commits)?;
+    tables.insert(COMMITS_TABLE.to_string(), to_ipc_stream(&batch)?);
+
+    if let Some(tags) = &process.tags {
+        let fields = Vec::<FieldRef>::from_type::<CommitTagInfo>(type_options())?;
+        let batch = serde_arrow::to_record_batch(&fields, tags)?;
+        tables.insert(TAGS_TABLE.to_string(), to_ipc_stream(&batch)?);
+    }
+
+    for (metric_name, values) in process.metric_values() {
+        let mut rows = Vec::with_capacity(values.len());
+
+        for (commit, value) in values {
+            let mut row = serde_json::Map::new();
+            row.insert("commit".to_string(), Value::String(commit.0));
+
+            if let Value::Object(fields) = serde_json::to_value(&value)? {
+                // Tag of the value, the same for all values of a metric
+                row.extend(fields.into_iter().filter(|(key, _)| key != "collector"));
+            }
+
+            rows.push(Value::Object(row));
+        }
+
+        tables.insert(
+            metric_name.to_string(),
+            to_ipc_stream(&metric_batch(rows)?)?,
+        );
+    }
+
+    Ok(tables)
+}
@@ -0,0 +1,297 @@
+//! Minimal C ABI for embedding the collection engine into non-Rust tooling.
+//!
+//! Configs and progress events cross the boundary as NUL-terminated UTF-8 JSON strings, results
+//! as a JSON string or as Arrow IPC streams. See `include/myaku.h`.
+
+use std::{
+    any::Any,
+    collections::HashMap,
+    ffi::{c_char, c_void, CStr, CString},
+    panic::{self, AssertUnwindSafe},
+    ptr,
+    sync::mpsc,
+};
+
+use myaku::{ExecutionProgressCallbackState, PostCollection, RunConfig};
+use serde::Deserialize;
+
+mod ipc;
+
+pub const MYAKU_OK: i32 = 0;
+pub const MYAKU_ERROR: i32 = 1;
+pub const MYAKU_INVALID_ARGUMENT: i32 = 2;
+
+/// Called with a JSON encoded progress event and the user data pointer passed to `myaku_run`.
+pub type ProgressCallback =
+    Option<unsafe extern "C" fn(event: *const c_char, user_data: *mut c_void)>;
+
+#[derive(Deserialize, Debug, Default, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum ResultFormat {
+    #[default]
+    Json,
+
+    /// One Arrow IPC stream per table, see `ipc::tables`
+    ArrowIpc,
+}
+
+#[derive(Deserialize, Debug)]
+struct Config {
+    #[serde(flatten)]
+    run: RunConfig,
+
+    #[serde(default)]
+    result_format: ResultFormat,
+}
+
+enum Results {
+    Json(CString),
+    ArrowIpc(HashMap<String, Vec<u8>>),
+}
+
+/// Opaque handle owning the results and the last error of a run.
+#[derive(Default)]
+pub struct MyakuHandle {
+    result: Option<Results>,
+    last_error: Option<CString>,
+}
+
+struct Callback {
+    callback: unsafe extern "C" fn(*const c_char, *mut c_void),
+    user_data: *mut c_void,
+}
+
+// The caller of `myaku_run` guarantees that the callback and its user data may be used from
+// another thread for the duration of the call.
+unsafe impl Send for Callback {}
+
+impl Callback {
+    fn emit(&self, state: &ExecutionProgressCallbackState) {
+        let Ok(event) = serde_json::to_string(state) else {
+            return;
+        };
+
+        let Ok(event) = CString::new(event) else {
+            return;
+        };
+
+        unsafe { (self.callback)(event.as_ptr(), self.user_data) };
+    }
+}
+
+fn run(config: RunConfig, callback: Option<Callback>) -> Result<PostCollection, String> {
+    let (tx, rx) = mpsc::channel::<ExecutionProgressCallbackState>();
+
+    let reader = std::thread::spawn(move || {
+        while let Ok(state) = rx.recv() {
+            if let Some(callback) = &callback {
+                callback.emit(&state);
+            }
+        }
+    });
+
+    let process = config.run(Some(tx));
+
+    reader
+        .join()
+        .map_err(|_| "Progress callback panicked".to_string())?;
+
+    process.map_err(|e| e.report())
+}
+
+fn serialize_results(process: &PostCollection, format: ResultFormat) -> Result<Results, String> {
+    match format {
+        ResultFormat::Json => process
+            .results_json()
+            .map_err(|e| e.to_string())
+            .and_then(|result| CString::new(result).map_err(|e| e.to_string()))
+            .map(Results::Json),
+        ResultFormat::ArrowIpc => ipc::tables(process)
+            .map(Results::ArrowIpc)
+            .map_err(|e| e.to_string()),
+    }
+}
+
+/// Message of a caught panic, which is usually a string
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown cause")
+}
+
+/// Parses the config and runs the collection, returning the results in the configured format or
+/// the status code and message of the failure.
+///
+/// # Safety
+///
+/// See `myaku_run`.
+unsafe fn run_json(
+    config_json: *const c_char,
+    callback: ProgressCallback,
+    user_data: *mut c_void,
+) -> Result<Results, (i32, String)> {
+    if config_json.is_null() {
+        return Err((
+            MYAKU_INVALID_ARGUMENT,
+            "Config must not be null".to_string(),
+        ));
+    }
+
+    let config = unsafe { CStr::from_ptr(config_json) }
+        .to_str()
+        .map_err(|e| (MYAKU_INVALID_ARGUMENT, e.to_string()))?;
+
+    let config: Config =
+        serde_json::from_str(config).map_err(|e| (MYAKU_INVALID_ARGUMENT, e.to_string()))?;
+
+    let callback = callback.map(|callback| Callback {
+        callback,
+        user_data,
+    });
+
+    let process = run(config.run, callback).map_err(|e| (MYAKU_ERROR, e))?;
+
+    serialize_results(&process, config.result_format).map_err(|e| (MYAKU_ERROR, e))
+}
+
+impl MyakuHandle {
+    fn fail(&mut self, code: i32, message: &str) -> i32 {
+        self.last_error = CString::new(message.replace('\0', "")).ok();
+        code
+    }
+}
+
+/// Creates a new handle. Must be released with `myaku_free`.
+#[no_mangle]
+pub extern "C" fn myaku_init() -> *mut MyakuHandle {
+    Box::into_raw(Box::default())
+}
+
+/// Runs a full collection for the given JSON encoded config.
+///
+/// Returns `MYAKU_OK` on success, in which case the results can be read using
+/// `myaku_result_json` or, if the config sets `"result_format": "arrow_ipc"`,
+/// `myaku_result_arrow`. Otherwise `myaku_last_error` describes the failure, a panic during the
+/// collection is reported as `MYAKU_ERROR` instead of unwinding into the caller.
+///
+/// # Safety
+///
+/// `handle` must have been created by `myaku_init` and `config_json` must point to a
+/// NUL-terminated string. The callback (if any) is invoked from a background thread while
+/// this function is running and must be safe to call with `user_data` from that thread.
+#[no_mangle]
+pub unsafe extern "C" fn myaku_run(
+    handle: *mut MyakuHandle,
+    config_json: *const c_char,
+    callback: ProgressCallback,
+    user_data: *mut c_void,
+) -> i32 {
+    let Some(handle) = (unsafe { handle.as_mut() }) else {
+        return MYAKU_INVALID_ARGUMENT;
+    };
+
+    handle.result = None;
+    handle.last_error = None;
+
+    // Unwinding across the C ABI is undefined behavior
+    let result = panic::catch_unwind(AssertUnwindSafe(|| unsafe {
+        run_json(config_json, callback, user_data)
+    }));
+
+    match result {
+        Ok(Ok(result)) => {
+            handle.result = Some(result);
+            MYAKU_OK
+        }
+        Ok(Err((code, message))) => handle.fail(code, &message),
+        Err(payload) => handle.fail(
+            MYAKU_ERROR,
+            &format!("Collection panicked: {}", panic_message(payload.as_ref())),
+        ),
+    }
+}
+
+/// Returns the JSON encoded results of the last successful run, or null if it returned its
+/// results in another format.
+///
+/// The returned string is owned by the handle and valid until the next call to
+/// `myaku_run` or `myaku_free`.
+///
+/// # Safety
+///
+/// `handle` must be null or have been created by `myaku_init`.
+#[no_mangle]
+pub unsafe extern "C" fn myaku_result_json(handle: *const MyakuHandle) -> *const c_char {
+    match unsafe { handle.as_ref() }.and_then(|h| h.result.as_ref()) {
+        Some(Results::Json(result)) => result.as_ptr(),
+        _ => ptr::null(),
+    }
+}
+
+/// Returns a table of the results of the last successful run as an Arrow IPC stream and writes
+/// its length to `length`, or returns null if there is no such table. Tables are `commits`,
+/// `tags` if tags were collected and one per metric, named after it.
+///
+/// The returned buffer is owned by the handle and valid until the next call to
+/// `myaku_run` or `myaku_free`.
+///
+/// # Safety
+///
+/// `handle` must be null or have been created by `myaku_init`, `table` must point to a
+/// NUL-terminated string and `length` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn myaku_result_arrow(
+    handle: *const MyakuHandle,
+    table: *const c_char,
+    length: *mut usize,
+) -> *const u8 {
+    if table.is_null() || length.is_null() {
+        return ptr::null();
+    }
+
+    let Some(Results::ArrowIpc(tables)) =
+        unsafe { handle.as_ref() }.and_then(|h| h.result.as_ref())
+    else {
+        return ptr::null();
+    };
+
+    let Some(buffer) = unsafe { CStr::from_ptr(table) }
+        .to_str()
+        .ok()
+        .and_then(|table| tables.get(table))
+    else {
+        return ptr::null();
+    };
+
+    unsafe { length.write(buffer.len()) };
+    buffer.as_ptr()
+}
+
+/// Returns the error message of the last failed run, or null.
+///
+/// The returned string is owned by the handle and valid until the next call to
+/// `myaku_run` or `myaku_free`.
+///
+/// # Safety
+///
+/// `handle` must be null or have been created by `myaku_init`.
+#[no_mangle]
+pub unsafe extern "C" fn myaku_last_error(handle: *const MyakuHandle) -> *const c_char {
+    unsafe { handle.as_ref() }
+        .and_then(|h| h.last_error.as_ref())
+        .map_or(ptr::null(), |e| e.as_ptr())
+}
+
+/// Releases a handle and all strings owned by it.
+///
+/// # Safety
+///
+/// `handle` must be null or have been created by `myaku_init` and not been freed before.
+#[no_mangle]
+pub unsafe extern "C" fn myaku_free(handle: *mut MyakuHandle) {
+    if !handle.is_null() {
+        drop(unsafe { Box::from_raw(handle) });
+    }
+}
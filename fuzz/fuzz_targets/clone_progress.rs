@@ -0,0 +1,16 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use myaku::{fuzzing::parse_clone_progress, CloneProgress};
+
+fuzz_target!(|line: &str| {
+    match parse_clone_progress(line) {
+        Some(CloneProgress::EnumeratingObjects) | None => {}
+        Some(
+            CloneProgress::CountingObjects { .. }
+            | CloneProgress::CompressingObjects { .. }
+            | CloneProgress::ReceivingObjects { .. }
+            | CloneProgress::ResolvingDeltas { .. },
+        ) => assert!(line.contains('/')),
+    }
+});
@@ -0,0 +1,23 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use myaku::fuzzing::split_delimited;
+
+const DELIMITERS: [char; 2] = ['\n', '\r'];
+
+fuzz_target!(|input: (u8, &[u8])| {
+    let (capacity, bytes) = input;
+
+    let lines = split_delimited(bytes, &DELIMITERS, usize::from(capacity));
+
+    for line in &lines {
+        assert!(!line.is_empty());
+        assert!(!line.contains(DELIMITERS));
+    }
+
+    // Independent of the buffer size, valid UTF-8 comes out unchanged
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        let expected: Vec<&str> = text.split(DELIMITERS).filter(|l| !l.is_empty()).collect();
+        assert_eq!(expected, lines);
+    }
+});
@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use myaku::fuzzing::count_grep_matches;
+
+fuzz_target!(|output: &str| {
+    assert!(count_grep_matches(output) <= output.lines().count());
+});
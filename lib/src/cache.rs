@@ -2,8 +2,13 @@ use std::{
     fs::File,
     io::{BufReader, Read, Write},
     path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
 };
 
+use globset::Glob;
 use sha1::{Digest, Sha1};
 use thiserror::Error;
 
@@ -11,26 +16,70 @@ use crate::{collectors::CollectorValue, config::CollectorConfig, git::CommitHash
 
 #[derive(Error, Debug)]
 pub enum CacheError {
-    #[error("IO error: {0}")]
-    IO(#[from] std::io::Error),
+    #[error("Could not access cache file {}", path.display())]
+    IO {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
 
-    #[error("Could not parse string: {0}")]
-    StringParsing(#[from] std::string::FromUtf8Error),
+    #[error("Cache file {} is not valid UTF-8", path.display())]
+    StringParsing {
+        path: PathBuf,
+        #[source]
+        source: std::string::FromUtf8Error,
+    },
 
-    #[error("Serde JSON error: {0}")]
-    SerdeJson(#[from] serde_json::Error),
+    #[error("Could not convert the value of cache file {} from or to JSON", path.display())]
+    SerdeJson {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
 }
 
+impl CacheError {
+    /// Path of the cache entry the error occurred at
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        match self {
+            CacheError::IO { path, .. }
+            | CacheError::StringParsing { path, .. }
+            | CacheError::SerdeJson { path, .. } => path,
+        }
+    }
+}
+
+/// Identifies the files hidden from all collectors, see [`crate::Initial::exclude_paths`], so
+/// values collected with different exclusions are cached apart. `None` if no files are excluded,
+/// which keeps the entries of caches written before files could be excluded.
+#[must_use]
+pub fn exclude_paths_id(exclude_paths: &[Glob]) -> Option<String> {
+    let mut globs: Vec<&str> = exclude_paths.iter().map(Glob::glob).collect();
+    globs.sort_unstable();
+    globs.dedup();
+
+    if globs.is_empty() {
+        return None;
+    }
+
+    Some(format!("{:x}", Sha1::digest(globs.join("\n"))))
+}
+
+/// Values of collectors at commits, keyed additionally by the [`exclude_paths_id`] of the files
+/// hidden from the collector when the value was collected
 pub trait Cache: core::fmt::Debug {
     fn lookup(
         &self,
         collector_config: &CollectorConfig,
+        exclude_paths_id: Option<&str>,
         commit_hash: &CommitHash,
     ) -> Result<Option<CollectorValue>, CacheError>;
 
     fn store(
         &self,
         collector_config: &CollectorConfig,
+        exclude_paths_id: Option<&str>,
         commit_hash: &CommitHash,
         value: &CollectorValue,
     ) -> Result<(), CacheError>;
@@ -54,23 +103,21 @@ impl FileCache {
     fn get_data_point_path(
         &self,
         collector_config: &CollectorConfig,
+        exclude_paths_id: Option<&str>,
         commit: &CommitHash,
-    ) -> Result<PathBuf, CacheError> {
-        let config_hash = {
-            let mut hasher = Sha1::new();
-            hasher.update(serde_json::to_string(collector_config)?);
-            let bytes = hasher.finalize();
-            format!("{bytes:x}")
+    ) -> PathBuf {
+        let base = match exclude_paths_id {
+            Some(id) => self.base.join(format!("excluding-{id}")),
+            None => self.base.clone(),
         };
 
-        let mut path = self
-            .base
-            .join(PathBuf::from(config_hash))
+        let mut path = base
+            .join(PathBuf::from(collector_config.id()))
             .join(PathBuf::from(&commit.0));
 
         path.set_extension("json");
 
-        Ok(path)
+        path
     }
 }
 
@@ -78,23 +125,36 @@ impl Cache for FileCache {
     fn lookup(
         &self,
         collector_config: &CollectorConfig,
+        exclude_paths_id: Option<&str>,
         commit_hash: &CommitHash,
     ) -> Result<Option<CollectorValue>, CacheError> {
-        let file_path = self.get_data_point_path(collector_config, commit_hash)?;
+        let file_path = self.get_data_point_path(collector_config, exclude_paths_id, commit_hash);
 
         if !file_path.exists() {
             return Ok(None);
         }
 
-        let file = File::open(file_path)?;
+        let io = |source| CacheError::IO {
+            path: file_path.clone(),
+            source,
+        };
+
+        let file = File::open(&file_path).map_err(io)?;
         let mut output = Vec::new();
         let mut reader = BufReader::new(file);
 
-        reader.read_to_end(&mut output)?;
+        reader.read_to_end(&mut output).map_err(io)?;
 
-        let contents = String::from_utf8(output)?;
+        let contents = String::from_utf8(output).map_err(|source| CacheError::StringParsing {
+            path: file_path.clone(),
+            source,
+        })?;
 
-        let value: CollectorValue = serde_json::from_str(&contents)?;
+        let value: CollectorValue =
+            serde_json::from_str(&contents).map_err(|source| CacheError::SerdeJson {
+                path: file_path.clone(),
+                source,
+            })?;
 
         Ok(Some(value))
     }
@@ -102,20 +162,188 @@ impl Cache for FileCache {
     fn store(
         &self,
         collector_config: &CollectorConfig,
+        exclude_paths_id: Option<&str>,
         commit_hash: &CommitHash,
         value: &CollectorValue,
     ) -> Result<(), CacheError> {
-        let file_path = self.get_data_point_path(collector_config, commit_hash)?;
+        let file_path = self.get_data_point_path(collector_config, exclude_paths_id, commit_hash);
+
+        let io = |source| CacheError::IO {
+            path: file_path.clone(),
+            source,
+        };
 
         if let Some(parent) = file_path.parent() {
-            std::fs::create_dir_all(parent)?;
+            std::fs::create_dir_all(parent).map_err(io)?;
         }
 
-        let contents = serde_json::to_string(value)?;
+        let contents = serde_json::to_string(value).map_err(|source| CacheError::SerdeJson {
+            path: file_path.clone(),
+            source,
+        })?;
 
-        let mut file = File::create(file_path)?;
-        file.write_all(contents.as_bytes())?;
+        let mut file = File::create(&file_path).map_err(io)?;
+        file.write_all(contents.as_bytes()).map_err(io)?;
 
         Ok(())
     }
 }
+
+/// Cache of a single branch, which takes the values it misses from the caches of other branches
+/// of the same repository. Taken values are copied, so the cache of every branch stays complete
+/// on its own and can be removed together with the branch.
+#[derive(Debug)]
+pub struct BranchCache {
+    own: FileCache,
+    others: Vec<FileCache>,
+    shared_value_count: Arc<AtomicU64>,
+}
+
+impl BranchCache {
+    #[must_use]
+    pub fn new(own: FileCache, others: Vec<FileCache>) -> Self {
+        Self {
+            own,
+            others,
+            shared_value_count: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Number of values taken from other branches, which can still be read once the cache was
+    /// handed to a collection
+    #[must_use]
+    pub fn shared_value_count(&self) -> Arc<AtomicU64> {
+        Arc::clone(&self.shared_value_count)
+    }
+}
+
+impl Cache for BranchCache {
+    fn lookup(
+        &self,
+        collector_config: &CollectorConfig,
+        exclude_paths_id: Option<&str>,
+        commit_hash: &CommitHash,
+    ) -> Result<Option<CollectorValue>, CacheError> {
+        if let Some(value) = self
+            .own
+            .lookup(collector_config, exclude_paths_id, commit_hash)?
+        {
+            return Ok(Some(value));
+        }
+
+        for other in &self.others {
+            if let Some(value) = other.lookup(collector_config, exclude_paths_id, commit_hash)? {
+                self.own
+                    .store(collector_config, exclude_paths_id, commit_hash, &value)?;
+                self.shared_value_count.fetch_add(1, Ordering::Relaxed);
+
+                return Ok(Some(value));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn store(
+        &self,
+        collector_config: &CollectorConfig,
+        exclude_paths_id: Option<&str>,
+        commit_hash: &CommitHash,
+        value: &CollectorValue,
+    ) -> Result<(), CacheError> {
+        self.own
+            .store(collector_config, exclude_paths_id, commit_hash, value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use super::*;
+    use crate::collectors::TotalLocValue;
+
+    #[test]
+    fn test_branch_cache_copies_shared_values() {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .subsec_nanos();
+        let root = std::env::temp_dir().join(format!("myaku-branch-cache-{nanos}"));
+
+        let collector = CollectorConfig::TotalLoc {
+            scope: Box::default(),
+        };
+        let shared = CommitHash("a".to_string());
+        let value = CollectorValue::TotalLoc(TotalLocValue { loc: 1 });
+
+        FileCache::new(&root.join("main"))
+            .store(&collector, None, &shared, &value)
+            .unwrap();
+
+        let cache = BranchCache::new(
+            FileCache::new(&root.join("feature")),
+            vec![FileCache::new(&root.join("main"))],
+        );
+        let shared_value_count = cache.shared_value_count();
+
+        assert!(cache.lookup(&collector, None, &shared).unwrap().is_some());
+        assert!(cache
+            .lookup(&collector, None, &CommitHash("b".to_string()))
+            .unwrap()
+            .is_none());
+
+        // The second lookup is answered by the cache of the branch itself
+        assert!(cache.lookup(&collector, None, &shared).unwrap().is_some());
+        assert_eq!(shared_value_count.load(Ordering::Relaxed), 1);
+
+        assert!(FileCache::new(&root.join("feature"))
+            .lookup(&collector, None, &shared)
+            .unwrap()
+            .is_some());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_values_are_cached_apart_per_exclude_paths() {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .subsec_nanos();
+        let root = std::env::temp_dir().join(format!("myaku-exclude-cache-{nanos}"));
+
+        let glob = |glob: &str| Glob::new(glob).unwrap();
+        assert_eq!(exclude_paths_id(&[]), None);
+        assert_eq!(
+            exclude_paths_id(&[glob("vendor/**"), glob("target/**")]),
+            exclude_paths_id(&[glob("target/**"), glob("vendor/**")])
+        );
+
+        let vendored = exclude_paths_id(&[glob("vendor/**")]);
+        assert!(vendored.is_some());
+
+        let collector = CollectorConfig::TotalLoc {
+            scope: Box::default(),
+        };
+        let commit = CommitHash("a".to_string());
+        let cache = FileCache::new(&root);
+
+        cache
+            .store(
+                &collector,
+                None,
+                &commit,
+                &CollectorValue::TotalLoc(TotalLocValue { loc: 1 }),
+            )
+            .unwrap();
+
+        assert!(cache
+            .lookup(&collector, vendored.as_deref(), &commit)
+            .unwrap()
+            .is_none());
+        assert!(cache.lookup(&collector, None, &commit).unwrap().is_some());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
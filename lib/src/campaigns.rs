@@ -0,0 +1,271 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    collectors::{
+        expression::{self, ExpressionError},
+        CollectorValue,
+    },
+    config::{CollectorConfig, MetricConfig},
+    git::{CommitHash, CommitInfo},
+    graph::sample_commits,
+    Branches,
+};
+
+/// Number of latest sampled commits the trend is fitted to unless configured
+const DEFAULT_WINDOW: usize = 10;
+
+#[derive(Error, Debug)]
+pub enum CampaignError {
+    #[error("Value of metric {metric} is not a number")]
+    NotNumeric { metric: String },
+
+    #[error(
+        "Value of metric {metric} has several numbers, select one of {} as the field of its campaign",
+        fields.join(", ")
+    )]
+    AmbiguousValue { metric: String, fields: Vec<String> },
+}
+
+impl CampaignError {
+    fn from_expression(metric: &str, error: ExpressionError) -> Self {
+        match error {
+            ExpressionError::AmbiguousOperand { fields, .. } => CampaignError::AmbiguousValue {
+                metric: metric.to_string(),
+                fields,
+            },
+            _ => CampaignError::NotNumeric {
+                metric: metric.to_string(),
+            },
+        }
+    }
+}
+
+/// Projected completion of the campaign of a metric, based on the trend of its latest values
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CampaignProjection {
+    pub metric: String,
+    pub target: f64,
+
+    /// Latest sampled commit with a value of the metric
+    pub commit: CommitHash,
+    pub value: f64,
+
+    /// Change of the value per day, the slope of a least squares fit of the values in the window
+    pub change_per_day: f64,
+
+    /// Day the trend reaches the target when continued from the latest value, empty if it doesn't
+    /// move towards the target
+    pub projected_date: Option<NaiveDate>,
+}
+
+impl std::fmt::Display for CampaignProjection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            metric,
+            target,
+            commit,
+            value,
+            change_per_day,
+            ..
+        } = self;
+
+        match self.projected_date {
+            Some(date) => write!(
+                f,
+                "{metric} is {value} at {commit}, changing by {change_per_day:.2} per day it reaches {target} on {date}"
+            ),
+            None => write!(
+                f,
+                "{metric} is {value} at {commit}, changing by {change_per_day:.2} per day it doesn't reach {target}"
+            ),
+        }
+    }
+}
+
+/// Slope of the least squares line through the values over their times in days, `None` if all
+/// values are at the same time
+#[allow(clippy::cast_precision_loss)]
+fn slope_per_day(values: &[(DateTime<Utc>, f64)]) -> Option<f64> {
+    let first_time = values.first()?.0;
+    let points: Vec<(f64, f64)> = values
+        .iter()
+        .map(|(time, value)| {
+            let days = (*time - first_time).num_seconds() as f64 / 86_400.0;
+            (days, *value)
+        })
+        .collect();
+
+    let count = points.len() as f64;
+    let mean_days = points.iter().map(|(days, _)| days).sum::<f64>() / count;
+    let mean_value = points.iter().map(|(_, value)| value).sum::<f64>() / count;
+
+    let covariance: f64 = points
+        .iter()
+        .map(|(days, value)| (days - mean_days) * (value - mean_value))
+        .sum();
+    let variance: f64 = points
+        .iter()
+        .map(|(days, _)| (days - mean_days).powi(2))
+        .sum();
+
+    (variance > 0.0).then(|| covariance / variance)
+}
+
+/// Day the value reaches the target at the given rate of change, starting at the time
+#[allow(clippy::cast_possible_truncation)]
+fn projected_date(
+    time: DateTime<Utc>,
+    value: f64,
+    target: f64,
+    change_per_day: f64,
+) -> Option<NaiveDate> {
+    if value == target {
+        return Some(time.date_naive());
+    }
+
+    let days = (target - value) / change_per_day;
+    if !days.is_finite() || days < 0.0 {
+        return None;
+    }
+
+    let seconds = (days * 86_400.0).ceil();
+    if seconds > i64::MAX as f64 {
+        return None;
+    }
+
+    time.checked_add_signed(TimeDelta::try_seconds(seconds as i64)?)
+        .map(|time| time.date_naive())
+}
+
+/// Projects when every metric with a campaign reaches its target by fitting a line to the values
+/// of its latest sampled commits. Commits without a value, e.g. skipped ones, are passed over and
+/// metrics with less than two values have no projection.
+pub(crate) fn project(
+    metrics: &HashMap<String, MetricConfig>,
+    commits: &[CommitInfo],
+    branches: &Branches,
+    storage: &DashMap<(CollectorConfig, CommitHash), CollectorValue>,
+) -> Result<Vec<CampaignProjection>, CampaignError> {
+    let mut sorted_commits = commits.to_vec();
+    sorted_commits.sort_by(|a, b| a.time.cmp(&b.time));
+
+    let mut metric_names: Vec<&String> = metrics.keys().collect();
+    metric_names.sort();
+
+    let mut projections = vec![];
+    for metric_name in metric_names {
+        let metric = &metrics[metric_name];
+        let Some(campaign) = &metric.campaign else {
+            continue;
+        };
+
+        let branch_commits: Vec<CommitInfo> = sorted_commits
+            .iter()
+            .filter(|commit| branches.contains(metric, &commit.id))
+            .cloned()
+            .collect();
+
+        let window = campaign.window.unwrap_or(DEFAULT_WINDOW);
+
+        // Values of the window, newest first
+        let mut values: Vec<(CommitHash, DateTime<Utc>, f64)> = vec![];
+        for (commit, _) in sample_commits(
+            &branch_commits,
+            &metric.frequency,
+            metric.align_to_calendar.unwrap_or(false),
+            metric.phase_offset.unwrap_or_default(),
+            true,
+        )
+        .into_iter()
+        .rev()
+        {
+            let Some(value) = storage.get(&(metric.collector.clone(), commit.id.clone())) else {
+                continue;
+            };
+
+            if matches!(value.value(), CollectorValue::Skipped(_)) {
+                continue;
+            }
+
+            let number = expression::number(value.value(), metric_name, campaign.field.as_deref())
+                .map_err(|error| CampaignError::from_expression(metric_name, error))?;
+
+            if let Some(number) = number {
+                values.push((commit.id.clone(), commit.time, number));
+            }
+
+            if values.len() == window {
+                break;
+            }
+        }
+
+        let Some((commit, time, value)) = values.first().cloned() else {
+            continue;
+        };
+
+        let trend: Vec<(DateTime<Utc>, f64)> = values
+            .iter()
+            .rev()
+            .map(|(_, time, value)| (*time, *value))
+            .collect();
+        let Some(change_per_day) = slope_per_day(&trend) else {
+            continue;
+        };
+
+        let target = campaign.target.unwrap_or(0.0);
+
+        projections.push(CampaignProjection {
+            metric: metric_name.clone(),
+            target,
+            commit,
+            value,
+            change_per_day,
+            projected_date: projected_date(time, value, target, change_per_day),
+        });
+    }
+
+    Ok(projections)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_projected_date() {
+        let time = |day: u32| {
+            format!("2024-01-{day:02}T00:00:00Z")
+                .parse::<DateTime<Utc>>()
+                .unwrap()
+        };
+
+        // Burning down by 10 per day
+        let change_per_day =
+            slope_per_day(&[(time(1), 100.0), (time(2), 92.0), (time(3), 80.0)]).unwrap();
+        assert!((change_per_day + 10.0).abs() < 1e-9);
+
+        assert_eq!(
+            NaiveDate::from_ymd_opt(2024, 1, 11),
+            projected_date(time(3), 80.0, 0.0, change_per_day)
+        );
+        assert_eq!(
+            NaiveDate::from_ymd_opt(2024, 1, 6),
+            projected_date(time(3), 80.0, 50.0, change_per_day)
+        );
+
+        // Moving away from the target or not at all
+        assert_eq!(None, projected_date(time(3), 80.0, 0.0, 10.0));
+        assert_eq!(None, projected_date(time(3), 80.0, 0.0, 0.0));
+        assert_eq!(
+            NaiveDate::from_ymd_opt(2024, 1, 3),
+            projected_date(time(3), 0.0, 0.0, 0.0)
+        );
+
+        assert_eq!(None, slope_per_day(&[(time(1), 100.0)]));
+    }
+}
@@ -0,0 +1,75 @@
+use dashmap::DashMap;
+use petgraph::graph::NodeIndex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    config::CollectorConfig,
+    git::{CommitHash, CommitInfo, GitError, WorktreeHandle},
+    graph::CollectionExecutionGraph,
+};
+
+use super::{BaseCollector, CollectorValue};
+
+#[derive(Debug)]
+pub(crate) struct AuthorDomain;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AuthorDomainValue {
+    /// Lowercased domain of the author's email address, `None` if it has none
+    pub domain: Option<String>,
+}
+
+#[derive(Error, Debug)]
+pub enum AuthorDomainError {
+    #[error(transparent)]
+    Git(#[from] GitError),
+}
+
+fn email_domain(email: &str) -> Option<String> {
+    let (_, domain) = email.trim().trim_end_matches('>').rsplit_once('@')?;
+    (!domain.is_empty()).then(|| domain.to_lowercase())
+}
+
+/// Domain of the email address of the commit's author
+pub(super) fn author_domain(commit: &CommitInfo) -> AuthorDomainValue {
+    AuthorDomainValue {
+        domain: commit.author.email.as_deref().and_then(email_domain),
+    }
+}
+
+impl BaseCollector for AuthorDomain {
+    type Error = AuthorDomainError;
+
+    #[tracing::instrument(level = "trace", skip_all)]
+    fn collect(
+        &self,
+        _storage: &DashMap<(CollectorConfig, CommitHash), CollectorValue>,
+        repo: &mut WorktreeHandle,
+        graph: &CollectionExecutionGraph,
+        current_node_idx: NodeIndex,
+    ) -> Result<CollectorValue, AuthorDomainError> {
+        let commit_hash = &graph.graph[current_node_idx].commit_hash;
+        let commit = repo.repository().get_commit(commit_hash)?;
+        Ok(author_domain(&commit).into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_email_domain() {
+        assert_eq!(
+            email_domain("jane@Example.COM").as_deref(),
+            Some("example.com")
+        );
+        assert_eq!(
+            email_domain("1234+jane@users.noreply.github.com").as_deref(),
+            Some("users.noreply.github.com")
+        );
+        assert_eq!(email_domain("jane"), None);
+        assert_eq!(email_domain("jane@"), None);
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::BTreeMap;
+
+use dashmap::DashMap;
+use petgraph::graph::NodeIndex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    config::CollectorConfig,
+    git::{CommitHash, GitError, WorktreeHandle},
+    graph::CollectionExecutionGraph,
+};
+
+use super::{
+    author_domain::{author_domain, AuthorDomainValue},
+    registry::DependencyKey,
+    utils::get_dependency_window_commits,
+    BaseCollector, CollectorValue,
+};
+
+#[derive(Debug)]
+pub(crate) struct AuthorDomains {
+    pub window: usize,
+}
+
+/// Share of the commits within the window ending at the current one per email domain of their
+/// authors, e.g. to tell contributions of a company from external ones.
+///
+/// The window covers the last `window` sampled commits together with the commits skipped before
+/// each of them, so every commit since the sampled one preceding the window is counted.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct AuthorDomainsValue {
+    /// Number of commits the shares are computed from
+    pub commits: u32,
+    pub commits_by_domain: BTreeMap<String, u32>,
+
+    /// Fraction of the commits per domain, commits whose authors have no email domain aren't
+    /// attributed to any so the shares might not add up to one
+    pub share_by_domain: BTreeMap<String, f64>,
+}
+
+#[derive(Error, Debug)]
+pub enum AuthorDomainsError {
+    #[error(transparent)]
+    Git(#[from] GitError),
+
+    #[error(transparent)]
+    TryFromIntError(#[from] std::num::TryFromIntError),
+}
+
+fn shares(domains: &[AuthorDomainValue]) -> Result<AuthorDomainsValue, AuthorDomainsError> {
+    let commits = u32::try_from(domains.len())?;
+
+    let mut commits_by_domain: BTreeMap<String, u32> = BTreeMap::new();
+    for domain in domains.iter().filter_map(|value| value.domain.as_ref()) {
+        *commits_by_domain.entry(domain.clone()).or_default() += 1;
+    }
+
+    let share_by_domain = commits_by_domain
+        .iter()
+        .map(|(domain, count)| (domain.clone(), f64::from(*count) / f64::from(commits)))
+        .collect();
+
+    Ok(AuthorDomainsValue {
+        commits,
+        commits_by_domain,
+        share_by_domain,
+    })
+}
+
+impl BaseCollector for AuthorDomains {
+    type Error = AuthorDomainsError;
+
+    #[tracing::instrument(level = "trace", skip_all)]
+    fn collect(
+        &self,
+        _storage: &DashMap<(CollectorConfig, CommitHash), CollectorValue>,
+        repo: &mut WorktreeHandle,
+        graph: &CollectionExecutionGraph,
+        current_node_idx: NodeIndex,
+    ) -> Result<CollectorValue, AuthorDomainsError> {
+        let commit_hash = &graph.graph[current_node_idx].commit_hash;
+
+        // The dependency's window reaches one sampled commit further back than this one's, to the
+        // commit preceding the window. It is missing at the start of the history, where all
+        // earlier commits belong to the window.
+        let window_commits =
+            get_dependency_window_commits(graph, current_node_idx, &DependencyKey::Source);
+        let preceding_commit = self
+            .window
+            .checked_sub(1)
+            .and_then(|index| window_commits.get(index));
+
+        let commits = match preceding_commit {
+            Some(preceding_commit) => repo
+                .repository()
+                .get_commits_between(preceding_commit, commit_hash)?,
+            None => repo.repository().get_commits_reachable_from(commit_hash)?,
+        };
+
+        let domains: Vec<AuthorDomainValue> = commits.iter().map(author_domain).collect();
+
+        Ok(shares(&domains)?.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn domain(domain: Option<&str>) -> AuthorDomainValue {
+        AuthorDomainValue {
+            domain: domain.map(ToString::to_string),
+        }
+    }
+
+    #[test]
+    fn test_shares() {
+        let value = shares(&[
+            domain(Some("example.com")),
+            domain(Some("gmail.com")),
+            domain(Some("example.com")),
+            domain(None),
+        ])
+        .unwrap();
+
+        assert_eq!(value.commits, 4);
+        assert_eq!(value.commits_by_domain["example.com"], 2);
+        assert_eq!(value.commits_by_domain["gmail.com"], 1);
+        assert!((value.share_by_domain["example.com"] - 0.5).abs() < f64::EPSILON);
+        assert!((value.share_by_domain["gmail.com"] - 0.25).abs() < f64::EPSILON);
+    }
+}
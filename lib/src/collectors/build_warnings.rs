@@ -0,0 +1,206 @@
+use std::{path::Path, process::Command};
+
+use dashmap::DashMap;
+use petgraph::graph::NodeIndex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::debug;
+
+use crate::{
+    config::CollectorConfig,
+    git::{CommitHash, WorktreeHandle},
+    graph::CollectionExecutionGraph,
+};
+
+use super::{
+    changed_files::ChangedFilesValue,
+    registry::DependencyKey,
+    utils::{get_dependency_value, get_previous_commit_value_of_collector, LookupError},
+    BaseCollector, CollectorValue, CollectorValueCastError,
+};
+
+const DEFAULT_COMMAND: [&str; 3] = ["cargo", "check", "--message-format=json"];
+
+/// Files which can influence the diagnostics of a Rust build
+const RELEVANT_FILE_NAMES: [&str; 6] = [
+    "Cargo.toml",
+    "Cargo.lock",
+    "clippy.toml",
+    ".clippy.toml",
+    "rust-toolchain",
+    "rust-toolchain.toml",
+];
+
+#[derive(Debug)]
+pub(crate) struct BuildWarnings {
+    /// Build command emitting cargo JSON messages on stdout
+    pub command: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuildWarningsValue {
+    pub warnings: u32,
+    pub errors: u32,
+
+    /// Whether the build command exited successfully
+    pub succeeded: bool,
+}
+
+#[derive(Error, Debug)]
+pub enum BuildWarningsError {
+    #[error(transparent)]
+    Lookup(#[from] LookupError),
+
+    #[error(transparent)]
+    Cast(#[from] CollectorValueCastError),
+
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+
+    #[error("Build command must not be empty")]
+    EmptyCommand,
+
+    #[error(transparent)]
+    TryFromIntError(#[from] std::num::TryFromIntError),
+}
+
+#[derive(Deserialize)]
+struct PartialDiagnostic {
+    level: String,
+    spans: Vec<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "reason")]
+enum PartialCargoMessage {
+    #[serde(rename = "compiler-message")]
+    CompilerMessage { message: PartialDiagnostic },
+}
+
+/// Returns the number of warnings and errors reported in the given cargo JSON messages.
+fn count_diagnostics(output: &str) -> (usize, usize) {
+    let mut warnings = 0;
+    let mut errors = 0;
+
+    for line in output.lines() {
+        let Ok(PartialCargoMessage::CompilerMessage { message }) = serde_json::from_str(line)
+        else {
+            continue;
+        };
+
+        // Summaries like "aborting due to 2 previous errors" don't point to any code
+        if message.spans.is_empty() {
+            continue;
+        }
+
+        match message.level.as_str() {
+            "warning" => warnings += 1,
+            "error" => errors += 1,
+            _ => {}
+        }
+    }
+
+    (warnings, errors)
+}
+
+fn is_relevant_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "rs")
+        || path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| RELEVANT_FILE_NAMES.contains(&name))
+}
+
+impl BaseCollector for BuildWarnings {
+    type Error = BuildWarningsError;
+
+    #[tracing::instrument(level = "trace", skip_all)]
+    fn collect(
+        &self,
+        storage: &DashMap<(CollectorConfig, CommitHash), CollectorValue>,
+        repo: &mut WorktreeHandle,
+        graph: &CollectionExecutionGraph,
+        current_node_idx: NodeIndex,
+    ) -> Result<CollectorValue, BuildWarningsError> {
+        let changed_files_in_current_commit_value: ChangedFilesValue = get_dependency_value(
+            storage,
+            graph,
+            current_node_idx,
+            &DependencyKey::ChangedFiles,
+        )?
+        .try_into()?;
+
+        let has_modified_relevant_file = changed_files_in_current_commit_value
+            .files
+            .iter()
+            .any(|relative_path| is_relevant_file(Path::new(relative_path)));
+
+        if !has_modified_relevant_file {
+            let previous_commit_value =
+                get_previous_commit_value_of_collector(storage, graph, current_node_idx);
+
+            if let Some(previous_commit_value) = previous_commit_value {
+                return Ok(previous_commit_value);
+            }
+        }
+
+        let (program, args) = match &self.command {
+            Some(command) => {
+                let (program, args) = command
+                    .split_first()
+                    .ok_or(BuildWarningsError::EmptyCommand)?;
+
+                (program.as_str(), args.iter().map(String::as_str).collect())
+            }
+            None => (DEFAULT_COMMAND[0], DEFAULT_COMMAND[1..].to_vec()),
+        };
+
+        let output = Command::new(program)
+            .args(args)
+            .current_dir(&repo.path)
+            .output()?;
+
+        if !output.status.success() {
+            debug!("Build command exited with {}", output.status);
+        }
+
+        let (warnings, errors) = count_diagnostics(&String::from_utf8_lossy(&output.stdout));
+
+        let value = BuildWarningsValue {
+            warnings: u32::try_from(warnings)?,
+            errors: u32::try_from(errors)?,
+            succeeded: output.status.success(),
+        };
+
+        Ok(value.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(
+        concat!(
+            r#"{"reason":"compiler-artifact","target":{"name":"foo"}}"#, "\n",
+            r#"{"reason":"compiler-message","message":{"level":"warning","message":"unused variable: `x`","spans":[{"file_name":"src/lib.rs"}]}}"#, "\n",
+            r#"{"reason":"compiler-message","message":{"level":"warning","message":"1 warning emitted","spans":[]}}"#, "\n",
+            r#"{"reason":"build-finished","success":true}"#, "\n",
+        ),
+        (1, 0)
+    )]
+    #[case(
+        concat!(
+            r#"{"reason":"compiler-message","message":{"level":"error","message":"mismatched types","spans":[{"file_name":"src/main.rs"}]}}"#, "\n",
+            r#"{"reason":"compiler-message","message":{"level":"warning","message":"unused import","spans":[{"file_name":"src/main.rs"}]}}"#, "\n",
+            r#"{"reason":"compiler-message","message":{"level":"error","message":"aborting due to 1 previous error","spans":[]}}"#, "\n",
+        ),
+        (1, 1)
+    )]
+    #[case("error: could not find `Cargo.toml`\n", (0, 0))]
+    fn test_count_diagnostics(#[case] output: &str, #[case] expected: (usize, usize)) {
+        assert_eq!(expected, count_diagnostics(output));
+    }
+}
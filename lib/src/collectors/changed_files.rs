@@ -23,7 +23,7 @@ pub struct ChangedFilesValue {
 
 #[derive(Error, Debug)]
 pub enum ChangedFilesError {
-    #[error("{0}")]
+    #[error(transparent)]
     Git(#[from] GitError),
 }
 
@@ -35,10 +35,11 @@ impl BaseCollector for ChangedFiles {
         &self,
         _storage: &DashMap<(CollectorConfig, CommitHash), CollectorValue>,
         repo: &mut WorktreeHandle,
-        _graph: &CollectionExecutionGraph,
-        _current_node_idx: NodeIndex,
+        graph: &CollectionExecutionGraph,
+        current_node_idx: NodeIndex,
     ) -> Result<CollectorValue, ChangedFilesError> {
-        let files_changed_in_current_commit = repo.get_current_changed_file_paths()?;
+        let commit_hash = &graph.graph[current_node_idx].commit_hash;
+        let files_changed_in_current_commit = repo.repository().changed_files(commit_hash)?;
         let value = ChangedFilesValue {
             files: files_changed_in_current_commit,
         };
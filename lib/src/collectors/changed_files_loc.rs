@@ -8,9 +8,9 @@ use tokei::LanguageType;
 use tracing::warn;
 
 use crate::{
-    collectors::{utils::get_value_of_preceeding_node, ChangedFilesValue},
+    collectors::{registry::DependencyKey, utils::get_dependency_value, ChangedFilesValue},
     config::CollectorConfig,
-    git::{CommitHash, WorktreeHandle},
+    git::{CommitHash, GitError, WorktreeHandle},
     graph::CollectionExecutionGraph,
 };
 
@@ -26,11 +26,14 @@ pub struct ChangedFilesLocValue {
 
 #[derive(Error, Debug)]
 pub enum ChangedFilesLocError {
-    #[error("{0}")]
+    #[error(transparent)]
     Lookup(#[from] LookupError),
 
-    #[error("{0}")]
+    #[error(transparent)]
     Cast(#[from] CollectorValueCastError),
+
+    #[error(transparent)]
+    Git(#[from] GitError),
 }
 
 impl BaseCollector for ChangedFilesLoc {
@@ -44,44 +47,49 @@ impl BaseCollector for ChangedFilesLoc {
         graph: &CollectionExecutionGraph,
         current_node_idx: NodeIndex,
     ) -> Result<CollectorValue, ChangedFilesLocError> {
-        let changed_files_in_current_commit_value: ChangedFilesValue =
-            get_value_of_preceeding_node(
-                storage,
-                graph,
-                current_node_idx,
-                |e| e.distance == 0,
-                |n| n.collector_config == CollectorConfig::ChangedFiles,
-            )?
-            .try_into()?;
+        let changed_files_in_current_commit_value: ChangedFilesValue = get_dependency_value(
+            storage,
+            graph,
+            current_node_idx,
+            &DependencyKey::ChangedFiles,
+        )?
+        .try_into()?;
 
         let changed_files_in_current_commit = changed_files_in_current_commit_value.files;
 
+        let commit_hash = &graph.graph[current_node_idx].commit_hash;
+        let repo = repo.repository();
+
         let config = tokei::Config::default();
 
         // TODO: Does it make sense to use rayon here?
         let reports = changed_files_in_current_commit
             .into_iter()
-            .map(|changed_file_relative_path| -> (String, Option<usize>) {
-                // Get the absolute path of the file
-                let path = repo.path.join(&changed_file_relative_path);
-
-                let language = LanguageType::from_path(&path, &config);
-
-                let Some(language) = language else {
-                    return (changed_file_relative_path, None);
-                };
-
-                let result = language.parse(path, &config);
-
-                match result {
-                    Ok(report) => (
-                        changed_file_relative_path,
-                        Some(report.stats.summarise().lines()),
-                    ),
-                    Err(_) => (changed_file_relative_path, None),
-                }
-            })
-            .collect();
+            .map(
+                |changed_file_relative_path| -> Result<(String, Option<usize>), GitError> {
+                    // The language is only detected from the path, the file is read from the object
+                    // database since the commit is not checked out
+                    let path = repo.path.join(&changed_file_relative_path);
+
+                    let language = LanguageType::from_path(&path, &config);
+
+                    let Some(language) = language else {
+                        return Ok((changed_file_relative_path, None));
+                    };
+
+                    // Deleted files don't exist at the current commit
+                    let Some(contents) =
+                        repo.read_file(commit_hash, &changed_file_relative_path)?
+                    else {
+                        return Ok((changed_file_relative_path, None));
+                    };
+
+                    let stats = language.parse_from_slice(contents, &config);
+
+                    Ok((changed_file_relative_path, Some(stats.summarise().lines())))
+                },
+            )
+            .collect::<Result<_, _>>()?;
 
         let value = ChangedFilesLocValue { files: reports };
 
@@ -0,0 +1,253 @@
+use std::{collections::BTreeMap, io::Read, path::Path};
+
+use dashmap::DashMap;
+use petgraph::graph::NodeIndex;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value as Yaml;
+use thiserror::Error;
+use tracing::warn;
+
+use crate::{
+    config::CollectorConfig,
+    git::{CommitHash, GitError, WorktreeHandle},
+    graph::CollectionExecutionGraph,
+};
+
+use super::{
+    changed_files::ChangedFilesValue,
+    registry::DependencyKey,
+    utils::{get_dependency_value, get_previous_commit_value_of_collector, LookupError},
+    BaseCollector, CollectorValue, CollectorValueCastError,
+};
+
+const GITHUB_WORKFLOWS_DIR: &str = ".github/workflows";
+
+const CI_CONFIG_FILES: [&str; 7] = [
+    ".gitlab-ci.yml",
+    ".circleci/config.yml",
+    ".travis.yml",
+    "azure-pipelines.yml",
+    "bitbucket-pipelines.yml",
+    ".drone.yml",
+    "Jenkinsfile",
+];
+
+/// Top-level keys of a `.gitlab-ci.yml` which are not jobs
+const GITLAB_RESERVED_KEYS: [&str; 10] = [
+    "image",
+    "services",
+    "stages",
+    "types",
+    "before_script",
+    "after_script",
+    "variables",
+    "cache",
+    "include",
+    "default",
+];
+
+#[derive(Debug)]
+pub(crate) struct CiWorkflows;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CiWorkflowsValue {
+    pub total_files: u32,
+    pub total_jobs: u32,
+
+    /// Number of jobs per CI configuration file, `None` if the jobs could not be determined
+    pub jobs_by_file: BTreeMap<String, Option<u32>>,
+}
+
+#[derive(Error, Debug)]
+pub enum CiWorkflowsError {
+    #[error(transparent)]
+    Lookup(#[from] LookupError),
+
+    #[error(transparent)]
+    Cast(#[from] CollectorValueCastError),
+
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Git(#[from] GitError),
+
+    #[error(transparent)]
+    TryFromIntError(#[from] std::num::TryFromIntError),
+}
+
+fn is_ci_config(relative_path: &str) -> bool {
+    let path = Path::new(relative_path);
+
+    if path.parent() == Some(Path::new(GITHUB_WORKFLOWS_DIR)) {
+        return path
+            .extension()
+            .is_some_and(|ext| ext == "yml" || ext == "yaml");
+    }
+
+    CI_CONFIG_FILES.contains(&relative_path)
+}
+
+fn mapping_len(value: Option<&Yaml>) -> Option<usize> {
+    value
+        .and_then(Yaml::as_mapping)
+        .map(serde_yaml::Mapping::len)
+}
+
+fn sequence_len(value: Option<&Yaml>) -> Option<usize> {
+    value.and_then(Yaml::as_sequence).map(Vec::len)
+}
+
+fn count_jobs(relative_path: &str, contents: &str) -> Option<usize> {
+    if relative_path == "Jenkinsfile" {
+        return Some(contents.matches("stage(").count());
+    }
+
+    let yaml: Yaml = match serde_yaml::from_str(contents) {
+        Ok(yaml) => yaml,
+        Err(e) => {
+            warn!("Failed to parse CI configuration {}: {}", relative_path, e);
+            return None;
+        }
+    };
+
+    match relative_path {
+        ".gitlab-ci.yml" => yaml.as_mapping().map(|mapping| {
+            mapping
+                .iter()
+                .filter(|(key, value)| {
+                    key.as_str().is_some_and(|key| {
+                        !key.starts_with('.') && !GITLAB_RESERVED_KEYS.contains(&key)
+                    }) && value.is_mapping()
+                })
+                .count()
+        }),
+        ".travis.yml" => sequence_len(yaml.get("jobs").and_then(|jobs| jobs.get("include")))
+            .or_else(|| sequence_len(yaml.get("matrix").and_then(|m| m.get("include"))))
+            .or(Some(1)),
+        "azure-pipelines.yml" => {
+            if let Some(stages) = yaml.get("stages").and_then(Yaml::as_sequence) {
+                Some(
+                    stages
+                        .iter()
+                        .filter_map(|stage| sequence_len(stage.get("jobs")))
+                        .sum(),
+                )
+            } else if yaml.get("jobs").is_some() {
+                sequence_len(yaml.get("jobs"))
+            } else {
+                yaml.get("steps").map(|_| 1)
+            }
+        }
+        "bitbucket-pipelines.yml" => Some(contents.matches("- step:").count()),
+        ".drone.yml" => Some(
+            serde_yaml::Deserializer::from_str(contents)
+                .map_while(|document| Yaml::deserialize(document).ok())
+                .filter(|document| document.get("kind").and_then(Yaml::as_str) == Some("pipeline"))
+                .count(),
+        ),
+        // GitHub Actions workflows and CircleCI configs both declare a `jobs` mapping
+        _ => mapping_len(yaml.get("jobs")),
+    }
+}
+
+impl BaseCollector for CiWorkflows {
+    type Error = CiWorkflowsError;
+
+    #[tracing::instrument(level = "trace", skip_all)]
+    fn collect(
+        &self,
+        storage: &DashMap<(CollectorConfig, CommitHash), CollectorValue>,
+        repo: &mut WorktreeHandle,
+        graph: &CollectionExecutionGraph,
+        current_node_idx: NodeIndex,
+    ) -> Result<CollectorValue, CiWorkflowsError> {
+        let changed_files_in_current_commit_value: ChangedFilesValue = get_dependency_value(
+            storage,
+            graph,
+            current_node_idx,
+            &DependencyKey::ChangedFiles,
+        )?
+        .try_into()?;
+
+        let has_modified_ci_config = changed_files_in_current_commit_value
+            .files
+            .iter()
+            .any(|relative_path| is_ci_config(relative_path));
+
+        if !has_modified_ci_config {
+            let previous_commit_value =
+                get_previous_commit_value_of_collector(storage, graph, current_node_idx);
+
+            if let Some(previous_commit_value) = previous_commit_value {
+                return Ok(previous_commit_value);
+            }
+        }
+
+        let mut jobs_by_file = BTreeMap::new();
+        for relative_path in repo.walk_tracked_files(None)? {
+            if !is_ci_config(&relative_path) {
+                continue;
+            }
+
+            let Some(mut reader) = repo.read_file(&relative_path)? else {
+                continue;
+            };
+            let mut contents = Vec::new();
+            reader.read_to_end(&mut contents)?;
+            let contents = String::from_utf8_lossy(&contents);
+
+            let jobs = count_jobs(&relative_path, &contents)
+                .map(u32::try_from)
+                .transpose()?;
+
+            jobs_by_file.insert(relative_path, jobs);
+        }
+
+        let value = CiWorkflowsValue {
+            total_files: u32::try_from(jobs_by_file.len())?,
+            total_jobs: jobs_by_file.values().flatten().sum(),
+            jobs_by_file,
+        };
+
+        Ok(value.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(
+        ".github/workflows/check.yml",
+        "on: push\njobs:\n  a:\n    runs-on: x\n  b:\n    runs-on: x\n",
+        Some(2)
+    )]
+    #[case(".circleci/config.yml", "version: 2.1\njobs:\n  build: {}\n", Some(1))]
+    #[case(
+        ".gitlab-ci.yml",
+        "stages: [test]\n.template: {}\nlint:\n  script: x\ntest:\n  script: y\n",
+        Some(2)
+    )]
+    #[case(
+        "azure-pipelines.yml",
+        "stages:\n- stage: A\n  jobs:\n  - job: a\n  - job: b\n- stage: B\n  jobs:\n  - job: c\n",
+        Some(3)
+    )]
+    #[case(".travis.yml", "language: rust\n", Some(1))]
+    #[case(
+        "Jenkinsfile",
+        "pipeline { stages { stage('a') {} stage('b') {} } }",
+        Some(2)
+    )]
+    #[case(".github/workflows/broken.yml", "jobs: [", None)]
+    fn test_count_jobs(
+        #[case] path: &str,
+        #[case] contents: &str,
+        #[case] expected: Option<usize>,
+    ) {
+        assert_eq!(expected, count_jobs(path, contents));
+    }
+}
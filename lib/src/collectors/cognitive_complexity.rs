@@ -0,0 +1,432 @@
+use std::path::Path;
+
+use dashmap::DashMap;
+use petgraph::graph::NodeIndex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tree_sitter::{Language, Node, Parser, Tree};
+
+use crate::{
+    config::CollectorConfig,
+    git::{CommitHash, GitError, WorktreeHandle},
+    graph::CollectionExecutionGraph,
+};
+
+use super::{
+    changed_files::ChangedFilesValue,
+    registry::DependencyKey,
+    utils::{get_dependency_value, get_previous_commit_value_of_collector, LookupError},
+    BaseCollector, CollectorValue, CollectorValueCastError,
+};
+
+#[derive(Debug)]
+pub(crate) struct CognitiveComplexity;
+
+/// Cognitive complexity of all functions as defined by `SonarSource`, which unlike the cyclomatic
+/// complexity punishes nested control flow.
+///
+/// Rust, Python and JavaScript files are analyzed. Recursion is not counted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CognitiveComplexityValue {
+    pub functions: u32,
+    pub total: u64,
+
+    /// 90th percentile of the complexity per function
+    pub p90: u32,
+    pub max: u32,
+}
+
+#[derive(Error, Debug)]
+pub enum CognitiveComplexityError {
+    #[error(transparent)]
+    Lookup(#[from] LookupError),
+
+    #[error(transparent)]
+    Cast(#[from] CollectorValueCastError),
+
+    #[error(transparent)]
+    Git(#[from] GitError),
+
+    #[error(transparent)]
+    Language(#[from] tree_sitter::LanguageError),
+
+    #[error(transparent)]
+    TryFromIntError(#[from] std::num::TryFromIntError),
+}
+
+/// Node kinds of a tree-sitter grammar relevant for the cognitive complexity
+struct Grammar {
+    /// Functions which are not nested in another function are measured on their own
+    functions: &'static [&'static str],
+
+    /// Increase the nesting without increasing the complexity, e.g. closures
+    nesting_only: &'static [&'static str],
+
+    /// Increase the complexity by one plus the current nesting and the nesting of their children
+    nesting: &'static [&'static str],
+
+    if_statement: &'static str,
+    else_clause: &'static str,
+
+    /// Python's `elif`, other languages nest an `if` inside the `else`
+    elif_clause: Option<&'static str>,
+
+    logical_expression: &'static str,
+    logical_operators: &'static [&'static str],
+
+    /// Only increase the complexity if they jump to a label
+    jumps: &'static [&'static str],
+}
+
+const RUST: Grammar = Grammar {
+    functions: &["function_item"],
+    nesting_only: &["closure_expression"],
+    nesting: &[
+        "match_expression",
+        "for_expression",
+        "while_expression",
+        "loop_expression",
+    ],
+    if_statement: "if_expression",
+    else_clause: "else_clause",
+    elif_clause: None,
+    logical_expression: "binary_expression",
+    logical_operators: &["&&", "||"],
+    jumps: &["break_expression", "continue_expression"],
+};
+
+const PYTHON: Grammar = Grammar {
+    functions: &["function_definition"],
+    nesting_only: &["lambda"],
+    nesting: &[
+        "match_statement",
+        "for_statement",
+        "while_statement",
+        "except_clause",
+        "conditional_expression",
+    ],
+    if_statement: "if_statement",
+    else_clause: "else_clause",
+    elif_clause: Some("elif_clause"),
+    logical_expression: "boolean_operator",
+    logical_operators: &["and", "or"],
+    jumps: &[],
+};
+
+const JAVASCRIPT: Grammar = Grammar {
+    functions: &[
+        "function_declaration",
+        "function_expression",
+        "generator_function_declaration",
+        "generator_function",
+        "arrow_function",
+        "method_definition",
+    ],
+    nesting_only: &[],
+    nesting: &[
+        "switch_statement",
+        "for_statement",
+        "for_in_statement",
+        "while_statement",
+        "do_statement",
+        "catch_clause",
+        "ternary_expression",
+    ],
+    if_statement: "if_statement",
+    else_clause: "else_clause",
+    elif_clause: None,
+    logical_expression: "binary_expression",
+    logical_operators: &["&&", "||", "??"],
+    jumps: &["break_statement", "continue_statement"],
+};
+
+fn grammar_for_path(path: &Path) -> Option<(&'static Grammar, Language)> {
+    match path.extension()?.to_str()? {
+        "rs" => Some((&RUST, tree_sitter_rust::LANGUAGE.into())),
+        "py" => Some((&PYTHON, tree_sitter_python::LANGUAGE.into())),
+        "js" | "mjs" | "cjs" | "jsx" => {
+            Some((&JAVASCRIPT, tree_sitter_javascript::LANGUAGE.into()))
+        }
+        _ => None,
+    }
+}
+
+fn logical_operator<'a>(grammar: &Grammar, node: Node<'a>) -> Option<&'a str> {
+    if node.kind() != grammar.logical_expression {
+        return None;
+    }
+
+    let operator = node.child_by_field_name("operator")?.kind();
+    grammar
+        .logical_operators
+        .contains(&operator)
+        .then_some(operator)
+}
+
+fn has_label(node: Node) -> bool {
+    node.child_by_field_name("label").is_some()
+        || node
+            .children(&mut node.walk())
+            .any(|child| child.kind() == "label")
+}
+
+/// Returns the cognitive complexity of every function in the tree
+fn function_complexities(grammar: &Grammar, tree: &Tree) -> Vec<usize> {
+    let mut complexities: Vec<usize> = vec![];
+
+    // Node, nesting level and index of the function it belongs to. Walked without recursion
+    // since deeply nested expressions are common in generated code.
+    let mut stack: Vec<(Node, usize, Option<usize>)> = vec![(tree.root_node(), 0, None)];
+
+    while let Some((node, nesting, function)) = stack.pop() {
+        let kind = node.kind();
+        let parent_kind = node.parent().map(|parent| parent.kind());
+
+        let mut function = function;
+        let mut increment = 0;
+        let mut child_nesting = nesting;
+        // Branches of an if are on the same level as the if itself
+        let mut branch_nesting = None;
+
+        if grammar.functions.contains(&kind) && function.is_none() {
+            complexities.push(0);
+            function = Some(complexities.len() - 1);
+            child_nesting = 0;
+        } else if grammar.functions.contains(&kind) || grammar.nesting_only.contains(&kind) {
+            child_nesting = nesting + 1;
+        } else if grammar.nesting.contains(&kind) {
+            increment = 1 + nesting;
+            child_nesting = nesting + 1;
+        } else if kind == grammar.if_statement {
+            let is_else_if = parent_kind == Some(grammar.else_clause);
+            increment = if is_else_if { 1 } else { 1 + nesting };
+            child_nesting = nesting + 1;
+            branch_nesting = Some(nesting);
+        } else if Some(kind) == grammar.elif_clause {
+            increment = 1;
+            child_nesting = nesting + 1;
+        } else if kind == grammar.else_clause && parent_kind == Some(grammar.if_statement) {
+            let is_else_if = node
+                .named_children(&mut node.walk())
+                .any(|child| child.kind() == grammar.if_statement);
+
+            if !is_else_if {
+                increment = 1;
+                child_nesting = nesting + 1;
+            }
+        } else if let Some(operator) = logical_operator(grammar, node) {
+            // Only the first operator of a sequence of equal ones is counted
+            let continues_sequence = node
+                .parent()
+                .and_then(|parent| logical_operator(grammar, parent))
+                .is_some_and(|parent_operator| parent_operator == operator);
+
+            if !continues_sequence {
+                increment = 1;
+            }
+        } else if grammar.jumps.contains(&kind) && has_label(node) {
+            increment = 1;
+        }
+
+        if let Some(function) = function {
+            complexities[function] += increment;
+        }
+
+        for child in node.children(&mut node.walk()) {
+            let is_branch =
+                child.kind() == grammar.else_clause || Some(child.kind()) == grammar.elif_clause;
+
+            let nesting = match branch_nesting {
+                Some(branch_nesting) if is_branch => branch_nesting,
+                _ => child_nesting,
+            };
+
+            stack.push((child, nesting, function));
+        }
+    }
+
+    complexities
+}
+
+/// Nearest-rank percentile
+fn p90(values: &mut [usize]) -> usize {
+    if values.is_empty() {
+        return 0;
+    }
+
+    values.sort_unstable();
+
+    values[(values.len() * 9).div_ceil(10) - 1]
+}
+
+impl BaseCollector for CognitiveComplexity {
+    type Error = CognitiveComplexityError;
+    const READS_TREE: bool = true;
+
+    #[tracing::instrument(level = "trace", skip_all)]
+    fn collect(
+        &self,
+        storage: &DashMap<(CollectorConfig, CommitHash), CollectorValue>,
+        repo: &mut WorktreeHandle,
+        graph: &CollectionExecutionGraph,
+        current_node_idx: NodeIndex,
+    ) -> Result<CollectorValue, CognitiveComplexityError> {
+        let changed_files_in_current_commit_value: ChangedFilesValue = get_dependency_value(
+            storage,
+            graph,
+            current_node_idx,
+            &DependencyKey::ChangedFiles,
+        )?
+        .try_into()?;
+
+        let has_modified_source_file = changed_files_in_current_commit_value
+            .files
+            .iter()
+            .any(|relative_path| grammar_for_path(Path::new(relative_path)).is_some());
+
+        if !has_modified_source_file {
+            let previous_commit_value =
+                get_previous_commit_value_of_collector(storage, graph, current_node_idx);
+
+            if let Some(previous_commit_value) = previous_commit_value {
+                return Ok(previous_commit_value);
+            }
+        }
+
+        let commit_hash = &graph.graph[current_node_idx].commit_hash;
+
+        let mut parser = Parser::new();
+        let mut complexities = vec![];
+        let mut result = Ok(());
+
+        repo.tree_reader(commit_hash)?.for_each_file(
+            |relative_path| grammar_for_path(Path::new(relative_path)).is_some(),
+            |relative_path, contents| {
+                let Some((grammar, language)) = grammar_for_path(Path::new(relative_path)) else {
+                    return;
+                };
+
+                if let Err(e) = parser.set_language(&language) {
+                    result = Err(e);
+                    return;
+                }
+
+                // Only fails if parsing was cancelled
+                if let Some(tree) = parser.parse(contents, None) {
+                    complexities.extend(function_complexities(grammar, &tree));
+                }
+            },
+        )?;
+
+        result?;
+
+        let value = CognitiveComplexityValue {
+            functions: u32::try_from(complexities.len())?,
+            total: u64::try_from(complexities.iter().sum::<usize>())?,
+            p90: u32::try_from(p90(&mut complexities))?,
+            max: u32::try_from(complexities.iter().max().copied().unwrap_or(0))?,
+        };
+
+        Ok(value.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(
+        "lib.rs",
+        r#"
+        fn sum_of_primes(max: u64) -> u64 {
+            let mut total = 0;
+            'outer: for i in 1..=max {
+                for j in 2..i {
+                    if i % j == 0 {
+                        continue 'outer;
+                    }
+                }
+                total += i;
+            }
+            total
+        }
+
+        fn get_words(number: u64) -> &'static str {
+            match number {
+                1 => "one",
+                _ => "many",
+            }
+        }
+        "#,
+        vec![7, 1]
+    )]
+    #[case(
+        "lib.rs",
+        r"
+        fn branches(a: bool, b: bool, c: bool) {
+            if a && b || c {
+                items.iter().for_each(|item| if item.valid { run() });
+            } else if b {
+            } else {
+            }
+        }
+        ",
+        vec![8]
+    )]
+    #[case(
+        "main.py",
+        r"
+def classify(x, y, z):
+    if x and y or z:
+        return 1
+    elif x:
+        for i in range(x):
+            if i:
+                pass
+    else:
+        return 3
+        ",
+        vec![10]
+    )]
+    #[case(
+        "index.js",
+        r"
+        function f(a) {
+            const g = () => a ? 1 : 2;
+            switch (a) { case 1: break; }
+            return g;
+        }
+
+        const h = (x) => { if (x ?? a) {} };
+        ",
+        vec![3, 2]
+    )]
+    fn test_function_complexities(
+        #[case] path: &str,
+        #[case] source: &str,
+        #[case] expected: Vec<usize>,
+    ) {
+        let (grammar, language) = grammar_for_path(Path::new(path)).unwrap();
+        let mut parser = Parser::new();
+        parser.set_language(&language).unwrap();
+        let tree = parser.parse(source, None).unwrap();
+
+        let mut complexities = function_complexities(grammar, &tree);
+        let mut expected = expected;
+        complexities.sort_unstable();
+        expected.sort_unstable();
+
+        assert_eq!(expected, complexities);
+    }
+
+    #[rstest]
+    #[case(vec![], 0)]
+    #[case(vec![5], 5)]
+    #[case(vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10], 9)]
+    #[case(vec![10, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1], 1)]
+    fn test_p90(#[case] mut values: Vec<usize>, #[case] expected: usize) {
+        assert_eq!(expected, p90(&mut values));
+    }
+}
@@ -0,0 +1,113 @@
+use std::path::Path;
+
+use dashmap::DashMap;
+use petgraph::graph::NodeIndex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokei::{LanguageType, Languages};
+
+use crate::{
+    config::CollectorConfig,
+    git::{CommitHash, WorktreeHandle},
+    graph::CollectionExecutionGraph,
+};
+
+use super::{
+    changed_files::ChangedFilesValue,
+    registry::DependencyKey,
+    utils::{get_dependency_value, get_previous_commit_value_of_collector, LookupError},
+    BaseCollector, CollectorValue, CollectorValueCastError,
+};
+
+#[derive(Debug)]
+pub(crate) struct CommentDensity;
+
+/// Lines of code and comments of all languages known to tokei.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommentDensityValue {
+    pub code_lines: u64,
+    pub comment_lines: u64,
+
+    /// Percentage of comment lines among code and comment lines, `None` if there are neither
+    pub comment_percentage: Option<f64>,
+}
+
+#[derive(Error, Debug)]
+pub enum CommentDensityError {
+    #[error(transparent)]
+    Lookup(#[from] LookupError),
+
+    #[error(transparent)]
+    Cast(#[from] CollectorValueCastError),
+
+    #[error(transparent)]
+    TryFromIntError(#[from] std::num::TryFromIntError),
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn comment_percentage(code_lines: u64, comment_lines: u64) -> Option<f64> {
+    let total = code_lines + comment_lines;
+
+    if total == 0 {
+        return None;
+    }
+
+    Some(comment_lines as f64 / total as f64 * 100.0)
+}
+
+impl BaseCollector for CommentDensity {
+    type Error = CommentDensityError;
+
+    #[tracing::instrument(level = "trace", skip_all)]
+    fn collect(
+        &self,
+        storage: &DashMap<(CollectorConfig, CommitHash), CollectorValue>,
+        repo: &mut WorktreeHandle,
+        graph: &CollectionExecutionGraph,
+        current_node_idx: NodeIndex,
+    ) -> Result<CollectorValue, CommentDensityError> {
+        let changed_files_in_current_commit_value: ChangedFilesValue = get_dependency_value(
+            storage,
+            graph,
+            current_node_idx,
+            &DependencyKey::ChangedFiles,
+        )?
+        .try_into()?;
+
+        let has_modified_source_file =
+            changed_files_in_current_commit_value
+                .files
+                .iter()
+                .any(|relative_path| {
+                    Path::new(relative_path)
+                        .extension()
+                        .and_then(|ext| ext.to_str())
+                        .and_then(LanguageType::from_file_extension)
+                        .is_some()
+                });
+
+        if !has_modified_source_file {
+            let previous_commit_value =
+                get_previous_commit_value_of_collector(storage, graph, current_node_idx);
+
+            if let Some(previous_commit_value) = previous_commit_value {
+                return Ok(previous_commit_value);
+            }
+        }
+
+        let mut languages = Languages::new();
+        languages.get_statistics(&[&repo.path], &[".git"], &tokei::Config::default());
+        let total = languages.total();
+
+        let code_lines = u64::try_from(total.code)?;
+        let comment_lines = u64::try_from(total.comments)?;
+
+        let value = CommentDensityValue {
+            code_lines,
+            comment_lines,
+            comment_percentage: comment_percentage(code_lines, comment_lines),
+        };
+
+        Ok(value.into())
+    }
+}
@@ -0,0 +1,156 @@
+use std::io::BufRead;
+
+use dashmap::DashMap;
+use globset::{Candidate, Glob, GlobSet, GlobSetBuilder};
+use petgraph::graph::NodeIndex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    config::CollectorConfig,
+    git::{CommitHash, GitError, WorktreeHandle},
+    graph::CollectionExecutionGraph,
+};
+
+use super::{
+    changed_files::ChangedFilesValue,
+    registry::DependencyKey,
+    utils::{get_dependency_value, get_previous_commit_value_of_collector, LookupError},
+    BaseCollector, CollectorValue, CollectorValueCastError,
+};
+
+const DEFAULT_GLOBS: [&str; 5] = [
+    "**/*.json",
+    "**/*.yaml",
+    "**/*.yml",
+    "**/*.toml",
+    "**/*.ini",
+];
+
+#[derive(Debug)]
+pub(crate) struct ConfigFiles {
+    /// Globs matching the configuration files, JSON, YAML, TOML and INI files if not set
+    pub files: Option<Vec<Glob>>,
+}
+
+/// Number of configuration files and their total lines, independent of the languages known to
+/// the `loc` collector.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigFilesValue {
+    pub file_count: u32,
+    pub total_lines: u64,
+}
+
+#[derive(Error, Debug)]
+pub enum ConfigFilesError {
+    #[error(transparent)]
+    Lookup(#[from] LookupError),
+
+    #[error(transparent)]
+    Cast(#[from] CollectorValueCastError),
+
+    #[error(transparent)]
+    Git(#[from] GitError),
+
+    #[error(transparent)]
+    Glob(#[from] globset::Error),
+
+    #[error(transparent)]
+    TryFromIntError(#[from] std::num::TryFromIntError),
+}
+
+impl ConfigFiles {
+    fn globset(&self) -> Result<GlobSet, globset::Error> {
+        let mut builder = GlobSetBuilder::new();
+
+        match &self.files {
+            Some(globs) => {
+                for glob in globs.iter().cloned() {
+                    builder.add(glob);
+                }
+            }
+            None => {
+                for glob in DEFAULT_GLOBS {
+                    builder.add(Glob::new(glob)?);
+                }
+            }
+        }
+
+        builder.build()
+    }
+}
+
+impl BaseCollector for ConfigFiles {
+    type Error = ConfigFilesError;
+    const READS_TREE: bool = true;
+
+    #[tracing::instrument(level = "trace", skip_all)]
+    fn collect(
+        &self,
+        storage: &DashMap<(CollectorConfig, CommitHash), CollectorValue>,
+        repo: &mut WorktreeHandle,
+        graph: &CollectionExecutionGraph,
+        current_node_idx: NodeIndex,
+    ) -> Result<CollectorValue, ConfigFilesError> {
+        let globset = self.globset()?;
+
+        let changed_files_in_current_commit_value: ChangedFilesValue = get_dependency_value(
+            storage,
+            graph,
+            current_node_idx,
+            &DependencyKey::ChangedFiles,
+        )?
+        .try_into()?;
+
+        let has_modified_config_file = changed_files_in_current_commit_value
+            .files
+            .iter()
+            .any(|relative_path| globset.is_match_candidate(&Candidate::new(relative_path)));
+
+        if !has_modified_config_file {
+            let previous_commit_value =
+                get_previous_commit_value_of_collector(storage, graph, current_node_idx);
+
+            if let Some(previous_commit_value) = previous_commit_value {
+                return Ok(previous_commit_value);
+            }
+        }
+
+        let commit_hash = &graph.graph[current_node_idx].commit_hash;
+
+        let mut file_count = 0;
+        let mut total_lines = 0;
+
+        repo.tree_reader(commit_hash)?.for_each_file(
+            |relative_path| globset.is_match_candidate(&Candidate::new(relative_path)),
+            |_, contents| {
+                file_count += 1;
+                total_lines += contents.lines().count();
+            },
+        )?;
+
+        let value = ConfigFilesValue {
+            file_count: u32::try_from(file_count)?,
+            total_lines: u64::try_from(total_lines)?,
+        };
+
+        Ok(value.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("Cargo.toml", true)]
+    #[case("config/app.yml", true)]
+    #[case(".github/workflows/ci.yaml", true)]
+    #[case("src/main.rs", false)]
+    #[case("package.json5", false)]
+    fn test_default_globs(#[case] path: &str, #[case] expected: bool) {
+        let globset = ConfigFiles { files: None }.globset().unwrap();
+        assert_eq!(expected, globset.is_match(path));
+    }
+}
@@ -0,0 +1,243 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    io::Read,
+    path::Path,
+};
+
+use dashmap::DashMap;
+use petgraph::graph::NodeIndex;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value as Yaml;
+use thiserror::Error;
+
+use crate::{
+    config::CollectorConfig,
+    git::{CommitHash, GitError, WorktreeHandle},
+    graph::CollectionExecutionGraph,
+};
+
+use super::{
+    changed_files::ChangedFilesValue,
+    registry::DependencyKey,
+    utils::{get_dependency_value, get_previous_commit_value_of_collector, LookupError},
+    BaseCollector, CollectorValue, CollectorValueCastError,
+};
+
+#[derive(Debug)]
+pub(crate) struct ContainerConfig;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContainerConfigValue {
+    pub dockerfiles: u32,
+    pub compose_files: u32,
+    pub kubernetes_manifests: u32,
+
+    /// Number of `FROM` instructions per base image across all Dockerfiles
+    pub base_images: BTreeMap<String, u32>,
+}
+
+#[derive(Error, Debug)]
+pub enum ContainerConfigError {
+    #[error(transparent)]
+    Lookup(#[from] LookupError),
+
+    #[error(transparent)]
+    Cast(#[from] CollectorValueCastError),
+
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Git(#[from] GitError),
+
+    #[error(transparent)]
+    TryFromIntError(#[from] std::num::TryFromIntError),
+}
+
+#[derive(Debug, PartialEq)]
+enum ContainerFileKind {
+    Dockerfile,
+    Compose,
+    /// Any YAML file, which might contain Kubernetes manifests
+    Yaml,
+}
+
+fn container_file_kind(path: &Path) -> Option<ContainerFileKind> {
+    let file_name = path.file_name()?.to_str()?;
+
+    if file_name == "Dockerfile"
+        || file_name == "Containerfile"
+        || file_name.starts_with("Dockerfile.")
+        || file_name.ends_with(".Dockerfile")
+    {
+        return Some(ContainerFileKind::Dockerfile);
+    }
+
+    let is_yaml = path
+        .extension()
+        .is_some_and(|ext| ext == "yml" || ext == "yaml");
+
+    if !is_yaml {
+        return None;
+    }
+
+    if file_name.starts_with("docker-compose") || file_name.starts_with("compose.") {
+        return Some(ContainerFileKind::Compose);
+    }
+
+    Some(ContainerFileKind::Yaml)
+}
+
+/// Returns the base images of all stages in a Dockerfile, ignoring references to earlier stages.
+fn parse_base_images(contents: &str) -> Vec<String> {
+    let contents = contents.replace("\\\n", " ");
+
+    let mut stages: HashSet<String> = HashSet::new();
+    let mut base_images = Vec::new();
+
+    for line in contents.lines() {
+        let mut tokens = line.split_whitespace();
+
+        if !tokens
+            .next()
+            .is_some_and(|instruction| instruction.eq_ignore_ascii_case("FROM"))
+        {
+            continue;
+        }
+
+        let mut tokens = tokens.skip_while(|token| token.starts_with("--"));
+
+        let Some(image) = tokens.next() else {
+            continue;
+        };
+
+        if !stages.contains(&image.to_lowercase()) {
+            base_images.push(image.to_string());
+        }
+
+        if tokens
+            .next()
+            .is_some_and(|token| token.eq_ignore_ascii_case("AS"))
+        {
+            if let Some(stage) = tokens.next() {
+                stages.insert(stage.to_lowercase());
+            }
+        }
+    }
+
+    base_images
+}
+
+fn is_kubernetes_manifest(contents: &str) -> bool {
+    serde_yaml::Deserializer::from_str(contents)
+        .map_while(|document| Yaml::deserialize(document).ok())
+        .any(|document| document.get("apiVersion").is_some() && document.get("kind").is_some())
+}
+
+impl BaseCollector for ContainerConfig {
+    type Error = ContainerConfigError;
+
+    #[tracing::instrument(level = "trace", skip_all)]
+    fn collect(
+        &self,
+        storage: &DashMap<(CollectorConfig, CommitHash), CollectorValue>,
+        repo: &mut WorktreeHandle,
+        graph: &CollectionExecutionGraph,
+        current_node_idx: NodeIndex,
+    ) -> Result<CollectorValue, ContainerConfigError> {
+        let changed_files_in_current_commit_value: ChangedFilesValue = get_dependency_value(
+            storage,
+            graph,
+            current_node_idx,
+            &DependencyKey::ChangedFiles,
+        )?
+        .try_into()?;
+
+        let has_modified_container_config = changed_files_in_current_commit_value
+            .files
+            .iter()
+            .any(|relative_path| container_file_kind(Path::new(relative_path)).is_some());
+
+        if !has_modified_container_config {
+            let previous_commit_value =
+                get_previous_commit_value_of_collector(storage, graph, current_node_idx);
+
+            if let Some(previous_commit_value) = previous_commit_value {
+                return Ok(previous_commit_value);
+            }
+        }
+
+        let mut dockerfiles = 0_usize;
+        let mut compose_files = 0_usize;
+        let mut kubernetes_manifests = 0_usize;
+        let mut base_images: BTreeMap<String, u32> = BTreeMap::new();
+
+        for relative_path in repo.walk_tracked_files(None)? {
+            let Some(kind) = container_file_kind(Path::new(&relative_path)) else {
+                continue;
+            };
+
+            if kind == ContainerFileKind::Compose {
+                compose_files += 1;
+                continue;
+            }
+
+            let Some(mut reader) = repo.read_file(&relative_path)? else {
+                continue;
+            };
+            let mut contents = Vec::new();
+            reader.read_to_end(&mut contents)?;
+            let contents = String::from_utf8_lossy(&contents);
+
+            if kind == ContainerFileKind::Dockerfile {
+                dockerfiles += 1;
+
+                for image in parse_base_images(&contents) {
+                    *base_images.entry(image).or_default() += 1;
+                }
+            } else if is_kubernetes_manifest(&contents) {
+                kubernetes_manifests += 1;
+            }
+        }
+
+        let value = ContainerConfigValue {
+            dockerfiles: u32::try_from(dockerfiles)?,
+            compose_files: u32::try_from(compose_files)?,
+            kubernetes_manifests: u32::try_from(kubernetes_manifests)?,
+            base_images,
+        };
+
+        Ok(value.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("FROM rust:1.79\nRUN cargo build\n", vec!["rust:1.79"])]
+    #[case(
+        "FROM --platform=$BUILDPLATFORM rust:1.79 AS builder\nFROM debian:bookworm-slim\nCOPY --from=builder /app /app\n",
+        vec!["rust:1.79", "debian:bookworm-slim"]
+    )]
+    #[case("FROM node:20 as build\nFROM build AS test\nFROM nginx\n", vec!["node:20", "nginx"])]
+    #[case("from \\\n  alpine:3.20\n", vec!["alpine:3.20"])]
+    #[case("# FROM ignored\nRUN echo FROM\n", vec![])]
+    fn test_parse_base_images(#[case] contents: &str, #[case] expected: Vec<&str>) {
+        assert_eq!(expected, parse_base_images(contents));
+    }
+
+    #[rstest]
+    #[case("apiVersion: v1\nkind: Service\n", true)]
+    #[case(
+        "name: ci\non: push\n---\napiVersion: apps/v1\nkind: Deployment\n",
+        true
+    )]
+    #[case("name: ci\non: push\n", false)]
+    #[case("[", false)]
+    fn test_is_kubernetes_manifest(#[case] contents: &str, #[case] expected: bool) {
+        assert_eq!(expected, is_kubernetes_manifest(contents));
+    }
+}
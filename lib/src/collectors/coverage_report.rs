@@ -0,0 +1,256 @@
+use std::{io::Read, path::Path};
+
+use dashmap::DashMap;
+use petgraph::graph::NodeIndex;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::warn;
+
+use crate::{
+    config::CollectorConfig,
+    git::{CommitHash, GitError, WorktreeHandle},
+    graph::CollectionExecutionGraph,
+};
+
+use super::{
+    changed_files::ChangedFilesValue,
+    registry::DependencyKey,
+    utils::{get_dependency_value, get_previous_commit_value_of_collector, LookupError},
+    BaseCollector, CollectorValue, CollectorValueCastError,
+};
+
+#[derive(Debug)]
+pub(crate) struct CoverageReport;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoverageReportValue {
+    pub total_reports: u32,
+    pub lines_valid: u32,
+    pub lines_covered: u32,
+
+    /// Percentage of covered lines across all reports, `None` if no report contained any lines
+    pub line_coverage: Option<f64>,
+}
+
+#[derive(Error, Debug)]
+pub enum CoverageReportError {
+    #[error(transparent)]
+    Lookup(#[from] LookupError),
+
+    #[error(transparent)]
+    Cast(#[from] CollectorValueCastError),
+
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Git(#[from] GitError),
+
+    #[error(transparent)]
+    TryFromIntError(#[from] std::num::TryFromIntError),
+}
+
+#[derive(Debug, PartialEq)]
+enum ReportFormat {
+    Lcov,
+    Cobertura,
+}
+
+fn report_format(path: &Path) -> Option<ReportFormat> {
+    let file_name = path.file_name()?.to_str()?;
+
+    if file_name == "lcov.info" || path.extension().is_some_and(|ext| ext == "lcov") {
+        return Some(ReportFormat::Lcov);
+    }
+
+    if file_name == "coverage.xml"
+        || file_name == "cobertura-coverage.xml"
+        || file_name.ends_with("cobertura.xml")
+    {
+        return Some(ReportFormat::Cobertura);
+    }
+
+    None
+}
+
+/// Returns the number of instrumented and covered lines of an lcov tracefile.
+fn parse_lcov(contents: &str) -> Option<(usize, usize)> {
+    let mut is_tracefile = false;
+
+    let mut lines_found = 0;
+    let mut lines_hit = 0;
+
+    // Fallback for records without a `LF`/`LH` summary
+    let mut record_has_summary = false;
+    let mut record_lines_found = 0;
+    let mut record_lines_hit = 0;
+
+    for line in contents.lines() {
+        let line = line.trim();
+
+        if line.starts_with("SF:") {
+            is_tracefile = true;
+        } else if let Some(count) = line.strip_prefix("LF:") {
+            record_has_summary = true;
+            lines_found += count.parse::<usize>().ok()?;
+        } else if let Some(count) = line.strip_prefix("LH:") {
+            lines_hit += count.parse::<usize>().ok()?;
+        } else if let Some(data) = line.strip_prefix("DA:") {
+            let hits = data.split(',').nth(1)?;
+            record_lines_found += 1;
+            if hits.parse::<f64>().ok()? > 0.0 {
+                record_lines_hit += 1;
+            }
+        } else if line == "end_of_record" {
+            if !record_has_summary {
+                lines_found += record_lines_found;
+                lines_hit += record_lines_hit;
+            }
+
+            record_has_summary = false;
+            record_lines_found = 0;
+            record_lines_hit = 0;
+        }
+    }
+
+    is_tracefile.then_some((lines_found, lines_hit))
+}
+
+/// Returns the number of valid and covered lines of a Cobertura XML report.
+fn parse_cobertura(contents: &str) -> Option<(usize, usize)> {
+    let root_re = Regex::new(r"<coverage\b([^>]*)>").expect("Failed to compile regex");
+    let root_attributes = root_re.captures(contents)?.get(1)?.as_str();
+
+    let attribute = |name: &str| -> Option<usize> {
+        let re = Regex::new(&format!(r#"\b{name}="(\d+)""#)).expect("Failed to compile regex");
+        re.captures(root_attributes)?.get(1)?.as_str().parse().ok()
+    };
+
+    if let (Some(lines_valid), Some(lines_covered)) =
+        (attribute("lines-valid"), attribute("lines-covered"))
+    {
+        return Some((lines_valid, lines_covered));
+    }
+
+    // Older Cobertura versions only report rates on the root element, count the lines instead
+    let line_re = Regex::new(r#"<line\b[^>]*\bhits="(\d+)""#).expect("Failed to compile regex");
+
+    let mut lines_valid = 0;
+    let mut lines_covered = 0;
+    for captures in line_re.captures_iter(contents) {
+        lines_valid += 1;
+        if captures[1].parse::<usize>().ok()? > 0 {
+            lines_covered += 1;
+        }
+    }
+
+    Some((lines_valid, lines_covered))
+}
+
+impl BaseCollector for CoverageReport {
+    type Error = CoverageReportError;
+
+    #[tracing::instrument(level = "trace", skip_all)]
+    fn collect(
+        &self,
+        storage: &DashMap<(CollectorConfig, CommitHash), CollectorValue>,
+        repo: &mut WorktreeHandle,
+        graph: &CollectionExecutionGraph,
+        current_node_idx: NodeIndex,
+    ) -> Result<CollectorValue, CoverageReportError> {
+        let changed_files_in_current_commit_value: ChangedFilesValue = get_dependency_value(
+            storage,
+            graph,
+            current_node_idx,
+            &DependencyKey::ChangedFiles,
+        )?
+        .try_into()?;
+
+        let has_modified_report = changed_files_in_current_commit_value
+            .files
+            .iter()
+            .any(|relative_path| report_format(Path::new(relative_path)).is_some());
+
+        if !has_modified_report {
+            let previous_commit_value =
+                get_previous_commit_value_of_collector(storage, graph, current_node_idx);
+
+            if let Some(previous_commit_value) = previous_commit_value {
+                return Ok(previous_commit_value);
+            }
+        }
+
+        let mut total_reports = 0_usize;
+        let mut lines_valid = 0_usize;
+        let mut lines_covered = 0_usize;
+
+        for relative_path in repo.walk_tracked_files(None)? {
+            let Some(format) = report_format(Path::new(&relative_path)) else {
+                continue;
+            };
+
+            let Some(mut reader) = repo.read_file(&relative_path)? else {
+                continue;
+            };
+            let mut contents = Vec::new();
+            reader.read_to_end(&mut contents)?;
+            let contents = String::from_utf8_lossy(&contents);
+
+            let lines = match format {
+                ReportFormat::Lcov => parse_lcov(&contents),
+                ReportFormat::Cobertura => parse_cobertura(&contents),
+            };
+
+            let Some((valid, covered)) = lines else {
+                warn!("Skipping unrecognized coverage report {}", relative_path);
+                continue;
+            };
+
+            total_reports += 1;
+            lines_valid += valid;
+            lines_covered += covered;
+        }
+
+        #[allow(clippy::cast_precision_loss)]
+        let line_coverage =
+            (lines_valid > 0).then(|| lines_covered as f64 / lines_valid as f64 * 100.0);
+
+        let value = CoverageReportValue {
+            total_reports: u32::try_from(total_reports)?,
+            lines_valid: u32::try_from(lines_valid)?,
+            lines_covered: u32::try_from(lines_covered)?,
+            line_coverage,
+        };
+
+        Ok(value.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("TN:\nSF:a.rs\nDA:1,1\nDA:2,0\nLF:2\nLH:1\nend_of_record\nSF:b.rs\nLF:3\nLH:3\nend_of_record\n", Some((5, 4)))]
+    #[case("SF:a.rs\nDA:1,3\nDA:2,0\nDA:3,1\nend_of_record\n", Some((3, 2)))]
+    #[case("not a tracefile\n", None)]
+    fn test_parse_lcov(#[case] contents: &str, #[case] expected: Option<(usize, usize)>) {
+        assert_eq!(expected, parse_lcov(contents));
+    }
+
+    #[rstest]
+    #[case(
+        r#"<?xml version="1.0" ?><coverage line-rate="0.75" lines-covered="3" lines-valid="4" version="7.2"></coverage>"#,
+        Some((4, 3))
+    )]
+    #[case(
+        r#"<coverage line-rate="0.5"><packages><package><classes><class><lines><line number="1" hits="2"/><line number="2" hits="0"/></lines></class></classes></package></packages></coverage>"#,
+        Some((2, 1))
+    )]
+    #[case(r#"<report name="jacoco"></report>"#, None)]
+    fn test_parse_cobertura(#[case] contents: &str, #[case] expected: Option<(usize, usize)>) {
+        assert_eq!(expected, parse_cobertura(contents));
+    }
+}
@@ -0,0 +1,96 @@
+use dashmap::DashMap;
+use petgraph::graph::NodeIndex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    config::{CollectorConfig, FileScope},
+    git::CommitHash,
+    graph::CollectionExecutionGraph,
+};
+
+use super::{
+    registry::DependencyKey,
+    total_diff_stat::TotalDiffStatValue,
+    utils::{find_preceding_node, get_dependency_value, LookupError},
+    CollectorValue, CollectorValueCastError, DerivedCollector,
+};
+
+#[derive(Debug)]
+pub(crate) struct CumulativeDiffStat {
+    pub scope: Box<FileScope>,
+}
+
+/// Running totals of lines added and deleted by all commits up to and including the current one.
+///
+/// Only the diff stats of commits which are part of the graph are accumulated, so the totals are
+/// only complete for metrics collected per commit.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct CumulativeDiffStatValue {
+    pub insertions: u64,
+    pub deletions: u64,
+}
+
+#[derive(Error, Debug)]
+pub enum CumulativeDiffStatError {
+    #[error(transparent)]
+    Lookup(#[from] LookupError),
+
+    #[error(transparent)]
+    Cast(#[from] CollectorValueCastError),
+}
+
+impl DerivedCollector for CumulativeDiffStat {
+    type Error = CumulativeDiffStatError;
+
+    #[tracing::instrument(level = "trace", skip_all)]
+    fn collect(
+        &self,
+        storage: &DashMap<(CollectorConfig, CommitHash), CollectorValue>,
+        graph: &CollectionExecutionGraph,
+        current_node_idx: NodeIndex,
+    ) -> Result<CollectorValue, CumulativeDiffStatError> {
+        let diff_stat_value: TotalDiffStatValue =
+            get_dependency_value(storage, graph, current_node_idx, &DependencyKey::Source)?
+                .try_into()?;
+
+        // The value of this collector at the previous commit in the graph, regardless of how
+        // many commits were skipped in between
+        let previous_node_idx = find_preceding_node(
+            graph,
+            current_node_idx,
+            |_| true,
+            |n| {
+                n.collector_config
+                    == CollectorConfig::CumulativeDiffStat {
+                        scope: self.scope.clone(),
+                    }
+            },
+        );
+
+        let previous_value = match previous_node_idx {
+            Some(task_idx) => {
+                let task = &graph.graph[task_idx];
+
+                let value: CumulativeDiffStatValue = storage
+                    .get(&(task.collector_config.clone(), task.commit_hash.clone()))
+                    .ok_or(LookupError { task_idx })?
+                    .clone()
+                    .try_into()?;
+
+                value
+            }
+            None => CumulativeDiffStatValue {
+                insertions: 0,
+                deletions: 0,
+            },
+        };
+
+        let value = CumulativeDiffStatValue {
+            insertions: previous_value.insertions + u64::from(diff_stat_value.insertions),
+            deletions: previous_value.deletions + u64::from(diff_stat_value.deletions),
+        };
+
+        Ok(value.into())
+    }
+}
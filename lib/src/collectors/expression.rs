@@ -0,0 +1,580 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use dashmap::DashMap;
+use petgraph::graph::NodeIndex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    config::{CollectorConfig, MetricConfig, MetricConfigError},
+    git::CommitHash,
+    graph::CollectionExecutionGraph,
+};
+
+use super::{
+    registry::DependencyKey,
+    utils::{get_dependency_value, LookupError},
+    CollectorValue, DerivedCollector,
+};
+
+/// Evaluates an arithmetic expression over the values of other metrics at the same commit
+#[derive(Debug)]
+pub(crate) struct Expression {
+    pub expression: String,
+    pub operands: BTreeMap<String, CollectorConfig>,
+}
+
+/// Result of an expression, `None` if an operand has no value or the expression divides by zero
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExpressionValue {
+    pub value: Option<f64>,
+}
+
+#[derive(Error, Debug, PartialEq)]
+#[error("{message} at position {position}")]
+pub struct ExpressionSyntaxError {
+    message: String,
+
+    /// Character the problem was found at, starting at 1
+    position: usize,
+}
+
+#[derive(Error, Debug)]
+pub enum ExpressionError {
+    #[error(transparent)]
+    Syntax(#[from] ExpressionSyntaxError),
+
+    #[error(transparent)]
+    Lookup(#[from] LookupError),
+
+    #[error("Operand {0} was not resolved to a collector")]
+    UnresolvedOperand(String),
+
+    #[error("Operand {operand} is not a number")]
+    NotNumeric { operand: String },
+
+    #[error("Operand {operand} has several numbers, select one of {}", fields.join(", "))]
+    AmbiguousOperand {
+        operand: String,
+        fields: Vec<String>,
+    },
+
+    #[error("Serde JSON error: {0}")]
+    SerdeJson(#[from] serde_json::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Number(f64),
+
+    /// Value of a metric, optionally a single field of it, e.g. `coverage.line_coverage`
+    Operand {
+        metric: String,
+        field: Option<String>,
+    },
+
+    Negate(Box<Node>),
+
+    Binary {
+        operator: Operator,
+        lhs: Box<Node>,
+        rhs: Box<Node>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Operator(char),
+    Open,
+    Close,
+}
+
+/// Splits an expression into tokens and the positions they start at. Names may contain `-`, so
+/// subtracting two metrics requires spaces around the operator.
+fn tokenize(expression: &str) -> Result<Vec<(Token, usize)>, ExpressionSyntaxError> {
+    let mut tokens = vec![];
+    let mut chars = expression.chars().enumerate().peekable();
+
+    while let Some((index, character)) = chars.next() {
+        let position = index + 1;
+
+        let token = match character {
+            c if c.is_whitespace() => continue,
+            '+' | '-' | '*' | '/' => Token::Operator(character),
+            '(' => Token::Open,
+            ')' => Token::Close,
+            c if c.is_ascii_digit() || c == '.' => {
+                let mut number = String::from(c);
+                while let Some((_, c)) = chars.next_if(|(_, c)| c.is_ascii_digit() || *c == '.') {
+                    number.push(c);
+                }
+
+                Token::Number(number.parse().map_err(|_| ExpressionSyntaxError {
+                    message: format!("Invalid number {number}"),
+                    position,
+                })?)
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut name = String::from(c);
+                while let Some((_, c)) =
+                    chars.next_if(|(_, c)| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'))
+                {
+                    name.push(c);
+                }
+
+                Token::Name(name)
+            }
+            c => {
+                return Err(ExpressionSyntaxError {
+                    message: format!("Unexpected character {c}"),
+                    position,
+                })
+            }
+        };
+
+        tokens.push((token, position));
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    index: usize,
+
+    /// Position after the last character, reported for expressions ending too early
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.index).map(|(token, _)| token)
+    }
+
+    fn error(&self, message: &str) -> ExpressionSyntaxError {
+        ExpressionSyntaxError {
+            message: message.to_string(),
+            position: self
+                .tokens
+                .get(self.index)
+                .map_or(self.end, |(_, position)| *position),
+        }
+    }
+
+    /// `sum := product (('+' | '-') product)*`
+    fn sum(&mut self) -> Result<Node, ExpressionSyntaxError> {
+        let mut node = self.product()?;
+
+        while let Some(Token::Operator(operator @ ('+' | '-'))) = self.peek() {
+            let operator = if *operator == '+' {
+                Operator::Add
+            } else {
+                Operator::Subtract
+            };
+            self.index += 1;
+
+            node = Node::Binary {
+                operator,
+                lhs: Box::new(node),
+                rhs: Box::new(self.product()?),
+            };
+        }
+
+        Ok(node)
+    }
+
+    /// `product := unary (('*' | '/') unary)*`
+    fn product(&mut self) -> Result<Node, ExpressionSyntaxError> {
+        let mut node = self.unary()?;
+
+        while let Some(Token::Operator(operator @ ('*' | '/'))) = self.peek() {
+            let operator = if *operator == '*' {
+                Operator::Multiply
+            } else {
+                Operator::Divide
+            };
+            self.index += 1;
+
+            node = Node::Binary {
+                operator,
+                lhs: Box::new(node),
+                rhs: Box::new(self.unary()?),
+            };
+        }
+
+        Ok(node)
+    }
+
+    /// `unary := '-' unary | number | name | '(' sum ')'`
+    fn unary(&mut self) -> Result<Node, ExpressionSyntaxError> {
+        let Some(token) = self.peek().cloned() else {
+            return Err(self.error("Unexpected end of expression"));
+        };
+
+        match token {
+            Token::Operator('-') => {
+                self.index += 1;
+                Ok(Node::Negate(Box::new(self.unary()?)))
+            }
+            Token::Number(number) => {
+                self.index += 1;
+                Ok(Node::Number(number))
+            }
+            Token::Name(name) => {
+                self.index += 1;
+
+                let (metric, field) = match name.split_once('.') {
+                    Some((metric, field)) => (metric.to_string(), Some(field.to_string())),
+                    None => (name, None),
+                };
+
+                Ok(Node::Operand { metric, field })
+            }
+            Token::Open => {
+                self.index += 1;
+                let node = self.sum()?;
+
+                if self.peek() != Some(&Token::Close) {
+                    return Err(self.error("Expected )"));
+                }
+                self.index += 1;
+
+                Ok(node)
+            }
+            Token::Operator(_) | Token::Close => Err(self.error("Expected a number or a metric")),
+        }
+    }
+}
+
+fn parse(expression: &str) -> Result<Node, ExpressionSyntaxError> {
+    let mut parser = Parser {
+        tokens: tokenize(expression)?,
+        index: 0,
+        end: expression.chars().count() + 1,
+    };
+
+    let node = parser.sum()?;
+
+    if parser.peek().is_some() {
+        return Err(parser.error("Expected an operator"));
+    }
+
+    Ok(node)
+}
+
+impl Node {
+    fn metrics<'a>(&'a self, metrics: &mut BTreeSet<&'a str>) {
+        match self {
+            Node::Number(_) => {}
+            Node::Operand { metric, .. } => {
+                metrics.insert(metric);
+            }
+            Node::Negate(node) => node.metrics(metrics),
+            Node::Binary { lhs, rhs, .. } => {
+                lhs.metrics(metrics);
+                rhs.metrics(metrics);
+            }
+        }
+    }
+
+    fn evaluate<E>(
+        &self,
+        operand: &impl Fn(&str, Option<&str>) -> Result<Option<f64>, E>,
+    ) -> Result<Option<f64>, E> {
+        let value = match self {
+            Node::Number(number) => Some(*number),
+            Node::Operand { metric, field } => operand(metric, field.as_deref())?,
+            Node::Negate(node) => node.evaluate(operand)?.map(|value| -value),
+            Node::Binary { operator, lhs, rhs } => {
+                let (Some(lhs), Some(rhs)) = (lhs.evaluate(operand)?, rhs.evaluate(operand)?)
+                else {
+                    return Ok(None);
+                };
+
+                Some(match operator {
+                    Operator::Add => lhs + rhs,
+                    Operator::Subtract => lhs - rhs,
+                    Operator::Multiply => lhs * rhs,
+                    Operator::Divide => lhs / rhs,
+                })
+            }
+        };
+
+        // Divisions by zero
+        Ok(value.filter(|value| value.is_finite()))
+    }
+}
+
+/// Checks the syntax of an expression
+pub(crate) fn validate(expression: &str) -> Result<(), ExpressionSyntaxError> {
+    parse(expression).map(|_| ())
+}
+
+/// Names of the metrics an expression refers to
+pub(crate) fn referenced_metrics(
+    expression: &str,
+) -> Result<BTreeSet<String>, ExpressionSyntaxError> {
+    let node = parse(expression)?;
+
+    let mut metrics = BTreeSet::new();
+    node.metrics(&mut metrics);
+
+    Ok(metrics.into_iter().map(ToString::to_string).collect())
+}
+
+/// Number of a value, either the given field or its only numeric one. `null` fields have no value.
+pub(crate) fn number(
+    value: &CollectorValue,
+    operand: &str,
+    field: Option<&str>,
+) -> Result<Option<f64>, ExpressionError> {
+    let serde_json::Value::Object(mut fields) = serde_json::to_value(value)? else {
+        return Err(ExpressionError::NotNumeric {
+            operand: operand.to_string(),
+        });
+    };
+    fields.remove("collector");
+
+    let field_value = if let Some(field) = field {
+        fields.remove(field)
+    } else {
+        let mut numeric: Vec<(String, serde_json::Value)> = fields
+            .into_iter()
+            .filter(|(_, value)| value.is_number() || value.is_null())
+            .collect();
+
+        if numeric.len() > 1 {
+            return Err(ExpressionError::AmbiguousOperand {
+                operand: operand.to_string(),
+                fields: numeric.into_iter().map(|(field, _)| field).collect(),
+            });
+        }
+
+        numeric.pop().map(|(_, value)| value)
+    };
+
+    match field_value {
+        Some(serde_json::Value::Null) => Ok(None),
+        Some(serde_json::Value::Number(number)) => Ok(number.as_f64()),
+        _ => Err(ExpressionError::NotNumeric {
+            operand: field.map_or(operand.to_string(), |field| format!("{operand}.{field}")),
+        }),
+    }
+}
+
+impl DerivedCollector for Expression {
+    type Error = ExpressionError;
+
+    #[tracing::instrument(level = "trace", skip_all)]
+    fn collect(
+        &self,
+        storage: &DashMap<(CollectorConfig, CommitHash), CollectorValue>,
+        graph: &CollectionExecutionGraph,
+        current_node_idx: NodeIndex,
+    ) -> Result<CollectorValue, ExpressionError> {
+        let node = parse(&self.expression)?;
+
+        let value = node.evaluate(&|metric, field| {
+            if !self.operands.contains_key(metric) {
+                return Err(ExpressionError::UnresolvedOperand(metric.to_string()));
+            }
+
+            let value = get_dependency_value(
+                storage,
+                graph,
+                current_node_idx,
+                &DependencyKey::Operand(metric.to_string()),
+            )?;
+
+            number(&value, metric, field)
+        })?;
+
+        Ok(ExpressionValue { value }.into())
+    }
+}
+
+/// Fills in the collectors of the metrics referenced by the expression metrics. Operands which
+/// were resolved before are kept if their metric is not part of the given ones.
+pub(crate) fn resolve(
+    metrics: &mut HashMap<String, MetricConfig>,
+) -> Result<(), (String, MetricConfigError)> {
+    let mut metric_names: Vec<String> = metrics.keys().cloned().collect();
+    metric_names.sort();
+
+    let mut resolved: HashMap<String, CollectorConfig> = HashMap::new();
+    for metric_name in &metric_names {
+        let collector = resolve_collector(metrics, metric_name, &mut resolved, &mut HashSet::new())
+            .map_err(|error| (metric_name.clone(), error))?;
+
+        if let Some(metric) = metrics.get_mut(metric_name) {
+            metric.collector = collector;
+        }
+    }
+
+    Ok(())
+}
+
+fn resolve_collector(
+    metrics: &HashMap<String, MetricConfig>,
+    metric_name: &str,
+    resolved: &mut HashMap<String, CollectorConfig>,
+    visiting: &mut HashSet<String>,
+) -> Result<CollectorConfig, MetricConfigError> {
+    if let Some(collector) = resolved.get(metric_name) {
+        return Ok(collector.clone());
+    }
+
+    let collector = &metrics[metric_name].collector;
+
+    let CollectorConfig::Expression {
+        expression,
+        operands,
+    } = collector
+    else {
+        return Ok(collector.clone());
+    };
+
+    if !visiting.insert(metric_name.to_string()) {
+        return Err(MetricConfigError::CyclicExpression(metric_name.to_string()));
+    }
+
+    let mut resolved_operands = BTreeMap::new();
+    for operand in referenced_metrics(expression).map_err(MetricConfigError::Expression)? {
+        let collector = if metrics.contains_key(&operand) {
+            resolve_collector(metrics, &operand, resolved, visiting)?
+        } else if let Some(collector) = operands.get(&operand) {
+            collector.clone()
+        } else {
+            return Err(MetricConfigError::UnknownOperand(operand));
+        };
+
+        resolved_operands.insert(operand, collector);
+    }
+
+    visiting.remove(metric_name);
+
+    let collector = CollectorConfig::Expression {
+        expression: expression.clone(),
+        operands: resolved_operands,
+    };
+    resolved.insert(metric_name.to_string(), collector.clone());
+
+    Ok(collector)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{collectors::TotalLocValue, Frequency};
+
+    #[test]
+    fn test_expression_evaluation() {
+        let evaluate = |expression: &str| {
+            parse(expression)
+                .unwrap()
+                .evaluate(&|metric, _| -> Result<Option<f64>, ()> {
+                    Ok(match metric {
+                        "total-loc" => Some(200.0),
+                        "todos" => Some(4.0),
+                        _ => None,
+                    })
+                })
+                .unwrap()
+        };
+
+        assert_eq!(evaluate("todos / total-loc * 1000"), Some(20.0));
+        assert_eq!(evaluate("-(todos - 1) + 2 * 3"), Some(3.0));
+        assert_eq!(evaluate("todos - total-loc / (todos - 4)"), None);
+        assert_eq!(evaluate("score * 2"), None);
+
+        assert_eq!(
+            parse("todos /").unwrap_err(),
+            ExpressionSyntaxError {
+                message: "Unexpected end of expression".to_string(),
+                position: 8,
+            }
+        );
+        assert_eq!(parse("(todos").unwrap_err().position, 7);
+        assert_eq!(parse("todos loc").unwrap_err().position, 7);
+        assert_eq!(parse("todos % 2").unwrap_err().position, 7);
+    }
+
+    #[test]
+    fn test_operand_numbers() {
+        let loc = CollectorValue::TotalLoc(TotalLocValue { loc: 12 });
+        assert_eq!(number(&loc, "loc", None).unwrap(), Some(12.0));
+        assert_eq!(number(&loc, "loc", Some("loc")).unwrap(), Some(12.0));
+        assert!(number(&loc, "loc", Some("lines")).is_err());
+
+        let value = CollectorValue::Expression(ExpressionValue { value: None });
+        assert_eq!(number(&value, "ratio", None).unwrap(), None);
+    }
+
+    #[test]
+    fn test_resolve_operands() {
+        let metric = |collector: CollectorConfig| MetricConfig {
+            collector,
+            frequency: Frequency::PerCommit,
+            branch: None,
+            cache: None,
+            thresholds: None,
+            campaign: None,
+            align_to_calendar: None,
+            phase_offset: None,
+            max_concurrency: None,
+        };
+        let expression = |expression: &str| {
+            metric(CollectorConfig::Expression {
+                expression: expression.to_string(),
+                operands: BTreeMap::new(),
+            })
+        };
+
+        let loc = CollectorConfig::TotalLoc {
+            scope: Box::default(),
+        };
+
+        let mut metrics = HashMap::from([
+            ("loc".to_string(), metric(loc.clone())),
+            ("half".to_string(), expression("loc / 2")),
+            ("quarter".to_string(), expression("half / 2")),
+        ]);
+        resolve(&mut metrics).unwrap();
+
+        let CollectorConfig::Expression { operands, .. } = &metrics["quarter"].collector else {
+            panic!("unexpected collector {:?}", metrics["quarter"].collector);
+        };
+        assert_eq!(
+            operands["half"],
+            CollectorConfig::Expression {
+                expression: "loc / 2".to_string(),
+                operands: BTreeMap::from([("loc".to_string(), loc)]),
+            }
+        );
+
+        let mut metrics = HashMap::from([("a".to_string(), expression("b + 1"))]);
+        assert!(matches!(
+            resolve(&mut metrics),
+            Err((_, MetricConfigError::UnknownOperand(operand))) if operand == "b"
+        ));
+
+        let mut metrics = HashMap::from([
+            ("a".to_string(), expression("b + 1")),
+            ("b".to_string(), expression("a + 1")),
+        ]);
+        assert!(matches!(
+            resolve(&mut metrics),
+            Err((_, MetricConfigError::CyclicExpression(_)))
+        ));
+    }
+}
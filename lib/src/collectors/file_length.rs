@@ -0,0 +1,148 @@
+use std::path::Path;
+
+use dashmap::DashMap;
+use petgraph::graph::NodeIndex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokei::{LanguageType, Languages};
+
+use crate::{
+    config::CollectorConfig,
+    git::{CommitHash, WorktreeHandle},
+    graph::CollectionExecutionGraph,
+};
+
+use super::{
+    changed_files::ChangedFilesValue,
+    registry::DependencyKey,
+    utils::{get_dependency_value, get_previous_commit_value_of_collector, LookupError},
+    BaseCollector, CollectorValue, CollectorValueCastError,
+};
+
+#[derive(Debug)]
+pub(crate) struct FileLength;
+
+/// Lines of code per source file, counted the same way as the `loc` collector.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileLengthValue {
+    pub file_count: u32,
+    pub average_loc: f64,
+    pub median_loc: f64,
+}
+
+#[derive(Error, Debug)]
+pub enum FileLengthError {
+    #[error(transparent)]
+    Lookup(#[from] LookupError),
+
+    #[error(transparent)]
+    Cast(#[from] CollectorValueCastError),
+
+    #[error(transparent)]
+    TryFromIntError(#[from] std::num::TryFromIntError),
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn average(values: &[usize]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+
+    values.iter().sum::<usize>() as f64 / values.len() as f64
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn median(values: &mut [usize]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+
+    values.sort_unstable();
+
+    let middle = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[middle - 1] + values[middle]) as f64 / 2.0
+    } else {
+        values[middle] as f64
+    }
+}
+
+impl BaseCollector for FileLength {
+    type Error = FileLengthError;
+
+    #[tracing::instrument(level = "trace", skip_all)]
+    fn collect(
+        &self,
+        storage: &DashMap<(CollectorConfig, CommitHash), CollectorValue>,
+        repo: &mut WorktreeHandle,
+        graph: &CollectionExecutionGraph,
+        current_node_idx: NodeIndex,
+    ) -> Result<CollectorValue, FileLengthError> {
+        let changed_files_in_current_commit_value: ChangedFilesValue = get_dependency_value(
+            storage,
+            graph,
+            current_node_idx,
+            &DependencyKey::ChangedFiles,
+        )?
+        .try_into()?;
+
+        let has_modified_source_file =
+            changed_files_in_current_commit_value
+                .files
+                .iter()
+                .any(|relative_path| {
+                    Path::new(relative_path)
+                        .extension()
+                        .and_then(|ext| ext.to_str())
+                        .and_then(LanguageType::from_file_extension)
+                        .is_some()
+                });
+
+        if !has_modified_source_file {
+            let previous_commit_value =
+                get_previous_commit_value_of_collector(storage, graph, current_node_idx);
+
+            if let Some(previous_commit_value) = previous_commit_value {
+                return Ok(previous_commit_value);
+            }
+        }
+
+        let mut languages = Languages::new();
+        languages.get_statistics(&[&repo.path], &[".git"], &tokei::Config::default());
+
+        let mut file_lengths: Vec<usize> = languages
+            .values()
+            .flat_map(|language| &language.reports)
+            .map(|report| report.stats.summarise().code)
+            .filter(|code| *code > 0)
+            .collect();
+
+        let value = FileLengthValue {
+            file_count: u32::try_from(file_lengths.len())?,
+            average_loc: average(&file_lengths),
+            median_loc: median(&mut file_lengths),
+        };
+
+        Ok(value.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(vec![], 0.0, 0.0)]
+    #[case(vec![10], 10.0, 10.0)]
+    #[case(vec![30, 10, 20], 20.0, 20.0)]
+    #[case(vec![1, 100, 3, 2], 26.5, 2.5)]
+    fn test_average_and_median(
+        #[case] mut values: Vec<usize>,
+        #[case] expected_average: f64,
+        #[case] expected_median: f64,
+    ) {
+        assert!((average(&values) - expected_average).abs() < f64::EPSILON);
+        assert!((median(&mut values) - expected_median).abs() < f64::EPSILON);
+    }
+}
@@ -4,7 +4,7 @@ use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use crate::{
-    config::CollectorConfig,
+    config::{CollectorConfig, FileScope},
     git::{CommitHash, GitError, WorktreeHandle},
     graph::CollectionExecutionGraph,
 };
@@ -12,7 +12,9 @@ use crate::{
 use super::{BaseCollector, CollectorValue};
 
 #[derive(Debug)]
-pub(crate) struct FileList;
+pub(crate) struct FileList {
+    pub scope: Box<FileScope>,
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct FileListValue {
@@ -21,8 +23,11 @@ pub struct FileListValue {
 
 #[derive(Error, Debug)]
 pub enum FileListError {
-    #[error("{0}")]
+    #[error(transparent)]
     Git(#[from] GitError),
+
+    #[error(transparent)]
+    Glob(#[from] globset::Error),
 }
 
 impl BaseCollector for FileList {
@@ -36,7 +41,8 @@ impl BaseCollector for FileList {
         _graph: &CollectionExecutionGraph,
         _current_node_idx: NodeIndex,
     ) -> Result<CollectorValue, FileListError> {
-        let files_at_current_commit = repo.list_files()?;
+        let scope = self.scope.matcher()?;
+        let files_at_current_commit = repo.list_files(|path| scope.is_match(path))?;
 
         let value = FileListValue {
             files: files_at_current_commit,
@@ -0,0 +1,105 @@
+use dashmap::DashMap;
+use petgraph::graph::NodeIndex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    config::CollectorConfig,
+    git::{CommitHash, GitError, WorktreeHandle},
+    graph::CollectionExecutionGraph,
+};
+
+use super::{BaseCollector, CollectorValue};
+
+#[derive(Debug)]
+pub(crate) struct FormattingChanges;
+
+/// Classifies the changes of a commit into formatting (whitespace only) and substantive ones.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct FormattingChangesValue {
+    /// Number of inserted and deleted lines
+    pub changed_lines: u32,
+
+    /// Number of inserted and deleted lines when ignoring whitespace
+    pub substantive_lines: u32,
+
+    /// True if the commit changed lines, but none of them when ignoring whitespace
+    pub formatting_only: bool,
+
+    /// Percentage of changed lines which only differ in whitespace, `None` if no line changed
+    pub formatting_percentage: Option<f64>,
+}
+
+#[derive(Error, Debug)]
+pub enum FormattingChangesError {
+    #[error(transparent)]
+    Git(#[from] GitError),
+
+    #[error(transparent)]
+    TryFromIntError(#[from] std::num::TryFromIntError),
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn formatting_percentage(changed_lines: usize, substantive_lines: usize) -> Option<f64> {
+    if changed_lines == 0 {
+        return None;
+    }
+
+    let formatting_lines = changed_lines.saturating_sub(substantive_lines);
+
+    Some(formatting_lines as f64 / changed_lines as f64 * 100.0)
+}
+
+impl BaseCollector for FormattingChanges {
+    type Error = FormattingChangesError;
+
+    #[tracing::instrument(level = "trace", skip_all)]
+    fn collect(
+        &self,
+        _storage: &DashMap<(CollectorConfig, CommitHash), CollectorValue>,
+        repo: &mut WorktreeHandle,
+        graph: &CollectionExecutionGraph,
+        current_node_idx: NodeIndex,
+    ) -> Result<CollectorValue, FormattingChangesError> {
+        let commit_hash = &graph.graph[current_node_idx].commit_hash;
+        let repo = repo.repository();
+
+        let (_, insertions, deletions) = repo.get_total_diff_stat(commit_hash)?;
+        let (_, substantive_insertions, substantive_deletions) =
+            repo.get_total_diff_stat_ignoring_whitespace(commit_hash)?;
+
+        let changed_lines = insertions + deletions;
+        let substantive_lines = substantive_insertions + substantive_deletions;
+
+        let value = FormattingChangesValue {
+            changed_lines: u32::try_from(changed_lines)?,
+            substantive_lines: u32::try_from(substantive_lines)?,
+            formatting_only: changed_lines > 0 && substantive_lines == 0,
+            formatting_percentage: formatting_percentage(changed_lines, substantive_lines),
+        };
+
+        Ok(value.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(0, 0, None)]
+    #[case(10, 0, Some(100.0))]
+    #[case(10, 10, Some(0.0))]
+    #[case(8, 6, Some(25.0))]
+    fn test_formatting_percentage(
+        #[case] changed_lines: usize,
+        #[case] substantive_lines: usize,
+        #[case] expected: Option<f64>,
+    ) {
+        assert_eq!(
+            expected,
+            formatting_percentage(changed_lines, substantive_lines)
+        );
+    }
+}
@@ -0,0 +1,196 @@
+use std::collections::BTreeMap;
+
+use dashmap::DashMap;
+use petgraph::graph::NodeIndex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    config::{CollectorConfig, FileScope},
+    git::{CommitHash, GitError, WorktreeHandle},
+    graph::CollectionExecutionGraph,
+};
+
+use super::{
+    changed_files::ChangedFilesValue,
+    registry::DependencyKey,
+    suppressions::{Suppressions, SuppressionsError},
+    utils::{get_dependency_value, is_binary, LookupError},
+    BaseCollector, CollectorValue, CollectorValueCastError,
+};
+
+/// Shorter strings are never flagged, their entropy is too low to tell keys from words
+const MIN_LENGTH: usize = 20;
+
+/// Bits of entropy per character above which a string of base64 characters is flagged
+const BASE64_THRESHOLD: f64 = 4.5;
+
+/// Bits of entropy per character above which a string of hex digits is flagged, at most 4 bits
+/// are possible with 16 digits
+const HEX_THRESHOLD: f64 = 3.0;
+
+#[derive(Debug)]
+pub(crate) struct HighEntropyStrings {
+    pub scope: Box<FileScope>,
+}
+
+/// Strings in the files changed by the commit which look random enough to be keys or tokens.
+/// Only their number is kept, never the strings themselves.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HighEntropyStringsValue {
+    pub total: u32,
+
+    /// Number of strings per changed file containing any
+    pub strings_by_file: BTreeMap<String, u32>,
+}
+
+#[derive(Error, Debug)]
+pub enum HighEntropyStringsError {
+    #[error(transparent)]
+    Lookup(#[from] LookupError),
+
+    #[error(transparent)]
+    Cast(#[from] CollectorValueCastError),
+
+    #[error(transparent)]
+    Git(#[from] GitError),
+
+    #[error(transparent)]
+    Glob(#[from] globset::Error),
+
+    #[error(transparent)]
+    TryFromIntError(#[from] std::num::TryFromIntError),
+
+    #[error(transparent)]
+    Suppressions(#[from] SuppressionsError),
+}
+
+/// Shannon entropy of the bytes of the string in bits per byte
+#[allow(clippy::cast_precision_loss)]
+fn entropy(string: &[u8]) -> f64 {
+    let mut counts = [0_usize; 256];
+    for byte in string {
+        counts[usize::from(*byte)] += 1;
+    }
+
+    let length = string.len() as f64;
+    counts
+        .iter()
+        .filter(|count| **count > 0)
+        .map(|count| {
+            let probability = *count as f64 / length;
+            -probability * probability.log2()
+        })
+        .sum()
+}
+
+fn is_base64_character(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || matches!(byte, b'+' | b'/' | b'=' | b'_' | b'-')
+}
+
+/// Number of runs of base64 characters in the contents of the file at the path whose entropy is
+/// above the threshold, unless they are suppressed
+fn count_high_entropy_strings(contents: &[u8], path: &str, suppressions: &Suppressions) -> usize {
+    contents
+        .split(|byte| !is_base64_character(*byte))
+        .filter(|string| string.len() >= MIN_LENGTH)
+        .filter(|string| {
+            let threshold = if string.iter().all(u8::is_ascii_hexdigit) {
+                HEX_THRESHOLD
+            } else {
+                BASE64_THRESHOLD
+            };
+
+            entropy(string) > threshold
+        })
+        .filter(|string| !suppressions.is_suppressed(path, &String::from_utf8_lossy(string)))
+        .count()
+}
+
+impl BaseCollector for HighEntropyStrings {
+    type Error = HighEntropyStringsError;
+    const READS_TREE: bool = true;
+
+    #[tracing::instrument(level = "trace", skip_all)]
+    fn collect(
+        &self,
+        storage: &DashMap<(CollectorConfig, CommitHash), CollectorValue>,
+        repo: &mut WorktreeHandle,
+        graph: &CollectionExecutionGraph,
+        current_node_idx: NodeIndex,
+    ) -> Result<CollectorValue, HighEntropyStringsError> {
+        let changed_files_in_current_commit_value: ChangedFilesValue = get_dependency_value(
+            storage,
+            graph,
+            current_node_idx,
+            &DependencyKey::ChangedFiles,
+        )?
+        .try_into()?;
+
+        let scope = self.scope.matcher()?;
+
+        let commit_hash = &graph.graph[current_node_idx].commit_hash;
+        let tree = repo.tree_reader(commit_hash)?;
+        let suppressions = Suppressions::of_commit(repo, commit_hash)?;
+
+        let mut strings_by_file = BTreeMap::new();
+        for path in changed_files_in_current_commit_value.files {
+            if !scope.is_match(&path) {
+                continue;
+            }
+
+            // Removed in the current commit
+            let Some(contents) = tree.read_file(&path)? else {
+                continue;
+            };
+
+            if is_binary(&contents) {
+                continue;
+            }
+
+            let count = count_high_entropy_strings(&contents, &path, &suppressions);
+            if count > 0 {
+                strings_by_file.insert(path, u32::try_from(count)?);
+            }
+        }
+
+        let value = HighEntropyStringsValue {
+            total: strings_by_file.values().sum(),
+            strings_by_file,
+        };
+
+        Ok(value.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_count_high_entropy_strings() {
+        let suppressions = Suppressions::default();
+
+        assert!((entropy(b"aaaa")).abs() < f64::EPSILON);
+        assert!((entropy(b"abcd") - 2.0).abs() < f64::EPSILON);
+
+        // Identifiers and paths are below the threshold
+        assert_eq!(
+            0,
+            count_high_entropy_strings(
+                b"let configuration_directory = \"src/collectors/configuration\";",
+                "src/main.rs",
+                &suppressions
+            )
+        );
+        // Random tokens and digests above it
+        assert_eq!(
+            2,
+            count_high_entropy_strings(
+                b"token = \"q8Vz3LmK9pXw2RtY7nB4cJ6hF1sD5gA0\"\nsha = \"9f86d081884c7d659a2feaa0c55ad015a3bf4f1b\"",
+                "src/main.rs",
+                &suppressions
+            )
+        );
+    }
+}
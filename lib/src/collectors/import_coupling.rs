@@ -0,0 +1,511 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::Read,
+    path::Path,
+};
+
+use dashmap::DashMap;
+use petgraph::graph::NodeIndex;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    config::CollectorConfig,
+    git::{CommitHash, GitError, WorktreeHandle},
+    graph::CollectionExecutionGraph,
+};
+
+use super::{
+    changed_files::ChangedFilesValue,
+    registry::DependencyKey,
+    utils::{get_dependency_value, get_previous_commit_value_of_collector, LookupError},
+    BaseCollector, CollectorValue, CollectorValueCastError,
+};
+
+const JS_EXTENSIONS: [&str; 6] = ["js", "jsx", "mjs", "cjs", "ts", "tsx"];
+
+/// Directories absolute Python imports are resolved from
+const PYTHON_ROOTS: [&str; 2] = ["", "src"];
+
+#[derive(Debug)]
+pub(crate) struct ImportCoupling;
+
+/// Coupling between the source files of a repository, based on the imports between them.
+///
+/// Only imports which resolve to another file of the repository are considered, imports of
+/// external packages are ignored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportCouplingValue {
+    pub modules: u32,
+
+    /// Number of distinct pairs of files where one imports the other
+    pub internal_edges: u32,
+
+    /// Average number of files importing a file, across all files imported at least once
+    pub average_fan_in: f64,
+
+    /// Average number of files imported by a file, across all files importing at least one
+    pub average_fan_out: f64,
+
+    pub max_fan_in: u32,
+    pub max_fan_out: u32,
+}
+
+#[derive(Error, Debug)]
+pub enum ImportCouplingError {
+    #[error(transparent)]
+    Lookup(#[from] LookupError),
+
+    #[error(transparent)]
+    Cast(#[from] CollectorValueCastError),
+
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Git(#[from] GitError),
+
+    #[error(transparent)]
+    TryFromIntError(#[from] std::num::TryFromIntError),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Language {
+    Rust,
+    JavaScript,
+    Python,
+}
+
+fn language(path: &Path) -> Option<Language> {
+    let extension = path.extension()?.to_str()?;
+
+    match extension {
+        "rs" => Some(Language::Rust),
+        "py" => Some(Language::Python),
+        _ if JS_EXTENSIONS.contains(&extension) => Some(Language::JavaScript),
+        _ => None,
+    }
+}
+
+/// Resolves `.` and `..` segments of a relative path, `None` if it leaves the repository.
+fn normalize(path: &str) -> Option<String> {
+    let mut segments: Vec<&str> = Vec::new();
+
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop()?;
+            }
+            _ => segments.push(segment),
+        }
+    }
+
+    Some(segments.join("/"))
+}
+
+fn parent_directory(path: &str) -> &str {
+    path.rsplit_once('/').map_or("", |(parent, _)| parent)
+}
+
+fn join(directory: &str, path: &str) -> String {
+    if directory.is_empty() {
+        path.to_string()
+    } else {
+        format!("{directory}/{path}")
+    }
+}
+
+/// Expands a Rust use tree like `crate::a::{b, c::{self, D}}` into its individual paths.
+fn expand_use_tree(tree: &str) -> Vec<Vec<String>> {
+    let Some(open) = tree.find('{') else {
+        let segments: Vec<String> = tree
+            .split("::")
+            .filter(|segment| !segment.is_empty() && *segment != "*")
+            .map(|segment| segment.split(" as ").next().unwrap_or(segment).trim())
+            .map(ToString::to_string)
+            .collect();
+
+        return vec![segments];
+    };
+
+    let prefix: Vec<String> = tree[..open]
+        .split("::")
+        .filter(|segment| !segment.is_empty())
+        .map(ToString::to_string)
+        .collect();
+
+    let close = tree.rfind('}').unwrap_or(tree.len());
+    let inner = &tree[open + 1..close.max(open + 1)];
+
+    let mut items = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (idx, c) in inner.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(&inner[start..idx]);
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&inner[start..]);
+
+    items
+        .into_iter()
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .flat_map(expand_use_tree)
+        .map(|path| {
+            let mut full_path = prefix.clone();
+            full_path.extend(path.into_iter().filter(|segment| segment != "self"));
+            full_path
+        })
+        .collect()
+}
+
+/// Returns the directory containing the submodules of the module defined in the given file.
+fn rust_module_directory(path: &str) -> String {
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+
+    match file_name {
+        "mod.rs" | "lib.rs" | "main.rs" => parent_directory(path).to_string(),
+        _ => path.trim_end_matches(".rs").to_string(),
+    }
+}
+
+/// Returns the candidates for the file defining the module in the given directory.
+fn rust_module_files(directory: &str, crate_root: &str) -> Vec<String> {
+    if directory == crate_root {
+        return vec![join(directory, "lib.rs"), join(directory, "main.rs")];
+    }
+
+    vec![format!("{directory}.rs"), join(directory, "mod.rs")]
+}
+
+fn rust_import_candidates(path: &str, contents: &str) -> Vec<Vec<String>> {
+    let comment_re = Regex::new(r"//[^\n]*").expect("Failed to compile regex");
+    let use_re = Regex::new(r"\buse\s+([^;]+);").expect("Failed to compile regex");
+
+    // Modules of a crate live below its `src` directory
+    let segments: Vec<&str> = path.split('/').collect();
+    let Some(src_idx) = segments[..segments.len() - 1]
+        .iter()
+        .rposition(|segment| *segment == "src")
+    else {
+        return Vec::new();
+    };
+    let crate_root = segments[..=src_idx].join("/");
+    let crate_root = crate_root.as_str();
+
+    let contents = comment_re.replace_all(contents, "");
+
+    let mut candidates = Vec::new();
+
+    for captures in use_re.captures_iter(&contents) {
+        let tree: String = captures[1].split_whitespace().collect::<Vec<_>>().join(" ");
+
+        for segments in expand_use_tree(&tree) {
+            let mut segments = segments.iter().map(String::as_str).peekable();
+
+            let mut directory = match segments.next() {
+                Some("crate") => crate_root.to_string(),
+                Some("self") => rust_module_directory(path),
+                Some("super") => parent_directory(&rust_module_directory(path)).to_string(),
+                _ => continue,
+            };
+
+            while segments.next_if_eq(&"super").is_some() {
+                directory = parent_directory(&directory).to_string();
+            }
+
+            let segments: Vec<&str> = segments.collect();
+
+            // Trailing segments might refer to items instead of modules, prefer the longest match
+            let mut path_candidates = Vec::new();
+            for length in (1..=segments.len()).rev() {
+                let module_directory = join(&directory, &segments[..length].join("/"));
+                path_candidates.extend(rust_module_files(&module_directory, crate_root));
+            }
+            path_candidates.extend(rust_module_files(&directory, crate_root));
+
+            candidates.push(path_candidates);
+        }
+    }
+
+    candidates
+}
+
+fn javascript_import_candidates(path: &str, contents: &str) -> Vec<Vec<String>> {
+    let import_re =
+        Regex::new(r#"(?:\bfrom\s*|\bimport\s*\(?\s*|\brequire\s*\(\s*)['"](\.{1,2}/[^'"]*)['"]"#)
+            .expect("Failed to compile regex");
+
+    let directory = parent_directory(path);
+
+    import_re
+        .captures_iter(contents)
+        .filter_map(|captures| normalize(&join(directory, &captures[1])))
+        .map(|base| {
+            let mut path_candidates = vec![base.clone()];
+            for extension in JS_EXTENSIONS {
+                path_candidates.push(format!("{base}.{extension}"));
+            }
+            for extension in JS_EXTENSIONS {
+                path_candidates.push(join(&base, &format!("index.{extension}")));
+            }
+            path_candidates
+        })
+        .collect()
+}
+
+fn python_module_candidates(directory: &str, module: &str) -> Vec<String> {
+    let module_path = join(directory, &module.replace('.', "/"));
+
+    vec![
+        format!("{module_path}.py"),
+        join(&module_path, "__init__.py"),
+    ]
+}
+
+fn python_import_candidates(path: &str, contents: &str) -> Vec<Vec<String>> {
+    let import_re = Regex::new(r"(?m)^\s*import\s+([\w., ]+)$").expect("Failed to compile regex");
+    let from_re = Regex::new(r"(?m)^\s*from\s+(\.*)([\w.]*)\s+import\s+\(?([\w., ]+)")
+        .expect("Failed to compile regex");
+
+    let mut candidates = Vec::new();
+
+    for captures in import_re.captures_iter(contents) {
+        for module in captures[1].split(',') {
+            let module = module.split(" as ").next().unwrap_or(module).trim();
+
+            candidates.push(
+                PYTHON_ROOTS
+                    .iter()
+                    .flat_map(|root| python_module_candidates(root, module))
+                    .collect(),
+            );
+        }
+    }
+
+    for captures in from_re.captures_iter(contents) {
+        let level = captures[1].len();
+        let module = &captures[2];
+
+        let directories: Vec<String> = if level == 0 {
+            PYTHON_ROOTS.iter().map(ToString::to_string).collect()
+        } else {
+            let mut directory = parent_directory(path);
+            for _ in 1..level {
+                directory = parent_directory(directory);
+            }
+            vec![directory.to_string()]
+        };
+
+        for name in captures[3].split(',') {
+            let name = name.split(" as ").next().unwrap_or(name).trim();
+
+            if name.is_empty() {
+                continue;
+            }
+
+            let mut path_candidates = Vec::new();
+
+            // The imported name might be a submodule or an item of the module
+            for directory in &directories {
+                let submodule = [module, name]
+                    .iter()
+                    .filter(|s| !s.is_empty())
+                    .copied()
+                    .collect::<Vec<_>>()
+                    .join(".");
+                path_candidates.extend(python_module_candidates(directory, &submodule));
+            }
+
+            if !module.is_empty() {
+                for directory in &directories {
+                    path_candidates.extend(python_module_candidates(directory, module));
+                }
+            }
+
+            candidates.push(path_candidates);
+        }
+    }
+
+    candidates
+}
+
+/// Returns all distinct pairs of files where the first one imports the second one.
+fn import_edges(files: &HashMap<String, String>) -> HashSet<(&str, &str)> {
+    let mut edges = HashSet::new();
+
+    for (path, contents) in files {
+        let Some(language) = language(Path::new(path)) else {
+            continue;
+        };
+
+        let candidates = match language {
+            Language::Rust => rust_import_candidates(path, contents),
+            Language::JavaScript => javascript_import_candidates(path, contents),
+            Language::Python => python_import_candidates(path, contents),
+        };
+
+        for path_candidates in candidates {
+            let Some((target, _)) = path_candidates
+                .iter()
+                .find_map(|candidate| files.get_key_value(candidate))
+            else {
+                continue;
+            };
+
+            if target != path {
+                edges.insert((path.as_str(), target.as_str()));
+            }
+        }
+    }
+
+    edges
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn average_degree(edges: usize, degrees: &HashMap<&str, usize>) -> f64 {
+    if degrees.is_empty() {
+        return 0.0;
+    }
+
+    edges as f64 / degrees.len() as f64
+}
+
+impl BaseCollector for ImportCoupling {
+    type Error = ImportCouplingError;
+
+    #[tracing::instrument(level = "trace", skip_all)]
+    fn collect(
+        &self,
+        storage: &DashMap<(CollectorConfig, CommitHash), CollectorValue>,
+        repo: &mut WorktreeHandle,
+        graph: &CollectionExecutionGraph,
+        current_node_idx: NodeIndex,
+    ) -> Result<CollectorValue, ImportCouplingError> {
+        let changed_files_in_current_commit_value: ChangedFilesValue = get_dependency_value(
+            storage,
+            graph,
+            current_node_idx,
+            &DependencyKey::ChangedFiles,
+        )?
+        .try_into()?;
+
+        let has_modified_source_file = changed_files_in_current_commit_value
+            .files
+            .iter()
+            .any(|relative_path| language(Path::new(relative_path)).is_some());
+
+        if !has_modified_source_file {
+            let previous_commit_value =
+                get_previous_commit_value_of_collector(storage, graph, current_node_idx);
+
+            if let Some(previous_commit_value) = previous_commit_value {
+                return Ok(previous_commit_value);
+            }
+        }
+
+        let mut files: HashMap<String, String> = HashMap::new();
+
+        for relative_path in repo.walk_tracked_files(None)? {
+            if language(Path::new(&relative_path)).is_none() {
+                continue;
+            }
+
+            let Some(mut reader) = repo.read_file(&relative_path)? else {
+                continue;
+            };
+            let mut contents = Vec::new();
+            reader.read_to_end(&mut contents)?;
+
+            files.insert(
+                relative_path,
+                String::from_utf8_lossy(&contents).to_string(),
+            );
+        }
+
+        let edges = import_edges(&files);
+
+        let mut fan_in: HashMap<&str, usize> = HashMap::new();
+        let mut fan_out: HashMap<&str, usize> = HashMap::new();
+        for (from, to) in &edges {
+            *fan_out.entry(from).or_default() += 1;
+            *fan_in.entry(to).or_default() += 1;
+        }
+
+        let value = ImportCouplingValue {
+            modules: u32::try_from(files.len())?,
+            internal_edges: u32::try_from(edges.len())?,
+            average_fan_in: average_degree(edges.len(), &fan_in),
+            average_fan_out: average_degree(edges.len(), &fan_out),
+            max_fan_in: u32::try_from(fan_in.values().max().copied().unwrap_or(0))?,
+            max_fan_out: u32::try_from(fan_out.values().max().copied().unwrap_or(0))?,
+        };
+
+        Ok(value.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("crate::a::B", vec![vec!["crate", "a", "B"]])]
+    #[case("std::io::Read as _", vec![vec!["std", "io", "Read"]])]
+    #[case(
+        "crate::{a::{self, B}, c::*}",
+        vec![vec!["crate", "a"], vec!["crate", "a", "B"], vec!["crate", "c"]]
+    )]
+    fn test_expand_use_tree(#[case] tree: &str, #[case] expected: Vec<Vec<&str>>) {
+        assert_eq!(expected, expand_use_tree(tree));
+    }
+
+    #[rstest]
+    #[case(
+        vec![
+            ("src/lib.rs", "mod a;\nmod b;\nuse std::fs;\n"),
+            ("src/a.rs", "use crate::b::{Item, c};\n// use crate::lib;\n"),
+            ("src/b/mod.rs", "use super::a::Other;\npub mod c;\n"),
+            ("src/b/c.rs", "use super::Item;\n"),
+        ],
+        vec![("src/a.rs", "src/b/mod.rs"), ("src/a.rs", "src/b/c.rs"), ("src/b/mod.rs", "src/a.rs"), ("src/b/c.rs", "src/b/mod.rs")]
+    )]
+    #[case(
+        vec![
+            ("web/index.ts", "import { a } from './a';\nimport React from 'react';\n"),
+            ("web/a.tsx", "export * from \"../lib\";\nconst b = require('./b/index.js');\n"),
+            ("web/b/index.js", ""),
+            ("lib/index.js", "import('../web/a').then(() => {});\n"),
+        ],
+        vec![("web/index.ts", "web/a.tsx"), ("web/a.tsx", "lib/index.js"), ("web/a.tsx", "web/b/index.js"), ("lib/index.js", "web/a.tsx")]
+    )]
+    #[case(
+        vec![
+            ("pkg/__init__.py", "from .core import run\n"),
+            ("pkg/core.py", "import os, pkg.util as u\nfrom . import helpers\n"),
+            ("pkg/util.py", "from pkg.core import (run,\n"),
+            ("pkg/helpers.py", ""),
+        ],
+        vec![("pkg/__init__.py", "pkg/core.py"), ("pkg/core.py", "pkg/util.py"), ("pkg/core.py", "pkg/helpers.py"), ("pkg/util.py", "pkg/core.py")]
+    )]
+    fn test_import_edges(#[case] files: Vec<(&str, &str)>, #[case] expected: Vec<(&str, &str)>) {
+        let files: HashMap<String, String> = files
+            .into_iter()
+            .map(|(path, contents)| (path.to_string(), contents.to_string()))
+            .collect();
+
+        let expected: HashSet<(&str, &str)> = expected.into_iter().collect();
+
+        assert_eq!(expected, import_edges(&files));
+    }
+}
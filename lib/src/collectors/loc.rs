@@ -7,7 +7,7 @@ use thiserror::Error;
 use tokei::{LanguageType, Languages};
 
 use crate::{
-    config::CollectorConfig,
+    config::{CollectorConfig, FileScope},
     git::{CommitHash, GitError, WorktreeHandle},
     graph::CollectionExecutionGraph,
 };
@@ -15,7 +15,9 @@ use crate::{
 use super::{BaseCollector, CollectorValue};
 
 #[derive(Debug)]
-pub(crate) struct Loc;
+pub(crate) struct Loc {
+    pub scope: Box<FileScope>,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LocValue {
@@ -24,8 +26,11 @@ pub struct LocValue {
 
 #[derive(Error, Debug)]
 pub enum LocError {
-    #[error("{0}")]
+    #[error(transparent)]
     Git(#[from] GitError),
+
+    #[error(transparent)]
+    Glob(#[from] globset::Error),
 }
 
 impl BaseCollector for Loc {
@@ -39,11 +44,31 @@ impl BaseCollector for Loc {
         _graph: &CollectionExecutionGraph,
         _current_node_idx: NodeIndex,
     ) -> Result<CollectorValue, LocError> {
+        let scope = self.scope.matcher()?;
+
         let mut languages = Languages::new();
         languages.get_statistics(&[&repo.path], &[".git"], &tokei::Config::default());
         let value: BTreeMap<LanguageType, usize> = languages
             .iter()
-            .map(|(lang, info)| (*lang, info.code))
+            .map(|(lang, info)| {
+                if self.scope.is_unrestricted() {
+                    return (*lang, info.code);
+                }
+
+                let code = info
+                    .reports
+                    .iter()
+                    .filter(|report| {
+                        report
+                            .name
+                            .strip_prefix(&repo.path)
+                            .is_ok_and(|path| scope.is_match(path))
+                    })
+                    .map(|report| report.stats.code)
+                    .sum();
+
+                (*lang, code)
+            })
             .filter(|(_, value)| *value > 0)
             .collect();
 
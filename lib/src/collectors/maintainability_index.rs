@@ -0,0 +1,123 @@
+use dashmap::DashMap;
+use petgraph::graph::NodeIndex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{config::CollectorConfig, git::CommitHash, graph::CollectionExecutionGraph};
+
+use super::{
+    cognitive_complexity::CognitiveComplexityValue,
+    comment_density::CommentDensityValue,
+    registry::DependencyKey,
+    total_loc::TotalLocValue,
+    utils::{get_dependency_value, LookupError},
+    CollectorValue, CollectorValueCastError, DerivedCollector,
+};
+
+#[derive(Debug)]
+pub(crate) struct MaintainabilityIndex;
+
+/// Maintainability index between 0 and 100 in the variant used by radon and Visual Studio.
+///
+/// The Halstead volume is not collected and left out of the formula, which makes the score
+/// higher than the one of these tools. It is meant to compare commits of the same repository.
+/// The score is `None` if the repository has no analyzed functions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaintainabilityIndexValue {
+    pub score: Option<f64>,
+}
+
+#[derive(Error, Debug)]
+pub enum MaintainabilityIndexError {
+    #[error(transparent)]
+    Lookup(#[from] LookupError),
+
+    #[error(transparent)]
+    Cast(#[from] CollectorValueCastError),
+}
+
+/// Computes the score from the average lines of code and complexity per function and the
+/// percentage of comment lines
+fn maintainability_index(
+    loc_per_function: f64,
+    complexity_per_function: f64,
+    comment_percentage: f64,
+) -> f64 {
+    let score = 171.0 - 0.23 * complexity_per_function - 16.2 * loc_per_function.ln()
+        + 50.0 * (2.46 * comment_percentage.to_radians()).sqrt().sin();
+
+    (score * 100.0 / 171.0).clamp(0.0, 100.0)
+}
+
+impl DerivedCollector for MaintainabilityIndex {
+    type Error = MaintainabilityIndexError;
+
+    #[tracing::instrument(level = "trace", skip_all)]
+    fn collect(
+        &self,
+        storage: &DashMap<(CollectorConfig, CommitHash), CollectorValue>,
+        graph: &CollectionExecutionGraph,
+        current_node_idx: NodeIndex,
+    ) -> Result<CollectorValue, MaintainabilityIndexError> {
+        let total_loc_value: TotalLocValue =
+            get_dependency_value(storage, graph, current_node_idx, &DependencyKey::TotalLoc)?
+                .try_into()?;
+
+        let cognitive_complexity_value: CognitiveComplexityValue = get_dependency_value(
+            storage,
+            graph,
+            current_node_idx,
+            &DependencyKey::CognitiveComplexity,
+        )?
+        .try_into()?;
+
+        let comment_density_value: CommentDensityValue = get_dependency_value(
+            storage,
+            graph,
+            current_node_idx,
+            &DependencyKey::CommentDensity,
+        )?
+        .try_into()?;
+
+        let functions = f64::from(cognitive_complexity_value.functions);
+
+        #[allow(clippy::cast_precision_loss)]
+        let score =
+            (cognitive_complexity_value.functions > 0 && total_loc_value.loc > 0).then(|| {
+                maintainability_index(
+                    f64::from(total_loc_value.loc) / functions,
+                    cognitive_complexity_value.total as f64 / functions,
+                    comment_density_value.comment_percentage.unwrap_or(0.0),
+                )
+            });
+
+        Ok(MaintainabilityIndexValue { score }.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(1.0, 0.0, 0.0, 100.0)]
+    #[case(20.0, 3.0, 0.0, 71.216)]
+    #[case(20.0, 3.0, 25.0, 96.374)]
+    #[case(500.0, 40.0, 0.0, 35.745)]
+    #[case(1_000_000.0, 0.0, 0.0, 0.0)]
+    fn test_maintainability_index(
+        #[case] loc_per_function: f64,
+        #[case] complexity_per_function: f64,
+        #[case] comment_percentage: f64,
+        #[case] expected: f64,
+    ) {
+        let score = maintainability_index(
+            loc_per_function,
+            complexity_per_function,
+            comment_percentage,
+        );
+
+        assert!((score - expected).abs() < 0.001, "{score} != {expected}");
+    }
+}
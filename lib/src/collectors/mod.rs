@@ -1,60 +1,174 @@
+use author_domain::{AuthorDomain, AuthorDomainError};
+use author_domains::{AuthorDomains, AuthorDomainsError};
+use build_warnings::{BuildWarnings, BuildWarningsError};
 use changed_files::{ChangedFiles, ChangedFilesError};
 use changed_files_loc::{ChangedFilesLoc, ChangedFilesLocError};
+use ci_workflows::{CiWorkflows, CiWorkflowsError};
+use cognitive_complexity::{CognitiveComplexity, CognitiveComplexityError};
+use comment_density::{CommentDensity, CommentDensityError};
+use config_files::{ConfigFiles, ConfigFilesError};
+use container_config::{ContainerConfig, ContainerConfigError};
+use coverage_report::{CoverageReport, CoverageReportError};
+use cumulative_diff_stat::{CumulativeDiffStat, CumulativeDiffStatError};
 use dashmap::DashMap;
+use expression::{Expression, ExpressionError};
+use file_length::{FileLength, FileLengthError};
 use file_list::{FileList, FileListError};
+use formatting_changes::{FormattingChanges, FormattingChangesError};
+use high_entropy_strings::{HighEntropyStrings, HighEntropyStringsError};
+use import_coupling::{ImportCoupling, ImportCouplingError};
 use loc::{Loc, LocError};
+use maintainability_index::{MaintainabilityIndex, MaintainabilityIndexError};
 use pattern_occurences::{PatternOccurences, PatternOccurencesError};
 use petgraph::graph::NodeIndex;
+use rolling_diff_stat::{RollingDiffStat, RollingDiffStatError};
+use secrets::{Secrets, SecretsError};
 use serde::{Deserialize, Serialize};
+use shellcheck::{Shellcheck, ShellcheckError};
+use suppressions::SuppressionsError;
 use thiserror::Error;
 use total_cargo_dependencies::{TotalCargoDependencies, TotalCargoDependenciesError};
+use total_cargo_features::{TotalCargoFeatures, TotalCargoFeaturesError};
 use total_diff_stat::{TotalDiffStat, TotalDiffStatError};
 use total_file_count::{TotalFileCount, TotalFileCountError};
 use total_loc::{TotalLoc, TotalLocError};
 use total_pattern_occurences::{TotalPatternOccurences, TotalPatternOccurencesError};
+use total_symlinks_and_submodules::{TotalSymlinksAndSubmodules, TotalSymlinksAndSubmodulesError};
+use total_workspace_members::{TotalWorkspaceMembers, TotalWorkspaceMembersError};
 
 use crate::{
     config::CollectorConfig,
-    git::{CommitHash, WorktreeHandle},
+    git::{is_transient_io_error, CommitHash, WorktreeHandle},
     graph::CollectionExecutionGraph,
 };
 
+mod author_domain;
+mod author_domains;
+mod build_warnings;
 mod changed_files;
 mod changed_files_loc;
+mod ci_workflows;
+mod cognitive_complexity;
+mod comment_density;
+mod config_files;
+mod container_config;
+mod coverage_report;
+mod cumulative_diff_stat;
+pub(crate) mod expression;
+mod file_length;
 mod file_list;
+mod formatting_changes;
+mod high_entropy_strings;
+mod import_coupling;
 mod loc;
+mod maintainability_index;
 mod pattern_occurences;
+pub(crate) mod registry;
+mod rolling_diff_stat;
+mod secrets;
+mod shellcheck;
+mod suppressions;
 mod total_cargo_dependencies;
+mod total_cargo_features;
 mod total_diff_stat;
 mod total_file_count;
 mod total_loc;
 mod total_pattern_occurences;
+mod total_symlinks_and_submodules;
+mod total_workspace_members;
 mod utils;
 
+#[cfg(feature = "fuzzing")]
+pub(crate) use pattern_occurences::get_matches_from_grep_output;
+
+pub use author_domain::AuthorDomainValue;
+pub use author_domains::AuthorDomainsValue;
+pub use build_warnings::BuildWarningsValue;
 pub use changed_files::ChangedFilesValue;
 pub use changed_files_loc::ChangedFilesLocValue;
+pub use ci_workflows::CiWorkflowsValue;
+pub use cognitive_complexity::CognitiveComplexityValue;
+pub use comment_density::CommentDensityValue;
+pub use config_files::ConfigFilesValue;
+pub use container_config::ContainerConfigValue;
+pub use coverage_report::CoverageReportValue;
+pub use cumulative_diff_stat::CumulativeDiffStatValue;
+pub use expression::{ExpressionSyntaxError, ExpressionValue};
+pub use file_length::FileLengthValue;
 pub use file_list::FileListValue;
+pub use formatting_changes::FormattingChangesValue;
+pub use high_entropy_strings::HighEntropyStringsValue;
+pub use import_coupling::ImportCouplingValue;
 pub use loc::LocValue;
-pub use pattern_occurences::PatternOccurencesValue;
+pub use maintainability_index::MaintainabilityIndexValue;
+pub use pattern_occurences::{
+    PartialGrepText, PartialMatchData, PartialMatchDataSubmatch, PatternOccurencesValue,
+};
+pub use rolling_diff_stat::RollingDiffStatValue;
+pub use secrets::{SecretFinding, SecretsValue};
+pub use shellcheck::ShellcheckValue;
 pub use total_cargo_dependencies::TotalCargoDependenciesValue;
+pub use total_cargo_features::TotalCargoFeaturesValue;
 pub use total_diff_stat::TotalDiffStatValue;
 pub use total_file_count::TotalFileCountValue;
 pub use total_loc::TotalLocValue;
 pub use total_pattern_occurences::TotalPatternOccurencesValue;
+pub use total_symlinks_and_submodules::TotalSymlinksAndSubmodulesValue;
+pub use total_workspace_members::TotalWorkspaceMembersValue;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(tag = "collector")]
+#[non_exhaustive]
 pub enum CollectorValue {
     ChangedFiles(changed_files::ChangedFilesValue),
     Loc(loc::LocValue),
     PatternOccurences(pattern_occurences::PatternOccurencesValue),
     TotalCargoDependencies(total_cargo_dependencies::TotalCargoDependenciesValue),
+    TotalCargoFeatures(total_cargo_features::TotalCargoFeaturesValue),
     TotalDiffStat(total_diff_stat::TotalDiffStatValue),
     TotalLoc(total_loc::TotalLocValue),
     TotalPatternOccurences(total_pattern_occurences::TotalPatternOccurencesValue),
     FileList(file_list::FileListValue),
     TotalFileCount(total_file_count::TotalFileCountValue),
     ChangedFilesLoc(changed_files_loc::ChangedFilesLocValue),
+    TotalWorkspaceMembers(total_workspace_members::TotalWorkspaceMembersValue),
+    CiWorkflows(ci_workflows::CiWorkflowsValue),
+    ContainerConfig(container_config::ContainerConfigValue),
+    TotalSymlinksAndSubmodules(total_symlinks_and_submodules::TotalSymlinksAndSubmodulesValue),
+    CoverageReport(coverage_report::CoverageReportValue),
+    FileLength(file_length::FileLengthValue),
+    CumulativeDiffStat(cumulative_diff_stat::CumulativeDiffStatValue),
+    RollingDiffStat(rolling_diff_stat::RollingDiffStatValue),
+    BuildWarnings(build_warnings::BuildWarningsValue),
+    FormattingChanges(formatting_changes::FormattingChangesValue),
+    ImportCoupling(import_coupling::ImportCouplingValue),
+    ConfigFiles(config_files::ConfigFilesValue),
+    Shellcheck(shellcheck::ShellcheckValue),
+    CognitiveComplexity(cognitive_complexity::CognitiveComplexityValue),
+    CommentDensity(comment_density::CommentDensityValue),
+    MaintainabilityIndex(maintainability_index::MaintainabilityIndexValue),
+    Expression(expression::ExpressionValue),
+    AuthorDomain(author_domain::AuthorDomainValue),
+    AuthorDomains(author_domains::AuthorDomainsValue),
+    Secrets(secrets::SecretsValue),
+    HighEntropyStrings(high_entropy_strings::HighEntropyStringsValue),
+    Skipped(SkippedValue),
+}
+
+/// Stands in for the value of a collector that was not run for a commit
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SkippedValue {
+    pub reason: SkipReason,
+    pub file_count: usize,
+    pub total_bytes: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum SkipReason {
+    /// The tree of the commit exceeds the configured [`crate::TreeLimits`]
+    TooLarge,
 }
 
 macro_rules! impl_from {
@@ -77,6 +191,10 @@ impl_from!(
     total_cargo_dependencies::TotalCargoDependenciesValue,
     TotalCargoDependencies
 );
+impl_from!(
+    total_cargo_features::TotalCargoFeaturesValue,
+    TotalCargoFeatures
+);
 impl_from!(total_diff_stat::TotalDiffStatValue, TotalDiffStat);
 impl_from!(total_loc::TotalLocValue, TotalLoc);
 impl_from!(
@@ -86,6 +204,49 @@ impl_from!(
 impl_from!(file_list::FileListValue, FileList);
 impl_from!(total_file_count::TotalFileCountValue, TotalFileCount);
 impl_from!(changed_files_loc::ChangedFilesLocValue, ChangedFilesLoc);
+impl_from!(
+    total_workspace_members::TotalWorkspaceMembersValue,
+    TotalWorkspaceMembers
+);
+impl_from!(ci_workflows::CiWorkflowsValue, CiWorkflows);
+impl_from!(container_config::ContainerConfigValue, ContainerConfig);
+impl_from!(
+    total_symlinks_and_submodules::TotalSymlinksAndSubmodulesValue,
+    TotalSymlinksAndSubmodules
+);
+impl_from!(coverage_report::CoverageReportValue, CoverageReport);
+impl_from!(file_length::FileLengthValue, FileLength);
+impl_from!(
+    cumulative_diff_stat::CumulativeDiffStatValue,
+    CumulativeDiffStat
+);
+impl_from!(rolling_diff_stat::RollingDiffStatValue, RollingDiffStat);
+impl_from!(build_warnings::BuildWarningsValue, BuildWarnings);
+impl_from!(
+    formatting_changes::FormattingChangesValue,
+    FormattingChanges
+);
+impl_from!(import_coupling::ImportCouplingValue, ImportCoupling);
+impl_from!(config_files::ConfigFilesValue, ConfigFiles);
+impl_from!(shellcheck::ShellcheckValue, Shellcheck);
+impl_from!(
+    cognitive_complexity::CognitiveComplexityValue,
+    CognitiveComplexity
+);
+impl_from!(comment_density::CommentDensityValue, CommentDensity);
+impl_from!(
+    maintainability_index::MaintainabilityIndexValue,
+    MaintainabilityIndex
+);
+impl_from!(expression::ExpressionValue, Expression);
+impl_from!(author_domain::AuthorDomainValue, AuthorDomain);
+impl_from!(author_domains::AuthorDomainsValue, AuthorDomains);
+impl_from!(secrets::SecretsValue, Secrets);
+impl_from!(
+    high_entropy_strings::HighEntropyStringsValue,
+    HighEntropyStrings
+);
+impl_from!(SkippedValue, Skipped);
 
 #[derive(Error, Debug)]
 #[error("Could not unpack {to} from {from:?}")]
@@ -122,6 +283,10 @@ impl_try_into!(
     total_cargo_dependencies::TotalCargoDependenciesValue,
     TotalCargoDependencies
 );
+impl_try_into!(
+    total_cargo_features::TotalCargoFeaturesValue,
+    TotalCargoFeatures
+);
 impl_try_into!(total_diff_stat::TotalDiffStatValue, TotalDiffStat);
 impl_try_into!(total_loc::TotalLocValue, TotalLoc);
 impl_try_into!(
@@ -131,29 +296,183 @@ impl_try_into!(
 impl_try_into!(file_list::FileListValue, FileList);
 impl_try_into!(total_file_count::TotalFileCountValue, TotalFileCount);
 impl_try_into!(changed_files_loc::ChangedFilesLocValue, ChangedFilesLoc);
+impl_try_into!(
+    total_workspace_members::TotalWorkspaceMembersValue,
+    TotalWorkspaceMembers
+);
+impl_try_into!(ci_workflows::CiWorkflowsValue, CiWorkflows);
+impl_try_into!(container_config::ContainerConfigValue, ContainerConfig);
+impl_try_into!(
+    total_symlinks_and_submodules::TotalSymlinksAndSubmodulesValue,
+    TotalSymlinksAndSubmodules
+);
+impl_try_into!(coverage_report::CoverageReportValue, CoverageReport);
+impl_try_into!(file_length::FileLengthValue, FileLength);
+impl_try_into!(
+    cumulative_diff_stat::CumulativeDiffStatValue,
+    CumulativeDiffStat
+);
+impl_try_into!(rolling_diff_stat::RollingDiffStatValue, RollingDiffStat);
+impl_try_into!(build_warnings::BuildWarningsValue, BuildWarnings);
+impl_try_into!(
+    formatting_changes::FormattingChangesValue,
+    FormattingChanges
+);
+impl_try_into!(import_coupling::ImportCouplingValue, ImportCoupling);
+impl_try_into!(config_files::ConfigFilesValue, ConfigFiles);
+impl_try_into!(shellcheck::ShellcheckValue, Shellcheck);
+impl_try_into!(
+    cognitive_complexity::CognitiveComplexityValue,
+    CognitiveComplexity
+);
+impl_try_into!(comment_density::CommentDensityValue, CommentDensity);
+impl_try_into!(
+    maintainability_index::MaintainabilityIndexValue,
+    MaintainabilityIndex
+);
+impl_try_into!(expression::ExpressionValue, Expression);
+impl_try_into!(author_domain::AuthorDomainValue, AuthorDomain);
+impl_try_into!(author_domains::AuthorDomainsValue, AuthorDomains);
+impl_try_into!(secrets::SecretsValue, Secrets);
+impl_try_into!(
+    high_entropy_strings::HighEntropyStringsValue,
+    HighEntropyStrings
+);
+impl_try_into!(SkippedValue, Skipped);
 
 #[derive(Error, Debug)]
 pub enum BaseCollectorError {
-    #[error("{0}")]
+    #[error(transparent)]
     ChangedFilesLoc(changed_files_loc::ChangedFilesLocError),
 
-    #[error("{0}")]
+    #[error(transparent)]
     ChangedFiles(changed_files::ChangedFilesError),
 
-    #[error("{0}")]
+    #[error(transparent)]
     FileList(file_list::FileListError),
 
-    #[error("{0}")]
+    #[error(transparent)]
     Loc(LocError),
 
-    #[error("{0}")]
+    #[error(transparent)]
     PatternOccurences(pattern_occurences::PatternOccurencesError),
 
-    #[error("{0}")]
+    #[error(transparent)]
     TotalCargoDependencies(total_cargo_dependencies::TotalCargoDependenciesError),
 
-    #[error("{0}")]
+    #[error(transparent)]
+    TotalCargoFeatures(total_cargo_features::TotalCargoFeaturesError),
+
+    #[error(transparent)]
     TotalDiffStat(total_diff_stat::TotalDiffStatError),
+
+    #[error(transparent)]
+    TotalWorkspaceMembers(total_workspace_members::TotalWorkspaceMembersError),
+
+    #[error(transparent)]
+    CiWorkflows(ci_workflows::CiWorkflowsError),
+
+    #[error(transparent)]
+    ContainerConfig(container_config::ContainerConfigError),
+
+    #[error(transparent)]
+    TotalSymlinksAndSubmodules(total_symlinks_and_submodules::TotalSymlinksAndSubmodulesError),
+
+    #[error(transparent)]
+    CoverageReport(coverage_report::CoverageReportError),
+
+    #[error(transparent)]
+    FileLength(file_length::FileLengthError),
+
+    #[error(transparent)]
+    BuildWarnings(build_warnings::BuildWarningsError),
+
+    #[error(transparent)]
+    FormattingChanges(formatting_changes::FormattingChangesError),
+
+    #[error(transparent)]
+    ImportCoupling(import_coupling::ImportCouplingError),
+
+    #[error(transparent)]
+    ConfigFiles(config_files::ConfigFilesError),
+
+    #[error(transparent)]
+    Shellcheck(shellcheck::ShellcheckError),
+
+    #[error(transparent)]
+    CognitiveComplexity(cognitive_complexity::CognitiveComplexityError),
+
+    #[error(transparent)]
+    CommentDensity(comment_density::CommentDensityError),
+
+    #[error(transparent)]
+    AuthorDomain(author_domain::AuthorDomainError),
+
+    #[error(transparent)]
+    AuthorDomains(author_domains::AuthorDomainsError),
+
+    #[error(transparent)]
+    Secrets(SecretsError),
+
+    #[error(transparent)]
+    HighEntropyStrings(HighEntropyStringsError),
+}
+
+impl BaseCollectorError {
+    /// Whether the failure is likely caused by the environment rather than the repository, e.g. an
+    /// interrupted read or a crashed tool, so collecting again may succeed
+    pub(crate) fn is_transient(&self) -> bool {
+        match self {
+            BaseCollectorError::BuildWarnings(BuildWarningsError::IO(e))
+            | BaseCollectorError::CiWorkflows(CiWorkflowsError::IO(e))
+            | BaseCollectorError::ContainerConfig(ContainerConfigError::IO(e))
+            | BaseCollectorError::CoverageReport(CoverageReportError::IO(e))
+            | BaseCollectorError::ImportCoupling(ImportCouplingError::IO(e))
+            | BaseCollectorError::PatternOccurences(PatternOccurencesError::IO(e))
+            | BaseCollectorError::Shellcheck(ShellcheckError::IO(e))
+            | BaseCollectorError::TotalWorkspaceMembers(TotalWorkspaceMembersError::IO(e)) => {
+                is_transient_io_error(e)
+            }
+            BaseCollectorError::AuthorDomain(AuthorDomainError::Git(e))
+            | BaseCollectorError::AuthorDomains(AuthorDomainsError::Git(e))
+            | BaseCollectorError::ChangedFiles(ChangedFilesError::Git(e))
+            | BaseCollectorError::ChangedFilesLoc(ChangedFilesLocError::Git(e))
+            | BaseCollectorError::CiWorkflows(CiWorkflowsError::Git(e))
+            | BaseCollectorError::CognitiveComplexity(CognitiveComplexityError::Git(e))
+            | BaseCollectorError::ConfigFiles(ConfigFilesError::Git(e))
+            | BaseCollectorError::ContainerConfig(ContainerConfigError::Git(e))
+            | BaseCollectorError::CoverageReport(CoverageReportError::Git(e))
+            | BaseCollectorError::FileList(FileListError::Git(e))
+            | BaseCollectorError::FormattingChanges(FormattingChangesError::Git(e))
+            | BaseCollectorError::HighEntropyStrings(HighEntropyStringsError::Git(e))
+            | BaseCollectorError::ImportCoupling(ImportCouplingError::Git(e))
+            | BaseCollectorError::Loc(LocError::Git(e))
+            | BaseCollectorError::PatternOccurences(PatternOccurencesError::Git(e))
+            | BaseCollectorError::Secrets(SecretsError::Git(e))
+            | BaseCollectorError::Shellcheck(ShellcheckError::Git(e))
+            | BaseCollectorError::HighEntropyStrings(HighEntropyStringsError::Suppressions(
+                SuppressionsError::Git(e),
+            ))
+            | BaseCollectorError::PatternOccurences(PatternOccurencesError::Suppressions(
+                SuppressionsError::Git(e),
+            ))
+            | BaseCollectorError::Secrets(SecretsError::Suppressions(SuppressionsError::Git(e)))
+            | BaseCollectorError::TotalCargoDependencies(TotalCargoDependenciesError::Git(e))
+            | BaseCollectorError::TotalCargoFeatures(TotalCargoFeaturesError::Git(e))
+            | BaseCollectorError::TotalDiffStat(TotalDiffStatError::Git(e))
+            | BaseCollectorError::TotalSymlinksAndSubmodules(
+                TotalSymlinksAndSubmodulesError::Git(e),
+            )
+            | BaseCollectorError::TotalWorkspaceMembers(TotalWorkspaceMembersError::Git(e)) => {
+                e.is_transient()
+            }
+            // Terminated by a signal instead of exiting
+            BaseCollectorError::Shellcheck(ShellcheckError::Failed(status)) => {
+                status.code().is_none()
+            }
+            _ => false,
+        }
+    }
 }
 
 impl From<ChangedFilesLocError> for BaseCollectorError {
@@ -192,23 +511,143 @@ impl From<TotalCargoDependenciesError> for BaseCollectorError {
     }
 }
 
+impl From<TotalCargoFeaturesError> for BaseCollectorError {
+    fn from(value: TotalCargoFeaturesError) -> Self {
+        BaseCollectorError::TotalCargoFeatures(value)
+    }
+}
+
 impl From<TotalDiffStatError> for BaseCollectorError {
     fn from(value: TotalDiffStatError) -> Self {
         BaseCollectorError::TotalDiffStat(value)
     }
 }
 
+impl From<TotalWorkspaceMembersError> for BaseCollectorError {
+    fn from(value: TotalWorkspaceMembersError) -> Self {
+        BaseCollectorError::TotalWorkspaceMembers(value)
+    }
+}
+
+impl From<CiWorkflowsError> for BaseCollectorError {
+    fn from(value: CiWorkflowsError) -> Self {
+        BaseCollectorError::CiWorkflows(value)
+    }
+}
+
+impl From<ContainerConfigError> for BaseCollectorError {
+    fn from(value: ContainerConfigError) -> Self {
+        BaseCollectorError::ContainerConfig(value)
+    }
+}
+
+impl From<TotalSymlinksAndSubmodulesError> for BaseCollectorError {
+    fn from(value: TotalSymlinksAndSubmodulesError) -> Self {
+        BaseCollectorError::TotalSymlinksAndSubmodules(value)
+    }
+}
+
+impl From<CoverageReportError> for BaseCollectorError {
+    fn from(value: CoverageReportError) -> Self {
+        BaseCollectorError::CoverageReport(value)
+    }
+}
+
+impl From<FileLengthError> for BaseCollectorError {
+    fn from(value: FileLengthError) -> Self {
+        BaseCollectorError::FileLength(value)
+    }
+}
+
+impl From<BuildWarningsError> for BaseCollectorError {
+    fn from(value: BuildWarningsError) -> Self {
+        BaseCollectorError::BuildWarnings(value)
+    }
+}
+
+impl From<FormattingChangesError> for BaseCollectorError {
+    fn from(value: FormattingChangesError) -> Self {
+        BaseCollectorError::FormattingChanges(value)
+    }
+}
+
+impl From<ImportCouplingError> for BaseCollectorError {
+    fn from(value: ImportCouplingError) -> Self {
+        BaseCollectorError::ImportCoupling(value)
+    }
+}
+
+impl From<ConfigFilesError> for BaseCollectorError {
+    fn from(value: ConfigFilesError) -> Self {
+        BaseCollectorError::ConfigFiles(value)
+    }
+}
+
+impl From<ShellcheckError> for BaseCollectorError {
+    fn from(value: ShellcheckError) -> Self {
+        BaseCollectorError::Shellcheck(value)
+    }
+}
+
+impl From<SecretsError> for BaseCollectorError {
+    fn from(value: SecretsError) -> Self {
+        BaseCollectorError::Secrets(value)
+    }
+}
+
+impl From<HighEntropyStringsError> for BaseCollectorError {
+    fn from(value: HighEntropyStringsError) -> Self {
+        BaseCollectorError::HighEntropyStrings(value)
+    }
+}
+
+impl From<AuthorDomainError> for BaseCollectorError {
+    fn from(value: AuthorDomainError) -> Self {
+        BaseCollectorError::AuthorDomain(value)
+    }
+}
+
+impl From<AuthorDomainsError> for BaseCollectorError {
+    fn from(value: AuthorDomainsError) -> Self {
+        BaseCollectorError::AuthorDomains(value)
+    }
+}
+
+impl From<CognitiveComplexityError> for BaseCollectorError {
+    fn from(value: CognitiveComplexityError) -> Self {
+        BaseCollectorError::CognitiveComplexity(value)
+    }
+}
+
+impl From<CommentDensityError> for BaseCollectorError {
+    fn from(value: CommentDensityError) -> Self {
+        BaseCollectorError::CommentDensity(value)
+    }
+}
+
 #[allow(clippy::enum_variant_names)]
 #[derive(Error, Debug)]
 pub enum DerivedCollectorError {
-    #[error("{0}")]
+    #[error(transparent)]
     TotalFileCount(total_file_count::TotalFileCountError),
 
-    #[error("{0}")]
+    #[error(transparent)]
     TotalLoc(total_loc::TotalLocError),
 
-    #[error("{0}")]
+    #[error(transparent)]
     TotalPatternOccurences(total_pattern_occurences::TotalPatternOccurencesError),
+
+    #[error(transparent)]
+    CumulativeDiffStat(cumulative_diff_stat::CumulativeDiffStatError),
+
+    #[error(transparent)]
+    RollingDiffStat(rolling_diff_stat::RollingDiffStatError),
+
+    #[error(transparent)]
+    MaintainabilityIndex(maintainability_index::MaintainabilityIndexError),
+
+    #[error(transparent)]
+    Expression(ExpressionError),
 }
 
 impl From<TotalFileCountError> for DerivedCollectorError {
@@ -229,6 +668,30 @@ impl From<TotalPatternOccurencesError> for DerivedCollectorError {
     }
 }
 
+impl From<CumulativeDiffStatError> for DerivedCollectorError {
+    fn from(value: CumulativeDiffStatError) -> Self {
+        DerivedCollectorError::CumulativeDiffStat(value)
+    }
+}
+
+impl From<RollingDiffStatError> for DerivedCollectorError {
+    fn from(value: RollingDiffStatError) -> Self {
+        DerivedCollectorError::RollingDiffStat(value)
+    }
+}
+
+impl From<MaintainabilityIndexError> for DerivedCollectorError {
+    fn from(value: MaintainabilityIndexError) -> Self {
+        DerivedCollectorError::MaintainabilityIndex(value)
+    }
+}
+
+impl From<ExpressionError> for DerivedCollectorError {
+    fn from(value: ExpressionError) -> Self {
+        DerivedCollectorError::Expression(value)
+    }
+}
+
 pub(crate) enum BaseCollectorObj {
     ChangedFilesLoc(ChangedFilesLoc),
     ChangedFiles(ChangedFiles),
@@ -236,7 +699,25 @@ pub(crate) enum BaseCollectorObj {
     Loc(Loc),
     PatternOccurences(PatternOccurences),
     TotalCargoDependencies(TotalCargoDependencies),
+    TotalCargoFeatures(TotalCargoFeatures),
     TotalDiffStat(TotalDiffStat),
+    TotalWorkspaceMembers(TotalWorkspaceMembers),
+    CiWorkflows(CiWorkflows),
+    ContainerConfig(ContainerConfig),
+    TotalSymlinksAndSubmodules(TotalSymlinksAndSubmodules),
+    CoverageReport(CoverageReport),
+    FileLength(FileLength),
+    BuildWarnings(BuildWarnings),
+    FormattingChanges(FormattingChanges),
+    ImportCoupling(ImportCoupling),
+    ConfigFiles(ConfigFiles),
+    Shellcheck(Shellcheck),
+    CognitiveComplexity(CognitiveComplexity),
+    CommentDensity(CommentDensity),
+    AuthorDomain(AuthorDomain),
+    AuthorDomains(AuthorDomains),
+    Secrets(Secrets),
+    HighEntropyStrings(HighEntropyStrings),
 }
 
 impl BaseCollector for BaseCollectorObj {
@@ -268,9 +749,99 @@ impl BaseCollector for BaseCollectorObj {
             BaseCollectorObj::TotalCargoDependencies(collector) => collector
                 .collect(storage, repo, graph, current_node_idx)
                 .map_err(|err| err.into()),
+            BaseCollectorObj::TotalCargoFeatures(collector) => collector
+                .collect(storage, repo, graph, current_node_idx)
+                .map_err(|err| err.into()),
             BaseCollectorObj::TotalDiffStat(collector) => collector
                 .collect(storage, repo, graph, current_node_idx)
                 .map_err(|err| err.into()),
+            BaseCollectorObj::TotalWorkspaceMembers(collector) => collector
+                .collect(storage, repo, graph, current_node_idx)
+                .map_err(|err| err.into()),
+            BaseCollectorObj::CiWorkflows(collector) => collector
+                .collect(storage, repo, graph, current_node_idx)
+                .map_err(|err| err.into()),
+            BaseCollectorObj::ContainerConfig(collector) => collector
+                .collect(storage, repo, graph, current_node_idx)
+                .map_err(|err| err.into()),
+            BaseCollectorObj::TotalSymlinksAndSubmodules(collector) => collector
+                .collect(storage, repo, graph, current_node_idx)
+                .map_err(|err| err.into()),
+            BaseCollectorObj::CoverageReport(collector) => collector
+                .collect(storage, repo, graph, current_node_idx)
+                .map_err(|err| err.into()),
+            BaseCollectorObj::FileLength(collector) => collector
+                .collect(storage, repo, graph, current_node_idx)
+                .map_err(|err| err.into()),
+            BaseCollectorObj::BuildWarnings(collector) => collector
+                .collect(storage, repo, graph, current_node_idx)
+                .map_err(|err| err.into()),
+            BaseCollectorObj::FormattingChanges(collector) => collector
+                .collect(storage, repo, graph, current_node_idx)
+                .map_err(|err| err.into()),
+            BaseCollectorObj::ImportCoupling(collector) => collector
+                .collect(storage, repo, graph, current_node_idx)
+                .map_err(|err| err.into()),
+            BaseCollectorObj::ConfigFiles(collector) => collector
+                .collect(storage, repo, graph, current_node_idx)
+                .map_err(|err| err.into()),
+            BaseCollectorObj::Shellcheck(collector) => collector
+                .collect(storage, repo, graph, current_node_idx)
+                .map_err(|err| err.into()),
+            BaseCollectorObj::CognitiveComplexity(collector) => collector
+                .collect(storage, repo, graph, current_node_idx)
+                .map_err(|err| err.into()),
+            BaseCollectorObj::CommentDensity(collector) => collector
+                .collect(storage, repo, graph, current_node_idx)
+                .map_err(|err| err.into()),
+            BaseCollectorObj::AuthorDomain(collector) => collector
+                .collect(storage, repo, graph, current_node_idx)
+                .map_err(|err| err.into()),
+            BaseCollectorObj::AuthorDomains(collector) => collector
+                .collect(storage, repo, graph, current_node_idx)
+                .map_err(|err| err.into()),
+            BaseCollectorObj::Secrets(collector) => collector
+                .collect(storage, repo, graph, current_node_idx)
+                .map_err(|err| err.into()),
+            BaseCollectorObj::HighEntropyStrings(collector) => collector
+                .collect(storage, repo, graph, current_node_idx)
+                .map_err(|err| err.into()),
+        }
+    }
+}
+
+impl BaseCollectorObj {
+    /// Whether the collector reads the files of the commit through a tree reader, see
+    /// [`BaseCollector::READS_TREE`]
+    pub(crate) fn reads_tree(&self) -> bool {
+        match self {
+            BaseCollectorObj::ChangedFilesLoc(_) => ChangedFilesLoc::READS_TREE,
+            BaseCollectorObj::ChangedFiles(_) => ChangedFiles::READS_TREE,
+            BaseCollectorObj::FileList(_) => FileList::READS_TREE,
+            BaseCollectorObj::Loc(_) => Loc::READS_TREE,
+            BaseCollectorObj::PatternOccurences(_) => PatternOccurences::READS_TREE,
+            BaseCollectorObj::TotalCargoDependencies(_) => TotalCargoDependencies::READS_TREE,
+            BaseCollectorObj::TotalCargoFeatures(_) => TotalCargoFeatures::READS_TREE,
+            BaseCollectorObj::TotalDiffStat(_) => TotalDiffStat::READS_TREE,
+            BaseCollectorObj::TotalWorkspaceMembers(_) => TotalWorkspaceMembers::READS_TREE,
+            BaseCollectorObj::CiWorkflows(_) => CiWorkflows::READS_TREE,
+            BaseCollectorObj::ContainerConfig(_) => ContainerConfig::READS_TREE,
+            BaseCollectorObj::TotalSymlinksAndSubmodules(_) => {
+                TotalSymlinksAndSubmodules::READS_TREE
+            }
+            BaseCollectorObj::CoverageReport(_) => CoverageReport::READS_TREE,
+            BaseCollectorObj::FileLength(_) => FileLength::READS_TREE,
+            BaseCollectorObj::BuildWarnings(_) => BuildWarnings::READS_TREE,
+            BaseCollectorObj::FormattingChanges(_) => FormattingChanges::READS_TREE,
+            BaseCollectorObj::ImportCoupling(_) => ImportCoupling::READS_TREE,
+            BaseCollectorObj::ConfigFiles(_) => ConfigFiles::READS_TREE,
+            BaseCollectorObj::Shellcheck(_) => Shellcheck::READS_TREE,
+            BaseCollectorObj::CognitiveComplexity(_) => CognitiveComplexity::READS_TREE,
+            BaseCollectorObj::CommentDensity(_) => CommentDensity::READS_TREE,
+            BaseCollectorObj::AuthorDomain(_) => AuthorDomain::READS_TREE,
+            BaseCollectorObj::AuthorDomains(_) => AuthorDomains::READS_TREE,
+            BaseCollectorObj::Secrets(_) => Secrets::READS_TREE,
+            BaseCollectorObj::HighEntropyStrings(_) => HighEntropyStrings::READS_TREE,
         }
     }
 }
@@ -280,6 +851,10 @@ pub(crate) enum DerivedCollectorObj {
     TotalFileCount(TotalFileCount),
     TotalLoc(TotalLoc),
     TotalPatternOccurences(TotalPatternOccurences),
+    CumulativeDiffStat(CumulativeDiffStat),
+    RollingDiffStat(RollingDiffStat),
+    MaintainabilityIndex(MaintainabilityIndex),
+    Expression(Expression),
 }
 
 impl DerivedCollector for DerivedCollectorObj {
@@ -301,6 +876,18 @@ impl DerivedCollector for DerivedCollectorObj {
             DerivedCollectorObj::TotalPatternOccurences(collector) => collector
                 .collect(storage, graph, current_node_idx)
                 .map_err(|err| err.into()),
+            DerivedCollectorObj::CumulativeDiffStat(collector) => collector
+                .collect(storage, graph, current_node_idx)
+                .map_err(|err| err.into()),
+            DerivedCollectorObj::RollingDiffStat(collector) => collector
+                .collect(storage, graph, current_node_idx)
+                .map_err(|err| err.into()),
+            DerivedCollectorObj::MaintainabilityIndex(collector) => collector
+                .collect(storage, graph, current_node_idx)
+                .map_err(|err| err.into()),
+            DerivedCollectorObj::Expression(collector) => collector
+                .collect(storage, graph, current_node_idx)
+                .map_err(|err| err.into()),
         }
     }
 }
@@ -313,6 +900,10 @@ pub enum Collector {
 pub trait BaseCollector {
     type Error;
 
+    /// Whether the collector reads the files of the commit through a
+    /// [`TreeReader`](crate::git::TreeReader) instead of a checkout, so its tasks skip the checkout
+    const READS_TREE: bool = false;
+
     /// Collects the value for the given collector.
     fn collect(
         &self,
@@ -335,46 +926,141 @@ pub trait DerivedCollector {
 }
 
 impl From<&CollectorConfig> for Collector {
+    #[allow(clippy::too_many_lines)]
     fn from(value: &CollectorConfig) -> Self {
         match value {
-            CollectorConfig::Loc => Collector::Base(BaseCollectorObj::Loc(loc::Loc {})),
+            CollectorConfig::Loc { scope } => Collector::Base(BaseCollectorObj::Loc(loc::Loc {
+                scope: scope.clone(),
+            })),
             CollectorConfig::ChangedFiles => Collector::Base(BaseCollectorObj::ChangedFiles(
                 changed_files::ChangedFiles {},
             )),
-            CollectorConfig::TotalLoc => {
-                Collector::Derived(DerivedCollectorObj::TotalLoc(total_loc::TotalLoc {}))
+            CollectorConfig::TotalLoc { .. } => {
+                Collector::Derived(DerivedCollectorObj::TotalLoc(total_loc::TotalLoc))
             }
-            CollectorConfig::TotalDiffStat => Collector::Base(BaseCollectorObj::TotalDiffStat(
-                total_diff_stat::TotalDiffStat {},
-            )),
+            CollectorConfig::TotalDiffStat { scope } => Collector::Base(
+                BaseCollectorObj::TotalDiffStat(total_diff_stat::TotalDiffStat {
+                    scope: scope.clone(),
+                }),
+            ),
             CollectorConfig::TotalCargoDeps => {
                 Collector::Base(BaseCollectorObj::TotalCargoDependencies(
                     total_cargo_dependencies::TotalCargoDependencies {},
                 ))
             }
-            CollectorConfig::PatternOccurences { pattern, files } => Collector::Base(
-                BaseCollectorObj::PatternOccurences(pattern_occurences::PatternOccurences {
+            CollectorConfig::TotalCargoFeatures => Collector::Base(
+                BaseCollectorObj::TotalCargoFeatures(total_cargo_features::TotalCargoFeatures {}),
+            ),
+            CollectorConfig::PatternOccurences {
+                pattern,
+                files,
+                redact,
+                scope,
+            } => Collector::Base(BaseCollectorObj::PatternOccurences(
+                pattern_occurences::PatternOccurences {
                     pattern: pattern.clone(),
                     files: files.clone(),
-                }),
-            ),
-            CollectorConfig::TotalPatternOccurences { pattern, files } => {
+                    redact: *redact,
+                    scope: scope.clone(),
+                },
+            )),
+            CollectorConfig::TotalPatternOccurences { .. } => {
                 Collector::Derived(DerivedCollectorObj::TotalPatternOccurences(
-                    total_pattern_occurences::TotalPatternOccurences {
-                        pattern: pattern.clone(),
-                        files: files.clone(),
-                    },
+                    total_pattern_occurences::TotalPatternOccurences,
                 ))
             }
-            CollectorConfig::FileList => {
-                Collector::Base(BaseCollectorObj::FileList(file_list::FileList {}))
+            CollectorConfig::FileList { scope } => {
+                Collector::Base(BaseCollectorObj::FileList(file_list::FileList {
+                    scope: scope.clone(),
+                }))
             }
-            CollectorConfig::TotalFileCount => Collector::Derived(
-                DerivedCollectorObj::TotalFileCount(total_file_count::TotalFileCount {}),
+            CollectorConfig::TotalFileCount { .. } => Collector::Derived(
+                DerivedCollectorObj::TotalFileCount(total_file_count::TotalFileCount),
             ),
             CollectorConfig::ChangedFilesLoc => Collector::Base(BaseCollectorObj::ChangedFilesLoc(
                 changed_files_loc::ChangedFilesLoc {},
             )),
+            CollectorConfig::TotalWorkspaceMembers => {
+                Collector::Base(BaseCollectorObj::TotalWorkspaceMembers(
+                    total_workspace_members::TotalWorkspaceMembers {},
+                ))
+            }
+            CollectorConfig::CiWorkflows => {
+                Collector::Base(BaseCollectorObj::CiWorkflows(ci_workflows::CiWorkflows {}))
+            }
+            CollectorConfig::ContainerConfig => {
+                Collector::Base(BaseCollectorObj::ContainerConfig(ContainerConfig))
+            }
+            CollectorConfig::TotalSymlinksAndSubmodules => Collector::Base(
+                BaseCollectorObj::TotalSymlinksAndSubmodules(TotalSymlinksAndSubmodules),
+            ),
+            CollectorConfig::CoverageReport => {
+                Collector::Base(BaseCollectorObj::CoverageReport(CoverageReport))
+            }
+            CollectorConfig::FileLength => {
+                Collector::Base(BaseCollectorObj::FileLength(FileLength))
+            }
+            CollectorConfig::CumulativeDiffStat { scope } => Collector::Derived(
+                DerivedCollectorObj::CumulativeDiffStat(CumulativeDiffStat {
+                    scope: scope.clone(),
+                }),
+            ),
+            CollectorConfig::RollingDiffStat { .. } => {
+                Collector::Derived(DerivedCollectorObj::RollingDiffStat(RollingDiffStat))
+            }
+            CollectorConfig::BuildWarnings { command } => {
+                Collector::Base(BaseCollectorObj::BuildWarnings(BuildWarnings {
+                    command: command.clone(),
+                }))
+            }
+            CollectorConfig::FormattingChanges => {
+                Collector::Base(BaseCollectorObj::FormattingChanges(FormattingChanges))
+            }
+            CollectorConfig::ImportCoupling => {
+                Collector::Base(BaseCollectorObj::ImportCoupling(ImportCoupling))
+            }
+            CollectorConfig::ConfigFiles { files } => {
+                Collector::Base(BaseCollectorObj::ConfigFiles(ConfigFiles {
+                    files: files.clone(),
+                }))
+            }
+            CollectorConfig::Shellcheck => {
+                Collector::Base(BaseCollectorObj::Shellcheck(Shellcheck))
+            }
+            CollectorConfig::CognitiveComplexity => {
+                Collector::Base(BaseCollectorObj::CognitiveComplexity(CognitiveComplexity))
+            }
+            CollectorConfig::CommentDensity => {
+                Collector::Base(BaseCollectorObj::CommentDensity(CommentDensity))
+            }
+            CollectorConfig::MaintainabilityIndex => Collector::Derived(
+                DerivedCollectorObj::MaintainabilityIndex(MaintainabilityIndex),
+            ),
+            CollectorConfig::Expression {
+                expression,
+                operands,
+            } => Collector::Derived(DerivedCollectorObj::Expression(Expression {
+                expression: expression.clone(),
+                operands: operands.clone(),
+            })),
+            CollectorConfig::AuthorDomain => {
+                Collector::Base(BaseCollectorObj::AuthorDomain(AuthorDomain))
+            }
+            CollectorConfig::AuthorDomains { window } => {
+                Collector::Base(BaseCollectorObj::AuthorDomains(AuthorDomains {
+                    window: *window,
+                }))
+            }
+            CollectorConfig::Secrets { scope } => {
+                Collector::Base(BaseCollectorObj::Secrets(Secrets {
+                    scope: scope.clone(),
+                }))
+            }
+            CollectorConfig::HighEntropyStrings { scope } => {
+                Collector::Base(BaseCollectorObj::HighEntropyStrings(HighEntropyStrings {
+                    scope: scope.clone(),
+                }))
+            }
         }
     }
 }
@@ -7,17 +7,18 @@ use petgraph::graph::NodeIndex;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use tracing::{debug, warn};
-use walkdir::WalkDir;
 
 use crate::{
-    config::CollectorConfig,
-    git::{CommitHash, WorktreeHandle},
+    config::{CollectorConfig, FileScope, MatchRedaction},
+    git::{CommitHash, GitError, WorktreeHandle},
     graph::CollectionExecutionGraph,
 };
 
 use super::{
     changed_files::ChangedFilesValue,
-    utils::{get_previous_commit_value_of_collector, get_value_of_preceeding_node, LookupError},
+    registry::DependencyKey,
+    suppressions::{get_previous_commit_value_with_suppressions, Suppressions, SuppressionsError},
+    utils::{get_dependency_value, LookupError},
     BaseCollector, CollectorValue, CollectorValueCastError,
 };
 
@@ -53,9 +54,11 @@ enum PartialGrepJSONLine {
 pub(crate) struct PatternOccurences {
     pub pattern: String,
     pub files: Option<Vec<Glob>>,
+    pub redact: Option<MatchRedaction>,
+    pub scope: Box<FileScope>,
 }
 
-fn get_matches_from_grep_output(output: &str) -> HashSet<PartialMatchData> {
+pub(crate) fn get_matches_from_grep_output(output: &str) -> HashSet<PartialMatchData> {
     output
         .lines()
         .filter_map(|l| serde_json::from_str::<PartialGrepJSONLine>(l).ok())
@@ -65,13 +68,37 @@ fn get_matches_from_grep_output(output: &str) -> HashSet<PartialMatchData> {
         .collect()
 }
 
+/// Reads the matches written to the sink without the suppressed ones, with their text redacted if
+/// requested. Matches taken over from the previous commit were redacted already.
 fn get_matches_from_sink(
     sink: JSON<BufWriter<Vec<u8>>>,
+    suppressions: &Suppressions,
+    redact: Option<MatchRedaction>,
 ) -> Result<HashSet<PartialMatchData>, PatternOccurencesError> {
     let bytes = sink.into_inner().into_inner()?;
     let ripgrep_output = String::from_utf8(bytes)?;
 
-    let matches = get_matches_from_grep_output(&ripgrep_output);
+    let mut matches: HashSet<PartialMatchData> = get_matches_from_grep_output(&ripgrep_output)
+        .into_iter()
+        .filter_map(|mut data| {
+            data.submatches.retain(|submatch| {
+                !suppressions.is_suppressed(&data.path.text, &submatch.mtch.text)
+            });
+            (!data.submatches.is_empty()).then_some(data)
+        })
+        .collect();
+
+    if let Some(redact) = redact {
+        matches = matches
+            .into_iter()
+            .map(|mut data| {
+                for submatch in &mut data.submatches {
+                    submatch.mtch.text = redact.redact(&submatch.mtch.text);
+                }
+                data
+            })
+            .collect();
+    }
 
     Ok(matches)
 }
@@ -83,34 +110,39 @@ pub struct PatternOccurencesValue {
 
 #[derive(Error, Debug)]
 pub enum PatternOccurencesError {
-    #[error("{0}")]
+    #[error(transparent)]
     Lookup(#[from] LookupError),
 
-    #[error("{0}")]
+    #[error(transparent)]
     Cast(#[from] CollectorValueCastError),
 
-    #[error("{0}")]
+    #[error(transparent)]
     Regex(#[from] grep::regex::Error),
 
-    #[error("{0}")]
+    #[error(transparent)]
     IO(#[from] std::io::Error),
 
-    #[error("{0}")]
+    #[error(transparent)]
     IntoInner(#[from] std::io::IntoInnerError<BufWriter<Vec<u8>>>),
 
-    #[error("{0}")]
+    #[error(transparent)]
     FromUtf8Error(#[from] std::string::FromUtf8Error),
 
-    #[error("{0}")]
-    Walkdir(#[from] walkdir::Error),
+    #[error(transparent)]
+    Git(#[from] GitError),
 
-    #[error("{0}")]
-    StripPrefixError(#[from] std::path::StripPrefixError),
+    #[error(transparent)]
+    Glob(#[from] globset::Error),
+
+    #[error(transparent)]
+    Suppressions(#[from] SuppressionsError),
 }
 
 impl BaseCollector for PatternOccurences {
     type Error = PatternOccurencesError;
+    const READS_TREE: bool = true;
 
+    #[allow(clippy::similar_names)]
     #[tracing::instrument(level = "trace", skip_all)]
     fn collect(
         &self,
@@ -119,15 +151,13 @@ impl BaseCollector for PatternOccurences {
         graph: &CollectionExecutionGraph,
         current_node_idx: NodeIndex,
     ) -> Result<CollectorValue, PatternOccurencesError> {
-        let changed_files_in_current_commit_value: ChangedFilesValue =
-            get_value_of_preceeding_node(
-                storage,
-                graph,
-                current_node_idx,
-                |e| e.distance == 0,
-                |n| n.collector_config == CollectorConfig::ChangedFiles,
-            )?
-            .try_into()?;
+        let changed_files_in_current_commit_value: ChangedFilesValue = get_dependency_value(
+            storage,
+            graph,
+            current_node_idx,
+            &DependencyKey::ChangedFiles,
+        )?
+        .try_into()?;
 
         let changed_files_in_current_commit = changed_files_in_current_commit_value.files;
 
@@ -139,7 +169,8 @@ impl BaseCollector for PatternOccurences {
             match files.build() {
                 Ok(files) => Some(files),
                 Err(e) => {
-                    // TODO: Handle this error BEFORE the collector is run
+                    // Invalid globs are rejected before the collection starts, see
+                    // `MetricConfig::validate`
                     warn!("Error creating glob set: {}", e);
                     None
                 }
@@ -148,15 +179,18 @@ impl BaseCollector for PatternOccurences {
             None
         };
 
-        let matching_files_in_current_commit = if let Some(globs) = &globset {
-            changed_files_in_current_commit
-                .clone()
-                .into_iter()
-                .filter(|f| globs.is_match_candidate(&Candidate::new(f)))
-                .collect::<HashSet<_>>()
-        } else {
-            changed_files_in_current_commit.clone()
-        };
+        let scope = self.scope.matcher()?;
+
+        let matching_files_in_current_commit = changed_files_in_current_commit
+            .clone()
+            .into_iter()
+            .filter(|f| {
+                globset
+                    .as_ref()
+                    .is_none_or(|globs| globs.is_match_candidate(&Candidate::new(f)))
+            })
+            .filter(|f| scope.is_match(f))
+            .collect::<HashSet<_>>();
 
         let mut searcher = SearcherBuilder::new().line_number(true).build();
         let matcher = RegexMatcher::new(&self.pattern)?;
@@ -164,28 +198,35 @@ impl BaseCollector for PatternOccurences {
         let buffer = BufWriter::new(Vec::new());
         let mut sink = JSON::new(buffer);
 
-        let previous_commit_value =
-            get_previous_commit_value_of_collector(storage, graph, current_node_idx);
+        let commit_hash = &graph.graph[current_node_idx].commit_hash;
+        let tree = repo.tree_reader(commit_hash)?;
+
+        let suppressions = Suppressions::of_commit(repo, commit_hash)?;
+
+        let previous_commit_value = get_previous_commit_value_with_suppressions(
+            storage,
+            graph,
+            current_node_idx,
+            repo,
+            &suppressions,
+        )?;
 
         if let Some(previous_commit_value) = previous_commit_value {
             debug!("found value from previous commit, only searching changed files");
 
             for changed_file_relative_path in &matching_files_in_current_commit {
-                let changed_file_absolute_path = repo.path.join(changed_file_relative_path);
-
-                if !changed_file_absolute_path.exists() {
-                    // File was removed in the current commit
-                    // TODO: Already get this information from git to be more certain
+                // File was removed in the current commit
+                let Some(contents) = tree.read_file(changed_file_relative_path)? else {
                     continue;
-                }
+                };
 
                 debug!("searching file: {:?}", changed_file_relative_path);
 
                 let sink = sink.sink_with_path(&matcher, &changed_file_relative_path);
-                searcher.search_path(&matcher, &changed_file_absolute_path, sink)?;
+                searcher.search_slice(&matcher, &contents, sink)?;
             }
 
-            let matches = get_matches_from_sink(sink)?;
+            let matches = get_matches_from_sink(sink, &suppressions, self.redact)?;
 
             let previous_commit_value: PatternOccurencesValue = previous_commit_value.try_into()?;
 
@@ -208,40 +249,30 @@ impl BaseCollector for PatternOccurences {
         } else {
             debug!("did not find value from previous commit, searching all files");
 
-            let root_path = &repo.path.canonicalize()?;
-
-            for entry in WalkDir::new(root_path).into_iter().filter_entry(|e| {
-                // Skip .git directory
-                let is_dot_git_dir = e
-                    .file_name()
-                    .to_str()
-                    .is_some_and(|s| s.starts_with(".git"));
-
-                !is_dot_git_dir
-            }) {
-                let entry = entry?;
-
-                if !entry.file_type().is_file() {
-                    continue;
-                }
-
-                let path = entry.path();
-                let path_relative_to_root = path.canonicalize()?;
-                let path_relative_to_root = path_relative_to_root.strip_prefix(root_path)?;
-
-                if let Some(globs) = &globset {
-                    if !globs.is_match_candidate(&Candidate::new(path_relative_to_root)) {
-                        continue;
+            let mut result = Ok(());
+
+            tree.for_each_file(
+                |path| {
+                    globset
+                        .as_ref()
+                        .is_none_or(|globs| globs.is_match_candidate(&Candidate::new(path)))
+                        && scope.is_match(path)
+                },
+                |path, contents| {
+                    if result.is_err() {
+                        return;
                     }
-                }
 
-                debug!("searching file: {:?}", path_relative_to_root);
+                    debug!("searching file: {:?}", path);
 
-                let mut sink = sink.sink_with_path(&matcher, path_relative_to_root);
-                searcher.search_path(&matcher, entry.path(), &mut sink)?;
-            }
+                    let mut sink = sink.sink_with_path(&matcher, &path);
+                    result = searcher.search_slice(&matcher, contents, &mut sink);
+                },
+            )?;
+
+            result?;
 
-            let matches = get_matches_from_sink(sink)?;
+            let matches = get_matches_from_sink(sink, &suppressions, self.redact)?;
 
             let value = PatternOccurencesValue { matches };
 
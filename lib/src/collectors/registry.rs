@@ -0,0 +1,213 @@
+//! Dependencies of the collectors on the values of other collectors at the same commit, declared
+//! once for building the execution graph and for looking the values up while collecting.
+
+use std::collections::HashSet;
+
+use crate::config::CollectorConfig;
+
+/// Name a collector looks the value of one of its dependencies up by
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum DependencyKey {
+    ChangedFiles,
+
+    /// Collector a total is computed from, e.g. `loc` for `total-loc`
+    Source,
+
+    TotalLoc,
+    CognitiveComplexity,
+    CommentDensity,
+
+    /// Metric referenced by an expression
+    Operand(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Dependency {
+    pub key: DependencyKey,
+    pub collector_config: CollectorConfig,
+
+    /// Number of previously sampled commits whose values are required in addition to the value
+    /// at the same commit, see [`super::utils::get_dependency_window_values`]
+    pub window: usize,
+}
+
+impl Dependency {
+    fn new(key: DependencyKey, collector_config: CollectorConfig) -> Self {
+        Self {
+            key,
+            collector_config,
+            window: 0,
+        }
+    }
+
+    fn with_window(mut self, window: usize) -> Self {
+        self.window = window;
+        self
+    }
+}
+
+/// Returns the collectors whose values at the same commit are required by the given collector.
+/// The execution graph contains a task for each of them, which the collector finds with
+/// [`super::utils::get_dependency_value`].
+pub(crate) fn dependencies(collector_config: &CollectorConfig) -> Vec<Dependency> {
+    match collector_config {
+        // TODO: Optimize the dependency creation of the pattern occurences task
+        // This might be inefficient when there are multiple `TotalPatternOccurences` tasks
+        // We would create a new pattern occurences task for each one of them
+        // Maybe we should combine the files and patterns of all tasks and create a single one
+        CollectorConfig::TotalPatternOccurences {
+            pattern,
+            files,
+            redact,
+            scope,
+        } => vec![Dependency::new(
+            DependencyKey::Source,
+            CollectorConfig::PatternOccurences {
+                pattern: pattern.clone(),
+                files: files.clone(),
+                redact: *redact,
+                scope: scope.clone(),
+            },
+        )],
+        CollectorConfig::PatternOccurences { .. }
+        | CollectorConfig::Secrets { .. }
+        | CollectorConfig::HighEntropyStrings { .. }
+        | CollectorConfig::TotalCargoDeps
+        | CollectorConfig::CommentDensity
+        | CollectorConfig::CognitiveComplexity
+        | CollectorConfig::Shellcheck
+        | CollectorConfig::ConfigFiles { .. }
+        | CollectorConfig::ImportCoupling
+        | CollectorConfig::BuildWarnings { .. }
+        | CollectorConfig::FileLength
+        | CollectorConfig::CoverageReport
+        | CollectorConfig::ContainerConfig
+        | CollectorConfig::CiWorkflows
+        | CollectorConfig::TotalCargoFeatures
+        | CollectorConfig::ChangedFilesLoc
+        | CollectorConfig::TotalWorkspaceMembers => vec![Dependency::new(
+            DependencyKey::ChangedFiles,
+            CollectorConfig::ChangedFiles,
+        )],
+        CollectorConfig::TotalLoc { scope } => vec![Dependency::new(
+            DependencyKey::Source,
+            CollectorConfig::Loc {
+                scope: scope.clone(),
+            },
+        )],
+        CollectorConfig::TotalFileCount { scope } => vec![Dependency::new(
+            DependencyKey::Source,
+            CollectorConfig::FileList {
+                scope: scope.clone(),
+            },
+        )],
+        CollectorConfig::CumulativeDiffStat { scope } => vec![Dependency::new(
+            DependencyKey::Source,
+            CollectorConfig::TotalDiffStat {
+                scope: scope.clone(),
+            },
+        )],
+        CollectorConfig::RollingDiffStat { window, scope } => vec![Dependency::new(
+            DependencyKey::Source,
+            CollectorConfig::TotalDiffStat {
+                scope: scope.clone(),
+            },
+        )
+        .with_window(window.saturating_sub(1))],
+        CollectorConfig::AuthorDomains { window } => {
+            vec![
+                Dependency::new(DependencyKey::Source, CollectorConfig::AuthorDomain)
+                    .with_window(*window),
+            ]
+        }
+        CollectorConfig::MaintainabilityIndex => vec![
+            Dependency::new(
+                DependencyKey::TotalLoc,
+                CollectorConfig::TotalLoc {
+                    scope: Box::default(),
+                },
+            ),
+            Dependency::new(
+                DependencyKey::CognitiveComplexity,
+                CollectorConfig::CognitiveComplexity,
+            ),
+            Dependency::new(
+                DependencyKey::CommentDensity,
+                CollectorConfig::CommentDensity,
+            ),
+        ],
+        CollectorConfig::Expression { operands, .. } => operands
+            .iter()
+            .map(|(metric, collector_config)| {
+                Dependency::new(
+                    DependencyKey::Operand(metric.clone()),
+                    collector_config.clone(),
+                )
+            })
+            .collect(),
+        CollectorConfig::Loc { .. }
+        | CollectorConfig::TotalDiffStat { .. }
+        | CollectorConfig::ChangedFiles
+        | CollectorConfig::FileList { .. }
+        | CollectorConfig::TotalSymlinksAndSubmodules
+        | CollectorConfig::FormattingChanges
+        | CollectorConfig::AuthorDomain => vec![],
+    }
+}
+
+/// Collectors whose values the given collector requires directly or through its dependencies
+pub(crate) fn transitive_dependencies(
+    collector_config: &CollectorConfig,
+) -> HashSet<CollectorConfig> {
+    let mut found = HashSet::new();
+    let mut pending = vec![collector_config.clone()];
+
+    while let Some(current) = pending.pop() {
+        for dependency in dependencies(&current) {
+            if found.insert(dependency.collector_config.clone()) {
+                pending.push(dependency.collector_config);
+            }
+        }
+    }
+
+    found
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    #[test]
+    fn test_expression_dependencies_are_keyed_by_metric() {
+        let collector_config = CollectorConfig::Expression {
+            expression: "warnings / loc.loc".to_string(),
+            operands: BTreeMap::from([
+                (
+                    "loc".to_string(),
+                    CollectorConfig::TotalLoc {
+                        scope: Box::default(),
+                    },
+                ),
+                ("warnings".to_string(), CollectorConfig::Shellcheck),
+            ]),
+        };
+
+        assert_eq!(
+            dependencies(&collector_config),
+            vec![
+                Dependency::new(
+                    DependencyKey::Operand("loc".to_string()),
+                    CollectorConfig::TotalLoc {
+                        scope: Box::default(),
+                    },
+                ),
+                Dependency::new(
+                    DependencyKey::Operand("warnings".to_string()),
+                    CollectorConfig::Shellcheck,
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use dashmap::DashMap;
+use petgraph::graph::NodeIndex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{config::CollectorConfig, git::CommitHash, graph::CollectionExecutionGraph};
+
+use super::{
+    registry::DependencyKey,
+    total_diff_stat::TotalDiffStatValue,
+    utils::{get_dependency_value, get_dependency_window_values, LookupError},
+    CollectorValue, CollectorValueCastError, DerivedCollector,
+};
+
+#[derive(Debug)]
+pub(crate) struct RollingDiffStat;
+
+/// Averages of the diff stats of the sampled commits within the window ending at the current one.
+///
+/// Only the diff stat of each sampled commit itself is included, the ones of the commits skipped
+/// in between are not.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct RollingDiffStatValue {
+    /// Number of sampled commits the averages are computed from, less than the window at the start
+    /// of the history
+    pub commits: u32,
+    pub files_changed: f64,
+    pub insertions: f64,
+    pub deletions: f64,
+}
+
+#[derive(Error, Debug)]
+pub enum RollingDiffStatError {
+    #[error(transparent)]
+    Lookup(#[from] LookupError),
+
+    #[error(transparent)]
+    Cast(#[from] CollectorValueCastError),
+
+    #[error(transparent)]
+    TryFromIntError(#[from] std::num::TryFromIntError),
+}
+
+fn average(
+    diff_stats: &[TotalDiffStatValue],
+) -> Result<RollingDiffStatValue, RollingDiffStatError> {
+    let commits = u32::try_from(diff_stats.len())?;
+
+    let mean = |field: fn(&TotalDiffStatValue) -> u32| -> f64 {
+        let sum: f64 = diff_stats.iter().map(|value| f64::from(field(value))).sum();
+        sum / f64::from(commits)
+    };
+
+    Ok(RollingDiffStatValue {
+        commits,
+        files_changed: mean(|value| value.files_changed),
+        insertions: mean(|value| value.insertions),
+        deletions: mean(|value| value.deletions),
+    })
+}
+
+impl DerivedCollector for RollingDiffStat {
+    type Error = RollingDiffStatError;
+
+    #[tracing::instrument(level = "trace", skip_all)]
+    fn collect(
+        &self,
+        storage: &DashMap<(CollectorConfig, CommitHash), CollectorValue>,
+        graph: &CollectionExecutionGraph,
+        current_node_idx: NodeIndex,
+    ) -> Result<CollectorValue, RollingDiffStatError> {
+        let mut values = vec![get_dependency_value(
+            storage,
+            graph,
+            current_node_idx,
+            &DependencyKey::Source,
+        )?];
+
+        values.extend(get_dependency_window_values(
+            storage,
+            graph,
+            current_node_idx,
+            &DependencyKey::Source,
+        )?);
+
+        let diff_stats = values
+            .into_iter()
+            .map(TryInto::try_into)
+            .collect::<Result<Vec<TotalDiffStatValue>, _>>()?;
+
+        Ok(average(&diff_stats)?.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn diff_stat(files_changed: u32, insertions: u32, deletions: u32) -> TotalDiffStatValue {
+        TotalDiffStatValue {
+            files_changed,
+            insertions,
+            deletions,
+        }
+    }
+
+    #[test]
+    fn test_average() {
+        let value = average(&[diff_stat(1, 10, 0), diff_stat(3, 20, 5)]).unwrap();
+
+        assert_eq!(value.commits, 2);
+        assert!((value.files_changed - 2.0).abs() < f64::EPSILON);
+        assert!((value.insertions - 15.0).abs() < f64::EPSILON);
+        assert!((value.deletions - 2.5).abs() < f64::EPSILON);
+    }
+}
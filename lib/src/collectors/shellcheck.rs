@@ -0,0 +1,303 @@
+use std::{
+    io::{BufRead, BufReader, Read},
+    path::Path,
+    process::Command,
+    sync::Once,
+};
+
+use dashmap::DashMap;
+use petgraph::graph::NodeIndex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::{debug, warn};
+
+use crate::{
+    config::CollectorConfig,
+    git::{CommitHash, GitError, WorktreeHandle},
+    graph::CollectionExecutionGraph,
+};
+
+use super::{
+    changed_files::ChangedFilesValue,
+    registry::DependencyKey,
+    utils::{get_dependency_value, get_previous_commit_value_of_collector, LookupError},
+    BaseCollector, CollectorValue, CollectorValueCastError,
+};
+
+const SHELL_EXTENSIONS: [&str; 4] = ["sh", "bash", "ksh", "dash"];
+
+/// Interpreters shellcheck supports, scripts without extension are recognized by their shebang
+const SHELL_INTERPRETERS: [&str; 4] = ["sh", "bash", "ksh", "dash"];
+
+/// Only the beginning of files without extension is read to look for a shebang
+const MAX_SHEBANG_LENGTH: u64 = 128;
+
+/// Number of scripts passed to a single shellcheck invocation to stay below argument limits
+const SCRIPTS_PER_INVOCATION: usize = 500;
+
+static MISSING_SHELLCHECK_WARNING: Once = Once::new();
+
+#[derive(Debug)]
+pub(crate) struct Shellcheck;
+
+/// Findings of shellcheck per severity. The counts are `None` if shellcheck is not installed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShellcheckValue {
+    pub scripts: u32,
+    pub errors: Option<u32>,
+    pub warnings: Option<u32>,
+    pub infos: Option<u32>,
+    pub styles: Option<u32>,
+}
+
+#[derive(Error, Debug)]
+pub enum ShellcheckError {
+    #[error(transparent)]
+    Lookup(#[from] LookupError),
+
+    #[error(transparent)]
+    Cast(#[from] CollectorValueCastError),
+
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Git(#[from] GitError),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    #[error("shellcheck exited with {0}")]
+    Failed(std::process::ExitStatus),
+
+    #[error(transparent)]
+    TryFromIntError(#[from] std::num::TryFromIntError),
+}
+
+#[derive(Deserialize)]
+struct PartialShellcheckComment {
+    level: String,
+}
+
+#[derive(Deserialize)]
+struct PartialShellcheckOutput {
+    comments: Vec<PartialShellcheckComment>,
+}
+
+#[derive(Default, Debug, PartialEq)]
+struct Findings {
+    errors: usize,
+    warnings: usize,
+    infos: usize,
+    styles: usize,
+}
+
+impl Findings {
+    /// Adds the comments of shellcheck's `json1` output
+    fn add_output(&mut self, output: &str) -> Result<(), serde_json::Error> {
+        let output: PartialShellcheckOutput = serde_json::from_str(output)?;
+
+        for comment in output.comments {
+            match comment.level.as_str() {
+                "error" => self.errors += 1,
+                "warning" => self.warnings += 1,
+                "info" => self.infos += 1,
+                "style" => self.styles += 1,
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn has_shell_extension(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| SHELL_EXTENSIONS.contains(&ext))
+}
+
+fn is_shell_shebang(line: &str) -> bool {
+    let Some(command) = line.strip_prefix("#!") else {
+        return false;
+    };
+
+    let mut parts = command.split_whitespace();
+
+    let Some(mut interpreter) = parts.next() else {
+        return false;
+    };
+
+    // e.g. `#!/usr/bin/env bash`
+    if interpreter.ends_with("/env") {
+        let Some(argument) = parts.next() else {
+            return false;
+        };
+        interpreter = argument;
+    }
+
+    let name = interpreter.rsplit('/').next().unwrap_or(interpreter);
+
+    SHELL_INTERPRETERS.contains(&name)
+}
+
+fn is_shell_script(repo: &WorktreeHandle, relative_path: &str) -> Result<bool, ShellcheckError> {
+    let path = Path::new(relative_path);
+    if has_shell_extension(path) {
+        return Ok(true);
+    }
+
+    if path.extension().is_some() {
+        return Ok(false);
+    }
+
+    let Some(reader) = repo.read_file(path)? else {
+        return Ok(false);
+    };
+
+    let mut first_line = String::new();
+    // Binary files are not valid UTF-8 and can't be scripts
+    if BufReader::new(reader.take(MAX_SHEBANG_LENGTH))
+        .read_line(&mut first_line)
+        .is_err()
+    {
+        return Ok(false);
+    }
+
+    Ok(is_shell_shebang(&first_line))
+}
+
+impl BaseCollector for Shellcheck {
+    type Error = ShellcheckError;
+
+    #[tracing::instrument(level = "trace", skip_all)]
+    fn collect(
+        &self,
+        storage: &DashMap<(CollectorConfig, CommitHash), CollectorValue>,
+        repo: &mut WorktreeHandle,
+        graph: &CollectionExecutionGraph,
+        current_node_idx: NodeIndex,
+    ) -> Result<CollectorValue, ShellcheckError> {
+        let changed_files_in_current_commit_value: ChangedFilesValue = get_dependency_value(
+            storage,
+            graph,
+            current_node_idx,
+            &DependencyKey::ChangedFiles,
+        )?
+        .try_into()?;
+
+        // Files without extension might be scripts with a shebang
+        let has_modified_possible_script =
+            changed_files_in_current_commit_value
+                .files
+                .iter()
+                .any(|relative_path| {
+                    let path = Path::new(relative_path);
+                    has_shell_extension(path) || path.extension().is_none()
+                });
+
+        if !has_modified_possible_script {
+            let previous_commit_value =
+                get_previous_commit_value_of_collector(storage, graph, current_node_idx);
+
+            if let Some(previous_commit_value) = previous_commit_value {
+                return Ok(previous_commit_value);
+            }
+        }
+
+        let mut scripts = vec![];
+        for relative_path in repo.walk_tracked_files(None)? {
+            if is_shell_script(repo, &relative_path)? {
+                scripts.push(relative_path);
+            }
+        }
+
+        let mut findings = Findings::default();
+        let mut is_available = true;
+
+        for chunk in scripts.chunks(SCRIPTS_PER_INVOCATION) {
+            let output = Command::new("shellcheck")
+                .arg("--format=json1")
+                .args(chunk)
+                .current_dir(&repo.path)
+                .output();
+
+            let output = match output {
+                Ok(output) => output,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    MISSING_SHELLCHECK_WARNING.call_once(|| {
+                        warn!("shellcheck is not installed, only counting shell scripts");
+                    });
+                    is_available = false;
+                    break;
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            // Exits with 1 if there are findings
+            if !matches!(output.status.code(), Some(0 | 1)) {
+                debug!("{}", String::from_utf8_lossy(&output.stderr));
+                return Err(ShellcheckError::Failed(output.status));
+            }
+
+            findings.add_output(&String::from_utf8_lossy(&output.stdout))?;
+        }
+
+        let count = |value: usize| -> Result<Option<u32>, ShellcheckError> {
+            if is_available {
+                Ok(Some(u32::try_from(value)?))
+            } else {
+                Ok(None)
+            }
+        };
+
+        let value = ShellcheckValue {
+            scripts: u32::try_from(scripts.len())?,
+            errors: count(findings.errors)?,
+            warnings: count(findings.warnings)?,
+            infos: count(findings.infos)?,
+            styles: count(findings.styles)?,
+        };
+
+        Ok(value.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("#!/bin/sh\n", true)]
+    #[case("#!/bin/bash -e\n", true)]
+    #[case("#!/usr/bin/env bash\n", true)]
+    #[case("#! /bin/dash\n", true)]
+    #[case("#!/usr/bin/env python3\n", false)]
+    #[case("#!/bin/zsh\n", false)]
+    #[case("echo hello\n", false)]
+    fn test_is_shell_shebang(#[case] line: &str, #[case] expected: bool) {
+        assert_eq!(expected, is_shell_shebang(line));
+    }
+
+    #[test]
+    fn test_add_output() {
+        let mut findings = Findings::default();
+        findings
+            .add_output(
+                r#"{"comments":[{"file":"a.sh","line":3,"level":"warning","code":2034},{"file":"a.sh","line":5,"level":"style","code":2086},{"file":"b.sh","line":1,"level":"error","code":1073}]}"#,
+            )
+            .unwrap();
+        findings.add_output(r#"{"comments":[]}"#).unwrap();
+
+        assert_eq!(
+            Findings {
+                errors: 1,
+                warnings: 1,
+                infos: 0,
+                styles: 1,
+            },
+            findings
+        );
+    }
+}
@@ -1,8 +1,4 @@
-use std::{
-    collections::HashSet,
-    fs::File,
-    io::{BufReader, Read},
-};
+use std::{collections::HashSet, str::FromStr};
 
 use cargo_lock::Lockfile;
 use dashmap::DashMap;
@@ -12,13 +8,14 @@ use thiserror::Error;
 
 use crate::{
     config::CollectorConfig,
-    git::{CommitHash, WorktreeHandle},
+    git::{CommitHash, GitError, WorktreeHandle},
     graph::CollectionExecutionGraph,
 };
 
 use super::{
     changed_files::ChangedFilesValue,
-    utils::{get_previous_commit_value_of_collector, get_value_of_preceeding_node, LookupError},
+    registry::DependencyKey,
+    utils::{get_dependency_value, get_previous_commit_value_of_collector, LookupError},
     BaseCollector, CollectorValue, CollectorValueCastError,
 };
 
@@ -57,27 +54,31 @@ pub struct TotalCargoDependenciesValue {
 
 #[derive(Error, Debug)]
 pub enum TotalCargoDependenciesError {
-    #[error("{0}")]
+    #[error(transparent)]
     Lookup(#[from] LookupError),
 
-    #[error("{0}")]
+    #[error(transparent)]
     Cast(#[from] CollectorValueCastError),
 
-    #[error("{0}")]
-    IO(#[from] std::io::Error),
+    #[error(transparent)]
+    Utf8(#[from] std::str::Utf8Error),
+
+    #[error(transparent)]
+    Git(#[from] GitError),
 
-    #[error("{0}")]
+    #[error(transparent)]
     Lockfile(#[from] cargo_lock::Error),
 
-    #[error("{0}")]
+    #[error(transparent)]
     TryFromIntError(#[from] std::num::TryFromIntError),
 
-    #[error("{0}")]
+    #[error(transparent)]
     TomlDeserializationError(#[from] toml::de::Error),
 }
 
 impl BaseCollector for TotalCargoDependencies {
     type Error = TotalCargoDependenciesError;
+    const READS_TREE: bool = true;
 
     #[tracing::instrument(level = "trace", skip_all)]
     fn collect(
@@ -87,15 +88,13 @@ impl BaseCollector for TotalCargoDependencies {
         graph: &CollectionExecutionGraph,
         current_node_idx: NodeIndex,
     ) -> Result<CollectorValue, TotalCargoDependenciesError> {
-        let changed_files_in_current_commit_value: ChangedFilesValue =
-            get_value_of_preceeding_node(
-                storage,
-                graph,
-                current_node_idx,
-                |e| e.distance == 0,
-                |n| n.collector_config == CollectorConfig::ChangedFiles,
-            )?
-            .try_into()?;
+        let changed_files_in_current_commit_value: ChangedFilesValue = get_dependency_value(
+            storage,
+            graph,
+            current_node_idx,
+            &DependencyKey::ChangedFiles,
+        )?
+        .try_into()?;
 
         let changed_files_in_current_commit: HashSet<String> =
             changed_files_in_current_commit_value.files;
@@ -127,17 +126,17 @@ impl BaseCollector for TotalCargoDependencies {
             }
         }
 
+        let commit_hash = &graph.graph[current_node_idx].commit_hash;
+        let tree = repo.tree_reader(commit_hash)?;
+
         let mut crates_in_repo: HashSet<CargoTomlPackage> = HashSet::new();
         for relative_path in modified_cargo_toml_paths {
-            let path = repo.path.join(relative_path);
-            let p = &path;
-
-            let file = File::open(p)?;
-            let mut buf_reader = BufReader::new(file);
-            let mut contents = String::new();
-            buf_reader.read_to_string(&mut contents)?;
+            // Removed in the current commit
+            let Some(contents) = tree.read_file(relative_path)? else {
+                continue;
+            };
 
-            let cargo_toml: CargoToml = toml::from_str(&contents)?;
+            let cargo_toml: CargoToml = toml::from_str(std::str::from_utf8(&contents)?)?;
 
             if let Some(package) = cargo_toml.package {
                 crates_in_repo.insert(package);
@@ -146,10 +145,11 @@ impl BaseCollector for TotalCargoDependencies {
 
         let mut dependencies: HashSet<CargoLockPackage> = HashSet::new();
         for relative_path in modified_cargo_lock_paths {
-            let path = repo.path.join(relative_path);
-            let p = &path;
+            let Some(contents) = tree.read_file(relative_path)? else {
+                continue;
+            };
 
-            let lockfile = Lockfile::load(p)?;
+            let lockfile = Lockfile::from_str(std::str::from_utf8(&contents)?)?;
 
             for package in lockfile.packages {
                 dependencies.insert(CargoLockPackage(package));
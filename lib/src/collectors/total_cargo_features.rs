@@ -0,0 +1,194 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::Path,
+};
+
+use dashmap::DashMap;
+use petgraph::graph::NodeIndex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::warn;
+
+use crate::{
+    config::CollectorConfig,
+    git::{CommitHash, GitError, WorktreeHandle},
+    graph::CollectionExecutionGraph,
+};
+
+use super::{
+    changed_files::ChangedFilesValue,
+    registry::DependencyKey,
+    utils::{get_dependency_value, get_previous_commit_value_of_collector, LookupError},
+    BaseCollector, CollectorValue, CollectorValueCastError,
+};
+
+#[derive(Deserialize, Debug)]
+struct CargoToml {
+    #[serde(default)]
+    features: BTreeMap<String, toml::Value>,
+
+    #[serde(default)]
+    dependencies: BTreeMap<String, toml::Value>,
+}
+
+impl CargoToml {
+    fn optional_dependency_count(&self) -> usize {
+        self.dependencies
+            .values()
+            .filter(|d| {
+                d.get("optional")
+                    .and_then(toml::Value::as_bool)
+                    .unwrap_or(false)
+            })
+            .count()
+    }
+}
+
+/// Manifests that are not valid UTF-8 or TOML are skipped instead of failing the commit
+fn parse_manifest(relative_path: &str, contents: &[u8]) -> Option<CargoToml> {
+    let contents = match std::str::from_utf8(contents) {
+        Ok(contents) => contents,
+        Err(e) => {
+            warn!("Skipping invalid manifest {}: {}", relative_path, e);
+            return None;
+        }
+    };
+
+    match toml::from_str(contents) {
+        Ok(cargo_toml) => Some(cargo_toml),
+        Err(e) => {
+            warn!("Skipping invalid manifest {}: {}", relative_path, e);
+            None
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct TotalCargoFeatures;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TotalCargoFeaturesValue {
+    pub total_features: u32,
+    pub total_optional_dependencies: u32,
+}
+
+#[derive(Error, Debug)]
+pub enum TotalCargoFeaturesError {
+    #[error(transparent)]
+    Lookup(#[from] LookupError),
+
+    #[error(transparent)]
+    Cast(#[from] CollectorValueCastError),
+
+    #[error(transparent)]
+    Git(#[from] GitError),
+
+    #[error(transparent)]
+    TryFromIntError(#[from] std::num::TryFromIntError),
+}
+
+impl BaseCollector for TotalCargoFeatures {
+    type Error = TotalCargoFeaturesError;
+    const READS_TREE: bool = true;
+
+    #[tracing::instrument(level = "trace", skip_all)]
+    fn collect(
+        &self,
+        storage: &DashMap<(CollectorConfig, CommitHash), CollectorValue>,
+        repo: &mut WorktreeHandle,
+        graph: &CollectionExecutionGraph,
+        current_node_idx: NodeIndex,
+    ) -> Result<CollectorValue, TotalCargoFeaturesError> {
+        let changed_files_in_current_commit_value: ChangedFilesValue = get_dependency_value(
+            storage,
+            graph,
+            current_node_idx,
+            &DependencyKey::ChangedFiles,
+        )?
+        .try_into()?;
+
+        let changed_files_in_current_commit: HashSet<String> =
+            changed_files_in_current_commit_value.files;
+
+        let has_modified_cargo_toml = changed_files_in_current_commit
+            .iter()
+            .any(|relative_path| Path::new(relative_path).ends_with("Cargo.toml"));
+
+        if !has_modified_cargo_toml {
+            let previous_commit_value =
+                get_previous_commit_value_of_collector(storage, graph, current_node_idx);
+
+            if let Some(previous_commit_value) = previous_commit_value {
+                return Ok(previous_commit_value);
+            }
+        }
+
+        let mut total_features = 0_usize;
+        let mut total_optional_dependencies = 0_usize;
+
+        let commit_hash = &graph.graph[current_node_idx].commit_hash;
+
+        repo.tree_reader(commit_hash)?.for_each_file(
+            |relative_path| Path::new(relative_path).ends_with("Cargo.toml"),
+            |relative_path, contents| {
+                if let Some(cargo_toml) = parse_manifest(relative_path, contents) {
+                    total_features += cargo_toml.features.len();
+                    total_optional_dependencies += cargo_toml.optional_dependency_count();
+                }
+            },
+        )?;
+
+        let value = TotalCargoFeaturesValue {
+            total_features: u32::try_from(total_features)?,
+            total_optional_dependencies: u32::try_from(total_optional_dependencies)?,
+        };
+
+        Ok(value.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("", 0, 0)]
+    #[case("[package]\nname = \"a\"\nversion = \"0.1.0\"\n", 0, 0)]
+    #[case(
+        "[features]\ndefault = [\"std\"]\nstd = []\nserde = [\"dep:serde\"]\n",
+        3,
+        0
+    )]
+    #[case(
+        "[dependencies]\nlog = \"0.4\"\nserde = { version = \"1\", optional = true }\nrand = { version = \"0.8\", optional = false }\n",
+        0,
+        1
+    )]
+    #[case(
+        "[features]\nfull = [\"dep:a\", \"dep:b\"]\n\n[dependencies.a]\nversion = \"1\"\noptional = true\n\n[dependencies.b]\npath = \"../b\"\noptional = true\n",
+        1,
+        2
+    )]
+    fn test_parse_manifest(
+        #[case] contents: &str,
+        #[case] expected_features: usize,
+        #[case] expected_optional_dependencies: usize,
+    ) {
+        let cargo_toml = parse_manifest("Cargo.toml", contents.as_bytes()).unwrap();
+
+        assert_eq!(expected_features, cargo_toml.features.len());
+        assert_eq!(
+            expected_optional_dependencies,
+            cargo_toml.optional_dependency_count()
+        );
+    }
+
+    #[rstest]
+    #[case(b"[dependencies\nlog = \"0.4\"\n")]
+    #[case(b"features = [\"std\"]\n")]
+    #[case(b"[features]\n\xff = []\n")]
+    fn test_parse_manifest_skips_invalid_manifests(#[case] contents: &[u8]) {
+        assert!(parse_manifest("Cargo.toml", contents).is_none());
+    }
+}
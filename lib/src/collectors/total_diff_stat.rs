@@ -4,7 +4,7 @@ use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 use crate::{
-    config::CollectorConfig,
+    config::{CollectorConfig, FileScope},
     git::{CommitHash, GitError, WorktreeHandle},
     graph::CollectionExecutionGraph,
 };
@@ -12,7 +12,9 @@ use crate::{
 use super::{BaseCollector, CollectorValue};
 
 #[derive(Debug)]
-pub(crate) struct TotalDiffStat;
+pub(crate) struct TotalDiffStat {
+    pub scope: Box<FileScope>,
+}
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct TotalDiffStatValue {
@@ -23,11 +25,14 @@ pub struct TotalDiffStatValue {
 
 #[derive(Error, Debug)]
 pub enum TotalDiffStatError {
-    #[error("{0}")]
+    #[error(transparent)]
     Git(#[from] GitError),
 
-    #[error("{0}")]
+    #[error(transparent)]
     TryFromIntError(#[from] std::num::TryFromIntError),
+
+    #[error(transparent)]
+    Glob(#[from] globset::Error),
 }
 
 impl BaseCollector for TotalDiffStat {
@@ -38,10 +43,17 @@ impl BaseCollector for TotalDiffStat {
         &self,
         _storage: &DashMap<(CollectorConfig, CommitHash), CollectorValue>,
         repo: &mut WorktreeHandle,
-        _graph: &CollectionExecutionGraph,
-        _current_node_idx: NodeIndex,
+        graph: &CollectionExecutionGraph,
+        current_node_idx: NodeIndex,
     ) -> Result<CollectorValue, TotalDiffStatError> {
-        let (files_changed, insertions, deletions) = repo.get_current_total_diff_stat()?;
+        let commit_hash = &graph.graph[current_node_idx].commit_hash;
+        let (files_changed, insertions, deletions) = if self.scope.is_unrestricted() {
+            repo.repository().get_total_diff_stat(commit_hash)?
+        } else {
+            let scope = self.scope.matcher()?;
+            repo.repository()
+                .get_total_diff_stat_of_files(commit_hash, |path| scope.is_match(path))?
+        };
 
         let value = TotalDiffStatValue {
             files_changed: u32::try_from(files_changed)?,
@@ -6,7 +6,8 @@ use thiserror::Error;
 use crate::{config::CollectorConfig, git::CommitHash, graph::CollectionExecutionGraph};
 
 use super::{
-    utils::{get_value_of_preceeding_node, LookupError},
+    registry::DependencyKey,
+    utils::{get_dependency_value, LookupError},
     CollectorValue, CollectorValueCastError, DerivedCollector, FileListValue,
 };
 
@@ -20,13 +21,13 @@ pub struct TotalFileCountValue {
 
 #[derive(Error, Debug)]
 pub enum TotalFileCountError {
-    #[error("{0}")]
+    #[error(transparent)]
     Lookup(#[from] LookupError),
 
-    #[error("{0}")]
+    #[error(transparent)]
     Cast(#[from] CollectorValueCastError),
 
-    #[error("{0}")]
+    #[error(transparent)]
     TryFromIntError(#[from] std::num::TryFromIntError),
 }
 
@@ -40,14 +41,9 @@ impl DerivedCollector for TotalFileCount {
         graph: &CollectionExecutionGraph,
         current_node_idx: NodeIndex,
     ) -> Result<CollectorValue, TotalFileCountError> {
-        let file_list_value: FileListValue = get_value_of_preceeding_node(
-            storage,
-            graph,
-            current_node_idx,
-            |e| e.distance == 0,
-            |n| n.collector_config == CollectorConfig::FileList,
-        )?
-        .try_into()?;
+        let file_list_value: FileListValue =
+            get_dependency_value(storage, graph, current_node_idx, &DependencyKey::Source)?
+                .try_into()?;
 
         let value = TotalFileCountValue {
             total_file_count: u32::try_from(file_list_value.files.len())?,
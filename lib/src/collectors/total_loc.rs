@@ -7,7 +7,8 @@ use crate::{config::CollectorConfig, git::CommitHash, graph::CollectionExecution
 
 use super::{
     loc::LocValue,
-    utils::{get_value_of_preceeding_node, LookupError},
+    registry::DependencyKey,
+    utils::{get_dependency_value, LookupError},
     CollectorValue, CollectorValueCastError, DerivedCollector,
 };
 
@@ -21,13 +22,13 @@ pub struct TotalLocValue {
 
 #[derive(Error, Debug)]
 pub enum TotalLocError {
-    #[error("{0}")]
+    #[error(transparent)]
     Lookup(#[from] LookupError),
 
-    #[error("{0}")]
+    #[error(transparent)]
     Cast(#[from] CollectorValueCastError),
 
-    #[error("{0}")]
+    #[error(transparent)]
     TryFromIntError(#[from] std::num::TryFromIntError),
 }
 
@@ -41,14 +42,9 @@ impl DerivedCollector for TotalLoc {
         graph: &CollectionExecutionGraph,
         current_node_idx: NodeIndex,
     ) -> Result<CollectorValue, TotalLocError> {
-        let loc_value: LocValue = get_value_of_preceeding_node(
-            storage,
-            graph,
-            current_node_idx,
-            |e| e.distance == 0,
-            |n| n.collector_config == CollectorConfig::Loc,
-        )?
-        .try_into()?;
+        let loc_value: LocValue =
+            get_dependency_value(storage, graph, current_node_idx, &DependencyKey::Source)?
+                .try_into()?;
 
         let value = TotalLocValue {
             loc: u32::try_from(loc_value.loc_by_language.values().sum::<usize>())?,
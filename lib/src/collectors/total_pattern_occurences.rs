@@ -1,5 +1,4 @@
 use dashmap::DashMap;
-use globset::Glob;
 use petgraph::graph::NodeIndex;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
@@ -8,15 +7,13 @@ use crate::{config::CollectorConfig, git::CommitHash, graph::CollectionExecution
 
 use super::{
     pattern_occurences::PatternOccurencesValue,
-    utils::{get_value_of_preceeding_node, LookupError},
+    registry::DependencyKey,
+    utils::{get_dependency_value, LookupError},
     CollectorValue, CollectorValueCastError, DerivedCollector,
 };
 
 #[derive(Debug)]
-pub(crate) struct TotalPatternOccurences {
-    pub pattern: String,
-    pub files: Option<Vec<Glob>>,
-}
+pub(crate) struct TotalPatternOccurences;
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct TotalPatternOccurencesValue {
@@ -25,13 +22,13 @@ pub struct TotalPatternOccurencesValue {
 
 #[derive(Error, Debug)]
 pub enum TotalPatternOccurencesError {
-    #[error("{0}")]
+    #[error(transparent)]
     Lookup(#[from] LookupError),
 
-    #[error("{0}")]
+    #[error(transparent)]
     Cast(#[from] CollectorValueCastError),
 
-    #[error("{0}")]
+    #[error(transparent)]
     TryFromIntError(#[from] std::num::TryFromIntError),
 }
 
@@ -45,20 +42,9 @@ impl DerivedCollector for TotalPatternOccurences {
         graph: &CollectionExecutionGraph,
         current_node_idx: NodeIndex,
     ) -> Result<CollectorValue, TotalPatternOccurencesError> {
-        let pattern_occurences_value: PatternOccurencesValue = get_value_of_preceeding_node(
-            storage,
-            graph,
-            current_node_idx,
-            |e| e.distance == 0,
-            |n| {
-                n.collector_config
-                    == CollectorConfig::PatternOccurences {
-                        pattern: self.pattern.clone(),
-                        files: self.files.clone(),
-                    }
-            },
-        )?
-        .try_into()?;
+        let pattern_occurences_value: PatternOccurencesValue =
+            get_dependency_value(storage, graph, current_node_idx, &DependencyKey::Source)?
+                .try_into()?;
 
         let value = TotalPatternOccurencesValue {
             total_occurences: u32::try_from(pattern_occurences_value.matches.len())?,
@@ -0,0 +1,52 @@
+use dashmap::DashMap;
+use petgraph::graph::NodeIndex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    config::CollectorConfig,
+    git::{CommitHash, GitError, WorktreeHandle},
+    graph::CollectionExecutionGraph,
+};
+
+use super::{BaseCollector, CollectorValue};
+
+#[derive(Debug)]
+pub(crate) struct TotalSymlinksAndSubmodules;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TotalSymlinksAndSubmodulesValue {
+    pub total_symlinks: u32,
+    pub total_submodules: u32,
+}
+
+#[derive(Error, Debug)]
+pub enum TotalSymlinksAndSubmodulesError {
+    #[error(transparent)]
+    Git(#[from] GitError),
+
+    #[error(transparent)]
+    TryFromIntError(#[from] std::num::TryFromIntError),
+}
+
+impl BaseCollector for TotalSymlinksAndSubmodules {
+    type Error = TotalSymlinksAndSubmodulesError;
+
+    #[tracing::instrument(level = "trace", skip_all)]
+    fn collect(
+        &self,
+        _storage: &DashMap<(CollectorConfig, CommitHash), CollectorValue>,
+        repo: &mut WorktreeHandle,
+        _graph: &CollectionExecutionGraph,
+        _current_node_idx: NodeIndex,
+    ) -> Result<CollectorValue, TotalSymlinksAndSubmodulesError> {
+        let (symlinks, submodules) = repo.count_symlinks_and_submodules()?;
+
+        let value = TotalSymlinksAndSubmodulesValue {
+            total_symlinks: u32::try_from(symlinks)?,
+            total_submodules: u32::try_from(submodules)?,
+        };
+
+        Ok(value.into())
+    }
+}
@@ -0,0 +1,242 @@
+use std::{
+    collections::HashSet,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use dashmap::DashMap;
+use globset::{Candidate, GlobBuilder, GlobSet, GlobSetBuilder};
+use petgraph::graph::NodeIndex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::warn;
+
+use crate::{
+    config::CollectorConfig,
+    git::{CommitHash, GitError, WorktreeHandle},
+    graph::CollectionExecutionGraph,
+};
+
+use super::{
+    changed_files::ChangedFilesValue,
+    registry::DependencyKey,
+    utils::{get_dependency_value, get_previous_commit_value_of_collector, LookupError},
+    BaseCollector, CollectorValue, CollectorValueCastError,
+};
+
+#[derive(Deserialize, Debug)]
+struct CargoWorkspace {
+    #[serde(default)]
+    members: Vec<String>,
+
+    #[serde(default)]
+    exclude: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct CargoToml {
+    workspace: Option<CargoWorkspace>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum NpmWorkspaces {
+    List(Vec<String>),
+    Object {
+        #[serde(default)]
+        packages: Vec<String>,
+    },
+}
+
+#[derive(Deserialize, Debug)]
+struct PackageJson {
+    workspaces: Option<NpmWorkspaces>,
+}
+
+#[derive(Debug)]
+pub(crate) struct TotalWorkspaceMembers;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TotalWorkspaceMembersValue {
+    pub cargo_members: u32,
+    pub npm_members: u32,
+    pub total_members: u32,
+}
+
+#[derive(Error, Debug)]
+pub enum TotalWorkspaceMembersError {
+    #[error(transparent)]
+    Lookup(#[from] LookupError),
+
+    #[error(transparent)]
+    Cast(#[from] CollectorValueCastError),
+
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Git(#[from] GitError),
+
+    #[error(transparent)]
+    Glob(#[from] globset::Error),
+
+    #[error(transparent)]
+    TryFromIntError(#[from] std::num::TryFromIntError),
+}
+
+fn read_to_string(
+    repo: &WorktreeHandle,
+    path: &Path,
+) -> Result<Option<String>, TotalWorkspaceMembersError> {
+    let Some(mut reader) = repo.read_file(path)? else {
+        return Ok(None);
+    };
+    let mut contents = String::new();
+    reader.read_to_string(&mut contents)?;
+    Ok(Some(contents))
+}
+
+fn build_globset(base: &Path, patterns: &[String]) -> Result<GlobSet, globset::Error> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let pattern = base.join(pattern.trim_start_matches("./"));
+        let glob = GlobBuilder::new(&pattern.to_string_lossy())
+            .literal_separator(true)
+            .build()?;
+        builder.add(glob);
+    }
+    builder.build()
+}
+
+/// Counts the package directories matched by the member patterns of a workspace manifest
+/// located in `base`.
+fn count_members(
+    package_dirs: &HashSet<PathBuf>,
+    base: &Path,
+    members: &[String],
+    exclude: &[String],
+) -> Result<usize, globset::Error> {
+    let members = build_globset(base, members)?;
+    let exclude = build_globset(base, exclude)?;
+
+    Ok(package_dirs
+        .iter()
+        .map(Candidate::new)
+        .filter(|dir| members.is_match_candidate(dir) && !exclude.is_match_candidate(dir))
+        .count())
+}
+
+impl BaseCollector for TotalWorkspaceMembers {
+    type Error = TotalWorkspaceMembersError;
+
+    #[tracing::instrument(level = "trace", skip_all)]
+    fn collect(
+        &self,
+        storage: &DashMap<(CollectorConfig, CommitHash), CollectorValue>,
+        repo: &mut WorktreeHandle,
+        graph: &CollectionExecutionGraph,
+        current_node_idx: NodeIndex,
+    ) -> Result<CollectorValue, TotalWorkspaceMembersError> {
+        let changed_files_in_current_commit_value: ChangedFilesValue = get_dependency_value(
+            storage,
+            graph,
+            current_node_idx,
+            &DependencyKey::ChangedFiles,
+        )?
+        .try_into()?;
+
+        let has_modified_manifest =
+            changed_files_in_current_commit_value
+                .files
+                .iter()
+                .any(|relative_path| {
+                    let path = Path::new(relative_path);
+                    path.ends_with("Cargo.toml") || path.ends_with("package.json")
+                });
+
+        if !has_modified_manifest {
+            let previous_commit_value =
+                get_previous_commit_value_of_collector(storage, graph, current_node_idx);
+
+            if let Some(previous_commit_value) = previous_commit_value {
+                return Ok(previous_commit_value);
+            }
+        }
+
+        let mut cargo_package_dirs: HashSet<PathBuf> = HashSet::new();
+        let mut npm_package_dirs: HashSet<PathBuf> = HashSet::new();
+
+        for relative_path in repo.walk_tracked_files(None)? {
+            let path = Path::new(&relative_path);
+            let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+
+            if path.ends_with("Cargo.toml") {
+                cargo_package_dirs.insert(dir);
+            } else if path.ends_with("package.json") {
+                npm_package_dirs.insert(dir);
+            }
+        }
+
+        let mut cargo_members = 0_usize;
+        for dir in &cargo_package_dirs {
+            let path = dir.join("Cargo.toml");
+            let Some(contents) = read_to_string(repo, &path)? else {
+                continue;
+            };
+            let cargo_toml: CargoToml = match toml::from_str(&contents) {
+                Ok(cargo_toml) => cargo_toml,
+                Err(e) => {
+                    warn!("Skipping invalid manifest {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+
+            if let Some(workspace) = cargo_toml.workspace {
+                cargo_members += count_members(
+                    &cargo_package_dirs,
+                    dir,
+                    &workspace.members,
+                    &workspace.exclude,
+                )?;
+            }
+        }
+
+        let mut npm_members = 0_usize;
+        for dir in &npm_package_dirs {
+            let path = dir.join("package.json");
+            let Some(contents) = read_to_string(repo, &path)? else {
+                continue;
+            };
+            let package_json: PackageJson = match serde_json::from_str(&contents) {
+                Ok(package_json) => package_json,
+                Err(e) => {
+                    warn!("Skipping invalid manifest {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+
+            let Some(NpmWorkspaces::List(patterns) | NpmWorkspaces::Object { packages: patterns }) =
+                package_json.workspaces
+            else {
+                continue;
+            };
+
+            let (exclude, include): (Vec<String>, Vec<String>) =
+                patterns.into_iter().partition(|p| p.starts_with('!'));
+            let exclude: Vec<String> = exclude
+                .into_iter()
+                .map(|p| p.trim_start_matches('!').to_string())
+                .collect();
+
+            npm_members += count_members(&npm_package_dirs, dir, &include, &exclude)?;
+        }
+
+        let value = TotalWorkspaceMembersValue {
+            cargo_members: u32::try_from(cargo_members)?,
+            npm_members: u32::try_from(npm_members)?,
+            total_members: u32::try_from(cargo_members + npm_members)?,
+        };
+
+        Ok(value.into())
+    }
+}
@@ -1,5 +1,8 @@
 use dashmap::DashMap;
-use petgraph::graph::{EdgeIndex, NodeIndex};
+use petgraph::{
+    graph::{EdgeIndex, NodeIndex},
+    visit::EdgeRef,
+};
 use thiserror::Error;
 
 use crate::{
@@ -8,7 +11,10 @@ use crate::{
     graph::{CollectionExecutionGraph, CollectionGraphEdge, CollectionTask},
 };
 
-use super::CollectorValue;
+use super::{
+    registry::{dependencies, Dependency, DependencyKey},
+    CollectorValue,
+};
 
 fn find_incoming_edges<F: Fn(&CollectionGraphEdge) -> bool>(
     graph: &CollectionExecutionGraph,
@@ -65,6 +71,16 @@ pub fn get_previous_commit_value_of_collector(
     graph: &CollectionExecutionGraph,
     current_node_idx: NodeIndex,
 ) -> Option<CollectorValue> {
+    get_previous_commit_and_value_of_collector(storage, graph, current_node_idx)
+        .map(|(_, value)| value)
+}
+
+/// Like [`get_previous_commit_value_of_collector`], along with the previous commit
+pub fn get_previous_commit_and_value_of_collector(
+    storage: &DashMap<(CollectorConfig, CommitHash), CollectorValue>,
+    graph: &CollectionExecutionGraph,
+    current_node_idx: NodeIndex,
+) -> Option<(CommitHash, CollectorValue)> {
     let current_node = &graph.graph[current_node_idx];
 
     let previous_node_index = find_preceding_node(
@@ -81,13 +97,16 @@ pub fn get_previous_commit_value_of_collector(
         previous_node.commit_hash.clone(),
     ));
 
-    value.map(|v| v.clone())
+    value
+        .map(|v| v.clone())
+        .filter(|v| !matches!(v, CollectorValue::Skipped(_)))
+        .map(|v| (previous_node.commit_hash.clone(), v))
 }
 
 #[derive(Error, Debug)]
 #[error("Could not read required value from storage for node {task_idx:?}")]
 pub struct LookupError {
-    task_idx: NodeIndex,
+    pub(crate) task_idx: NodeIndex,
 }
 
 pub fn get_value_of_preceeding_node<
@@ -113,3 +132,106 @@ pub fn get_value_of_preceeding_node<
 
     Ok(task_value.clone())
 }
+
+fn declared_dependency(
+    graph: &CollectionExecutionGraph,
+    current_node_idx: NodeIndex,
+    key: &DependencyKey,
+) -> Dependency {
+    let current_node = &graph.graph[current_node_idx];
+
+    dependencies(&current_node.collector_config)
+        .into_iter()
+        .find(|dependency| &dependency.key == key)
+        .unwrap_or_else(|| {
+            panic!(
+                "Collector {:?} does not declare a dependency {key:?}",
+                current_node.collector_config
+            )
+        })
+}
+
+/// Value of a dependency of the collector of the current task at the same commit, as declared in
+/// [`super::registry::dependencies`]
+pub fn get_dependency_value(
+    storage: &DashMap<(CollectorConfig, CommitHash), CollectorValue>,
+    graph: &CollectionExecutionGraph,
+    current_node_idx: NodeIndex,
+    key: &DependencyKey,
+) -> Result<CollectorValue, LookupError> {
+    let dependency = declared_dependency(graph, current_node_idx, key);
+
+    get_value_of_preceeding_node(
+        storage,
+        graph,
+        current_node_idx,
+        |e| e.offset == 0,
+        |n| n.collector_config == dependency.collector_config,
+    )
+}
+
+/// Tasks of a dependency of the collector of the current task at the previously sampled commits
+/// within its declared window, the most recent one first
+fn dependency_window_tasks(
+    graph: &CollectionExecutionGraph,
+    current_node_idx: NodeIndex,
+    key: &DependencyKey,
+) -> Vec<NodeIndex> {
+    let dependency = declared_dependency(graph, current_node_idx, key);
+
+    let mut window_tasks: Vec<(usize, NodeIndex)> = graph
+        .graph
+        .edges_directed(current_node_idx, petgraph::Direction::Incoming)
+        .filter(|edge| (1..=dependency.window).contains(&edge.weight().offset))
+        .filter(|edge| graph.graph[edge.source()].collector_config == dependency.collector_config)
+        .map(|edge| (edge.weight().offset, edge.source()))
+        .collect();
+
+    window_tasks.sort_by_key(|(offset, _)| *offset);
+
+    window_tasks
+        .into_iter()
+        .map(|(_, task_idx)| task_idx)
+        .collect()
+}
+
+/// Values of a dependency of the collector of the current task at the previously sampled commits
+/// within its declared window, the most recent one first. Commits before the first sampled one are
+/// missing, so there are fewer values at the start of the history.
+pub fn get_dependency_window_values(
+    storage: &DashMap<(CollectorConfig, CommitHash), CollectorValue>,
+    graph: &CollectionExecutionGraph,
+    current_node_idx: NodeIndex,
+    key: &DependencyKey,
+) -> Result<Vec<CollectorValue>, LookupError> {
+    dependency_window_tasks(graph, current_node_idx, key)
+        .into_iter()
+        .map(|task_idx| {
+            let task = &graph.graph[task_idx];
+
+            storage
+                .get(&(task.collector_config.clone(), task.commit_hash.clone()))
+                .map(|value| value.clone())
+                .ok_or(LookupError { task_idx })
+        })
+        .collect()
+}
+
+/// Previously sampled commits within the declared window of a dependency of the collector of the
+/// current task, the most recent one first, for collectors looking at the commits themselves
+/// rather than the values of the dependency
+pub fn get_dependency_window_commits<'a>(
+    graph: &'a CollectionExecutionGraph,
+    current_node_idx: NodeIndex,
+    key: &DependencyKey,
+) -> Vec<&'a CommitHash> {
+    dependency_window_tasks(graph, current_node_idx, key)
+        .into_iter()
+        .map(|task_idx| &graph.graph[task_idx].commit_hash)
+        .collect()
+}
+
+/// Files are treated as binary if their first bytes contain a NUL byte, like git does
+pub fn is_binary(contents: &[u8]) -> bool {
+    contents[..contents.len().min(8000)].contains(&0)
+}
@@ -1,35 +1,251 @@
-use globset::Glob;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::Path;
+
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use globset::{Candidate, Glob, GlobSet, GlobSetBuilder};
+use grep::regex::RegexMatcher;
 use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use thiserror::Error;
+
+use crate::collectors::{expression, registry::transitive_dependencies, ExpressionSyntaxError};
+use crate::git::{CommitHash, CommitInfo, TreeStats};
+use crate::graph::requires_previous_value;
+
+/// Paths hidden from all collectors unless the config sets its own, e.g. dependencies checked into
+/// the repository and generated files
+pub const DEFAULT_EXCLUDE_PATHS: [&str; 8] = [
+    "vendor/**",
+    "**/node_modules/**",
+    "target/**",
+    "**/*.min.js",
+    "**/*.min.css",
+    "**/*.pb.go",
+    "**/*_pb2.py",
+    "**/*.generated.*",
+];
+
+/// Restricts a collector to the files whose path relative to the repository root matches any of
+/// the `include` globs (all files if unset) and none of the `exclude` globs.
+///
+/// Unset globs are not serialized, so unrestricted collectors keep their cache keys. Boxed inside
+/// `CollectorConfig` to keep the errors and progress events containing it small.
+#[derive(PartialEq, Eq, Hash, Clone, Serialize, Deserialize, Debug, Default)]
+pub struct FileScope {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include: Option<Vec<Glob>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exclude: Option<Vec<Glob>>,
+}
+
+impl FileScope {
+    #[must_use]
+    pub fn is_unrestricted(&self) -> bool {
+        self.include.is_none() && self.exclude.is_none()
+    }
+
+    pub(crate) fn matcher(&self) -> Result<FileScopeMatcher, globset::Error> {
+        Ok(FileScopeMatcher {
+            include: self.include.as_deref().map(build_globset).transpose()?,
+            exclude: build_globset(self.exclude.as_deref().unwrap_or_default())?,
+        })
+    }
+
+    fn validate(&self, errors: &mut Vec<MetricConfigError>) {
+        for (field, globs) in [("include", &self.include), ("exclude", &self.exclude)] {
+            if let Some(Err(source)) = globs.as_deref().map(build_globset) {
+                errors.push(MetricConfigError::Glob { field, source });
+            }
+        }
+    }
+}
+
+fn build_globset(globs: &[Glob]) -> Result<GlobSet, globset::Error> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(glob.clone());
+    }
+    builder.build()
+}
+
+pub(crate) struct FileScopeMatcher {
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+}
+
+impl FileScopeMatcher {
+    pub(crate) fn is_match(&self, path: impl AsRef<Path>) -> bool {
+        let candidate = Candidate::new(path.as_ref());
+
+        self.include
+            .as_ref()
+            .is_none_or(|include| include.is_match_candidate(&candidate))
+            && !self.exclude.is_match_candidate(&candidate)
+    }
+}
 
 #[derive(PartialEq, Eq, Hash, Clone, Serialize, Deserialize, Debug)]
 #[serde(tag = "collector")]
+#[non_exhaustive]
 pub enum CollectorConfig {
     #[serde(rename = "total-loc")]
-    TotalLoc,
+    TotalLoc {
+        #[serde(flatten)]
+        scope: Box<FileScope>,
+    },
     #[serde(rename = "loc")]
-    Loc,
+    Loc {
+        #[serde(flatten)]
+        scope: Box<FileScope>,
+    },
     #[serde(rename = "total-diff-stat")]
-    TotalDiffStat,
+    TotalDiffStat {
+        #[serde(flatten)]
+        scope: Box<FileScope>,
+    },
     #[serde(rename = "total-cargo-deps")]
     TotalCargoDeps,
+    #[serde(rename = "total-cargo-features")]
+    TotalCargoFeatures,
     #[serde(rename = "total-pattern-occurences")]
     TotalPatternOccurences {
         pattern: String,
         files: Option<Vec<Glob>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        redact: Option<MatchRedaction>,
+        #[serde(flatten)]
+        scope: Box<FileScope>,
     },
     #[serde(rename = "pattern-occurences")]
     PatternOccurences {
         pattern: String,
         files: Option<Vec<Glob>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        redact: Option<MatchRedaction>,
+        #[serde(flatten)]
+        scope: Box<FileScope>,
     },
     #[serde(rename = "changed-files")]
     ChangedFiles,
     #[serde(rename = "file-list")]
-    FileList,
+    FileList {
+        #[serde(flatten)]
+        scope: Box<FileScope>,
+    },
     #[serde(rename = "total-file-count")]
-    TotalFileCount,
+    TotalFileCount {
+        #[serde(flatten)]
+        scope: Box<FileScope>,
+    },
     #[serde(rename = "changed-files-loc")]
     ChangedFilesLoc,
+    #[serde(rename = "total-workspace-members")]
+    TotalWorkspaceMembers,
+    #[serde(rename = "ci-workflows")]
+    CiWorkflows,
+    #[serde(rename = "container-config")]
+    ContainerConfig,
+    #[serde(rename = "total-symlinks-and-submodules")]
+    TotalSymlinksAndSubmodules,
+    #[serde(rename = "coverage-report")]
+    CoverageReport,
+    #[serde(rename = "file-length")]
+    FileLength,
+    #[serde(rename = "cumulative-diff-stat")]
+    CumulativeDiffStat {
+        #[serde(flatten)]
+        scope: Box<FileScope>,
+    },
+    #[serde(rename = "rolling-diff-stat")]
+    RollingDiffStat {
+        /// Number of sampled commits, including the current one, whose diff stats are averaged
+        window: usize,
+        #[serde(flatten)]
+        scope: Box<FileScope>,
+    },
+    #[serde(rename = "build-warnings")]
+    BuildWarnings { command: Option<Vec<String>> },
+    #[serde(rename = "formatting-changes")]
+    FormattingChanges,
+    #[serde(rename = "import-coupling")]
+    ImportCoupling,
+    #[serde(rename = "config-files")]
+    ConfigFiles { files: Option<Vec<Glob>> },
+    #[serde(rename = "shellcheck")]
+    Shellcheck,
+    #[serde(rename = "cognitive-complexity")]
+    CognitiveComplexity,
+    #[serde(rename = "comment-density")]
+    CommentDensity,
+    #[serde(rename = "maintainability-index")]
+    MaintainabilityIndex,
+    #[serde(rename = "expression")]
+    Expression {
+        expression: String,
+
+        /// Collectors of the metrics the expression refers to, filled in from the metrics of the
+        /// config when the collection is initialized
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        operands: BTreeMap<String, CollectorConfig>,
+    },
+    #[serde(rename = "author-domain")]
+    AuthorDomain,
+    #[serde(rename = "author-domains")]
+    AuthorDomains {
+        /// Number of sampled commits, including the current one, whose authors are counted together
+        /// with the ones of the commits skipped before them
+        window: usize,
+    },
+    #[serde(rename = "secrets")]
+    Secrets {
+        #[serde(flatten)]
+        scope: Box<FileScope>,
+    },
+    #[serde(rename = "high-entropy-strings")]
+    HighEntropyStrings {
+        #[serde(flatten)]
+        scope: Box<FileScope>,
+    },
+}
+
+impl CollectorConfig {
+    /// Stable identifier of the config, the hex encoded SHA-1 hash of its JSON representation
+    /// which is also used as the cache key
+    ///
+    /// # Panics
+    ///
+    /// Never, all collector configs can be serialized
+    #[must_use]
+    pub fn id(&self) -> String {
+        let json = serde_json::to_string(self).expect("Collector configs are always serializable");
+
+        let mut hasher = Sha1::new();
+        hasher.update(json);
+        format!("{:x}", hasher.finalize())
+    }
+}
+
+/// How the matched text of pattern occurences is kept out of the cache and outputs, e.g. when
+/// scanning for credentials. Paths and locations of the matches are kept either way.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum MatchRedaction {
+    /// Replaced by the hex encoded SHA-1 hash of the text, so identical matches can still be
+    /// recognized
+    Hash,
+
+    /// Replaced by an empty string
+    Omit,
+}
+
+impl MatchRedaction {
+    #[must_use]
+    pub fn redact(self, text: &str) -> String {
+        match self {
+            MatchRedaction::Hash => format!("{:x}", Sha1::digest(text)),
+            MatchRedaction::Omit => String::new(),
+        }
+    }
 }
 
 #[derive(PartialEq, Eq, Hash, Clone, Serialize, Deserialize, Debug)]
@@ -41,6 +257,49 @@ pub enum Frequency {
     Weekly,
     Daily,
     Hourly,
+
+    /// Only the latest commit, e.g. for scheduled snapshots of the current values. If all metrics
+    /// use it, the history is not walked at all.
+    LatestOnly,
+
+    /// Samples the first commit after every time (UTC) matching the cron expression, e.g.
+    /// `0 9 * * Tue` for the start of sprints. Expressions with five fields start at minutes,
+    /// six or seven fields at seconds.
+    Cron(String),
+}
+
+impl Frequency {
+    /// Schedule of a `cron` frequency, `None` for the others
+    pub(crate) fn cron_schedule(&self) -> Option<Result<cron::Schedule, cron::error::Error>> {
+        let Frequency::Cron(expression) = self else {
+            return None;
+        };
+
+        let expression = if expression.split_whitespace().count() == 5 {
+            format!("0 {expression}")
+        } else {
+            expression.clone()
+        };
+
+        Some(expression.parse())
+    }
+}
+
+impl std::fmt::Display for Frequency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Frequency::Cron(expression) => return write!(f, "cron({expression})"),
+            Frequency::PerCommit => "per-commit",
+            Frequency::Yearly => "yearly",
+            Frequency::Monthly => "monthly",
+            Frequency::Weekly => "weekly",
+            Frequency::Daily => "daily",
+            Frequency::Hourly => "hourly",
+            Frequency::LatestOnly => "latest-only",
+        };
+
+        write!(f, "{name}")
+    }
 }
 
 #[derive(PartialEq, Eq, Hash, Clone, Serialize, Deserialize, Debug)]
@@ -48,10 +307,1059 @@ pub struct MetricConfig {
     #[serde(flatten)]
     pub collector: CollectorConfig,
     pub frequency: Frequency,
+
+    /// Branch the metric is collected from instead of the branch of the reference
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+
+    /// Disabled for collectors whose values can change while the commit doesn't, e.g. versions
+    /// of external tools, so they are collected again in every run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache: Option<bool>,
+
+    /// Bounds of the latest value, checked by [`crate::PostCollection::threshold_violations`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thresholds: Option<Thresholds>,
+
+    /// Target the metric is burned down towards, projected by
+    /// [`crate::PostCollection::campaign_projections`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub campaign: Option<Campaign>,
+
+    /// Samples the first commit of every calendar period, e.g. ISO week, instead of comparing each
+    /// commit with the previously sampled one, see [`crate::sample_commits`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub align_to_calendar: Option<bool>,
+
+    /// Shifts the start of the periods of the frequency, e.g. to sample monthly metrics mid-month
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phase_offset: Option<PhaseOffset>,
+
+    /// Maximum number of tasks of the collector running at the same time, e.g. for collectors
+    /// building the project, while all others still run in parallel
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_concurrency: Option<usize>,
+}
+
+/// Time by which the periods of a frequency start later than the calendar ones, e.g. six days to
+/// start weeks on Sunday instead of Monday
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Default, Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct PhaseOffset {
+    #[serde(default)]
+    pub days: u32,
+
+    #[serde(default)]
+    pub hours: u32,
+}
+
+impl PhaseOffset {
+    pub(crate) fn duration(self) -> TimeDelta {
+        TimeDelta::days(i64::from(self.days)) + TimeDelta::hours(i64::from(self.hours))
+    }
+}
+
+/// Thresholds of a metric, e.g. to fail a CI run on a regression and only warn about smaller ones
+#[derive(PartialEq, Eq, Hash, Clone, Default, Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Thresholds {
+    /// Field the thresholds apply to, required for values with several numbers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warn: Option<Threshold>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fail: Option<Threshold>,
+}
+
+/// Bounds of the latest value of a metric, either absolute or relative to the value of the
+/// previous sampled commit
+#[derive(PartialEq, Clone, Copy, Default, Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Threshold {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+
+    /// Maximum increase over the previous value in percent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_increase: Option<f64>,
+
+    /// Maximum decrease below the previous value in percent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_decrease: Option<f64>,
+
+    /// Minimum decrease in percent below the value of the last sampled commit at least a month
+    /// earlier, e.g. to enforce the progress of a cleanup. Values of zero always satisfy it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_monthly_decrease: Option<f64>,
+}
+
+impl Threshold {
+    fn bounds(&self) -> [Option<f64>; 5] {
+        [
+            self.min,
+            self.max,
+            self.max_increase,
+            self.max_decrease,
+            self.min_monthly_decrease,
+        ]
+    }
+}
+
+// NaN bounds are rejected by `MetricConfig::validate`
+impl Eq for Threshold {}
+
+impl std::hash::Hash for Threshold {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        for bound in self.bounds() {
+            bound.map(f64::to_bits).hash(state);
+        }
+    }
+}
+
+/// Campaign moving a metric towards a target, e.g. the removal of a deprecated API, whose
+/// completion date is projected from the recent trend of the metric
+#[derive(PartialEq, Clone, Default, Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Campaign {
+    /// Field the campaign tracks, required for values with several numbers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+
+    /// Value the campaign aims for, zero by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<f64>,
+
+    /// Number of latest sampled commits the trend is fitted to, 10 by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window: Option<usize>,
+}
+
+impl Campaign {
+    fn validate(&self, errors: &mut Vec<MetricConfigError>) {
+        if self.target.is_some_and(|target| !target.is_finite()) {
+            errors.push(MetricConfigError::Campaign(
+                "target has to be a finite number",
+            ));
+        }
+
+        if self.window.is_some_and(|window| window < 2) {
+            errors.push(MetricConfigError::Campaign(
+                "window has to span at least two commits",
+            ));
+        }
+    }
+}
+
+// NaN targets are rejected by `MetricConfig::validate`
+impl Eq for Campaign {}
+
+impl std::hash::Hash for Campaign {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.field.hash(state);
+        self.target.map(f64::to_bits).hash(state);
+        self.window.hash(state);
+    }
+}
+
+/// Problem of a metric config which would otherwise only surface once its collector runs
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum MetricConfigError {
+    #[error("Invalid pattern")]
+    Pattern(#[source] grep::regex::Error),
+
+    #[error("Invalid globs")]
+    Glob {
+        field: &'static str,
+        #[source]
+        source: globset::Error,
+    },
+
+    #[error("Build command must not be empty")]
+    EmptyCommand,
+
+    #[error("Totals only include the sampled commits unless collected per-commit")]
+    IncompleteTotals,
+
+    #[error("Window must contain at least one commit")]
+    EmptyWindow,
+
+    #[error("Invalid cron expression")]
+    Cron(#[source] cron::error::Error),
+
+    #[error("Maximum concurrency must allow at least one task")]
+    ZeroConcurrency,
+
+    #[error("Phase offset only applies to calendar frequencies, not to {0}")]
+    UnusedPhaseOffset(Frequency),
+
+    #[error(
+        "Collector is shared with metric {metric} sampled {sampling}, so the values at the \
+        commits of either are written for both"
+    )]
+    SharedCollector { metric: String, sampling: String },
+
+    #[error(
+        "Metric {metric} sampled {sampling} depends on this collector, so their tasks overlap and \
+        the values at its commits are written for this metric as well"
+    )]
+    MismatchedDependency { metric: String, sampling: String },
+
+    #[error("Invalid expression")]
+    Expression(#[source] ExpressionSyntaxError),
+
+    #[error("Expression refers to unknown metric {0}")]
+    UnknownOperand(String),
+
+    #[error("Expression of metric {0} refers to itself")]
+    CyclicExpression(String),
+
+    #[error("Invalid thresholds: {0}")]
+    Thresholds(&'static str),
+
+    #[error("Invalid campaign: {0}")]
+    Campaign(&'static str),
+}
+
+impl MetricConfigError {
+    /// Option of the metric config the problem was found in
+    #[must_use]
+    pub fn field(&self) -> &'static str {
+        match self {
+            MetricConfigError::Pattern(_) => "pattern",
+            MetricConfigError::Glob { field, .. } => field,
+            MetricConfigError::EmptyCommand => "command",
+            MetricConfigError::IncompleteTotals
+            | MetricConfigError::Cron(_)
+            | MetricConfigError::SharedCollector { .. }
+            | MetricConfigError::MismatchedDependency { .. } => "frequency",
+            MetricConfigError::EmptyWindow => "window",
+            MetricConfigError::ZeroConcurrency => "max_concurrency",
+            MetricConfigError::UnusedPhaseOffset(_) => "phase_offset",
+            MetricConfigError::Expression(_)
+            | MetricConfigError::UnknownOperand(_)
+            | MetricConfigError::CyclicExpression(_) => "expression",
+            MetricConfigError::Thresholds(_) => "thresholds",
+            MetricConfigError::Campaign(_) => "campaign",
+        }
+    }
+
+    /// Whether the metric can still be collected, but its values might not be what was intended
+    #[must_use]
+    pub fn is_warning(&self) -> bool {
+        matches!(
+            self,
+            MetricConfigError::IncompleteTotals
+                | MetricConfigError::UnusedPhaseOffset(_)
+                | MetricConfigError::SharedCollector { .. }
+                | MetricConfigError::MismatchedDependency { .. }
+        )
+    }
+}
+
+impl MetricConfig {
+    /// Whether values of the metric are read from and written to the cache
+    #[must_use]
+    pub fn is_cached(&self) -> bool {
+        self.cache != Some(false)
+    }
+
+    /// Checks the options of the metric which can't be checked while deserializing it, e.g.
+    /// whether its pattern compiles
+    #[must_use]
+    pub fn validate(&self) -> Vec<MetricConfigError> {
+        let mut errors = vec![];
+
+        match &self.collector {
+            CollectorConfig::TotalPatternOccurences {
+                pattern,
+                files,
+                scope,
+                ..
+            }
+            | CollectorConfig::PatternOccurences {
+                pattern,
+                files,
+                scope,
+                ..
+            } => {
+                if let Err(e) = RegexMatcher::new(pattern) {
+                    errors.push(MetricConfigError::Pattern(e));
+                }
+
+                if let Some(Err(source)) = files.as_deref().map(build_globset) {
+                    errors.push(MetricConfigError::Glob {
+                        field: "files",
+                        source,
+                    });
+                }
+
+                scope.validate(&mut errors);
+            }
+            CollectorConfig::TotalLoc { scope }
+            | CollectorConfig::Loc { scope }
+            | CollectorConfig::TotalDiffStat { scope }
+            | CollectorConfig::FileList { scope }
+            | CollectorConfig::TotalFileCount { scope }
+            | CollectorConfig::CumulativeDiffStat { scope }
+            | CollectorConfig::Secrets { scope }
+            | CollectorConfig::HighEntropyStrings { scope } => scope.validate(&mut errors),
+            CollectorConfig::RollingDiffStat { window, scope } => {
+                if *window == 0 {
+                    errors.push(MetricConfigError::EmptyWindow);
+                }
+
+                scope.validate(&mut errors);
+            }
+            CollectorConfig::AuthorDomains { window } => {
+                if *window == 0 {
+                    errors.push(MetricConfigError::EmptyWindow);
+                }
+            }
+            CollectorConfig::ConfigFiles { files: Some(files) } => {
+                if let Err(source) = build_globset(files) {
+                    errors.push(MetricConfigError::Glob {
+                        field: "files",
+                        source,
+                    });
+                }
+            }
+            CollectorConfig::BuildWarnings {
+                command: Some(command),
+            } if command.is_empty() => errors.push(MetricConfigError::EmptyCommand),
+            CollectorConfig::Expression { expression, .. } => {
+                if let Err(e) = expression::validate(expression) {
+                    errors.push(MetricConfigError::Expression(e));
+                }
+            }
+            _ => {}
+        }
+
+        if let Some(Err(e)) = self.frequency.cron_schedule() {
+            errors.push(MetricConfigError::Cron(e));
+        }
+
+        if self.max_concurrency == Some(0) {
+            errors.push(MetricConfigError::ZeroConcurrency);
+        }
+
+        if self.phase_offset.is_some()
+            && matches!(
+                self.frequency,
+                Frequency::PerCommit | Frequency::LatestOnly | Frequency::Cron(_)
+            )
+        {
+            errors.push(MetricConfigError::UnusedPhaseOffset(self.frequency.clone()));
+        }
+
+        if requires_previous_value(&self.collector) && self.frequency != Frequency::PerCommit {
+            errors.push(MetricConfigError::IncompleteTotals);
+        }
+
+        if let Some(thresholds) = &self.thresholds {
+            thresholds.validate(&mut errors);
+        }
+
+        if let Some(campaign) = &self.campaign {
+            campaign.validate(&mut errors);
+        }
+
+        errors
+    }
+
+    /// Options deciding at which commits the metric is collected, compared between metrics
+    fn sampling(&self) -> (&Frequency, bool, PhaseOffset) {
+        (
+            &self.frequency,
+            self.align_to_calendar.unwrap_or(false),
+            self.phase_offset.unwrap_or_default(),
+        )
+    }
+
+    /// Sampling of the metric as described in warnings, e.g. `weekly aligned to the calendar`
+    fn describe_sampling(&self) -> String {
+        let mut description = self.frequency.to_string();
+
+        if self.align_to_calendar == Some(true) {
+            description.push_str(" aligned to the calendar");
+        }
+
+        if let Some(offset) = self.phase_offset {
+            description.push_str(&format!(
+                " offset by {} days and {} hours",
+                offset.days, offset.hours
+            ));
+        }
+
+        description
+    }
+}
+
+/// Checks the metrics against each other, e.g. whether metrics sharing a collector or depending on
+/// the collector of another metric sample it differently. The problems are returned with the name
+/// of the metric they were found in.
+#[must_use]
+pub fn validate_metrics(
+    metrics: &HashMap<String, MetricConfig>,
+) -> Vec<(String, MetricConfigError)> {
+    // Expressions only depend on the collectors of their operands once these are resolved,
+    // unknown operands are reported when the collection is initialized
+    let mut resolved = metrics.clone();
+    let _ = expression::resolve(&mut resolved);
+
+    let mut metric_names: Vec<&String> = resolved.keys().collect();
+    metric_names.sort();
+
+    let dependencies: HashMap<&String, HashSet<CollectorConfig>> = metric_names
+        .iter()
+        .map(|metric_name| {
+            (
+                *metric_name,
+                transitive_dependencies(&resolved[*metric_name].collector),
+            )
+        })
+        .collect();
+
+    let mut errors = vec![];
+
+    for metric_name in &metric_names {
+        let metric = &resolved[*metric_name];
+
+        for other_name in &metric_names {
+            let other = &resolved[*other_name];
+
+            if other_name == metric_name || other.sampling() == metric.sampling() {
+                continue;
+            }
+
+            if other.collector == metric.collector {
+                errors.push((
+                    (*metric_name).clone(),
+                    MetricConfigError::SharedCollector {
+                        metric: (*other_name).clone(),
+                        sampling: other.describe_sampling(),
+                    },
+                ));
+            } else if dependencies[*other_name].contains(&metric.collector) {
+                errors.push((
+                    (*metric_name).clone(),
+                    MetricConfigError::MismatchedDependency {
+                        metric: (*other_name).clone(),
+                        sampling: other.describe_sampling(),
+                    },
+                ));
+            }
+        }
+    }
+
+    errors
+}
+
+/// Retries of tasks whose collector failed for a likely transient reason, e.g. an IO error or a
+/// crashed tool. Every retry starts from a freshly reset worktree.
+#[derive(PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize, Debug)]
+pub struct RetryPolicy {
+    /// Attempts after the first one, no retries by default
+    pub max_retries: u32,
+}
+
+/// Size limits of the tree of a commit above which collectors that check the commit out are
+/// skipped for it, so pathological commits get a "skipped" value instead of stalling the
+/// collection. No limits by default.
+#[derive(PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize, Debug)]
+pub struct TreeLimits {
+    /// Maximum number of files at the commit
+    pub max_files: Option<usize>,
+    /// Maximum total size of the files at the commit in bytes
+    pub max_bytes: Option<u64>,
+}
+
+impl TreeLimits {
+    #[must_use]
+    pub fn is_unlimited(&self) -> bool {
+        self.max_files.is_none() && self.max_bytes.is_none()
+    }
+
+    #[must_use]
+    pub fn exceeded_by(&self, stats: &TreeStats) -> bool {
+        self.max_files.is_some_and(|max| stats.file_count > max)
+            || self.max_bytes.is_some_and(|max| stats.total_bytes > max)
+    }
+}
+
+impl Thresholds {
+    fn validate(&self, errors: &mut Vec<MetricConfigError>) {
+        for threshold in self.warn.iter().chain(&self.fail) {
+            let bounds = threshold.bounds();
+            if bounds.iter().flatten().any(|bound| !bound.is_finite()) {
+                errors.push(MetricConfigError::Thresholds(
+                    "bounds have to be finite numbers",
+                ));
+            } else if threshold
+                .min
+                .zip(threshold.max)
+                .is_some_and(|(min, max)| min > max)
+            {
+                errors.push(MetricConfigError::Thresholds("min is larger than max"));
+            } else if [
+                threshold.max_increase,
+                threshold.max_decrease,
+                threshold.min_monthly_decrease,
+            ]
+            .iter()
+            .flatten()
+            .any(|bound| *bound < 0.0)
+            {
+                errors.push(MetricConfigError::Thresholds(
+                    "changes are given as positive percentages",
+                ));
+            }
+        }
+    }
+}
+
+/// Commits skipped by specific collectors, keyed by [`CollectorConfig::id`], e.g. because they
+/// contain files with broken encodings or generated files no collector will ever get through.
+#[derive(PartialEq, Eq, Clone, Default, Serialize, Deserialize, Debug)]
+#[serde(transparent)]
+pub struct Quarantine(pub BTreeMap<String, BTreeSet<CommitHash>>);
+
+impl Quarantine {
+    #[must_use]
+    pub fn contains(&self, collector_id: &str, commit_hash: &CommitHash) -> bool {
+        self.0
+            .get(collector_id)
+            .is_some_and(|commits| commits.contains(commit_hash))
+    }
+
+    /// Returns false if the commit was already quarantined for the collector
+    pub fn insert(&mut self, collector_id: String, commit_hash: CommitHash) -> bool {
+        self.0.entry(collector_id).or_default().insert(commit_hash)
+    }
+
+    pub fn extend(&mut self, other: Quarantine) {
+        for (collector_id, commits) in other.0 {
+            self.0.entry(collector_id).or_default().extend(commits);
+        }
+    }
+
+    /// Number of quarantined tasks
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.0.values().map(BTreeSet::len).sum()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct GitRepository {
     pub url: String,
     pub branch: Option<String>,
+
+    /// Only commits made on or after this day (UTC) are collected
+    pub since: Option<NaiveDate>,
+
+    /// Only commits made on or before this day (UTC) are collected
+    pub until: Option<NaiveDate>,
+
+    /// Only the most recent commits up to this number are collected
+    pub max_commits: Option<usize>,
+
+    /// Only commits whose author email matches any of these globs are collected
+    pub include_authors: Option<Vec<Glob>>,
+
+    /// Commits whose author email matches any of these globs are left out, e.g. the ones of bots
+    /// with `*\[bot\]@users.noreply.github.com`
+    pub exclude_authors: Option<Vec<Glob>>,
+
+    /// Whether merge commits are collected along with the others, left out or the only ones
+    pub merge_commits: Option<MergeCommits>,
+
+    /// Only these commits are collected, each of them regardless of the frequency of the metrics,
+    /// e.g. release commits or bisection candidates. Abbreviated hashes select every commit
+    /// starting with them. Metrics with another frequency or calendar alignment are warned about.
+    pub commits: Option<Vec<CommitHash>>,
+
+    /// Commits reachable from this revision, e.g. the tag of the previous release, are not
+    /// collected, like `git log from..to`
+    pub from: Option<String>,
+
+    /// The history is walked from this revision instead of the head of the branch, e.g. the tag
+    /// of a release
+    pub to: Option<String>,
+
+    /// Only commits changing files matching any of these git pathspecs, relative to the
+    /// repository root, are collected, e.g. the directory of a subproject in a monorepo
+    pub paths: Option<Vec<String>>,
+
+    /// Only the first parents of merge commits are followed, so the commits of merged branches
+    /// are not collected. False by default.
+    pub first_parent: Option<bool>,
+
+    /// Repositories are cloned with only this many commits of history, which is deepened as far as
+    /// `since` and `max_commits` require. Without either of them the whole history is fetched
+    /// unless all metrics are latest-only.
+    pub clone_depth: Option<usize>,
+
+    /// Repositories are cloned without the contents of files, which are fetched once a commit
+    /// needs them, e.g. when it is checked out. False by default.
+    pub partial_clone: Option<bool>,
+
+    /// Submodules are checked out recursively together with the commits, so collectors include
+    /// their files unless they are excluded. False by default, leaving their paths empty.
+    pub submodules: Option<bool>,
+}
+
+/// Selection of commits by whether they are merge commits
+#[derive(PartialEq, Eq, Clone, Copy, Default, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum MergeCommits {
+    #[default]
+    Include,
+
+    /// Diffs of merge commits against their first parent contain all changes of the merged
+    /// branch, which distorts diff-based collectors
+    Exclude,
+
+    /// E.g. the merges of pull requests into the main branch
+    Only,
+}
+
+impl MergeCommits {
+    #[must_use]
+    pub fn includes(self, is_merge: bool) -> bool {
+        match self {
+            MergeCommits::Include => true,
+            MergeCommits::Exclude => !is_merge,
+            MergeCommits::Only => is_merge,
+        }
+    }
+}
+
+impl GitRepository {
+    /// Whether a commit made at the given time lies between `since` and `until`
+    #[must_use]
+    pub fn includes_commit_time(&self, time: &DateTime<Utc>) -> bool {
+        let date = time.date_naive();
+
+        self.since.is_none_or(|since| date >= since) && self.until.is_none_or(|until| date <= until)
+    }
+
+    /// Whether an author with the given email passes `include_authors` and `exclude_authors`.
+    /// Authors without an email only pass if no authors are included explicitly.
+    #[must_use]
+    pub fn includes_author(&self, email: Option<&str>) -> bool {
+        let matches = |globs: &[Glob]| {
+            email.is_some_and(|email| {
+                globs
+                    .iter()
+                    .any(|glob| glob.compile_matcher().is_match(email))
+            })
+        };
+
+        self.include_authors.as_deref().is_none_or(matches)
+            && !self.exclude_authors.as_deref().is_some_and(matches)
+    }
+
+    /// Whether a commit is one of the explicitly listed `commits`, if any are listed
+    #[must_use]
+    pub fn includes_commit(&self, commit: &CommitHash) -> bool {
+        self.commits.as_deref().is_none_or(|commits| {
+            commits
+                .iter()
+                .any(|listed| !listed.0.is_empty() && commit.0.starts_with(&listed.0))
+        })
+    }
+
+    /// Pathspecs the collected commits have to change files of, `None` if all commits are
+    /// collected
+    #[must_use]
+    pub fn path_filter(&self) -> Option<&[String]> {
+        self.paths.as_deref().filter(|paths| !paths.is_empty())
+    }
+
+    /// Commits within the date range, of the selected authors, passing `merge_commits` and among
+    /// the listed `commits`, limited to the `max_commits` most recent ones
+    #[must_use]
+    pub fn select_commits(&self, mut commits: Vec<CommitInfo>) -> Vec<CommitInfo> {
+        let merge_commits = self.merge_commits.unwrap_or_default();
+
+        commits.retain(|commit| {
+            self.includes_commit_time(&commit.time)
+                && self.includes_author(commit.author.email.as_deref())
+                && merge_commits.includes(commit.is_merge())
+                && self.includes_commit(&commit.id)
+        });
+
+        if let Some(max_commits) = self.max_commits {
+            if commits.len() > max_commits {
+                commits.sort_by(|a, b| b.time.cmp(&a.time));
+                commits.truncate(max_commits);
+            }
+        }
+
+        commits
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::git::Author;
+
+    use super::*;
+
+    #[test]
+    fn test_metric_config_with_file_scope() {
+        let metric: MetricConfig = toml::from_str(
+            r#"
+            collector = "total-loc"
+            frequency = "monthly"
+            include = ["src/**"]
+            exclude = ["src/generated/**"]
+            "#,
+        )
+        .unwrap();
+
+        let CollectorConfig::TotalLoc { scope } = &metric.collector else {
+            panic!("unexpected collector {:?}", metric.collector);
+        };
+
+        let matcher = scope.matcher().unwrap();
+        assert!(matcher.is_match("src/lib.rs"));
+        assert!(!matcher.is_match("src/generated/schema.rs"));
+        assert!(!matcher.is_match("tests/lib.rs"));
+
+        // The globs are part of the cache key, unrestricted collectors keep their previous one
+        assert_eq!(
+            r#"{"collector":"total-loc","include":["src/**"],"exclude":["src/generated/**"]}"#,
+            serde_json::to_string(&metric.collector).unwrap()
+        );
+        assert_eq!(
+            r#"{"collector":"total-loc"}"#,
+            serde_json::to_string(&CollectorConfig::TotalLoc {
+                scope: Box::default()
+            })
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_metric_config_without_cache() {
+        let metric: MetricConfig = toml::from_str(
+            r#"
+            collector = "total-loc"
+            frequency = "monthly"
+            cache = false
+            "#,
+        )
+        .unwrap();
+
+        assert!(!metric.is_cached());
+
+        // The collector of the metric keeps its cache key
+        assert_eq!(
+            r#"{"collector":"total-loc"}"#,
+            serde_json::to_string(&metric.collector).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_metric_config_validation() {
+        let validate = |config: &str| -> Vec<&'static str> {
+            let metric: MetricConfig = toml::from_str(config).unwrap();
+
+            metric
+                .validate()
+                .iter()
+                .map(|error| error.field())
+                .collect()
+        };
+
+        assert!(validate("collector = \"total-loc\"\nfrequency = \"monthly\"").is_empty());
+        assert_eq!(
+            validate(
+                "collector = \"pattern-occurences\"\npattern = \"todo(\"\nfrequency = \"monthly\""
+            ),
+            ["pattern"]
+        );
+        assert_eq!(
+            validate("collector = \"build-warnings\"\ncommand = []\nfrequency = \"monthly\""),
+            ["command"]
+        );
+        assert_eq!(
+            validate("collector = \"rolling-diff-stat\"\nwindow = 0\nfrequency = \"weekly\""),
+            ["window"]
+        );
+        assert!(
+            validate("collector = \"total-loc\"\nfrequency = { cron = \"0 9 * * Tue\" }")
+                .is_empty()
+        );
+        assert_eq!(
+            validate("collector = \"total-loc\"\nfrequency = { cron = \"0 9 * *\" }"),
+            ["frequency"]
+        );
+        assert_eq!(
+            validate("collector = \"total-loc\"\nfrequency = \"weekly\"\nmax_concurrency = 0"),
+            ["max_concurrency"]
+        );
+        assert!(validate(
+            "collector = \"total-loc\"\nfrequency = \"monthly\"\nphase_offset = { days = 14 }"
+        )
+        .is_empty());
+        assert_eq!(
+            validate("collector = \"total-loc\"\nfrequency = \"per-commit\"\nphase_offset = { hours = 6 }"),
+            ["phase_offset"]
+        );
+
+        let metric: MetricConfig =
+            toml::from_str("collector = \"cumulative-diff-stat\"\nfrequency = \"monthly\"")
+                .unwrap();
+        let errors = metric.validate();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].is_warning());
+    }
+
+    #[test]
+    fn test_metrics_sampling_shared_collectors_differently() {
+        let metrics: HashMap<String, MetricConfig> = toml::from_str(
+            r#"
+            [todos]
+            collector = "total-pattern-occurences"
+            pattern = "TODO"
+            frequency = "weekly"
+
+            [todo-files]
+            collector = "pattern-occurences"
+            pattern = "TODO"
+            frequency = "per-commit"
+
+            [loc]
+            collector = "total-loc"
+            frequency = "weekly"
+
+            [loc-copy]
+            collector = "total-loc"
+            frequency = "weekly"
+            "#,
+        )
+        .unwrap();
+
+        let errors = validate_metrics(&metrics);
+        assert_eq!(errors.len(), 1);
+
+        let (metric_name, error) = &errors[0];
+        assert_eq!(metric_name, "todo-files");
+        assert!(error.is_warning());
+        assert!(matches!(
+            error,
+            MetricConfigError::MismatchedDependency { metric, sampling }
+                if metric == "todos" && sampling == "weekly"
+        ));
+
+        let metrics: HashMap<String, MetricConfig> = toml::from_str(
+            r#"
+            [loc]
+            collector = "total-loc"
+            frequency = "weekly"
+
+            [loc-monthly]
+            collector = "total-loc"
+            frequency = "monthly"
+            align_to_calendar = true
+            "#,
+        )
+        .unwrap();
+
+        let errors: Vec<String> = validate_metrics(&metrics)
+            .into_iter()
+            .map(|(metric_name, error)| format!("{metric_name}: {error}"))
+            .collect();
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].starts_with(
+            "loc: Collector is shared with metric loc-monthly sampled monthly aligned to the calendar"
+        ));
+        assert!(errors[1]
+            .starts_with("loc-monthly: Collector is shared with metric loc sampled weekly"));
+    }
+
+    #[test]
+    fn test_collector_id_matches_cache_key() {
+        // The id names the cache directory of the collector, so it must not change
+        assert_eq!(
+            "256adf383b92969583e28392e8729705c9ceb791",
+            CollectorConfig::TotalLoc {
+                scope: Box::default()
+            }
+            .id()
+        );
+    }
+
+    #[test]
+    fn test_git_repository_date_range() {
+        let reference: GitRepository = toml::from_str(
+            r#"
+            url = "https://github.com/bezbac/myaku.git"
+            since = "2023-01-01"
+            until = "2023-12-31"
+            "#,
+        )
+        .unwrap();
+
+        let time = |s: &str| DateTime::parse_from_rfc3339(s).unwrap().to_utc();
+
+        assert!(!reference.includes_commit_time(&time("2022-12-31T23:59:59Z")));
+        assert!(reference.includes_commit_time(&time("2023-01-01T00:00:00Z")));
+        assert!(reference.includes_commit_time(&time("2023-12-31T23:59:59Z")));
+        assert!(!reference.includes_commit_time(&time("2024-01-01T00:00:00Z")));
+
+        // Both bounds are optional
+        assert!(reference.includes_commit_time(&time("2023-06-01T00:00:00+02:00")));
+        assert!(GitRepository {
+            until: None,
+            ..reference
+        }
+        .includes_commit_time(&time("2030-01-01T00:00:00Z")));
+    }
+
+    #[test]
+    fn test_author_filter() {
+        let reference: GitRepository = toml::from_str(
+            r#"
+            url = "git@github.com:bezbac/myaku.git"
+            include_authors = ["*@example.com", "*@users.noreply.github.com"]
+            exclude_authors = ['*\[bot\]@users.noreply.github.com']
+            "#,
+        )
+        .unwrap();
+
+        assert!(reference.includes_author(Some("jane@example.com")));
+        assert!(reference.includes_author(Some("jane@users.noreply.github.com")));
+        assert!(!reference.includes_author(Some("dependabot[bot]@users.noreply.github.com")));
+        assert!(!reference.includes_author(Some("jane@example.org")));
+        assert!(!reference.includes_author(None));
+
+        let reference = GitRepository {
+            include_authors: None,
+            ..reference
+        };
+        assert!(reference.includes_author(Some("jane@example.org")));
+        assert!(reference.includes_author(None));
+    }
+
+    #[test]
+    fn test_merge_commit_filter() {
+        let commit = |hash: &str, parent_count: usize| CommitInfo {
+            id: CommitHash(hash.to_string()),
+            author: Author {
+                name: None,
+                email: None,
+            },
+            committer: Author {
+                name: None,
+                email: None,
+            },
+            message: None,
+            time: DateTime::UNIX_EPOCH,
+            offset_minutes: 0,
+            parent_count,
+        };
+        let select = |reference: &GitRepository| -> Vec<String> {
+            reference
+                .select_commits(vec![commit("root", 0), commit("a", 1), commit("merge", 2)])
+                .into_iter()
+                .map(|commit| commit.id.0)
+                .collect()
+        };
+
+        let reference: GitRepository =
+            toml::from_str("url = \"git@github.com:bezbac/myaku.git\"").unwrap();
+        assert_eq!(select(&reference), ["root", "a", "merge"]);
+
+        let reference: GitRepository = toml::from_str(
+            "url = \"git@github.com:bezbac/myaku.git\"\nmerge_commits = \"exclude\"",
+        )
+        .unwrap();
+        assert_eq!(select(&reference), ["root", "a"]);
+
+        let reference = GitRepository {
+            merge_commits: Some(MergeCommits::Only),
+            ..reference
+        };
+        assert_eq!(select(&reference), ["merge"]);
+    }
+
+    #[test]
+    fn test_pattern_occurences_redaction() {
+        let metric: MetricConfig = toml::from_str(
+            r#"
+            collector = "pattern-occurences"
+            pattern = "AKIA[0-9A-Z]{16}"
+            redact = "hash"
+            frequency = "monthly"
+            "#,
+        )
+        .unwrap();
+
+        let CollectorConfig::PatternOccurences { redact, .. } = &metric.collector else {
+            panic!("unexpected collector {:?}", metric.collector);
+        };
+        assert_eq!(Some(MatchRedaction::Hash), *redact);
+
+        // Metrics without redaction keep their previous cache key
+        assert_eq!(
+            r#"{"collector":"pattern-occurences","pattern":"todo","files":null}"#,
+            serde_json::to_string(&CollectorConfig::PatternOccurences {
+                pattern: "todo".to_string(),
+                files: None,
+                redact: None,
+                scope: Box::default(),
+            })
+            .unwrap()
+        );
+
+        assert_eq!(
+            "e5e9fa1ba31ecd1ae84f75caaa474f3a663f05f4",
+            MatchRedaction::Hash.redact("secret")
+        );
+        assert_eq!("", MatchRedaction::Omit.redact("secret"));
+    }
+
+    #[test]
+    fn test_explicit_commits() {
+        let reference: GitRepository = toml::from_str(
+            "url = \"git@github.com:bezbac/myaku.git\"\ncommits = [\"3f1\", \"c0ffee\"]",
+        )
+        .unwrap();
+
+        assert!(reference.includes_commit(&CommitHash("3f1a9b".to_string())));
+        assert!(reference.includes_commit(&CommitHash("c0ffee".to_string())));
+        assert!(!reference.includes_commit(&CommitHash("c0ffe".to_string())));
+        assert!(!reference.includes_commit(&CommitHash("a3f1".to_string())));
+    }
+
+    #[test]
+    fn test_tree_limits() {
+        let limits: TreeLimits = toml::from_str("max_files = 10").unwrap();
+        let stats = |file_count, total_bytes| TreeStats {
+            file_count,
+            total_bytes,
+        };
+
+        assert!(!limits.is_unlimited());
+        assert!(!limits.exceeded_by(&stats(10, u64::MAX)));
+        assert!(limits.exceeded_by(&stats(11, 0)));
+
+        assert!(TreeLimits::default().is_unlimited());
+        assert!(!TreeLimits::default().exceeded_by(&stats(usize::MAX, u64::MAX)));
+    }
 }
@@ -0,0 +1,211 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::git::{deserialize_time, serialize_time, CommitHash, CommitInfo};
+
+#[derive(Error, Debug)]
+pub enum EventsError {
+    #[error("Could not read events file {}", path.display())]
+    IO {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("Could not parse events file {}", path.display())]
+    Csv {
+        path: PathBuf,
+        #[source]
+        source: csv::Error,
+    },
+
+    #[error("Could not parse events file {}", path.display())]
+    SerdeJson {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    #[error("Event {name} has invalid time {time}, expected a date or an RFC 3339 timestamp")]
+    InvalidTime { name: String, time: String },
+
+    #[error("Events file {} is neither a .csv nor a .json file", path.display())]
+    UnsupportedFormat { path: PathBuf },
+}
+
+/// External event, e.g. a deployment, incident or team change, written next to the metrics so
+/// shifts in their values can be correlated with it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    #[serde(
+        serialize_with = "serialize_time",
+        deserialize_with = "deserialize_time"
+    )]
+    pub time: DateTime<Utc>,
+    pub name: String,
+
+    /// Category of the event, e.g. `deployment` or `incident`
+    pub kind: Option<String>,
+
+    /// Latest commit of the reference branch at the time of the event, `None` if it happened
+    /// before the first collected commit
+    pub commit: Option<CommitHash>,
+}
+
+/// Event as it is declared in an events file
+#[derive(Deserialize)]
+struct EventRecord {
+    time: String,
+    name: String,
+    kind: Option<String>,
+}
+
+impl EventRecord {
+    fn parse(self) -> Result<Event, EventsError> {
+        let time = DateTime::parse_from_rfc3339(&self.time)
+            .map(|time| time.with_timezone(&Utc))
+            .or_else(|_| {
+                NaiveDate::parse_from_str(&self.time, "%Y-%m-%d")
+                    .map(|date| date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc())
+            })
+            .map_err(|_| EventsError::InvalidTime {
+                name: self.name.clone(),
+                time: self.time.clone(),
+            })?;
+
+        Ok(Event {
+            time,
+            name: self.name,
+            // Empty cells of CSV files are empty strings
+            kind: self.kind.filter(|kind| !kind.is_empty()),
+            commit: None,
+        })
+    }
+}
+
+/// Reads the events of a `.csv` file with a header row or a `.json` file containing an array,
+/// both with `time`, `name` and optionally `kind` fields. Times are dates or RFC 3339 timestamps,
+/// the events are sorted by them.
+pub fn read_events(path: &Path) -> Result<Vec<Event>, EventsError> {
+    let contents = fs::read_to_string(path).map_err(|source| EventsError::IO {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    let records: Vec<EventRecord> = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("csv") => csv::Reader::from_reader(contents.as_bytes())
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .map_err(|source| EventsError::Csv {
+                path: path.to_path_buf(),
+                source,
+            })?,
+        Some("json") => {
+            serde_json::from_str(&contents).map_err(|source| EventsError::SerdeJson {
+                path: path.to_path_buf(),
+                source,
+            })?
+        }
+        _ => {
+            return Err(EventsError::UnsupportedFormat {
+                path: path.to_path_buf(),
+            })
+        }
+    };
+
+    let mut events = records
+        .into_iter()
+        .map(EventRecord::parse)
+        .collect::<Result<Vec<_>, _>>()?;
+    events.sort_by(|a, b| a.time.cmp(&b.time));
+
+    Ok(events)
+}
+
+/// Sets the commit of every event to the latest of the given commits at its time
+pub fn attribute_events(events: &mut [Event], commits: &[CommitInfo]) {
+    let mut commits: Vec<&CommitInfo> = commits.iter().collect();
+    commits.sort_by(|a, b| a.time.cmp(&b.time));
+
+    for event in events {
+        let before = commits.partition_point(|commit| commit.time <= event.time);
+        event.commit = before
+            .checked_sub(1)
+            .map(|position| commits[position].id.clone());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use crate::git::Author;
+
+    use super::*;
+
+    #[test]
+    fn test_read_and_attribute_events() {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .subsec_nanos();
+        let root = std::env::temp_dir().join(format!("myaku-events-{nanos}"));
+        fs::create_dir_all(&root).unwrap();
+
+        let csv_path = root.join("events.csv");
+        fs::write(
+            &csv_path,
+            "time,name,kind\n2024-03-01T12:00:00+02:00,Outage,incident\n2024-01-15,Release 1.0,\n",
+        )
+        .unwrap();
+
+        let json_path = root.join("events.json");
+        fs::write(
+            &json_path,
+            r#"[{"time":"2024-01-15","name":"Release 1.0"},{"time":"2024-03-01T10:00:00Z","name":"Outage","kind":"incident"}]"#,
+        )
+        .unwrap();
+
+        let mut events = read_events(&csv_path).unwrap();
+        assert_eq!(events, read_events(&json_path).unwrap());
+        assert_eq!(events[0].name, "Release 1.0");
+        assert_eq!(events[0].kind, None);
+        assert_eq!(events[1].kind.as_deref(), Some("incident"));
+
+        let author = Author {
+            name: None,
+            email: None,
+        };
+        let commits: Vec<CommitInfo> =
+            [("a", "2024-02-01T00:00:00Z"), ("b", "2024-03-01T10:00:00Z")]
+                .iter()
+                .map(|(hash, time)| CommitInfo {
+                    id: CommitHash((*hash).to_string()),
+                    author: author.clone(),
+                    committer: author.clone(),
+                    message: None,
+                    time: time.parse::<DateTime<Utc>>().unwrap(),
+                    offset_minutes: 0,
+                    parent_count: 1,
+                })
+                .collect();
+
+        attribute_events(&mut events, &commits);
+        assert_eq!(events[0].commit, None);
+        assert_eq!(events[1].commit, Some(CommitHash("b".to_string())));
+
+        fs::write(root.join("events.txt"), "").unwrap();
+        assert!(matches!(
+            read_events(&root.join("events.txt")),
+            Err(EventsError::UnsupportedFormat { .. })
+        ));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,26 @@
+//! Entry points for the fuzz targets in `fuzz/`, covering everything that parses output of
+//! external processes.
+
+use std::io::BufReader;
+
+use crate::{collectors::get_matches_from_grep_output, git::delimited_by, CloneProgress};
+
+/// Parses a line of `git clone --progress` output
+#[must_use]
+pub fn parse_clone_progress(line: &str) -> Option<CloneProgress> {
+    CloneProgress::try_from(line).ok()
+}
+
+/// Splits the input like the output of `git clone` using a buffer of the given capacity
+#[must_use]
+pub fn split_delimited(input: &[u8], delimiters: &[char], capacity: usize) -> Vec<String> {
+    delimited_by(BufReader::with_capacity(capacity.max(1), input), delimiters)
+        .filter_map(Result::ok)
+        .collect()
+}
+
+/// Returns the number of distinct matches in the JSON output of the grep printer
+#[must_use]
+pub fn count_grep_matches(output: &str) -> usize {
+    get_matches_from_grep_output(output).len()
+}
@@ -1,21 +1,31 @@
 use std::{
-    collections::HashSet,
+    collections::{BTreeSet, HashSet},
     env::temp_dir,
     fmt::Formatter,
+    fs::File,
     io::{BufRead, BufReader},
+    mem::{discriminant, Discriminant},
     path::{Path, PathBuf},
     process::{Command, Stdio},
+    time::{Duration, Instant},
 };
 
-use chrono::{offset::LocalResult, DateTime, TimeZone, Utc};
+use chrono::{offset::LocalResult, DateTime, FixedOffset, NaiveDate, Offset, TimeZone, Utc};
 use execute::Execute;
-use git2::{Diff, DiffFormat, DiffOptions, Object, ObjectType, Oid, Repository, Signature, Sort};
+use git2::{
+    build::CheckoutBuilder, Diff, DiffFormat, DiffOptions, Object, ObjectType, Oid, Repository,
+    Signature, Sort, WorktreeLockStatus, WorktreePruneOptions,
+};
+use globset::{Candidate, Glob, GlobSet, GlobSetBuilder};
 use rand::{distributions::Alphanumeric, Rng};
 use regex::Regex;
 use serde::{Deserialize, Serialize, Serializer};
 use ssh_key::{LineEnding, PrivateKey};
 use thiserror::Error;
 use tracing::{debug, warn};
+use walkdir::WalkDir;
+
+use crate::cache::exclude_paths_id;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Author {
@@ -23,7 +33,7 @@ pub struct Author {
     pub email: Option<String>,
 }
 
-#[derive(PartialEq, Eq, Hash, Clone, Serialize, Deserialize, Debug)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Serialize, Deserialize, Debug)]
 pub struct CommitHash(pub String);
 
 impl From<String> for CommitHash {
@@ -44,14 +54,14 @@ impl std::fmt::Display for CommitHash {
     }
 }
 
-fn serialize_time<S>(x: &DateTime<Utc>, s: S) -> Result<S::Ok, S::Error>
+pub(crate) fn serialize_time<S>(x: &DateTime<Utc>, s: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
 {
     s.serialize_i64(x.timestamp())
 }
 
-fn deserialize_time<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+pub(crate) fn deserialize_time<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
 where
     D: serde::Deserializer<'de>,
 {
@@ -70,6 +80,49 @@ pub struct CommitInfo {
         deserialize_with = "deserialize_time"
     )]
     pub time: DateTime<Utc>,
+
+    /// Offset of the author's timezone from UTC in minutes, e.g. `120` for CEST
+    #[serde(default)]
+    pub offset_minutes: i32,
+
+    /// Number of parents, more than one for merge commits
+    #[serde(default)]
+    pub parent_count: usize,
+}
+
+impl CommitInfo {
+    #[must_use]
+    pub fn is_merge(&self) -> bool {
+        self.parent_count > 1
+    }
+
+    /// Time of the commit in the author's timezone, UTC if the offset is out of range
+    #[must_use]
+    pub fn local_time(&self) -> DateTime<FixedOffset> {
+        let offset = FixedOffset::east_opt(self.offset_minutes * 60).unwrap_or_else(|| Utc.fix());
+        self.time.with_timezone(&offset)
+    }
+}
+
+fn commit_info(commit: &git2::Commit) -> Result<CommitInfo, GitError> {
+    let Some(LocalResult::Single(time)) =
+        chrono::FixedOffset::east_opt(commit.time().offset_minutes() * 60)
+            .map(|time| time.timestamp_opt(commit.time().seconds(), 0))
+    else {
+        return Err(GitError::FailedToConvertGitObjectTime {
+            time: commit.time(),
+        });
+    };
+
+    Ok(CommitInfo {
+        id: commit.id().to_string().into(),
+        author: commit.author().into(),
+        committer: commit.committer().into(),
+        message: commit.message().map(|v| v.to_string()),
+        time: time.to_utc(),
+        offset_minutes: commit.author().when().offset_minutes(),
+        parent_count: commit.parent_count(),
+    })
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -78,6 +131,13 @@ pub struct CommitTagInfo {
     pub commit: CommitHash,
 }
 
+/// Number and total size of the files at a commit, not counting excluded paths
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TreeStats {
+    pub file_count: usize,
+    pub total_bytes: u64,
+}
+
 impl From<Signature<'_>> for Author {
     fn from(item: Signature) -> Self {
         Author {
@@ -89,6 +149,58 @@ impl From<Signature<'_>> for Author {
 
 const GIT_BINARY_PATH: &str = "git";
 
+/// Git command whose output is not localized, since progress lines and errors are parsed, and
+/// which reports progress right away instead of only for operations taking longer than 2 seconds
+fn git_command() -> Command {
+    let mut command = Command::new(GIT_BINARY_PATH);
+    command.env("LC_ALL", "C");
+    command.env("GIT_PROGRESS_DELAY", "0");
+    command
+}
+
+/// Number of alternative names tried when a worktree name is still taken after cleaning up
+const MAX_WORKTREE_NAME_ATTEMPTS: usize = 3;
+
+/// Minimum time between two clone progress updates of the same phase
+const CLONE_PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Attempts of checkouts failing because of a lock, e.g. an `index.lock` a network filesystem
+/// has not released yet, the delay doubles after every attempt
+const MAX_LOCK_ATTEMPTS: u32 = 5;
+const LOCK_RETRY_DELAY: Duration = Duration::from_millis(50);
+
+/// Runs the operation again with exponential backoff as long as it fails because of a lock
+fn retry_when_locked<T>(mut operation: impl FnMut() -> Result<T, GitError>) -> Result<T, GitError> {
+    let mut delay = LOCK_RETRY_DELAY;
+
+    for _ in 1..MAX_LOCK_ATTEMPTS {
+        match operation() {
+            Err(e) if e.is_locked() => {
+                debug!("{}, retrying in {:?}", e, delay);
+                std::thread::sleep(delay);
+                delay *= 2;
+            }
+            result => return result,
+        }
+    }
+
+    operation()
+}
+
+/// Reads the files of a commit straight from the object database, so collectors which only look
+/// at file contents don't need the commit checked out.
+///
+/// Excluded files and symlinks are skipped. The files of submodules are only read if the reader
+/// was created from a worktree which has them checked out, see [`WorktreeHandle::tree_reader`].
+pub struct TreeReader<'r> {
+    repo: &'r RepositoryHandle,
+    git2_repo: Repository,
+    tree_id: Oid,
+
+    /// Worktree whose checked out submodules are read as well
+    submodules_path: Option<PathBuf>,
+}
+
 #[derive(Debug)]
 pub struct WorktreeHandle<'r> {
     repo: &'r RepositoryHandle,
@@ -131,6 +243,31 @@ impl<'r> AsMut<WorktreeHandle<'r>> for TempWorktreeHandle<'r> {
 #[derive(Debug)]
 pub struct RepositoryHandle {
     pub path: PathBuf,
+
+    /// Bare repositories have no working directory and keep their branches as local branches,
+    /// e.g. when they were created with `git clone --mirror`
+    pub bare: bool,
+
+    /// Files hidden from the collectors, see `set_excluded_paths`
+    excluded_paths: GlobSet,
+
+    /// Key of the excluded files in the cache, see [`crate::cache::exclude_paths_id`]
+    excluded_paths_id: Option<String>,
+
+    /// Partial clones lack the blobs of commits which weren't checked out, they are fetched once
+    /// a checkout or read needs them, see `fetch_missing_blobs`
+    pub partial: bool,
+
+    /// Submodules are checked out recursively in worktrees, see `set_submodules`
+    pub submodules: bool,
+
+    /// Histories only follow the first parent of merge commits, see `set_first_parent`
+    pub first_parent: bool,
+
+    /// Authenticate the fetches of missing blobs, see `set_credentials`. Boxed, as the key is
+    /// large compared to the rest of the handle.
+    ssh_key: Option<Box<PrivateKey>>,
+    https_credentials: Option<HttpsCredentials>,
 }
 
 impl TryFrom<&RepositoryHandle> for Repository {
@@ -143,12 +280,15 @@ impl TryFrom<&RepositoryHandle> for Repository {
 
 #[derive(Error, Debug)]
 pub enum GitError {
-    #[error(".git directory does not exist in path {0}")]
+    #[error("Neither a .git directory nor a bare repository exists in path {0}")]
     NoGitDirectory(PathBuf),
 
     #[error("Could not determine remote URL")]
     FailedToDetermineRemoteURL,
 
+    #[error("Object store in path {0} is a mirror of a different repository")]
+    MismatchedObjectStore(PathBuf),
+
     #[error("Could not determine mainline branch")]
     FailedToDetermineMainlineBranch,
 
@@ -161,9 +301,18 @@ pub enum GitError {
     #[error("Unexpected tag name format: {tag_name}")]
     UnexpectedTagNameFormat { tag_name: String },
 
-    #[error("{0}")]
+    #[error(transparent)]
     CloneError(#[from] GitCloneError),
 
+    #[error("Could not fetch the repository")]
+    FetchError(#[source] GitCloneError),
+
+    #[error("Fetching the repository exited with {status}: {message}")]
+    FetchFailed {
+        status: std::process::ExitStatus,
+        message: String,
+    },
+
     #[error("IO error: {0}")]
     IO(#[from] std::io::Error),
 
@@ -172,23 +321,317 @@ pub enum GitError {
 
     #[error("Git error: {0}")]
     Git2Erorr(#[from] git2::Error),
+
+    #[error(transparent)]
+    Glob(#[from] globset::Error),
+
+    #[error("Updating the submodules failed with {status}: {message}")]
+    SubmoduleUpdateFailed {
+        status: std::process::ExitStatus,
+        message: String,
+    },
+
+    #[error("Listing the commits changing the paths failed with {status}: {message}")]
+    RevListFailed {
+        status: std::process::ExitStatus,
+        message: String,
+    },
+}
+
+impl GitError {
+    /// Whether the operation may succeed when attempted again, e.g. after a lock was released
+    pub(crate) fn is_transient(&self) -> bool {
+        match self {
+            GitError::IO(e) => is_transient_io_error(e),
+            GitError::Git2Erorr(e) => e.code() == git2::ErrorCode::Locked,
+            _ => false,
+        }
+    }
+
+    fn is_locked(&self) -> bool {
+        matches!(self, GitError::Git2Erorr(e) if e.code() == git2::ErrorCode::Locked)
+    }
+}
+
+/// IO errors which are not caused by the contents of the repository itself
+pub(crate) fn is_transient_io_error(error: &std::io::Error) -> bool {
+    !matches!(
+        error.kind(),
+        std::io::ErrorKind::NotFound
+            | std::io::ErrorKind::PermissionDenied
+            | std::io::ErrorKind::InvalidInput
+            | std::io::ErrorKind::InvalidData
+            | std::io::ErrorKind::Unsupported
+    )
 }
 
 impl RepositoryHandle {
     pub fn open(path: &Path) -> Result<RepositoryHandle, GitError> {
-        if path.join(".git").exists() {
-            return Ok(RepositoryHandle {
-                path: path.to_path_buf(),
+        let bare = if path.join(".git").exists() {
+            false
+        } else if path.join("HEAD").is_file() && path.join("objects").is_dir() {
+            true
+        } else {
+            return Err(GitError::NoGitDirectory(path.to_path_buf()));
+        };
+
+        // Clones with a filter mark their remote as promisor of the objects left out
+        let partial = Repository::open(path)
+            .and_then(|repo| repo.config()?.get_bool("remote.origin.promisor"))
+            .unwrap_or(false);
+
+        Ok(RepositoryHandle {
+            path: path.to_path_buf(),
+            bare,
+            excluded_paths: GlobSet::empty(),
+            excluded_paths_id: None,
+            partial,
+            submodules: false,
+            first_parent: false,
+            ssh_key: None,
+            https_credentials: None,
+        })
+    }
+
+    /// Checks the submodules out recursively whenever a worktree checks out a commit, so
+    /// collectors see their files like the ones of the repository itself. Submodules whose path is
+    /// excluded are left out. Otherwise their paths are empty directories.
+    ///
+    /// Files are only read from submodules once they are checked out, collectors reading from
+    /// the object database don't see them.
+    pub fn set_submodules(&mut self, enabled: bool) {
+        self.submodules = enabled;
+    }
+
+    /// Only follows the first parent of merge commits when listing the history of a commit, like
+    /// `git log --first-parent`. In repositories merging pull requests into the main branch this
+    /// yields the mainline without the commits of the merged branches.
+    pub fn set_first_parent(&mut self, enabled: bool) {
+        self.first_parent = enabled;
+    }
+
+    /// Sets the credentials used to fetch the blobs missing from a partial clone, which happens
+    /// while checking out or reading commits instead of through an explicit fetch.
+    pub fn set_credentials(
+        &mut self,
+        ssh_key: Option<&PrivateKey>,
+        https_credentials: Option<&HttpsCredentials>,
+    ) {
+        self.ssh_key = ssh_key.cloned().map(Box::new);
+        self.https_credentials = https_credentials.cloned();
+    }
+
+    /// Fetches the given blobs if they are missing from a partial clone, all of them in a single
+    /// request. libgit2 can't fetch them on its own like git does when it encounters them.
+    fn fetch_missing_blobs(
+        &self,
+        git2_repo: &Repository,
+        blobs: impl IntoIterator<Item = Oid>,
+    ) -> Result<(), GitError> {
+        if !self.partial {
+            return Ok(());
+        }
+
+        let odb = git2_repo.odb()?;
+        let missing: BTreeSet<Oid> = blobs.into_iter().filter(|id| !odb.exists(*id)).collect();
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        debug!("Fetching {} missing blobs", missing.len());
+
+        // The same arguments git passes to its own lazy fetches
+        let mut command = git_command();
+        command.current_dir(&self.path);
+        command.args([
+            "-c",
+            "fetch.negotiationAlgorithm=noop",
+            "fetch",
+            "--no-tags",
+            "--no-write-fetch-head",
+            "--recurse-submodules=no",
+            "--filter=blob:none",
+            "--stdin",
+            "origin",
+        ]);
+        command.stdout(Stdio::piped());
+        command.stderr(Stdio::piped());
+        set_ssh_key(&mut command, self.ssh_key.as_deref()).map_err(GitError::FetchError)?;
+        set_https_credentials(&mut command, self.https_credentials.as_ref());
+
+        let input: String = missing.iter().map(|id| format!("{id}\n")).collect();
+        let output = command.execute_input_output(&input)?;
+
+        if !output.status.success() {
+            return Err(GitError::FetchFailed {
+                status: output.status,
+                message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            });
+        }
+
+        // The packs written by the fetch are only picked up once the object database is reloaded
+        odb.refresh()?;
+
+        Ok(())
+    }
+
+    /// Fetches the blobs of the files of the tree which pass the filter, if they are missing from
+    /// a partial clone. Checkouts need the excluded ones as well, they are only removed afterwards.
+    fn fetch_missing_tree_blobs(
+        &self,
+        git2_repo: &Repository,
+        tree: &git2::Tree,
+        filter: impl Fn(&str) -> bool,
+    ) -> Result<(), GitError> {
+        if !self.partial {
+            return Ok(());
+        }
+
+        let mut blobs = vec![];
+        tree.walk(git2::TreeWalkMode::PreOrder, |root, entry| {
+            if let (Some(ObjectType::Blob), Some(name)) = (entry.kind(), entry.name()) {
+                let path = format!("{root}{name}");
+                if filter(&path) {
+                    blobs.push(entry.id());
+                }
+            }
+            git2::TreeWalkResult::Ok
+        })?;
+
+        self.fetch_missing_blobs(git2_repo, blobs)
+    }
+
+    /// Fetches the blobs of both sides of the changes of the diff, if they are missing from a
+    /// partial clone
+    fn fetch_missing_diff_blobs(
+        &self,
+        git2_repo: &Repository,
+        diff: &Diff,
+    ) -> Result<(), GitError> {
+        self.fetch_missing_blobs(
+            git2_repo,
+            diff.deltas()
+                .flat_map(|delta| [delta.old_file(), delta.new_file()])
+                .filter(|file| file.mode() != git2::FileMode::Commit)
+                .map(|file| file.id())
+                .filter(|id| !id.is_zero()),
+        )
+    }
+
+    /// Hides the files whose path relative to the repository root matches any of the globs from
+    /// all collectors. They are skipped when reading from the object database and removed from
+    /// worktrees after checking out a commit.
+    pub fn set_excluded_paths(&mut self, globs: &[Glob]) -> Result<(), GitError> {
+        let mut builder = GlobSetBuilder::new();
+        for glob in globs {
+            builder.add(glob.clone());
+        }
+        self.excluded_paths = builder.build()?;
+        self.excluded_paths_id = exclude_paths_id(globs);
+
+        Ok(())
+    }
+
+    pub(crate) fn excluded_paths_id(&self) -> Option<&str> {
+        self.excluded_paths_id.as_deref()
+    }
+
+    pub(crate) fn is_excluded(&self, path: impl AsRef<Path>) -> bool {
+        self.excluded_paths
+            .is_match_candidate(&Candidate::new(path.as_ref()))
+    }
+
+    /// Fetches the branches and tags of the remote, reporting progress like
+    /// [`clone_repository`]. Branches deleted on the remote are pruned.
+    pub fn fetch(
+        &self,
+        progress_callback: impl Fn(&CloneProgress),
+        ssh_key: Option<&PrivateKey>,
+        https_credentials: Option<&HttpsCredentials>,
+    ) -> Result<(), GitError> {
+        let mut command = git_command();
+        command.current_dir(&self.path);
+        command.args(["fetch", "--progress", "--prune"]);
+
+        // Tags of older commits would fetch their whole history into shallow repositories, the
+        // ones of fetched commits are still followed
+        if !self.is_shallow()? {
+            command.arg("--tags");
+        }
+
+        command.arg("origin");
+
+        // Bare clones have no refspec to fetch into, they receive the branches directly
+        if self.bare {
+            command.arg("+refs/heads/*:refs/heads/*");
+        }
+
+        set_ssh_key(&mut command, ssh_key).map_err(GitError::FetchError)?;
+        set_https_credentials(&mut command, https_credentials);
+
+        let output =
+            run_with_progress(&mut command, progress_callback).map_err(GitError::FetchError)?;
+
+        if !output.status.success() {
+            return Err(GitError::FetchFailed {
+                status: output.status,
+                message: output.error_message(),
             });
         }
 
-        Err(GitError::NoGitDirectory(path.to_path_buf()))
+        Ok(())
+    }
+
+    /// Whether the repository was cloned with a limited depth and lacks older commits
+    pub fn is_shallow(&self) -> Result<bool, GitError> {
+        let git2_repo: Repository = self.try_into()?;
+        Ok(git2_repo.is_shallow())
+    }
+
+    /// Fetches the history missing from a shallow repository
+    pub fn unshallow(
+        &self,
+        ssh_key: Option<&PrivateKey>,
+        https_credentials: Option<&HttpsCredentials>,
+    ) -> Result<(), GitError> {
+        self.fetch_history("--unshallow", ssh_key, https_credentials)
+    }
+
+    /// Fetches this many more commits of the history of a shallow repository
+    pub fn deepen(
+        &self,
+        commits: usize,
+        ssh_key: Option<&PrivateKey>,
+        https_credentials: Option<&HttpsCredentials>,
+    ) -> Result<(), GitError> {
+        self.fetch_history(&format!("--deepen={commits}"), ssh_key, https_credentials)
+    }
+
+    /// Fetches the history of a shallow repository back to the given day
+    pub fn deepen_since(
+        &self,
+        since: NaiveDate,
+        ssh_key: Option<&PrivateKey>,
+        https_credentials: Option<&HttpsCredentials>,
+    ) -> Result<(), GitError> {
+        self.fetch_history(
+            &format!("--shallow-since={}", since.format("%Y-%m-%d")),
+            ssh_key,
+            https_credentials,
+        )
     }
 
-    pub fn fetch(&self) -> Result<(), GitError> {
-        let mut command = Command::new(GIT_BINARY_PATH);
+    fn fetch_history(
+        &self,
+        depth_argument: &str,
+        ssh_key: Option<&PrivateKey>,
+        https_credentials: Option<&HttpsCredentials>,
+    ) -> Result<(), GitError> {
+        let mut command = git_command();
         command.current_dir(&self.path);
-        command.arg("fetch");
+        command.args(["fetch", depth_argument]);
+        set_ssh_key(&mut command, ssh_key)?;
+        set_https_credentials(&mut command, https_credentials);
         command.execute_check_exit_status_code(0)?;
 
         Ok(())
@@ -203,12 +646,28 @@ impl RepositoryHandle {
         Ok(url.to_string())
     }
 
+    /// Returns the revision of the given branch as received from the remote
+    #[must_use]
+    pub fn branch_revision(&self, branch: &str) -> String {
+        if self.bare {
+            format!("refs/heads/{branch}")
+        } else {
+            format!("origin/{branch}")
+        }
+    }
+
     pub fn find_main_branch(&self) -> Result<String, GitError> {
         let git2_repo: Repository = self.try_into()?;
 
+        let (prefix, branch_type) = if self.bare {
+            ("", git2::BranchType::Local)
+        } else {
+            ("origin/", git2::BranchType::Remote)
+        };
+
         let mut found = Option::None;
         for attempt in &["master", "main", "dev", "development", "develop"] {
-            match git2_repo.find_branch(&format!("origin/{attempt}"), git2::BranchType::Remote) {
+            match git2_repo.find_branch(&format!("{prefix}{attempt}"), branch_type) {
                 Result::Ok(_) => {
                     debug!("Found branch {attempt} in repository");
                     found = Some(attempt);
@@ -226,42 +685,129 @@ impl RepositoryHandle {
     }
 
     pub fn reset_hard(&self, revstring: &str) -> Result<(), GitError> {
+        if self.bare {
+            // There is no working directory to check out, only move `HEAD`
+            let git2_repo: Repository = self.try_into()?;
+            let (object, _) = git2_repo.revparse_ext(revstring)?;
+            git2_repo.set_head_detached(object.id())?;
+
+            return Ok(());
+        }
+
         let main_worktree = self.main_worktree();
         main_worktree.reset_hard(revstring)
     }
 
+    pub fn resolve_revision(&self, revstring: &str) -> Result<CommitHash, GitError> {
+        let git2_repo: Repository = self.try_into()?;
+
+        let commit = git2_repo.revparse_single(revstring)?.peel_to_commit()?;
+
+        Ok(commit.id().into())
+    }
+
     pub fn get_all_commits(&self) -> Result<Vec<CommitInfo>, GitError> {
         let git2_repo: Repository = self.try_into()?;
+        let head = git2_repo.head()?.peel_to_commit()?.id();
+
+        self.get_commits_reachable_from(&head.into())
+    }
+
+    /// The given commit and all of its ancestors, only the ones reached through first parents if
+    /// `first_parent` is set
+    pub fn get_commits_reachable_from(
+        &self,
+        commit_hash: &CommitHash,
+    ) -> Result<Vec<CommitInfo>, GitError> {
+        self.walk_commits(commit_hash, None)
+    }
+
+    /// The commits reachable from `to` but not from `from`, like `git log from..to` lists them,
+    /// e.g. the ones between two releases. Only first parents are followed if `first_parent` is
+    /// set.
+    pub fn get_commits_between(
+        &self,
+        from: &CommitHash,
+        to: &CommitHash,
+    ) -> Result<Vec<CommitInfo>, GitError> {
+        self.walk_commits(to, Some(from))
+    }
+
+    fn walk_commits(
+        &self,
+        commit_hash: &CommitHash,
+        hidden: Option<&CommitHash>,
+    ) -> Result<Vec<CommitInfo>, GitError> {
+        let git2_repo: Repository = self.try_into()?;
 
         let mut revwalk = git2_repo.revwalk()?;
 
         revwalk.set_sorting(Sort::NONE)?;
-        revwalk.push_head()?;
+        revwalk.push(Oid::from_str(&commit_hash.0)?)?;
+        if let Some(hidden) = hidden {
+            revwalk.hide(Oid::from_str(&hidden.0)?)?;
+        }
+        if self.first_parent {
+            revwalk.simplify_first_parent()?;
+        }
 
         let mut commits: Vec<_> = Vec::new();
         for id in revwalk {
             let oid = id?;
-            let commit = git2_repo.find_commit(oid)?;
-
-            let Some(LocalResult::Single(time)) =
-                chrono::FixedOffset::east_opt(commit.time().offset_minutes() * 60)
-                    .map(|time| time.timestamp_opt(commit.time().seconds(), 0))
-            else {
-                return Err(GitError::FailedToConvertGitObjectTime {
-                    time: commit.time(),
-                });
-            };
+            commits.push(commit_info(&git2_repo.find_commit(oid)?)?);
+        }
+
+        Ok(commits)
+    }
 
-            commits.push(CommitInfo {
-                id: commit.id().to_string().into(),
-                author: commit.author().into(),
-                committer: commit.committer().into(),
-                message: commit.message().map(|v| v.to_string()),
-                time: time.to_utc(),
+    /// The given commit and those of its ancestors which change any of the files matching the
+    /// pathspecs, as `git log -- <paths>` lists them. Git's history simplification applies, e.g.
+    /// merges which don't change the files compared to one of their parents are left out. Only
+    /// first parents are followed if `first_parent` is set, the commits reachable from `from` are
+    /// left out if it is given.
+    pub fn get_commits_touching_paths(
+        &self,
+        commit_hash: &CommitHash,
+        from: Option<&CommitHash>,
+        paths: &[String],
+    ) -> Result<Vec<CommitInfo>, GitError> {
+        let mut command = git_command();
+        command.current_dir(&self.path);
+        command.args(["rev-list", &commit_hash.0]);
+        if let Some(from) = from {
+            command.arg(format!("^{from}"));
+        }
+        if self.first_parent {
+            command.arg("--first-parent");
+        }
+        command.arg("--");
+        command.args(paths);
+        command.stdout(Stdio::piped());
+        command.stderr(Stdio::piped());
+
+        let output = command.output()?;
+        if !output.status.success() {
+            return Err(GitError::RevListFailed {
+                status: output.status,
+                message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
             });
         }
 
-        Ok(commits)
+        let git2_repo: Repository = self.try_into()?;
+
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|id| commit_info(&git2_repo.find_commit(Oid::from_str(id)?)?))
+            .collect()
+    }
+
+    /// Info of a single commit, without walking its history
+    pub fn get_commit(&self, commit_hash: &CommitHash) -> Result<CommitInfo, GitError> {
+        let git2_repo: Repository = self.try_into()?;
+
+        let commit = git2_repo.find_commit(Oid::from_str(&commit_hash.0)?)?;
+
+        commit_info(&commit)
     }
 
     pub fn get_all_commit_tags(&self) -> Result<Vec<CommitTagInfo>, GitError> {
@@ -324,6 +870,158 @@ impl RepositoryHandle {
         main_worktree.get_current_changed_file_paths()
     }
 
+    /// Like `get_current_total_diff_stat`, but for any commit without checking it out
+    pub fn get_total_diff_stat(
+        &self,
+        commit_hash: &CommitHash,
+    ) -> Result<(usize, usize, usize), GitError> {
+        self.get_total_diff_stat_of_files(commit_hash, |_| true)
+    }
+
+    /// Like `get_total_diff_stat`, but only counts the files whose path passes the filter
+    pub fn get_total_diff_stat_of_files(
+        &self,
+        commit_hash: &CommitHash,
+        filter: impl Fn(&Path) -> bool,
+    ) -> Result<(usize, usize, usize), GitError> {
+        let git2_repo: Repository = self.try_into()?;
+        let diff = get_diff_to_parent(&git2_repo, &commit_hash.0, &mut DiffOptions::new())?;
+        self.fetch_missing_diff_blobs(&git2_repo, &diff)?;
+        get_diff_stat(&diff, |path| !self.is_excluded(path) && filter(path))
+    }
+
+    /// Like `get_current_total_diff_stat_ignoring_whitespace`, but for any commit without checking
+    /// it out
+    pub fn get_total_diff_stat_ignoring_whitespace(
+        &self,
+        commit_hash: &CommitHash,
+    ) -> Result<(usize, usize, usize), GitError> {
+        let git2_repo: Repository = self.try_into()?;
+        let diff = get_diff_to_parent(
+            &git2_repo,
+            &commit_hash.0,
+            DiffOptions::new().ignore_whitespace(true),
+        )?;
+        self.fetch_missing_diff_blobs(&git2_repo, &diff)?;
+        get_diff_stat(&diff, |path| !self.is_excluded(path))
+    }
+
+    /// Paths of the files changed by the given commit, read from the object database without
+    /// checking the commit out.
+    ///
+    /// Merge commits are diffed against each of their parents, so the result includes the files
+    /// changed on any of the merged branches. The first commit is diffed against the empty tree.
+    pub fn changed_files(&self, commit_hash: &CommitHash) -> Result<HashSet<String>, GitError> {
+        let git2_repo: Repository = self.try_into()?;
+        let commit = git2_repo
+            .revparse_single(&commit_hash.0)?
+            .peel_to_commit()?;
+        let tree = commit.tree()?;
+
+        let mut changed_files = HashSet::new();
+
+        if commit.parent_count() == 0 {
+            let diff = git2_repo.diff_tree_to_tree(None, Some(&tree), None)?;
+            changed_files = get_changed_file_paths(&diff)?;
+        }
+
+        for parent in commit.parents() {
+            let diff = git2_repo.diff_tree_to_tree(Some(&parent.tree()?), Some(&tree), None)?;
+            changed_files.extend(get_changed_file_paths(&diff)?);
+        }
+
+        changed_files.retain(|path| !self.is_excluded(path));
+
+        Ok(changed_files)
+    }
+
+    /// Returns a reader for the files at the given commit in the object database. The files of
+    /// submodules are not read, they are not part of the object database of the repository.
+    pub fn tree_reader(&self, commit_hash: &CommitHash) -> Result<TreeReader<'_>, GitError> {
+        self.tree_reader_with_submodules(commit_hash, None)
+    }
+
+    fn tree_reader_with_submodules(
+        &self,
+        commit_hash: &CommitHash,
+        submodules_path: Option<PathBuf>,
+    ) -> Result<TreeReader<'_>, GitError> {
+        let git2_repo: Repository = self.try_into()?;
+        let tree_id = git2_repo
+            .revparse_single(&commit_hash.0)?
+            .peel_to_tree()?
+            .id();
+
+        Ok(TreeReader {
+            repo: self,
+            git2_repo,
+            tree_id,
+            submodules_path,
+        })
+    }
+
+    /// Reads the contents of a file at the given commit from the object database, `None` if the
+    /// file does not exist at that commit.
+    pub fn read_file(
+        &self,
+        commit_hash: &CommitHash,
+        path: &str,
+    ) -> Result<Option<Vec<u8>>, GitError> {
+        self.tree_reader(commit_hash)?.read_file(path)
+    }
+
+    /// Calls the callback with the path and contents of every file at the given commit, reading
+    /// them one after another from the object database instead of checking the commit out.
+    pub fn for_each_file(
+        &self,
+        commit_hash: &CommitHash,
+        callback: impl FnMut(&str, &[u8]),
+    ) -> Result<(), GitError> {
+        self.tree_reader(commit_hash)?
+            .for_each_file(|_| true, callback)
+    }
+
+    /// Counts the files at the given commit and sums up their sizes, reading only the object
+    /// headers instead of the file contents.
+    pub fn tree_stats(&self, commit_hash: &CommitHash) -> Result<TreeStats, GitError> {
+        let git2_repo: Repository = self.try_into()?;
+        let tree = git2_repo.revparse_single(&commit_hash.0)?.peel_to_tree()?;
+        // The headers of missing blobs can't be read either
+        self.fetch_missing_tree_blobs(&git2_repo, &tree, |path| !self.is_excluded(path))?;
+        let odb = git2_repo.odb()?;
+
+        let mut stats = TreeStats::default();
+        let mut result = Ok(());
+        tree.walk(git2::TreeWalkMode::PreOrder, |root, entry| {
+            if entry.kind() != Some(ObjectType::Blob) {
+                return git2::TreeWalkResult::Ok;
+            }
+
+            let Some(name) = entry.name() else {
+                return git2::TreeWalkResult::Ok;
+            };
+
+            if self.is_excluded(format!("{root}{name}")) {
+                return git2::TreeWalkResult::Ok;
+            }
+
+            match odb.read_header(entry.id()) {
+                Ok((size, _)) => {
+                    stats.file_count += 1;
+                    stats.total_bytes += size as u64;
+                    git2::TreeWalkResult::Ok
+                }
+                Err(e) => {
+                    result = Err(e);
+                    git2::TreeWalkResult::Abort
+                }
+            }
+        })?;
+        result?;
+
+        Ok(stats)
+    }
+
     pub fn create_worktree<'a>(
         &'a self,
         worktree_name: &str,
@@ -354,229 +1052,1843 @@ impl RepositoryHandle {
         }
     }
 
+    /// Creates a worktree which is removed once the handle is dropped.
+    ///
+    /// Registrations left behind by crashed runs are cleaned up, if the name is still taken
+    /// afterwards, a numbered suffix is appended to the name and path.
     pub fn create_temp_worktree<'a>(
         &'a self,
         worktree_name: &str,
         worktree_path: &Path,
     ) -> Result<TempWorktreeHandle<'a>, GitError> {
-        let worktree = self.create_worktree(worktree_name, worktree_path)?;
-        Ok(TempWorktreeHandle { worktree })
-    }
+        let mut result = self.create_worktree(worktree_name, worktree_path);
 
-    pub fn remove_worktree(
-        &self,
-        worktree_name: &str,
-        force: Option<bool>,
-    ) -> Result<(), GitError> {
-        let mut command = Command::new(GIT_BINARY_PATH);
-        command.current_dir(&self.path);
-        command.arg("worktree");
-        command.arg("remove");
+        if let Err(e) = &result {
+            warn!("Failed to create worktree {worktree_name}, removing stale registrations: {e}");
 
-        if let Some(true) = force {
-            command.arg("-f");
+            self.remove_stale_worktrees(worktree_name, worktree_path)?;
+            result = self.create_worktree(worktree_name, worktree_path);
         }
 
-        command.arg(worktree_name);
-
-        command.execute_check_exit_status_code(0)?;
+        for attempt in 1..=MAX_WORKTREE_NAME_ATTEMPTS {
+            if result.is_ok() {
+                break;
+            }
 
-        Ok(())
-    }
-}
+            let name = format!("{worktree_name}-{attempt}");
+            let mut path = worktree_path.as_os_str().to_owned();
+            path.push(format!("-{attempt}"));
 
-impl TryFrom<&WorktreeHandle<'_>> for Repository {
-    type Error = git2::Error;
+            debug!("Retrying worktree creation as {name}");
+            result = self.create_worktree(&name, Path::new(&path));
+        }
 
-    fn try_from(value: &WorktreeHandle<'_>) -> Result<Self, Self::Error> {
-        Repository::open(&value.path)
+        Ok(TempWorktreeHandle { worktree: result? })
+    }
+
+    /// Prunes all registrations whose working directory no longer exists, as well as the
+    /// registration, branch and directory of the given worktree, even if it is locked.
+    fn remove_stale_worktrees(
+        &self,
+        worktree_name: &str,
+        worktree_path: &Path,
+    ) -> Result<(), GitError> {
+        let git2_repo: Repository = self.try_into()?;
+
+        for name in git2_repo.worktrees()?.iter().flatten() {
+            let worktree = git2_repo.find_worktree(name)?;
+
+            if name == worktree_name {
+                if !matches!(worktree.is_locked()?, WorktreeLockStatus::Unlocked) {
+                    worktree.unlock()?;
+                }
+
+                worktree.prune(Some(
+                    WorktreePruneOptions::new().valid(true).working_tree(true),
+                ))?;
+            } else if worktree.validate().is_err() {
+                // Locked worktrees are kept, they might be on a drive that is currently unmounted
+                if let Err(e) = worktree.prune(None) {
+                    debug!("Failed to prune worktree {name}: {e}");
+                }
+            }
+        }
+
+        // Worktrees are created on a new branch of the same name
+        if let Ok(mut branch) = git2_repo.find_branch(worktree_name, git2::BranchType::Local) {
+            if let Err(e) = branch.delete() {
+                debug!("Failed to delete branch {worktree_name}: {e}");
+            }
+        }
+
+        if worktree_path.exists() {
+            std::fs::remove_dir_all(worktree_path)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn remove_worktree(
+        &self,
+        worktree_name: &str,
+        force: Option<bool>,
+    ) -> Result<(), GitError> {
+        let mut command = git_command();
+        command.current_dir(&self.path);
+        command.arg("worktree");
+        command.arg("remove");
+
+        if let Some(true) = force {
+            command.arg("-f");
+        }
+
+        command.arg(worktree_name);
+
+        command.execute_check_exit_status_code(0)?;
+
+        Ok(())
+    }
+}
+
+impl TryFrom<&WorktreeHandle<'_>> for Repository {
+    type Error = git2::Error;
+
+    fn try_from(value: &WorktreeHandle<'_>) -> Result<Self, Self::Error> {
+        Repository::open(&value.path)
+    }
+}
+
+impl WorktreeHandle<'_> {
+    /// Like `reset_hard`, but also discards all changes and untracked files left behind in the
+    /// worktree
+    pub fn reset_clean(&self, revstring: &str) -> Result<(), GitError> {
+        retry_when_locked(|| {
+            let git2_repo: Repository = self.try_into()?;
+
+            let (object, _) = git2_repo.revparse_ext(revstring)?;
+            self.repo
+                .fetch_missing_tree_blobs(&git2_repo, &object.peel_to_tree()?, |_| true)?;
+            git2_repo.checkout_tree(
+                &object,
+                Some(CheckoutBuilder::new().force().remove_untracked(true)),
+            )?;
+            git2_repo.set_head_detached(object.id())?;
+
+            self.remove_excluded_files(&object.peel_to_tree()?)?;
+
+            Ok(())
+        })?;
+
+        self.update_submodules(|_| true)
     }
-}
 
-impl WorktreeHandle<'_> {
     pub fn reset_hard(&self, revstring: &str) -> Result<(), GitError> {
+        retry_when_locked(|| {
+            let git2_repo: Repository = self.try_into()?;
+
+            let (object, _) = git2_repo.revparse_ext(revstring)?;
+            self.repo
+                .fetch_missing_tree_blobs(&git2_repo, &object.peel_to_tree()?, |_| true)?;
+            // Restores files which were removed by a partial checkout
+            git2_repo
+                .checkout_tree(&object, Some(CheckoutBuilder::new().recreate_missing(true)))?;
+            git2_repo.set_head_detached(object.id())?;
+
+            self.remove_excluded_files(&object.peel_to_tree()?)?;
+
+            Ok(())
+        })?;
+
+        self.update_submodules(|_| true)
+    }
+
+    /// Checks out the commits of the submodules recorded in the current commit which pass the
+    /// filter and aren't excluded, cloning them if needed, see
+    /// [`RepositoryHandle::set_submodules`]
+    fn update_submodules(&self, filter: impl Fn(&str) -> bool) -> Result<(), GitError> {
+        if !self.repo.submodules {
+            return Ok(());
+        }
+
+        // Nested submodules are updated by their parents
+        let git2_repo: Repository = self.try_into()?;
+        let mut paths = vec![];
+        git2_repo
+            .head()?
+            .peel_to_tree()?
+            .walk(git2::TreeWalkMode::PreOrder, |root, entry| {
+                if entry.filemode() == i32::from(git2::FileMode::Commit) {
+                    let path = format!("{root}{}", entry.name().unwrap_or_default());
+                    // Globs like `vendor/**` only match the contents of the submodule
+                    let excluded =
+                        self.repo.is_excluded(&path) || self.repo.is_excluded(format!("{path}/"));
+                    if filter(&path) && !excluded {
+                        paths.push(path);
+                    }
+                }
+                git2::TreeWalkResult::Ok
+            })?;
+
+        if paths.is_empty() {
+            return Ok(());
+        }
+
+        let mut command = git_command();
+        command.current_dir(&self.path);
+
+        // Submodules of the test repositories are cloned from local paths, which git only allows
+        // when asked to explicitly
+        #[cfg(test)]
+        command.args(["-c", "protocol.file.allow=always"]);
+
+        command.args([
+            "submodule",
+            "update",
+            "--init",
+            "--recursive",
+            "--force",
+            "--",
+        ]);
+        command.args(&paths);
+        command.stdout(Stdio::piped());
+        command.stderr(Stdio::piped());
+        set_ssh_key(&mut command, self.repo.ssh_key.as_deref()).map_err(GitError::FetchError)?;
+        set_https_credentials(&mut command, self.repo.https_credentials.as_ref());
+
+        let output = command.output()?;
+        if !output.status.success() {
+            return Err(GitError::SubmoduleUpdateFailed {
+                status: output.status,
+                message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Removes the excluded files of the tree from the working directory, checking the tree out
+    /// again restores them
+    fn remove_excluded_files(&self, tree: &git2::Tree) -> Result<(), GitError> {
+        if self.repo.excluded_paths.is_empty() {
+            return Ok(());
+        }
+
+        let mut excluded_files = vec![];
+        tree.walk(git2::TreeWalkMode::PreOrder, |root, entry| {
+            if let Some(name) = entry.name() {
+                let path = format!("{root}{name}");
+                if entry.kind() == Some(ObjectType::Blob) && self.repo.is_excluded(&path) {
+                    excluded_files.push(path);
+                }
+            }
+            git2::TreeWalkResult::Ok
+        })?;
+
+        for path in excluded_files {
+            match std::fs::remove_file(self.path.join(path)) {
+                // Not checked out by a partial checkout
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                result => result?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks out only the files matching the given pathspecs, e.g. `src` or `*.rs`, so
+    /// collectors scoped to a part of the repository don't have to materialize the entire tree.
+    ///
+    /// All other files are removed from the working directory, `reset_hard` restores them.
+    pub fn checkout_paths(&self, revstring: &str, paths: &[&str]) -> Result<(), GitError> {
+        let pathspec = git2::Pathspec::new(paths)?;
+
+        self.checkout_subset(revstring, paths, |path| {
+            pathspec.matches_path(Path::new(path), git2::PathspecFlags::DEFAULT)
+        })
+    }
+
+    /// Like `checkout_paths`, but checks out the files whose path passes the filter, e.g. the
+    /// ones matching the globs a metric is scoped to. The ignore files are checked out as well,
+    /// as they decide which files collectors like `loc` skip.
+    pub fn checkout_matching(
+        &self,
+        revstring: &str,
+        filter: impl Fn(&str) -> bool,
+    ) -> Result<(), GitError> {
         let git2_repo: Repository = self.try_into()?;
+        let tree = git2_repo.revparse_single(revstring)?.peel_to_tree()?;
+
+        let is_ignore_file = |name: &str| matches!(name, ".gitignore" | ".ignore");
+
+        let mut paths = BTreeSet::new();
+        tree.walk(git2::TreeWalkMode::PreOrder, |root, entry| {
+            let name = entry.name().unwrap_or_default();
+            let path = format!("{root}{name}");
+
+            let checked_out = match entry.kind() {
+                Some(ObjectType::Blob) => filter(&path) || is_ignore_file(name),
+                // Submodules
+                Some(ObjectType::Commit) => filter(&path),
+                _ => false,
+            };
+
+            if checked_out && !self.repo.is_excluded(&path) {
+                paths.insert(path);
+            }
+            git2::TreeWalkResult::Ok
+        })?;
+
+        // The paths are taken literally instead of as patterns
+        let pathspecs: Vec<String> = paths
+            .iter()
+            .map(|path| {
+                path.chars()
+                    .flat_map(|c| match c {
+                        '*' | '?' | '[' | '\\' => vec!['\\', c],
+                        _ => vec![c],
+                    })
+                    .collect()
+            })
+            .collect();
+        let pathspecs: Vec<&str> = pathspecs.iter().map(String::as_str).collect();
+
+        self.checkout_subset(revstring, &pathspecs, |path| paths.contains(path))
+    }
+
+    /// Empties the working directory and checks out the files matching the pathspecs, which the
+    /// filter has to match as well
+    fn checkout_subset(
+        &self,
+        revstring: &str,
+        paths: &[&str],
+        filter: impl Fn(&str) -> bool,
+    ) -> Result<(), GitError> {
+        let git2_repo: Repository = self.try_into()?;
+
+        let (object, _) = git2_repo.revparse_ext(revstring)?;
+
+        // Only the blobs of the checked out files are fetched
+        self.repo
+            .fetch_missing_tree_blobs(&git2_repo, &object.peel_to_tree()?, &filter)?;
+
+        for entry in std::fs::read_dir(&self.path)? {
+            let entry = entry?;
+
+            if entry.file_name() == ".git" {
+                continue;
+            }
+
+            if entry.file_type()?.is_dir() {
+                std::fs::remove_dir_all(entry.path())?;
+            } else {
+                std::fs::remove_file(entry.path())?;
+            }
+        }
+
+        retry_when_locked(|| {
+            // Without any paths, the checkout would include all files
+            if !paths.is_empty() {
+                let mut checkout = CheckoutBuilder::new();
+                checkout.force();
+                for path in paths {
+                    checkout.path(path);
+                }
+
+                git2_repo.checkout_tree(&object, Some(&mut checkout))?;
+            }
+            git2_repo.set_head_detached(object.id())?;
+
+            Ok(())
+        })?;
+
+        self.remove_excluded_files(&object.peel_to_tree()?)?;
+
+        self.update_submodules(&filter)
+    }
+
+    pub fn get_current_total_diff_stat(&self) -> Result<(usize, usize, usize), GitError> {
+        let git2_repo: Repository = self.try_into()?;
+        let diff = get_diff_to_parent(&git2_repo, "HEAD", &mut DiffOptions::new())?;
+        self.repo.fetch_missing_diff_blobs(&git2_repo, &diff)?;
+        get_diff_stat(&diff, |path| !self.repo.is_excluded(path))
+    }
+
+    /// Like `get_current_total_diff_stat`, but lines which only differ in whitespace are
+    /// considered unchanged
+    pub fn get_current_total_diff_stat_ignoring_whitespace(
+        &self,
+    ) -> Result<(usize, usize, usize), GitError> {
+        let git2_repo: Repository = self.try_into()?;
+        let diff = get_diff_to_parent(
+            &git2_repo,
+            "HEAD",
+            DiffOptions::new().ignore_whitespace(true),
+        )?;
+        self.repo.fetch_missing_diff_blobs(&git2_repo, &diff)?;
+        get_diff_stat(&diff, |path| !self.repo.is_excluded(path))
+    }
+
+    pub fn get_current_changed_file_paths(&self) -> Result<HashSet<String>, GitError> {
+        let git2_repo: Repository = self.try_into()?;
+        let diff = get_diff_to_parent(&git2_repo, "HEAD", &mut DiffOptions::new())?;
+        let mut changed_files = get_changed_file_paths(&diff)?;
+        changed_files.retain(|path| !self.repo.is_excluded(path));
+        Ok(changed_files)
+    }
+
+    /// Returns a reader for the files at the given commit in the object database, which doesn't
+    /// need the commit checked out. If submodules are enabled, the files of the ones checked out
+    /// in this worktree are read as well.
+    pub fn tree_reader(&self, commit_hash: &CommitHash) -> Result<TreeReader<'_>, GitError> {
+        self.repo.tree_reader_with_submodules(
+            commit_hash,
+            self.repo.submodules.then(|| self.path.clone()),
+        )
+    }
+
+    /// The repository this worktree belongs to, e.g. to read other commits from its object
+    /// database
+    #[must_use]
+    pub fn repository(&self) -> &RepositoryHandle {
+        self.repo
+    }
+
+    pub fn remove(self) -> Result<(), GitError> {
+        self.repo.remove_worktree(&self.name, Some(false))
+    }
+
+    /// Calls the callback with the path of the parent directory (empty or ending with `/`) and
+    /// each entry of the tree of the current commit which is not excluded, including the ones of
+    /// checked out submodules
+    fn walk_tree(&self, mut callback: impl FnMut(&str, &git2::TreeEntry)) -> Result<(), GitError> {
+        let git2_repo: Repository = self.try_into()?;
+
+        self.walk_repository_tree(&git2_repo, "", &mut callback)
+    }
+
+    fn walk_repository_tree(
+        &self,
+        git2_repo: &Repository,
+        prefix: &str,
+        callback: &mut impl FnMut(&str, &git2::TreeEntry),
+    ) -> Result<(), GitError> {
+        let mut submodules = vec![];
+
+        let tree = git2_repo.find_tree(git2_repo.head()?.peel_to_tree()?.id())?;
+        tree.walk(git2::TreeWalkMode::PreOrder, |root, entry| {
+            let root = format!("{prefix}{root}");
+            let path = format!("{root}{}", entry.name().unwrap_or_default());
+            if self.repo.is_excluded(&path) {
+                return git2::TreeWalkResult::Ok;
+            }
+
+            if self.repo.submodules && entry.filemode() == i32::from(git2::FileMode::Commit) {
+                submodules.push(path);
+            }
+
+            callback(&root, entry);
+            git2::TreeWalkResult::Ok
+        })?;
+
+        for path in submodules {
+            // Submodules which weren't checked out are empty directories
+            let Ok(submodule_repo) = Repository::open(self.path.join(&path)) else {
+                continue;
+            };
+
+            self.walk_repository_tree(&submodule_repo, &format!("{path}/"), callback)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the names of the entries in the tree of the current commit whose path passes the
+    /// filter
+    pub fn list_files(&self, filter: impl Fn(&str) -> bool) -> Result<Vec<String>, GitError> {
+        let mut files = Vec::new();
+
+        self.walk_tree(|root, entry| {
+            if let Some(name) = entry.name() {
+                if filter(&format!("{root}{name}")) {
+                    files.push(name.to_string());
+                }
+            }
+        })?;
+
+        Ok(files)
+    }
+
+    /// Opens a file of the worktree by its path relative to the root of the worktree, `None` if
+    /// it doesn't exist or is excluded
+    pub fn read_file(
+        &self,
+        relative_path: impl AsRef<Path>,
+    ) -> Result<Option<BufReader<File>>, GitError> {
+        let relative_path = relative_path.as_ref();
+        if self.repo.is_excluded(relative_path) {
+            return Ok(None);
+        }
+
+        match File::open(self.path.join(relative_path)) {
+            Ok(file) => Ok(Some(BufReader::new(file))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Returns the paths of the files in the tree of the current commit which match the globs,
+    /// relative to the root of the worktree. Excluded files, symlinks, submodules and files
+    /// missing from a partial checkout are skipped.
+    pub fn walk_tracked_files(&self, globs: Option<&GlobSet>) -> Result<Vec<String>, GitError> {
+        let mut files = Vec::new();
+
+        self.walk_tree(|root, entry| {
+            let is_file = entry.kind() == Some(ObjectType::Blob)
+                && entry.filemode() != i32::from(git2::FileMode::Link);
+
+            if let (true, Some(name)) = (is_file, entry.name()) {
+                let path = format!("{root}{name}");
+                if globs.is_none_or(|globs| globs.is_match_candidate(&Candidate::new(&path)))
+                    && self.path.join(&path).is_file()
+                {
+                    files.push(path);
+                }
+            }
+        })?;
+
+        Ok(files)
+    }
+
+    /// Returns the number of symlinks and submodules in the tree of the current commit
+    pub fn count_symlinks_and_submodules(&self) -> Result<(usize, usize), GitError> {
+        let mut symlinks = 0;
+        let mut submodules = 0;
+
+        self.walk_tree(|_, entry| {
+            let filemode = entry.filemode();
+
+            if filemode == i32::from(git2::FileMode::Link) {
+                symlinks += 1;
+            } else if filemode == i32::from(git2::FileMode::Commit) {
+                submodules += 1;
+            }
+        })?;
+
+        Ok((symlinks, submodules))
+    }
+}
+
+impl TreeReader<'_> {
+    /// Returns the paths of the files which pass the filter
+    pub fn list_files(&self, filter: impl Fn(&str) -> bool) -> Result<Vec<String>, GitError> {
+        let mut files = Vec::new();
+
+        self.walk_files(&self.git2_repo, self.tree_id, "", &mut |_, path, _| {
+            if filter(path) {
+                files.push(path.to_string());
+            }
+            Ok(())
+        })?;
+
+        Ok(files)
+    }
+
+    /// Reads the contents of a file by its path relative to the repository root, `None` if it
+    /// doesn't exist or is excluded
+    pub fn read_file(&self, path: &str) -> Result<Option<Vec<u8>>, GitError> {
+        if self.repo.is_excluded(path) {
+            return Ok(None);
+        }
+
+        self.read_repository_file(&self.git2_repo, self.tree_id, "", path)
+    }
+
+    /// Calls the callback with the path and contents of every file which passes the filter, one
+    /// after another. Blobs missing from a partial clone are fetched up front in one request.
+    pub fn for_each_file(
+        &self,
+        filter: impl Fn(&str) -> bool,
+        mut callback: impl FnMut(&str, &[u8]),
+    ) -> Result<(), GitError> {
+        let tree = self.git2_repo.find_tree(self.tree_id)?;
+        self.repo
+            .fetch_missing_tree_blobs(&self.git2_repo, &tree, |path| {
+                !self.repo.is_excluded(path) && filter(path)
+            })?;
+
+        self.walk_files(
+            &self.git2_repo,
+            self.tree_id,
+            "",
+            &mut |git2_repo, path, id| {
+                if filter(path) {
+                    callback(path, git2_repo.find_blob(id)?.content());
+                }
+                Ok(())
+            },
+        )
+    }
+
+    /// Calls the callback with the repository, path and blob id of each file of the tree which is
+    /// not excluded, followed by the ones of the submodules
+    fn walk_files(
+        &self,
+        git2_repo: &Repository,
+        tree_id: Oid,
+        prefix: &str,
+        callback: &mut impl FnMut(&Repository, &str, Oid) -> Result<(), GitError>,
+    ) -> Result<(), GitError> {
+        let mut submodules = vec![];
+        let mut result = Ok(());
+
+        let tree = git2_repo.find_tree(tree_id)?;
+        let walked = tree.walk(git2::TreeWalkMode::PreOrder, |root, entry| {
+            let Some(name) = entry.name() else {
+                return git2::TreeWalkResult::Ok;
+            };
+
+            let path = format!("{prefix}{root}{name}");
+            if self.repo.is_excluded(&path) {
+                return git2::TreeWalkResult::Ok;
+            }
+
+            if entry.filemode() == i32::from(git2::FileMode::Commit) {
+                submodules.push((path, entry.id()));
+            } else if entry.kind() == Some(ObjectType::Blob)
+                && entry.filemode() != i32::from(git2::FileMode::Link)
+            {
+                if let Err(e) = callback(git2_repo, &path, entry.id()) {
+                    result = Err(e);
+                    return git2::TreeWalkResult::Abort;
+                }
+            }
+
+            git2::TreeWalkResult::Ok
+        });
+
+        // Aborting the walk is reported as an error as well, the one of the callback is the cause
+        result?;
+        walked?;
+
+        for (path, commit_id) in submodules {
+            if let Some((submodule_repo, submodule_tree_id)) = self.open_submodule(&path, commit_id)
+            {
+                self.walk_files(
+                    &submodule_repo,
+                    submodule_tree_id,
+                    &format!("{path}/"),
+                    callback,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_repository_file(
+        &self,
+        git2_repo: &Repository,
+        tree_id: Oid,
+        prefix: &str,
+        path: &str,
+    ) -> Result<Option<Vec<u8>>, GitError> {
+        let tree = git2_repo.find_tree(tree_id)?;
+
+        if let Ok(entry) = tree.get_path(Path::new(path)) {
+            if entry.kind() != Some(ObjectType::Blob)
+                || entry.filemode() == i32::from(git2::FileMode::Link)
+            {
+                return Ok(None);
+            }
+
+            // Submodules are never partial clones
+            if prefix.is_empty() {
+                self.repo.fetch_missing_blobs(git2_repo, [entry.id()])?;
+            }
+
+            return Ok(Some(git2_repo.find_blob(entry.id())?.content().to_vec()));
+        }
+
+        // The file might be inside of a submodule
+        for (idx, _) in path.match_indices('/') {
+            let Ok(entry) = tree.get_path(Path::new(&path[..idx])) else {
+                return Ok(None);
+            };
+
+            if entry.filemode() == i32::from(git2::FileMode::Commit) {
+                let submodule_path = format!("{prefix}{}", &path[..idx]);
+                let Some((submodule_repo, submodule_tree_id)) =
+                    self.open_submodule(&submodule_path, entry.id())
+                else {
+                    return Ok(None);
+                };
+
+                return self.read_repository_file(
+                    &submodule_repo,
+                    submodule_tree_id,
+                    &format!("{submodule_path}/"),
+                    &path[idx + 1..],
+                );
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Opens the repository of a checked out submodule and finds the tree of the commit it is at,
+    /// `None` if it isn't checked out or doesn't have the commit
+    fn open_submodule(&self, path: &str, commit_id: Oid) -> Option<(Repository, Oid)> {
+        let submodule_repo = Repository::open(self.submodules_path.as_ref()?.join(path)).ok()?;
+        let tree_id = submodule_repo.find_commit(commit_id).ok()?.tree_id();
+
+        Some((submodule_repo, tree_id))
+    }
+}
+
+fn get_diff_to_parent<'a>(
+    repo: &'a Repository,
+    revstring: &str,
+    options: &mut DiffOptions,
+) -> Result<Diff<'a>, GitError> {
+    // To diff the first commit in a repository, we need something to diff it against other than it's parent
+    // This object is the empty tree. See https://stackoverflow.com/a/40884093 for more details.
+    let empty_tree = repo.find_tree(Oid::from_str("4b825dc642cb6eb9a060e54bf8d69288fbee4904")?)?;
+
+    let Some(t1) = tree_to_treeish(repo, Some(&format!("{revstring}^")))
+        .unwrap_or(Some(empty_tree.into_object()))
+    else {
+        return Err(GitError::FailedToGetGitObject);
+    };
+
+    let Some(t2) = tree_to_treeish(repo, Some(&revstring.to_string()))? else {
+        return Err(GitError::FailedToGetGitObject);
+    };
+
+    let diff = repo.diff_tree_to_tree(t1.as_tree(), t2.as_tree(), Some(options))?;
+
+    Ok(diff)
+}
+
+/// Returns the number of files changed and lines inserted and deleted, only counting the files
+/// whose path passes the filter
+fn get_diff_stat(
+    diff: &Diff,
+    filter: impl Fn(&Path) -> bool,
+) -> Result<(usize, usize, usize), GitError> {
+    let (mut files_changed, mut insertions, mut deletions) = (0, 0, 0);
+
+    for (idx, delta) in diff.deltas().enumerate() {
+        // Deleted files only have an old path
+        let Some(path) = delta.new_file().path().or(delta.old_file().path()) else {
+            continue;
+        };
+
+        if !filter(path) {
+            continue;
+        }
+
+        files_changed += 1;
+
+        // Binary files have no patch
+        if let Some(patch) = git2::Patch::from_diff(diff, idx)? {
+            let (_, additions, removals) = patch.line_stats()?;
+            insertions += additions;
+            deletions += removals;
+        }
+    }
+
+    Ok((files_changed, insertions, deletions))
+}
+
+fn get_changed_file_paths(diff: &Diff) -> Result<HashSet<String>, GitError> {
+    let mut diff_lines = Vec::new();
+    diff.print(DiffFormat::NameOnly, |_, _, l| {
+        diff_lines.push(l.content().to_vec());
+        true
+    })?;
+
+    let mut changed_files: HashSet<String> = HashSet::new();
+    for l in diff_lines {
+        changed_files.insert(String::from_utf8(l)?.trim_end().to_string());
+    }
+
+    Ok(changed_files)
+}
+
+fn tree_to_treeish<'a>(
+    repo: &'a Repository,
+    arg: Option<&String>,
+) -> Result<Option<Object<'a>>, git2::Error> {
+    let Some(arg) = arg else {
+        return Ok(None);
+    };
+
+    let obj = repo.revparse_single(arg)?;
+    let tree = obj.peel(ObjectType::Tree)?;
+    Ok(Some(tree))
+}
+
+#[derive(PartialEq, Debug)]
+pub enum CloneProgress {
+    EnumeratingObjects,
+    CountingObjects { finished: usize, total: usize },
+    CompressingObjects { finished: usize, total: usize },
+    ReceivingObjects { finished: usize, total: usize },
+    ResolvingDeltas { finished: usize, total: usize },
+}
+
+impl CloneProgress {
+    pub(crate) fn try_from(line: &str) -> Result<CloneProgress, GitCloneError> {
+        if line.starts_with("Enumerating objects:") {
+            return Ok(CloneProgress::EnumeratingObjects);
+        }
+
+        if line.starts_with("Counting objects:")
+            || line.starts_with("Compressing objects:")
+            || line.starts_with("Receiving objects:")
+            || line.starts_with("Resolving deltas:")
+        {
+            let re = Regex::new(r"%\s\((\d+\/\d+)\)")?;
+
+            let progress = re
+                .find(line)
+                .ok_or(GitCloneError::FailedToMatchProgress(line.to_string()))?;
+
+            let (finished, total) = {
+                let mut tmp = progress.as_str().split('(');
+                tmp.next();
+
+                let Some(tmp) = tmp.next() else {
+                    return Err(GitCloneError::FailedToMatchProgress(line.to_string()));
+                };
+
+                let tmp = tmp.replace(')', "");
+                let mut parts = tmp.split('/');
+
+                let Some(finished) = parts.next() else {
+                    return Err(GitCloneError::FailedToMatchProgress(line.to_string()));
+                };
+
+                let finished = finished.parse::<usize>()?;
+
+                let Some(total) = parts.next() else {
+                    return Err(GitCloneError::FailedToMatchProgress(line.to_string()));
+                };
+
+                let total = total.trim().parse::<usize>()?;
+
+                (finished, total)
+            };
+
+            if line.starts_with("Counting objects:") {
+                return Ok(CloneProgress::CountingObjects { finished, total });
+            }
+
+            if line.starts_with("Compressing objects:") {
+                return Ok(CloneProgress::CompressingObjects { finished, total });
+            }
+
+            if line.starts_with("Receiving objects:") {
+                return Ok(CloneProgress::ReceivingObjects { finished, total });
+            }
+
+            if line.starts_with("Resolving deltas:") {
+                return Ok(CloneProgress::ResolvingDeltas { finished, total });
+            }
+        }
+
+        Err(GitCloneError::FailedToMatchProgress(line.to_string()))
+    }
+
+    fn is_complete(&self) -> bool {
+        match self {
+            CloneProgress::EnumeratingObjects => true,
+            CloneProgress::CountingObjects { finished, total }
+            | CloneProgress::CompressingObjects { finished, total }
+            | CloneProgress::ReceivingObjects { finished, total }
+            | CloneProgress::ResolvingDeltas { finished, total } => finished >= total,
+        }
+    }
+}
+
+/// Limits how often progress is reported, git prints an update for every percent of every phase.
+///
+/// The first and last update of each phase are always reported.
+struct CloneProgressThrottle {
+    interval: Duration,
+    last_reported: Option<(Instant, Discriminant<CloneProgress>)>,
+}
+
+impl CloneProgressThrottle {
+    fn new(interval: Duration) -> Self {
+        CloneProgressThrottle {
+            interval,
+            last_reported: None,
+        }
+    }
+
+    fn should_report(&mut self, progress: &CloneProgress, now: Instant) -> bool {
+        let phase = discriminant(progress);
+
+        let should_report = match self.last_reported {
+            Some((last_time, last_phase)) => {
+                last_phase != phase
+                    || progress.is_complete()
+                    || now.duration_since(last_time) >= self.interval
+            }
+            None => true,
+        };
+
+        if should_report {
+            self.last_reported = Some((now, phase));
+        }
+
+        should_report
+    }
+}
+
+/// Summary of a finished clone, e.g. for logging
+#[derive(Debug, Clone)]
+pub struct CloneSummary {
+    /// Number of objects received from the remote as reported by git, zero if git didn't report
+    /// any progress, e.g. for local clones
+    pub objects: usize,
+
+    /// Size of the object database after cloning
+    pub bytes: u64,
+
+    pub duration: Duration,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::rstest;
+    use std::io::Read;
+
+    #[rstest]
+    #[case(std::io::ErrorKind::Interrupted, true)]
+    #[case(std::io::ErrorKind::TimedOut, true)]
+    #[case(std::io::ErrorKind::Other, true)]
+    #[case(std::io::ErrorKind::NotFound, false)]
+    #[case(std::io::ErrorKind::PermissionDenied, false)]
+    fn test_is_transient_io_error(#[case] kind: std::io::ErrorKind, #[case] expected: bool) {
+        assert_eq!(is_transient_io_error(&std::io::Error::from(kind)), expected);
+    }
+
+    #[test]
+    fn test_retry_when_locked() {
+        let locked = || {
+            GitError::Git2Erorr(git2::Error::new(
+                git2::ErrorCode::Locked,
+                git2::ErrorClass::Index,
+                "index is locked",
+            ))
+        };
+
+        let mut attempts = 0;
+        let result = retry_when_locked(|| {
+            attempts += 1;
+            if attempts < 3 {
+                Err(locked())
+            } else {
+                Ok(attempts)
+            }
+        });
+        assert_eq!(result.unwrap(), 3);
+
+        // Gives up after the last attempt and doesn't retry other errors
+        let mut attempts = 0;
+        assert!(retry_when_locked(|| -> Result<(), _> {
+            attempts += 1;
+            Err(locked())
+        })
+        .is_err());
+        assert_eq!(attempts, MAX_LOCK_ATTEMPTS);
+
+        let mut attempts = 0;
+        assert!(retry_when_locked(|| -> Result<(), _> {
+            attempts += 1;
+            Err(GitError::FailedToGetGitObject)
+        })
+        .is_err());
+        assert_eq!(attempts, 1);
+    }
+
+    #[rstest]
+    #[case("Enumerating objects: 2341, done.", CloneProgress::EnumeratingObjects)]
+    #[case("Counting objects:   1% (4/336)", CloneProgress::CountingObjects { finished: 4, total: 336 })]
+    #[case("Compressing objects:   1% (2/141)", CloneProgress::CompressingObjects { finished: 2, total: 141 })]
+    #[case("Receiving objects:   1% (24/2341)", CloneProgress::ReceivingObjects { finished: 24, total: 2341 })]
+    #[case("Resolving deltas:   1% (14/1203)", CloneProgress::ResolvingDeltas { finished: 14, total: 1203 })]
+    fn test_clone_progress_from_line(#[case] input: &str, #[case] expected: CloneProgress) {
+        assert_eq!(expected, CloneProgress::try_from(input).unwrap());
+    }
+
+    #[rstest]
+    #[case(b"a\nb\r\nc", vec!["a", "b", "c"])]
+    #[case(b"\r\r\n", vec![])]
+    #[case(b"", vec![])]
+    #[case("\u{fc}ber\r".as_bytes(), vec!["\u{fc}ber"])]
+    fn test_delimited_by(#[case] input: &[u8], #[case] expected: Vec<&str>) {
+        // A tiny buffer splits lines and characters across several reads
+        let reader = BufReader::with_capacity(2, input);
+
+        let lines: Vec<String> = delimited_by(reader, &['\n', '\r'])
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(expected, lines);
+    }
+
+    #[test]
+    fn test_clone_progress_throttle() {
+        let mut throttle = CloneProgressThrottle::new(Duration::from_millis(100));
+        let start = Instant::now();
+        let receiving = |finished| CloneProgress::ReceivingObjects {
+            finished,
+            total: 100,
+        };
+
+        assert!(throttle.should_report(&CloneProgress::EnumeratingObjects, start));
+        assert!(throttle.should_report(&receiving(1), start));
+        assert!(!throttle.should_report(&receiving(2), start + Duration::from_millis(50)));
+        assert!(throttle.should_report(&receiving(3), start + Duration::from_millis(100)));
+        assert!(!throttle.should_report(&receiving(4), start + Duration::from_millis(150)));
+        assert!(throttle.should_report(&receiving(100), start + Duration::from_millis(160)));
+        assert!(throttle.should_report(
+            &CloneProgress::ResolvingDeltas {
+                finished: 0,
+                total: 10
+            },
+            start + Duration::from_millis(170)
+        ));
+    }
+
+    /// Creates a repository with a single commit containing the given files and an empty
+    /// directory for worktrees
+    fn create_test_repository(files: &[&str]) -> (PathBuf, PathBuf) {
+        let suffix: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(12)
+            .map(char::from)
+            .collect();
+        let repository_path = temp_dir().join(format!("myaku-repo-{suffix}"));
+        let worktrees_path = temp_dir().join(format!("myaku-worktrees-{suffix}"));
+        std::fs::create_dir_all(&worktrees_path).unwrap();
+
+        let git2_repo = Repository::init(&repository_path).unwrap();
+        let mut index = git2_repo.index().unwrap();
+        for file in files {
+            let path = repository_path.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, file).unwrap();
+            index.add_path(Path::new(file)).unwrap();
+        }
+        index.write().unwrap();
+
+        let signature = Signature::now("Dummy", "dummy@test.com").unwrap();
+        let tree = git2_repo.find_tree(index.write_tree().unwrap()).unwrap();
+        git2_repo
+            .commit(Some("HEAD"), &signature, &signature, "Initial", &tree, &[])
+            .unwrap();
+
+        (repository_path, worktrees_path)
+    }
+
+    #[test]
+    fn test_clone_reports_progress() {
+        let (repository_path, worktrees_path) = create_test_repository(&["a.rs", "src/b.rs"]);
+        let clone_path = worktrees_path.join("clone");
+
+        let received_objects = std::sync::Mutex::new(None);
+
+        // Cloning through the file protocol runs the same transport as remotes, output in the
+        // user's language would not be recognized as progress at all
+        let (_, summary) = clone_repository(
+            &format!("file://{}", repository_path.display()),
+            &clone_path,
+            |progress| {
+                if let CloneProgress::ReceivingObjects { total, .. } = progress {
+                    *received_objects.lock().unwrap() = Some(*total);
+                }
+            },
+            None,
+            None,
+            None,
+            None,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(Some(summary.objects), *received_objects.lock().unwrap());
+        assert!(summary.objects > 0);
+
+        std::fs::remove_dir_all(&repository_path).unwrap();
+        std::fs::remove_dir_all(&worktrees_path).unwrap();
+    }
+
+    #[test]
+    fn test_fetch_receives_new_commits() {
+        let (repository_path, worktrees_path) = create_test_repository(&["a.rs"]);
+        let clone_path = worktrees_path.join("clone");
+        let url = format!("file://{}", repository_path.display());
+
+        let (clone, _) =
+            clone_repository(&url, &clone_path, |_| {}, None, None, None, None, false).unwrap();
+        let branch = clone.find_main_branch().unwrap();
+
+        let git2_repo = Repository::open(&repository_path).unwrap();
+        let parent = git2_repo.head().unwrap().peel_to_commit().unwrap();
+        let signature = Signature::now("Dummy", "dummy@test.com").unwrap();
+        let new_commit = git2_repo
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                "Second",
+                &parent.tree().unwrap(),
+                &[&parent],
+            )
+            .unwrap();
+
+        clone.fetch(|_| {}, None, None).unwrap();
+        assert_eq!(
+            clone
+                .resolve_revision(&clone.branch_revision(&branch))
+                .unwrap(),
+            CommitHash::from(new_commit)
+        );
+
+        // Errors of git are part of the message instead of only its exit code
+        std::fs::remove_dir_all(&repository_path).unwrap();
+        let error = clone.fetch(|_| {}, None, None).unwrap_err();
+        assert!(matches!(error, GitError::FetchFailed { .. }));
+        assert!(error.to_string().contains("fatal:"));
+
+        std::fs::remove_dir_all(&worktrees_path).unwrap();
+    }
+
+    #[test]
+    fn test_shallow_clone_is_deepened() {
+        let (repository_path, worktrees_path) = create_test_repository(&["a.rs"]);
+        let clone_path = worktrees_path.join("clone");
+
+        let git2_repo = Repository::open(&repository_path).unwrap();
+        let signature = Signature::now("Dummy", "dummy@test.com").unwrap();
+        for message in ["Second", "Third"] {
+            let parent = git2_repo.head().unwrap().peel_to_commit().unwrap();
+            git2_repo
+                .commit(
+                    Some("HEAD"),
+                    &signature,
+                    &signature,
+                    message,
+                    &parent.tree().unwrap(),
+                    &[&parent],
+                )
+                .unwrap();
+        }
+
+        // Local paths are copied regardless of the depth, only the file protocol respects it
+        let (clone, _) = clone_repository(
+            &format!("file://{}", repository_path.display()),
+            &clone_path,
+            |_| {},
+            None,
+            None,
+            None,
+            Some(1),
+            false,
+        )
+        .unwrap();
+
+        let reachable = |clone: &RepositoryHandle| {
+            let branch = clone.find_main_branch().unwrap();
+            let head = clone
+                .resolve_revision(&clone.branch_revision(&branch))
+                .unwrap();
+            clone.get_commits_reachable_from(&head).unwrap().len()
+        };
+
+        assert!(clone.is_shallow().unwrap());
+        assert_eq!(reachable(&clone), 1);
+
+        clone.deepen(1, None, None).unwrap();
+        assert_eq!(reachable(&clone), 2);
+
+        clone.unshallow(None, None).unwrap();
+        assert!(!clone.is_shallow().unwrap());
+        assert_eq!(reachable(&clone), 3);
+
+        std::fs::remove_dir_all(&repository_path).unwrap();
+        std::fs::remove_dir_all(&worktrees_path).unwrap();
+    }
+
+    #[test]
+    fn test_partial_clone_fetches_missing_blobs() {
+        let (repository_path, worktrees_path) = create_test_repository(&["a.rs", "src/b.rs"]);
+        let clone_path = worktrees_path.join("clone");
+
+        let git2_repo = Repository::open(&repository_path).unwrap();
+        git2_repo
+            .config()
+            .unwrap()
+            .set_bool("uploadpack.allowFilter", true)
+            .unwrap();
+
+        let parent = git2_repo.head().unwrap().peel_to_commit().unwrap();
+        let signature = Signature::now("Dummy", "dummy@test.com").unwrap();
+        std::fs::write(repository_path.join("a.rs"), "changed").unwrap();
+        let mut index = git2_repo.index().unwrap();
+        index.add_path(Path::new("a.rs")).unwrap();
+        let tree = git2_repo.find_tree(index.write_tree().unwrap()).unwrap();
+        git2_repo
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                "Second",
+                &tree,
+                &[&parent],
+            )
+            .unwrap();
+
+        let (clone, _) = clone_repository(
+            &format!("file://{}", repository_path.display()),
+            &clone_path,
+            |_| {},
+            None,
+            None,
+            None,
+            None,
+            true,
+        )
+        .unwrap();
+        assert!(clone.partial);
+        assert!(RepositoryHandle::open(&clone_path).unwrap().partial);
+
+        // Only the blobs of the checked out commit were cloned
+        let first = CommitHash(parent.id().to_string());
+        let first_blob = parent
+            .tree()
+            .unwrap()
+            .get_path(Path::new("a.rs"))
+            .unwrap()
+            .id();
+        let clone_repo = Repository::open(&clone_path).unwrap();
+        assert!(!clone_repo.odb().unwrap().exists(first_blob));
+
+        assert_eq!(
+            clone.read_file(&first, "a.rs").unwrap(),
+            Some(b"a.rs".to_vec())
+        );
+        assert_eq!(clone.get_total_diff_stat(&first).unwrap(), (2, 2, 0));
+
+        let worktree = clone
+            .create_temp_worktree("partial", &worktrees_path.join("partial"))
+            .unwrap();
+        worktree.as_ref().reset_hard(&first.0).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(worktrees_path.join("partial/src/b.rs")).unwrap(),
+            "src/b.rs"
+        );
+        drop(worktree);
+
+        std::fs::remove_dir_all(&repository_path).unwrap();
+        std::fs::remove_dir_all(&worktrees_path).unwrap();
+    }
 
-        let (object, _) = git2_repo.revparse_ext(revstring)?;
-        git2_repo.checkout_tree(&object, None)?;
-        git2_repo.set_head_detached(object.id())?;
+    #[test]
+    fn test_submodules_are_checked_out_unless_excluded() {
+        let (submodule_path, submodule_worktrees_path) = create_test_repository(&["lib.rs"]);
+        let (repository_path, worktrees_path) = create_test_repository(&["main.rs"]);
+
+        let git = |args: &[&str]| {
+            let mut command = git_command();
+            command.current_dir(&repository_path);
+            command.args(["-c", "protocol.file.allow=always"]);
+            command.args(["-c", "user.name=Dummy", "-c", "user.email=dummy@test.com"]);
+            command.args(args);
+            command.execute_check_exit_status_code(0).unwrap();
+        };
+        git(&[
+            "submodule",
+            "add",
+            &submodule_path.to_string_lossy(),
+            "vendor",
+        ]);
+        git(&["commit", "-m", "Add submodule"]);
+
+        let mut repo = RepositoryHandle::open(&repository_path).unwrap();
+        let head = repo.resolve_revision("HEAD").unwrap();
+
+        let worktree = repo
+            .create_temp_worktree("without", &worktrees_path.join("without"))
+            .unwrap();
+        worktree.as_ref().reset_hard(&head.0).unwrap();
+        assert!(!worktrees_path.join("without/vendor/lib.rs").exists());
+        drop(worktree);
+
+        repo.set_submodules(true);
+        let worktree = repo
+            .create_temp_worktree("with", &worktrees_path.join("with"))
+            .unwrap();
+        worktree.as_ref().reset_hard(&head.0).unwrap();
+        let mut files = worktree.as_ref().walk_tracked_files(None).unwrap();
+        files.sort();
+        assert_eq!(files, [".gitmodules", "main.rs", "vendor/lib.rs"]);
+
+        let reader = worktree.as_ref().tree_reader(&head).unwrap();
+        let mut files = reader.list_files(|_| true).unwrap();
+        files.sort();
+        assert_eq!(files, [".gitmodules", "main.rs", "vendor/lib.rs"]);
+        assert_eq!(
+            Some(b"lib.rs".to_vec()),
+            reader.read_file("vendor/lib.rs").unwrap()
+        );
+        drop(reader);
+        drop(worktree);
+
+        // The files of submodules are not part of the object database of the repository
+        let reader = repo.tree_reader(&head).unwrap();
+        assert_eq!(None, reader.read_file("vendor/lib.rs").unwrap());
+        assert_eq!(
+            vec![".gitmodules", "main.rs"],
+            reader.list_files(|_| true).unwrap()
+        );
+        drop(reader);
+
+        repo.set_excluded_paths(&[Glob::new("vendor/**").unwrap()])
+            .unwrap();
+        let worktree = repo
+            .create_temp_worktree("excluded", &worktrees_path.join("excluded"))
+            .unwrap();
+        worktree.as_ref().reset_hard(&head.0).unwrap();
+        assert!(!worktrees_path.join("excluded/vendor/lib.rs").exists());
+        drop(worktree);
+
+        for path in [
+            repository_path,
+            worktrees_path,
+            submodule_path,
+            submodule_worktrees_path,
+        ] {
+            std::fs::remove_dir_all(path).unwrap();
+        }
+    }
 
-        Ok(())
+    #[test]
+    fn test_https_credentials_are_answered() {
+        use std::io::Write;
+
+        let mut command = git_command();
+        command.args(["credential", "fill"]);
+        set_https_credentials(
+            &mut command,
+            Some(&HttpsCredentials::Token {
+                username: "x-access-token".to_string(),
+                token: "secret token".to_string(),
+            }),
+        );
+
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(b"protocol=https\nhost=example.com\n\n")
+            .unwrap();
+        let output = child.wait_with_output().unwrap();
+
+        assert!(output.status.success());
+        let output = String::from_utf8(output.stdout).unwrap();
+        assert!(output.contains("username=x-access-token\n"));
+        assert!(output.contains("password=secret token\n"));
     }
 
-    pub fn get_current_total_diff_stat(&self) -> Result<(usize, usize, usize), GitError> {
-        let git2_repo: Repository = self.try_into()?;
-        let diff = get_current_diff_to_parent(&git2_repo)?;
-        let stats = diff.stats()?;
-        Ok((stats.files_changed(), stats.insertions(), stats.deletions()))
+    #[test]
+    fn test_credential_helper_replaces_configured_ones() {
+        let mut command = git_command();
+        command.args(["config", "--get-all", "credential.helper"]);
+        set_https_credentials(
+            &mut command,
+            Some(&HttpsCredentials::Helper(
+                "store --file /run/secrets/git-credentials".to_string(),
+            )),
+        );
+
+        let output = command.output().unwrap();
+
+        // Helpers of the user's git config are listed before the empty one resetting them
+        assert!(output.status.success());
+        let output = String::from_utf8(output.stdout).unwrap();
+        let helpers: Vec<&str> = output.lines().rev().take(2).collect();
+        assert_eq!(helpers, ["store --file /run/secrets/git-credentials", ""]);
     }
 
-    pub fn get_current_changed_file_paths(&self) -> Result<HashSet<String>, GitError> {
-        let git2_repo: Repository = self.try_into()?;
-        let diff = get_current_diff_to_parent(&git2_repo)?;
+    #[test]
+    fn test_create_temp_worktree_with_stale_registration() {
+        let (repository_path, worktrees_path) = create_test_repository(&[]);
+        let worktree_path = worktrees_path.join("stale");
 
-        let mut diff_lines = Vec::new();
-        diff.print(DiffFormat::NameOnly, |_, _, l| {
-            diff_lines.push(l.content().to_vec());
-            true
-        })?;
+        let repo = RepositoryHandle::open(&repository_path).unwrap();
+        let git2_repo: Repository = (&repo).try_into().unwrap();
 
-        let mut changed_files: HashSet<String> = HashSet::new();
-        for l in diff_lines {
-            changed_files.insert(String::from_utf8(l)?.trim_end().to_string());
-        }
+        // Left behind by a crashed run
+        repo.create_worktree("stale", &worktree_path).unwrap();
+        git2_repo
+            .find_worktree("stale")
+            .unwrap()
+            .lock(None)
+            .unwrap();
 
-        Ok(changed_files)
+        let worktree = repo.create_temp_worktree("stale", &worktree_path).unwrap();
+        assert_eq!("stale", worktree.as_ref().name);
+        drop(worktree);
+
+        assert!(!worktree_path.exists());
+
+        std::fs::remove_dir_all(&repository_path).unwrap();
+        std::fs::remove_dir_all(&worktrees_path).unwrap();
     }
 
-    pub fn remove(self) -> Result<(), GitError> {
-        self.repo.remove_worktree(&self.name, Some(false))
+    #[test]
+    fn test_checkout_paths() {
+        let (repository_path, worktrees_path) =
+            create_test_repository(&["a/x.txt", "a/y.rs", "b/z.txt"]);
+
+        let repo = RepositoryHandle::open(&repository_path).unwrap();
+        let worktree = repo
+            .create_temp_worktree("partial", &worktrees_path.join("partial"))
+            .unwrap();
+        let worktree = worktree.as_ref();
+
+        worktree.checkout_paths("HEAD", &["a/*.txt"]).unwrap();
+        assert!(worktree.path.join("a/x.txt").exists());
+        assert!(!worktree.path.join("a/y.rs").exists());
+        assert!(!worktree.path.join("b/z.txt").exists());
+
+        worktree.checkout_paths("HEAD", &["b"]).unwrap();
+        assert!(!worktree.path.join("a/x.txt").exists());
+        assert!(worktree.path.join("b/z.txt").exists());
+
+        worktree.reset_hard("HEAD").unwrap();
+        assert!(worktree.path.join("a/x.txt").exists());
+        assert!(worktree.path.join("a/y.rs").exists());
+        assert!(worktree.path.join("b/z.txt").exists());
+
+        std::fs::remove_dir_all(&repository_path).unwrap();
+        std::fs::remove_dir_all(&worktrees_path).unwrap();
     }
 
-    pub fn list_files(&self) -> Result<Vec<String>, GitError> {
-        let git2_repo: Repository = self.try_into()?;
+    #[test]
+    fn test_commit_info_keeps_author_offset() {
+        let (repository_path, worktrees_path) = create_test_repository(&["a.rs"]);
+
+        let git2_repo = Repository::open(&repository_path).unwrap();
+        let parent = git2_repo.head().unwrap().peel_to_commit().unwrap();
+        let author = Signature::new(
+            "Dummy",
+            "dummy@test.com",
+            &git2::Time::new(1_700_000_000, -300),
+        )
+        .unwrap();
+        let id = git2_repo
+            .commit(
+                Some("HEAD"),
+                &author,
+                &author,
+                "Second",
+                &parent.tree().unwrap(),
+                &[&parent],
+            )
+            .unwrap();
+
+        let repo = RepositoryHandle::open(&repository_path).unwrap();
+        let commit = repo.get_commit(&id.into()).unwrap();
+        assert_eq!(commit.offset_minutes, -300);
+        assert_eq!(commit.time.timestamp(), 1_700_000_000);
+        assert_eq!(
+            commit.local_time().to_rfc3339(),
+            "2023-11-14T17:13:20-05:00"
+        );
+
+        std::fs::remove_dir_all(&repository_path).unwrap();
+        std::fs::remove_dir_all(&worktrees_path).unwrap();
+    }
+
+    #[test]
+    fn test_checkout_matching() {
+        let (repository_path, worktrees_path) =
+            create_test_repository(&["a/x.txt", "a/y.rs", "a/.gitignore", "b/[z].txt"]);
+
+        let repo = RepositoryHandle::open(&repository_path).unwrap();
+        let worktree = repo
+            .create_temp_worktree("sparse", &worktrees_path.join("sparse"))
+            .unwrap();
+        let worktree = worktree.as_ref();
+
+        let include = Glob::new("**/*.txt").unwrap().compile_matcher();
+        worktree
+            .checkout_matching("HEAD", |path| include.is_match(path))
+            .unwrap();
+        assert!(worktree.path.join("a/x.txt").exists());
+        assert!(worktree.path.join("a/.gitignore").exists());
+        assert!(!worktree.path.join("a/y.rs").exists());
+        assert!(worktree.path.join("b/[z].txt").exists());
+
+        // Nothing matches, so nothing is checked out
+        worktree.checkout_matching("HEAD", |_| false).unwrap();
+        assert!(!worktree.path.join("a/x.txt").exists());
+        assert!(worktree.path.join("a/.gitignore").exists());
+
+        worktree.reset_hard("HEAD").unwrap();
+        assert!(worktree.path.join("a/y.rs").exists());
+        assert!(worktree.path.join("b/[z].txt").exists());
+
+        std::fs::remove_dir_all(&repository_path).unwrap();
+        std::fs::remove_dir_all(&worktrees_path).unwrap();
+    }
+
+    #[test]
+    fn test_walk_tracked_files() {
+        let (repository_path, worktrees_path) =
+            create_test_repository(&["a/x.txt", "a/y.rs", "vendor/z.rs"]);
+
+        let mut repo = RepositoryHandle::open(&repository_path).unwrap();
+        repo.set_excluded_paths(&[Glob::new("vendor/**").unwrap()])
+            .unwrap();
+        let worktree = repo
+            .create_temp_worktree("walk", &worktrees_path.join("walk"))
+            .unwrap();
+        let worktree = worktree.as_ref();
+
+        // Untracked files are not part of the tree
+        std::fs::write(worktree.path.join("a/untracked.rs"), "").unwrap();
+
+        let mut files = worktree.walk_tracked_files(None).unwrap();
+        files.sort();
+        assert_eq!(vec!["a/x.txt", "a/y.rs"], files);
+
+        let mut globs = GlobSetBuilder::new();
+        globs.add(Glob::new("**/*.rs").unwrap());
+        assert_eq!(
+            vec!["a/y.rs"],
+            worktree
+                .walk_tracked_files(Some(&globs.build().unwrap()))
+                .unwrap()
+        );
+
+        let mut contents = String::new();
+        worktree
+            .read_file("a/x.txt")
+            .unwrap()
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!("a/x.txt", contents);
+        assert!(worktree.read_file("vendor/z.rs").unwrap().is_none());
+        assert!(worktree.read_file("a/missing.rs").unwrap().is_none());
+
+        std::fs::remove_dir_all(&repository_path).unwrap();
+        std::fs::remove_dir_all(&worktrees_path).unwrap();
+    }
+
+    #[test]
+    fn test_read_files_from_object_database() {
+        let (repository_path, worktrees_path) = create_test_repository(&["a/x.txt", "b.rs"]);
+
+        let repo = RepositoryHandle::open(&repository_path).unwrap();
+        let head = repo.resolve_revision("HEAD").unwrap();
+
+        // Not read from the working directory
+        std::fs::remove_dir_all(repository_path.join("a")).unwrap();
+
+        assert_eq!(
+            Some(b"a/x.txt".to_vec()),
+            repo.read_file(&head, "a/x.txt").unwrap()
+        );
+        assert_eq!(None, repo.read_file(&head, "a/missing.txt").unwrap());
+        assert_eq!(None, repo.read_file(&head, "a").unwrap());
+
         let mut files = Vec::new();
+        repo.for_each_file(&head, |path, contents| {
+            files.push((path.to_string(), contents.to_vec()));
+        })
+        .unwrap();
+        files.sort();
+        assert_eq!(
+            vec![
+                ("a/x.txt".to_string(), b"a/x.txt".to_vec()),
+                ("b.rs".to_string(), b"b.rs".to_vec()),
+            ],
+            files
+        );
 
-        let tree = git2_repo.find_tree(git2_repo.head()?.peel_to_tree()?.id())?;
-        tree.walk(git2::TreeWalkMode::PreOrder, |_, entry| {
-            files.push(entry.name().map(|e| e.to_string()));
-            git2::TreeWalkResult::Ok
-        })?;
+        assert_eq!(
+            HashSet::from(["a/x.txt".to_string(), "b.rs".to_string()]),
+            repo.changed_files(&head).unwrap()
+        );
+        assert_eq!((2, 2, 0), repo.get_total_diff_stat(&head).unwrap());
+        assert_eq!(
+            (1, 1, 0),
+            repo.get_total_diff_stat_of_files(&head, |path| path.starts_with("a"))
+                .unwrap()
+        );
 
-        let files = files.into_iter().flatten().collect();
+        std::fs::remove_dir_all(&repository_path).unwrap();
+        std::fs::remove_dir_all(&worktrees_path).unwrap();
+    }
 
-        Ok(files)
+    #[test]
+    fn test_get_commits_touching_paths() {
+        let (repository_path, worktrees_path) = create_test_repository(&["a/x.rs", "b/y.rs"]);
+
+        let git = |args: &[&str]| {
+            let mut command = git_command();
+            command.current_dir(&repository_path);
+            command.args(["-c", "user.name=Dummy", "-c", "user.email=dummy@test.com"]);
+            command.args(args);
+            command.execute_check_exit_status_code(0).unwrap();
+        };
+
+        let repo = RepositoryHandle::open(&repository_path).unwrap();
+        let initial = repo.resolve_revision("HEAD").unwrap();
+
+        std::fs::write(repository_path.join("b/y.rs"), "changed").unwrap();
+        git(&["commit", "-am", "Change b"]);
+        let changed_b = repo.resolve_revision("HEAD").unwrap();
+
+        std::fs::write(repository_path.join("a/x.rs"), "changed").unwrap();
+        git(&["commit", "-am", "Change a"]);
+        let changed_a = repo.resolve_revision("HEAD").unwrap();
+
+        let head = changed_a.clone();
+        let ids = |paths: &[&str]| -> Vec<CommitHash> {
+            let paths: Vec<String> = paths.iter().map(ToString::to_string).collect();
+            repo.get_commits_touching_paths(&head, None, &paths)
+                .unwrap()
+                .into_iter()
+                .map(|commit| commit.id)
+                .collect()
+        };
+
+        assert_eq!(vec![changed_a.clone(), initial.clone()], ids(&["a"]));
+        assert_eq!(vec![changed_b.clone(), initial.clone()], ids(&["b/*.rs"]));
+        assert_eq!(
+            vec![changed_a.clone(), changed_b.clone(), initial],
+            ids(&["a", "b"])
+        );
+        assert!(ids(&["c"]).is_empty());
+
+        // Ranges leave out the start and its ancestors
+        let paths = ["a".to_string(), "b".to_string()];
+        assert_eq!(
+            vec![changed_a.clone()],
+            repo.get_commits_touching_paths(&changed_a, Some(&changed_b), &paths)
+                .unwrap()
+                .into_iter()
+                .map(|commit| commit.id)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![changed_a.clone()],
+            repo.get_commits_between(&changed_b, &changed_a)
+                .unwrap()
+                .into_iter()
+                .map(|commit| commit.id)
+                .collect::<Vec<_>>()
+        );
+
+        std::fs::remove_dir_all(&repository_path).unwrap();
+        std::fs::remove_dir_all(&worktrees_path).unwrap();
     }
-}
 
-fn get_current_diff_to_parent(repo: &Repository) -> Result<Diff<'_>, GitError> {
-    // To diff the first commit in a repository, we need something to diff it against other than it's parent
-    // This object is the empty tree. See https://stackoverflow.com/a/40884093 for more details.
-    let empty_tree = repo.find_tree(Oid::from_str("4b825dc642cb6eb9a060e54bf8d69288fbee4904")?)?;
+    #[test]
+    fn test_first_parent_history() {
+        let (repository_path, worktrees_path) = create_test_repository(&["a.rs"]);
 
-    let Some(t1) =
-        tree_to_treeish(repo, Some(&"HEAD^".to_string())).unwrap_or(Some(empty_tree.into_object()))
-    else {
-        return Err(GitError::FailedToGetGitObject);
-    };
+        let git = |args: &[&str]| {
+            let mut command = git_command();
+            command.current_dir(&repository_path);
+            command.args(["-c", "user.name=Dummy", "-c", "user.email=dummy@test.com"]);
+            command.args(args);
+            command.execute_check_exit_status_code(0).unwrap();
+        };
 
-    let Some(t2) = tree_to_treeish(repo, Some(&"HEAD".to_string()))? else {
-        return Err(GitError::FailedToGetGitObject);
-    };
+        let mut repo = RepositoryHandle::open(&repository_path).unwrap();
+        let initial = repo.resolve_revision("HEAD").unwrap();
 
-    let diff = repo.diff_tree_to_tree(t1.as_tree(), t2.as_tree(), Some(&mut DiffOptions::new()))?;
+        git(&["checkout", "-b", "feature"]);
+        std::fs::write(repository_path.join("a.rs"), "feature").unwrap();
+        git(&["commit", "-am", "Feature"]);
+        let feature = repo.resolve_revision("HEAD").unwrap();
 
-    Ok(diff)
-}
+        git(&["checkout", "-"]);
+        git(&["merge", "--no-ff", "-m", "Merge feature", "feature"]);
+        let merge = repo.resolve_revision("HEAD").unwrap();
 
-fn tree_to_treeish<'a>(
-    repo: &'a Repository,
-    arg: Option<&String>,
-) -> Result<Option<Object<'a>>, git2::Error> {
-    let Some(arg) = arg else {
-        return Ok(None);
-    };
+        let ids = |commits: Vec<CommitInfo>| -> HashSet<CommitHash> {
+            commits.into_iter().map(|commit| commit.id).collect()
+        };
+        let paths = ["a.rs".to_string()];
 
-    let obj = repo.revparse_single(arg)?;
-    let tree = obj.peel(ObjectType::Tree)?;
-    Ok(Some(tree))
-}
+        assert_eq!(
+            HashSet::from([merge.clone(), feature.clone(), initial.clone()]),
+            ids(repo.get_commits_reachable_from(&merge).unwrap())
+        );
 
-#[derive(PartialEq, Debug)]
-pub enum CloneProgress {
-    EnumeratingObjects,
-    CountingObjects { finished: usize, total: usize },
-    CompressingObjects { finished: usize, total: usize },
-    ReceivingObjects { finished: usize, total: usize },
-    ResolvingDeltas { finished: usize, total: usize },
-}
+        repo.set_first_parent(true);
+        assert_eq!(
+            HashSet::from([merge.clone(), initial.clone()]),
+            ids(repo.get_commits_reachable_from(&merge).unwrap())
+        );
+        assert_eq!(
+            HashSet::from([merge.clone(), initial]),
+            ids(repo
+                .get_commits_touching_paths(&merge, None, &paths)
+                .unwrap())
+        );
 
-impl CloneProgress {
-    fn try_from(line: &str) -> Result<CloneProgress, GitCloneError> {
-        if line.starts_with("Enumerating objects:") {
-            return Ok(CloneProgress::EnumeratingObjects);
-        }
+        std::fs::remove_dir_all(&repository_path).unwrap();
+        std::fs::remove_dir_all(&worktrees_path).unwrap();
+    }
 
-        if line.starts_with("Counting objects:")
-            || line.starts_with("Compressing objects:")
-            || line.starts_with("Receiving objects:")
-            || line.starts_with("Resolving deltas:")
-        {
-            let re = Regex::new(r"%\s\((\d+\/\d+)\)")?;
+    #[test]
+    fn test_tree_reader() {
+        let (repository_path, worktrees_path) =
+            create_test_repository(&["a/x.txt", "a/y.rs", "vendor/z.rs"]);
 
-            let progress = re
-                .find(line)
-                .ok_or(GitCloneError::FailedToMatchProgress(line.to_string()))?;
+        let mut repo = RepositoryHandle::open(&repository_path).unwrap();
+        repo.set_excluded_paths(&[Glob::new("vendor/**").unwrap()])
+            .unwrap();
+        let head = repo.resolve_revision("HEAD").unwrap();
 
-            let (finished, total) = {
-                let mut tmp = progress.as_str().split('(');
-                tmp.next();
+        // Nothing is read from the working directory
+        std::fs::remove_dir_all(repository_path.join("a")).unwrap();
 
-                let Some(tmp) = tmp.next() else {
-                    return Err(GitCloneError::FailedToMatchProgress(line.to_string()));
-                };
+        let reader = repo.tree_reader(&head).unwrap();
+        assert_eq!(
+            vec!["a/x.txt", "a/y.rs"],
+            reader.list_files(|_| true).unwrap()
+        );
+        assert_eq!(
+            vec!["a/y.rs"],
+            reader.list_files(|path| path.ends_with(".rs")).unwrap()
+        );
 
-                let tmp = tmp.replace(')', "");
-                let mut parts = tmp.split('/');
+        assert_eq!(
+            Some(b"a/y.rs".to_vec()),
+            reader.read_file("a/y.rs").unwrap()
+        );
+        assert_eq!(None, reader.read_file("vendor/z.rs").unwrap());
+        assert_eq!(None, reader.read_file("a/missing.rs").unwrap());
+        assert_eq!(None, reader.read_file("a/x.txt/nested").unwrap());
 
-                let Some(finished) = parts.next() else {
-                    return Err(GitCloneError::FailedToMatchProgress(line.to_string()));
-                };
+        let mut files = Vec::new();
+        reader
+            .for_each_file(
+                |path| path.ends_with(".txt"),
+                |path, contents| files.push((path.to_string(), contents.to_vec())),
+            )
+            .unwrap();
+        assert_eq!(vec![("a/x.txt".to_string(), b"a/x.txt".to_vec())], files);
+
+        std::fs::remove_dir_all(&repository_path).unwrap();
+        std::fs::remove_dir_all(&worktrees_path).unwrap();
+    }
 
-                let finished = finished.parse::<usize>()?;
+    #[test]
+    fn test_excluded_paths() {
+        let (repository_path, worktrees_path) =
+            create_test_repository(&["vendor/lib/a.rs", "src/b.rs"]);
 
-                let Some(total) = parts.next() else {
-                    return Err(GitCloneError::FailedToMatchProgress(line.to_string()));
-                };
+        let mut repo = RepositoryHandle::open(&repository_path).unwrap();
+        repo.set_excluded_paths(&[Glob::new("vendor/**").unwrap()])
+            .unwrap();
+        let head = repo.resolve_revision("HEAD").unwrap();
 
-                let total = total.trim().parse::<usize>()?;
+        assert_eq!(None, repo.read_file(&head, "vendor/lib/a.rs").unwrap());
 
-                (finished, total)
-            };
+        let mut files = Vec::new();
+        repo.for_each_file(&head, |path, _| files.push(path.to_string()))
+            .unwrap();
+        assert_eq!(vec!["src/b.rs".to_string()], files);
 
-            if line.starts_with("Counting objects:") {
-                return Ok(CloneProgress::CountingObjects { finished, total });
-            }
+        assert_eq!(
+            HashSet::from(["src/b.rs".to_string()]),
+            repo.changed_files(&head).unwrap()
+        );
+        assert_eq!((1, 1, 0), repo.get_total_diff_stat(&head).unwrap());
 
-            if line.starts_with("Compressing objects:") {
-                return Ok(CloneProgress::CompressingObjects { finished, total });
-            }
+        repo.reset_hard(&head.0).unwrap();
+        assert!(!repository_path.join("vendor/lib/a.rs").exists());
+        assert!(repository_path.join("src/b.rs").exists());
 
-            if line.starts_with("Receiving objects:") {
-                return Ok(CloneProgress::ReceivingObjects { finished, total });
-            }
+        std::fs::remove_dir_all(&repository_path).unwrap();
+        std::fs::remove_dir_all(&worktrees_path).unwrap();
+    }
 
-            if line.starts_with("Resolving deltas:") {
-                return Ok(CloneProgress::ResolvingDeltas { finished, total });
+    #[test]
+    fn test_changed_files_of_merge_commit() {
+        let (repository_path, worktrees_path) = create_test_repository(&["a.txt"]);
+
+        let repo = RepositoryHandle::open(&repository_path).unwrap();
+        let git2_repo: Repository = (&repo).try_into().unwrap();
+        let signature = Signature::now("Dummy", "dummy@test.com").unwrap();
+        let base = git2_repo.head().unwrap().peel_to_commit().unwrap();
+
+        // Adds the given files to the tree of the first parent without touching the working
+        // directory
+        let commit = |files: &[&str], parents: &[&git2::Commit]| -> git2::Commit {
+            let mut builder = git2_repo
+                .treebuilder(Some(&parents[0].tree().unwrap()))
+                .unwrap();
+            for file in files {
+                let blob = git2_repo.blob(file.as_bytes()).unwrap();
+                builder.insert(file, blob, 0o100_644).unwrap();
             }
-        }
+            let tree = git2_repo.find_tree(builder.write().unwrap()).unwrap();
+            let oid = git2_repo
+                .commit(None, &signature, &signature, "Commit", &tree, parents)
+                .unwrap();
+            git2_repo.find_commit(oid).unwrap()
+        };
 
-        Err(GitCloneError::FailedToMatchProgress(line.to_string()))
-    }
-}
+        let main = commit(&["b.txt"], &[&base]);
+        let feature = commit(&["c.txt"], &[&base]);
+        // Contains the files of both branches and one changed during the merge
+        let merge = commit(&["c.txt", "d.txt"], &[&main, &feature]);
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use rstest::rstest;
+        assert_eq!(
+            HashSet::from(["b.txt".to_string()]),
+            repo.changed_files(&main.id().into()).unwrap()
+        );
+        assert_eq!(
+            HashSet::from([
+                "b.txt".to_string(),
+                "c.txt".to_string(),
+                "d.txt".to_string()
+            ]),
+            repo.changed_files(&merge.id().into()).unwrap()
+        );
 
-    #[rstest]
-    #[case("Enumerating objects: 2341, done.", CloneProgress::EnumeratingObjects)]
-    #[case("Counting objects:   1% (4/336)", CloneProgress::CountingObjects { finished: 4, total: 336 })]
-    #[case("Compressing objects:   1% (2/141)", CloneProgress::CompressingObjects { finished: 2, total: 141 })]
-    #[case("Receiving objects:   1% (24/2341)", CloneProgress::ReceivingObjects { finished: 24, total: 2341 })]
-    #[case("Resolving deltas:   1% (14/1203)", CloneProgress::ResolvingDeltas { finished: 14, total: 1203 })]
-    fn test_clone_progress_from_line(#[case] input: &str, #[case] expected: CloneProgress) {
-        assert_eq!(expected, CloneProgress::try_from(input).unwrap());
+        std::fs::remove_dir_all(&repository_path).unwrap();
+        std::fs::remove_dir_all(&worktrees_path).unwrap();
     }
 }
 
-struct DelimitedBy<R> {
+pub(crate) struct DelimitedBy<R> {
     reader: BufReader<R>,
     delimiters: HashSet<u8>,
 }
 
-fn delimited_by<R>(f: BufReader<R>, delimiters: &[char]) -> DelimitedBy<R> {
+pub(crate) fn delimited_by<R>(f: BufReader<R>, delimiters: &[char]) -> DelimitedBy<R> {
     DelimitedBy {
         reader: f,
         delimiters: delimiters.iter().map(|v| *v as u8).collect(),
@@ -587,44 +2899,50 @@ impl<R: std::io::Read> Iterator for DelimitedBy<R> {
     type Item = std::io::Result<String>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let (string, length) = {
-            match self.reader.fill_buf() {
-                Ok(buffer) => {
-                    let line_size = buffer
-                        .iter()
-                        .take_while(|c| !self.delimiters.contains(*c))
-                        .count();
-
-                    if buffer.is_empty() {
-                        return None;
-                    }
-
-                    let string = String::from_utf8_lossy(&buffer[..line_size]);
+        // Collected as bytes, a line or a multi-byte character can span several buffers
+        let mut line = Vec::new();
 
-                    // Add count of trailing delimiters to length, so that they are also consumed
-                    let mut length = line_size;
-                    if line_size < buffer.len() {
-                        let mut i = 0;
-                        while i < buffer.len() {
-                            if !self.delimiters.contains(&buffer[i]) {
-                                break;
-                            }
+        loop {
+            let buffer = match self.reader.fill_buf() {
+                Ok(buffer) => buffer,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Some(Err(e)),
+            };
 
-                            i += 1;
-                        }
+            if buffer.is_empty() {
+                break;
+            }
 
-                        length += i;
-                    }
+            // Consecutive delimiters separate a single line, so no empty lines are returned
+            if line.is_empty() {
+                let delimiter_count = buffer
+                    .iter()
+                    .take_while(|c| self.delimiters.contains(*c))
+                    .count();
 
-                    (string.to_string(), length)
+                if delimiter_count > 0 {
+                    self.reader.consume(delimiter_count);
+                    continue;
                 }
-                Err(e) => return Some(Err(e)),
             }
-        };
 
-        self.reader.consume(length);
+            if let Some(line_size) = buffer.iter().position(|c| self.delimiters.contains(c)) {
+                line.extend_from_slice(&buffer[..line_size]);
+                self.reader.consume(line_size);
+
+                return Some(Ok(String::from_utf8_lossy(&line).into_owned()));
+            }
+
+            let length = buffer.len();
+            line.extend_from_slice(buffer);
+            self.reader.consume(length);
+        }
+
+        if line.is_empty() {
+            return None;
+        }
 
-        Some(Ok(string))
+        Some(Ok(String::from_utf8_lossy(&line).into_owned()))
     }
 }
 
@@ -682,18 +3000,61 @@ pub enum GitCloneError {
     FailedToProcessCommandOutput(String),
 }
 
-pub fn clone_repository(
-    url: &str,
-    directory: &PathBuf,
-    progress_callback: impl Fn(&CloneProgress),
-    ssh_key: Option<&PrivateKey>,
-) -> Result<RepositoryHandle, GitCloneError> {
-    let mut command = Command::new(GIT_BINARY_PATH);
-    command.arg("clone");
-    command.arg(url);
-    command.arg(directory);
-    command.arg("--progress");
+/// Authenticates clones and fetches of repositories over HTTPS
+#[derive(Clone)]
+pub enum HttpsCredentials {
+    /// Token, e.g. a personal access token. Most hosts accept any username together with it.
+    Token { username: String, token: String },
+
+    /// Credential helper in the format of git's `credential.helper` option, e.g.
+    /// `store --file /run/secrets/git-credentials` or `!aws codecommit credential-helper $@`
+    Helper(String),
+}
+
+impl std::fmt::Debug for HttpsCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Token { username, .. } => f
+                .debug_struct("Token")
+                .field("username", username)
+                .field("token", &"<redacted>")
+                .finish(),
+            Self::Helper(helper) => f.debug_tuple("Helper").field(helper).finish(),
+        }
+    }
+}
 
+/// Answers the credential requests of git with the given token or helper instead of the helpers
+/// of the user's git config. Both are only passed in the environment of the command, so they are
+/// neither stored in the repository config nor visible in the arguments of the process.
+///
+/// Without credentials git asks the helpers of the user's git config as usual.
+fn set_https_credentials(command: &mut Command, credentials: Option<&HttpsCredentials>) {
+    let Some(credentials) = credentials else {
+        return;
+    };
+
+    let helper = match credentials {
+        HttpsCredentials::Token { username, token } => {
+            command.env("MYAKU_GIT_USERNAME", username);
+            command.env("MYAKU_GIT_TOKEN", token);
+
+            "!f() { test \"$1\" = get && echo \"username=$MYAKU_GIT_USERNAME\" && echo \"password=$MYAKU_GIT_TOKEN\"; }; f"
+        }
+        HttpsCredentials::Helper(helper) => helper.as_str(),
+    };
+
+    command.env("GIT_CONFIG_COUNT", "2");
+    // An empty helper drops the ones configured before, e.g. a credential store of the user
+    command.env("GIT_CONFIG_KEY_0", "credential.helper");
+    command.env("GIT_CONFIG_VALUE_0", "");
+    command.env("GIT_CONFIG_KEY_1", "credential.helper");
+    command.env("GIT_CONFIG_VALUE_1", helper);
+    // Rejected credentials fail the command instead of waiting for input
+    command.env("GIT_TERMINAL_PROMPT", "0");
+}
+
+fn set_ssh_key(command: &mut Command, ssh_key: Option<&PrivateKey>) -> Result<(), GitCloneError> {
     if let Some(private_key) = ssh_key {
         let private_key_file = create_temp_ssh_key_file(private_key)?;
         command.env(
@@ -705,23 +3066,111 @@ pub fn clone_repository(
         );
     }
 
+    Ok(())
+}
+
+/// Creates or updates a bare mirror of the repository at `url`, which clones of the same
+/// repository can borrow their objects from instead of storing them again.
+///
+/// Automatic garbage collection is disabled in the mirror and refs deleted on the remote are
+/// kept, since the clones still depend on the objects they reference.
+pub fn update_object_store(
+    url: &str,
+    directory: &Path,
+    ssh_key: Option<&PrivateKey>,
+    https_credentials: Option<&HttpsCredentials>,
+) -> Result<RepositoryHandle, GitError> {
+    if let Ok(store) = RepositoryHandle::open(directory) {
+        if store.remote_url()? != url {
+            return Err(GitError::MismatchedObjectStore(directory.to_path_buf()));
+        }
+
+        let mut command = git_command();
+        command.current_dir(directory);
+        command.args(["remote", "update"]);
+        set_ssh_key(&mut command, ssh_key)?;
+        set_https_credentials(&mut command, https_credentials);
+        command.execute_check_exit_status_code(0)?;
+
+        return Ok(store);
+    }
+
+    let mut command = git_command();
+    command.arg("clone");
+    command.arg("--mirror");
+    command.arg("--quiet");
+    command.arg(url);
+    command.arg(directory);
+    set_ssh_key(&mut command, ssh_key)?;
+    set_https_credentials(&mut command, https_credentials);
+    command.execute_check_exit_status_code(0)?;
+
+    let mut command = git_command();
+    command.current_dir(directory);
+    command.args(["config", "gc.auto", "0"]);
+    command.execute_check_exit_status_code(0)?;
+
+    RepositoryHandle::open(directory)
+}
+
+/// Output of a git command run with [`run_with_progress`]
+struct ProgressOutput {
+    status: std::process::ExitStatus,
+
+    /// Lines written to stderr, including the progress lines
+    lines: Vec<String>,
+
+    /// Number of objects received from the remote
+    objects: usize,
+}
+
+impl ProgressOutput {
+    /// Errors git reported, e.g. a rejected authentication, or all output if none were recognized
+    fn error_message(&self) -> String {
+        let errors: Vec<&str> = self
+            .lines
+            .iter()
+            .map(|line| line.trim())
+            .filter(|line| {
+                ["fatal:", "error:", "ERROR:"]
+                    .iter()
+                    .any(|prefix| line.starts_with(prefix))
+            })
+            .collect();
+
+        if errors.is_empty() {
+            self.lines.join("\n")
+        } else {
+            errors.join("\n")
+        }
+    }
+}
+
+/// Runs a git command reporting its progress on stderr, e.g. a clone or fetch, and passes the
+/// parsed progress on to the callback
+fn run_with_progress(
+    command: &mut Command,
+    progress_callback: impl Fn(&CloneProgress),
+) -> Result<ProgressOutput, GitCloneError> {
     let mut child = command
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .spawn()?;
 
-    let Some(stdout) = child.stderr.take() else {
+    let Some(stderr) = child.stderr.take() else {
         return Err(GitCloneError::FailedToProcessCommandOutput(
-            "No stdout available from git clone command".to_string(),
+            "No stderr available from git command".to_string(),
         ));
     };
 
     let mut lines = vec![];
-    let reader: BufReaderWithDelimitedBy<_> = BufReader::new(stdout).into();
+    let mut throttle = CloneProgressThrottle::new(CLONE_PROGRESS_INTERVAL);
+    let mut objects = 0;
+    let reader: BufReaderWithDelimitedBy<_> = BufReader::new(stderr).into();
 
     for line in reader.delimited_by(&['\n', '\r']) {
         let Ok(line) = line else {
-            warn!("Failed to read line from git clone output: {:?}", line);
+            warn!("Failed to read line from git output: {:?}", line);
             continue;
         };
 
@@ -731,20 +3180,103 @@ pub fn clone_repository(
 
         let progress = CloneProgress::try_from(&line);
         if let Ok(progress) = progress {
-            progress_callback(&progress);
+            if let CloneProgress::ReceivingObjects { total, .. } = progress {
+                objects = total;
+            }
+
+            if throttle.should_report(&progress, Instant::now()) {
+                progress_callback(&progress);
+            }
         }
 
         lines.push(line);
     }
 
-    let exit = child.wait()?;
+    let status = child.wait()?;
+
+    Ok(ProgressOutput {
+        status,
+        lines,
+        objects,
+    })
+}
+
+/// Clones the repository at `url` into `directory`. If a `reference` repository is given, objects
+/// it already contains are not downloaded again but borrowed from it through git's alternates.
+/// With a `depth` only that many commits of the history of every branch are cloned. `partial`
+/// clones leave out all blobs but the ones of the checked out commit, the others are fetched once
+/// they are needed.
+#[allow(clippy::too_many_arguments)]
+pub fn clone_repository(
+    url: &str,
+    directory: &PathBuf,
+    progress_callback: impl Fn(&CloneProgress) + Send + Sync,
+    ssh_key: Option<&PrivateKey>,
+    https_credentials: Option<&HttpsCredentials>,
+    reference: Option<&Path>,
+    depth: Option<usize>,
+    partial: bool,
+) -> Result<(RepositoryHandle, CloneSummary), GitCloneError> {
+    let start = Instant::now();
+
+    let mut command = git_command();
+    command.arg("clone");
+    command.arg(url);
+    command.arg(directory);
+    command.arg("--progress");
+
+    if let Some(reference) = reference {
+        command.arg("--reference");
+        command.arg(reference);
+    }
+
+    // Shallow clones only contain the default branch otherwise, metrics may use other branches
+    if let Some(depth) = depth {
+        command.arg(format!("--depth={depth}"));
+        command.arg("--no-single-branch");
+    }
+
+    if partial {
+        command.arg("--filter=blob:none");
+    }
+
+    set_ssh_key(&mut command, ssh_key)?;
+    set_https_credentials(&mut command, https_credentials);
 
-    if !exit.success() {
-        debug!("{}", lines.join("\n"));
-        return Err(GitCloneError::NonZeroExitCode(exit));
+    let output = run_with_progress(&mut command, progress_callback)?;
+
+    if !output.status.success() {
+        debug!("{}", output.lines.join("\n"));
+        return Err(GitCloneError::NonZeroExitCode(output.status));
     }
 
-    Ok(RepositoryHandle {
+    let objects = output.objects;
+
+    let bytes = WalkDir::new(directory.join(".git").join("objects"))
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(std::fs::Metadata::is_file)
+        .map(|metadata| metadata.len())
+        .sum();
+
+    let summary = CloneSummary {
+        objects,
+        bytes,
+        duration: start.elapsed(),
+    };
+
+    let repo = RepositoryHandle {
         path: directory.clone(),
-    })
+        bare: false,
+        excluded_paths: GlobSet::empty(),
+        excluded_paths_id: None,
+        partial,
+        submodules: false,
+        first_parent: false,
+        ssh_key: ssh_key.cloned().map(Box::new),
+        https_credentials: https_credentials.cloned(),
+    };
+
+    Ok((repo, summary))
 }
@@ -1,10 +1,12 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use chrono::{Datelike, Timelike};
-use petgraph::{graph::NodeIndex, Graph};
+use chrono::{DateTime, Datelike, NaiveDateTime, NaiveTime, Timelike, Utc, Weekday};
+use petgraph::{algo::toposort, graph::NodeIndex, Direction, Graph};
+use thiserror::Error;
 
 use crate::{
-    config::{CollectorConfig, MetricConfig},
+    collectors::{registry::dependencies, Collector},
+    config::{CollectorConfig, FileScope, MetricConfig, PhaseOffset},
     git::{CommitHash, CommitInfo},
     Frequency,
 };
@@ -19,6 +21,9 @@ pub struct CollectionTask {
 pub struct CollectionGraphEdge {
     /// The number of commits between the two nodes
     pub distance: usize,
+
+    /// The number of sampled commits between the two nodes, 0 for dependencies at the same commit
+    pub offset: usize,
 }
 
 #[derive(Clone, Debug)]
@@ -26,14 +31,245 @@ pub struct CollectionExecutionGraph {
     pub graph: Graph<CollectionTask, CollectionGraphEdge>,
 }
 
-#[allow(clippy::too_many_lines)]
+#[derive(Error, Debug)]
+pub enum GraphValidationError {
+    #[error(
+        "Execution graph contains a cycle involving {collector_config:?} at commit {commit_hash}"
+    )]
+    Cycle {
+        collector_config: CollectorConfig,
+        commit_hash: CommitHash,
+    },
+
+    #[error(
+        "Execution graph contains multiple tasks for {collector_config:?} at commit {commit_hash}"
+    )]
+    DuplicateTask {
+        collector_config: CollectorConfig,
+        commit_hash: CommitHash,
+    },
+
+    #[error("Task for {collector_config:?} at commit {commit_hash} is missing its dependency {dependency:?}")]
+    MissingDependency {
+        collector_config: CollectorConfig,
+        commit_hash: CommitHash,
+        dependency: Box<CollectorConfig>,
+    },
+}
+
+impl CollectionExecutionGraph {
+    /// Checks the invariants the collectors rely on, so a broken graph fails before execution
+    /// instead of panicking somewhere inside a collector.
+    pub fn validate(&self) -> Result<(), GraphValidationError> {
+        if let Err(cycle) = toposort(&self.graph, None) {
+            let task = &self.graph[cycle.node_id()];
+
+            return Err(GraphValidationError::Cycle {
+                collector_config: task.collector_config.clone(),
+                commit_hash: task.commit_hash.clone(),
+            });
+        }
+
+        let mut tasks: HashSet<(&CollectorConfig, &CommitHash)> = HashSet::new();
+
+        for node_idx in self.graph.node_indices() {
+            let task = &self.graph[node_idx];
+
+            if !tasks.insert((&task.collector_config, &task.commit_hash)) {
+                return Err(GraphValidationError::DuplicateTask {
+                    collector_config: task.collector_config.clone(),
+                    commit_hash: task.commit_hash.clone(),
+                });
+            }
+
+            for dependency in dependencies(&task.collector_config) {
+                let dependency = dependency.collector_config;
+                let has_dependency = self
+                    .graph
+                    .neighbors_directed(node_idx, Direction::Incoming)
+                    .map(|idx| &self.graph[idx])
+                    .any(|n| n.collector_config == dependency && n.commit_hash == task.commit_hash);
+
+                if !has_dependency {
+                    return Err(GraphValidationError::MissingDependency {
+                        collector_config: task.collector_config.clone(),
+                        commit_hash: task.commit_hash.clone(),
+                        dependency: Box::new(dependency),
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes all resolved tasks which are not required by any unresolved task, so the
+    /// execution only traverses tasks which actually need work.
+    #[must_use]
+    pub fn prune(&self, is_resolved: impl Fn(&CollectionTask) -> bool) -> CollectionExecutionGraph {
+        let graph = self.graph.filter_map(
+            |node_idx, task| {
+                let is_required = !is_resolved(task)
+                    || self
+                        .graph
+                        .neighbors_directed(node_idx, Direction::Outgoing)
+                        .any(|idx| !is_resolved(&self.graph[idx]));
+
+                is_required.then(|| task.clone())
+            },
+            |_, edge| Some(edge.clone()),
+        );
+
+        CollectionExecutionGraph { graph }
+    }
+
+    /// Removes the tasks matching the predicate together with all tasks depending on their values,
+    /// see [`Self::dependants`].
+    pub fn exclude(
+        &self,
+        is_excluded: impl Fn(&CollectionTask) -> bool,
+    ) -> CollectionExecutionGraph {
+        self.remove(&self.dependants(is_excluded))
+    }
+
+    /// Returns the tasks matching the predicate together with all tasks depending on their values,
+    /// i.e. the tasks of the same commit and the later ones requiring the previous value. Other
+    /// tasks of later commits do not depend on them and only have an edge to them.
+    pub fn dependants(&self, matches: impl Fn(&CollectionTask) -> bool) -> HashSet<NodeIndex> {
+        let mut dependants: HashSet<NodeIndex> = HashSet::new();
+        let mut pending: Vec<NodeIndex> = self
+            .graph
+            .node_indices()
+            .filter(|idx| matches(&self.graph[*idx]))
+            .collect();
+
+        while let Some(node_idx) = pending.pop() {
+            if !dependants.insert(node_idx) {
+                continue;
+            }
+
+            let task = &self.graph[node_idx];
+
+            pending.extend(
+                self.graph
+                    .neighbors_directed(node_idx, Direction::Outgoing)
+                    .filter(|idx| {
+                        let dependant = &self.graph[*idx];
+
+                        dependant.commit_hash == task.commit_hash
+                            || requires_preceding_values(&dependant.collector_config)
+                    }),
+            );
+        }
+
+        dependants
+    }
+
+    /// Returns the tasks which have to wait for the tasks of other commits, i.e. the ones
+    /// requiring values at previously sampled commits together with everything depending on
+    /// them, directly or through other tasks
+    pub fn sequential_tasks(&self) -> HashSet<NodeIndex> {
+        let mut sequential: HashSet<NodeIndex> = HashSet::new();
+        let mut pending: Vec<NodeIndex> = self
+            .graph
+            .node_indices()
+            .filter(|idx| requires_preceding_values(&self.graph[*idx].collector_config))
+            .collect();
+
+        while let Some(node_idx) = pending.pop() {
+            if sequential.insert(node_idx) {
+                pending.extend(self.graph.neighbors_directed(node_idx, Direction::Outgoing));
+            }
+        }
+
+        sequential
+    }
+
+    /// Removes the given tasks, keeping the edges between the remaining ones
+    pub fn remove(&self, tasks: &HashSet<NodeIndex>) -> CollectionExecutionGraph {
+        let graph = self.graph.filter_map(
+            |node_idx, task| (!tasks.contains(&node_idx)).then(|| task.clone()),
+            |_, edge| Some(edge.clone()),
+        );
+
+        CollectionExecutionGraph { graph }
+    }
+}
+
+/// Returns whether the given collector requires its own value at the previous commit, which means
+/// its tasks cannot be executed in parallel with the tasks of other commits
+pub fn requires_previous_value(collector_config: &CollectorConfig) -> bool {
+    matches!(collector_config, CollectorConfig::CumulativeDiffStat { .. })
+}
+
+/// Returns whether the given collector requires values at previously sampled commits, either its
+/// own or the ones of its dependencies, so its tasks have to wait for the tasks of other commits
+pub fn requires_preceding_values(collector_config: &CollectorConfig) -> bool {
+    requires_previous_value(collector_config)
+        || dependencies(collector_config)
+            .iter()
+            .any(|dependency| dependency.window > 0)
+}
+
+/// Returns whether the given collector needs the files of the commit checked out, collectors which
+/// only look at the diff or read the files from the object database can skip the checkout
+pub fn requires_checkout(collector_config: &CollectorConfig) -> bool {
+    !reads_tree(collector_config)
+        && !matches!(
+            collector_config,
+            CollectorConfig::ChangedFiles
+                | CollectorConfig::ChangedFilesLoc
+                | CollectorConfig::TotalDiffStat { .. }
+                | CollectorConfig::FormattingChanges
+                | CollectorConfig::AuthorDomain
+                | CollectorConfig::AuthorDomains { .. }
+        )
+}
+
+/// Returns whether the given collector reads the files of the commit through a
+/// [`TreeReader`](crate::git::TreeReader) instead of a checkout. It still needs one if submodules
+/// are enabled, their files are only read from the repositories checked out in the worktree.
+pub fn reads_tree(collector_config: &CollectorConfig) -> bool {
+    match Collector::from(collector_config) {
+        Collector::Base(collector) => collector.reads_tree(),
+        Collector::Derived(_) => false,
+    }
+}
+
+/// Returns the files the given collector is scoped to if it needs a checkout but none of the
+/// other files, so only these are checked out instead of the entire tree
+pub fn checkout_scope(collector_config: &CollectorConfig) -> Option<&FileScope> {
+    match collector_config {
+        CollectorConfig::Loc { scope } | CollectorConfig::PatternOccurences { scope, .. }
+            if !scope.is_unrestricted() =>
+        {
+            Some(scope)
+        }
+        _ => None,
+    }
+}
+
+/// Returns whether the given collector goes through all files of the commit and might stall on
+/// huge trees, either in a checkout or in the object database
+pub fn reads_whole_tree(collector_config: &CollectorConfig) -> bool {
+    requires_checkout(collector_config)
+        || matches!(
+            collector_config,
+            CollectorConfig::PatternOccurences { .. }
+                | CollectorConfig::Secrets { .. }
+                | CollectorConfig::ConfigFiles { .. }
+                | CollectorConfig::CognitiveComplexity
+        )
+}
+
 pub fn add_task(
     graph: &mut Graph<CollectionTask, CollectionGraphEdge>,
     created_tasks: &mut HashMap<(CollectorConfig, CommitHash), NodeIndex>,
     collector_config: &CollectorConfig,
     current_commit_hash: &CommitHash,
-    previous_commit_hash: Option<&CommitHash>,
-    previous_commit_distance: usize,
+    // Commits previously sampled for the metric together with their distance to the current one,
+    // the most recent one first
+    previous_commits: &[(&CommitHash, usize)],
 ) -> NodeIndex {
     if let Some(node_idx) =
         created_tasks.get(&(collector_config.clone(), current_commit_hash.clone()))
@@ -52,95 +288,54 @@ pub fn add_task(
     );
 
     // Create dependency tasks
-    match &collector_config {
-        CollectorConfig::TotalPatternOccurences { pattern, files } => {
-            // TODO: Optimize the dependency creation of the pattern occurences task
-            // This might be inefficient when there are multiple `TotalPatternOccurences` tasks
-            // We would create a new pattern occurences task for each one of them
-            // Maybe we should combine the files and patterns of all tasks and create a single one
-            let dependency_node_idx = add_task(
-                graph,
-                created_tasks,
-                &CollectorConfig::PatternOccurences {
-                    pattern: pattern.clone(),
-                    files: files.clone(),
-                },
-                current_commit_hash,
-                previous_commit_hash,
-                previous_commit_distance,
-            );
-
-            graph.add_edge(
-                dependency_node_idx,
-                node_idx,
-                CollectionGraphEdge { distance: 0 },
-            );
-        }
-        CollectorConfig::PatternOccurences {
-            pattern: _,
-            files: _,
-        }
-        | CollectorConfig::TotalCargoDeps
-        | CollectorConfig::ChangedFilesLoc => {
-            let dependency_node_idx = add_task(
-                graph,
-                created_tasks,
-                &CollectorConfig::ChangedFiles,
-                current_commit_hash,
-                previous_commit_hash,
-                previous_commit_distance,
-            );
-
-            graph.add_edge(
-                dependency_node_idx,
-                node_idx,
-                CollectionGraphEdge { distance: 0 },
-            );
-        }
-        CollectorConfig::TotalLoc => {
-            let dependency_node_idx = add_task(
-                graph,
-                created_tasks,
-                &CollectorConfig::Loc,
-                current_commit_hash,
-                previous_commit_hash,
-                previous_commit_distance,
-            );
+    for dependency in dependencies(collector_config) {
+        let dependency_node_idx = add_task(
+            graph,
+            created_tasks,
+            &dependency.collector_config,
+            current_commit_hash,
+            previous_commits,
+        );
 
-            graph.add_edge(
-                dependency_node_idx,
-                node_idx,
-                CollectionGraphEdge { distance: 0 },
-            );
-        }
-        CollectorConfig::TotalFileCount => {
-            let dependency_node_idx = add_task(
-                graph,
-                created_tasks,
-                &CollectorConfig::FileList,
-                current_commit_hash,
-                previous_commit_hash,
-                previous_commit_distance,
-            );
+        graph.add_edge(
+            dependency_node_idx,
+            node_idx,
+            CollectionGraphEdge {
+                distance: 0,
+                offset: 0,
+            },
+        );
 
-            graph.add_edge(
-                dependency_node_idx,
-                node_idx,
-                CollectionGraphEdge { distance: 0 },
-            );
+        // The tasks of the dependency at the previous commits were created together with the
+        // tasks of this collector at these commits
+        for (offset, (commit_hash, distance)) in
+            (1..).zip(previous_commits.iter().take(dependency.window))
+        {
+            if let Some(window_task_idx) =
+                created_tasks.get(&(dependency.collector_config.clone(), (*commit_hash).clone()))
+            {
+                graph.add_edge(
+                    *window_task_idx,
+                    node_idx,
+                    CollectionGraphEdge {
+                        distance: *distance,
+                        offset,
+                    },
+                );
+            }
         }
-        _ => {}
     }
 
-    if let Some(previous_commit_hash) = previous_commit_hash {
+    if let Some((previous_commit_hash, previous_commit_distance)) = previous_commits.first() {
         if let Some(last_commit_task_idx) =
-            created_tasks.get(&(collector_config.clone(), previous_commit_hash.clone()))
+            created_tasks.get(&(collector_config.clone(), (*previous_commit_hash).clone()))
         {
             graph.add_edge(
                 *last_commit_task_idx,
                 node_idx,
                 CollectionGraphEdge {
-                    distance: previous_commit_distance,
+                    distance: *previous_commit_distance,
+                    offset: 1,
                 },
             );
         }
@@ -149,6 +344,126 @@ pub fn add_task(
     node_idx
 }
 
+/// Returns whether a commit is skipped because it falls into the same period as the previously
+/// sampled commit
+fn is_same_period(
+    previous_time: &DateTime<Utc>,
+    current_time: &DateTime<Utc>,
+    frequency: &Frequency,
+) -> bool {
+    let previous_date = previous_time.date_naive();
+    let current_date = current_time.date_naive();
+
+    let is_same_year = previous_date.year_ce() == current_date.year_ce();
+
+    let is_same_month = is_same_year && previous_date.month0() == current_date.month0();
+
+    let is_same_week = is_same_month && previous_date.iso_week() == current_date.iso_week();
+
+    let is_same_day = is_same_week && previous_date.day0() == current_date.day0();
+
+    let is_same_hour = is_same_day && previous_time.hour() == current_time.hour();
+
+    match frequency {
+        // Cron schedules and latest-only are handled by `sample_commits`
+        Frequency::PerCommit | Frequency::Cron(_) | Frequency::LatestOnly => false,
+        Frequency::Yearly => is_same_year,
+        Frequency::Monthly => is_same_month,
+        Frequency::Weekly => is_same_week,
+        Frequency::Daily => is_same_day,
+        Frequency::Hourly => is_same_hour,
+    }
+}
+
+/// Start (UTC) of the calendar period a time falls into, weeks start on Monday like ISO weeks
+fn calendar_period_start(time: &DateTime<Utc>, frequency: &Frequency) -> Option<NaiveDateTime> {
+    let date = time.date_naive();
+
+    match frequency {
+        Frequency::PerCommit | Frequency::Cron(_) | Frequency::LatestOnly => None,
+        Frequency::Yearly => date
+            .with_ordinal(1)
+            .map(|date| date.and_time(NaiveTime::MIN)),
+        Frequency::Monthly => date.with_day(1).map(|date| date.and_time(NaiveTime::MIN)),
+        Frequency::Weekly => Some(date.week(Weekday::Mon).first_day().and_time(NaiveTime::MIN)),
+        Frequency::Daily => Some(date.and_time(NaiveTime::MIN)),
+        Frequency::Hourly => date.and_hms_opt(time.hour(), 0, 0),
+    }
+}
+
+/// Returns the commits a metric with the given frequency is collected for, together with the
+/// number of commits skipped since the previously sampled one
+///
+/// By default a commit is skipped if it falls into the same period as the previously sampled one,
+/// where e.g. a week also ends with its month. Aligned to the calendar, the first commit of every
+/// calendar period is sampled instead, so weeks spanning two months or years are not split.
+/// With a cron frequency, the first commit after every matching time is sampled in both cases.
+///
+/// Periods start later by the phase offset, e.g. weeks start on Sunday with an offset of six
+/// days, which doesn't apply to cron frequencies.
+///
+/// The commits have to be sorted by their time.
+#[must_use]
+pub fn sample_commits<'a>(
+    sorted_commits: &'a [CommitInfo],
+    frequency: &Frequency,
+    align_to_calendar: bool,
+    phase_offset: PhaseOffset,
+    // Sample the latest commit regardless of the frequency
+    force_latest_commit: bool,
+) -> Vec<(&'a CommitInfo, usize)> {
+    let mut sampled = vec![];
+
+    // Invalid expressions are rejected by the config validation, sample every commit otherwise
+    let schedule = frequency.cron_schedule().and_then(Result::ok);
+    let phase_offset = phase_offset.duration();
+
+    let mut distance = 0_usize;
+    let mut previous_commit: Option<&CommitInfo> = None;
+
+    for (index, current_commit) in sorted_commits.iter().enumerate() {
+        let is_latest_commit = index == sorted_commits.len() - 1;
+
+        let skipped = if force_latest_commit && is_latest_commit {
+            false
+        } else if *frequency == Frequency::LatestOnly {
+            !is_latest_commit
+        } else if let Some(previous_commit) = previous_commit {
+            if let Some(schedule) = &schedule {
+                schedule
+                    .after(&previous_commit.time)
+                    .next()
+                    .is_none_or(|time| time > current_commit.time)
+            } else {
+                let previous_time = previous_commit.time - phase_offset;
+                let current_time = current_commit.time - phase_offset;
+
+                if align_to_calendar {
+                    calendar_period_start(&previous_time, frequency).is_some_and(|start| {
+                        calendar_period_start(&current_time, frequency) == Some(start)
+                    })
+                } else {
+                    is_same_period(&previous_time, &current_time, frequency)
+                }
+            }
+        } else {
+            false
+        };
+
+        if skipped {
+            distance += 1;
+            continue;
+        }
+
+        sampled.push((current_commit, distance));
+        previous_commit = Some(current_commit);
+    }
+
+    sampled
+}
+
+/// Builds the graph for metrics which are all collected from the same commits
+#[cfg(test)]
 pub fn build_collection_execution_graph(
     metrics: &HashMap<String, MetricConfig>,
     commits: &[CommitInfo],
@@ -156,68 +471,55 @@ pub fn build_collection_execution_graph(
     // regardless of the frequency specified in the metric config
     force_latest_commit: bool,
 ) -> CollectionExecutionGraph {
-    let mut graph: Graph<CollectionTask, CollectionGraphEdge> = Graph::new();
-
     let mut sorted_commits = commits.to_vec();
     sorted_commits.sort_by(|a, b| a.time.cmp(&b.time));
 
+    build_collection_execution_graph_with(metrics, |_| &sorted_commits, force_latest_commit)
+}
+
+/// Builds the graph for metrics which sample their own commits, e.g. the ones of their branch.
+/// The commits have to be sorted by time.
+pub fn build_collection_execution_graph_with<'c>(
+    metrics: &HashMap<String, MetricConfig>,
+    commits_of_metric: impl Fn(&MetricConfig) -> &'c [CommitInfo],
+    force_latest_commit: bool,
+) -> CollectionExecutionGraph {
+    let mut graph: Graph<CollectionTask, CollectionGraphEdge> = Graph::new();
+
+    // Metrics sharing a collector reuse the tasks of the first one, which also decides the
+    // edges to the previous commits, so they are added in a fixed order
+    let mut sorted_metrics: Vec<(&String, &MetricConfig)> = metrics.iter().collect();
+    sorted_metrics.sort_by_key(|(metric_name, _)| *metric_name);
+
     let mut created_tasks: HashMap<(CollectorConfig, CommitHash), NodeIndex> = HashMap::new();
-    for metric_config in metrics.values() {
-        let mut distance = 0_usize;
-        let mut previous_commit: Option<&CommitInfo> = None;
-
-        for (index, current_commit) in sorted_commits.iter().enumerate() {
-            let current_commit_hash = &current_commit.id;
-            let is_latest_commit = index == sorted_commits.len() - 1;
-
-            let skipped = if force_latest_commit && is_latest_commit {
-                false
-            } else if let Some(previous_commit) = previous_commit {
-                let is_same_year = previous_commit.time.date_naive().year_ce()
-                    == current_commit.time.date_naive().year_ce();
-
-                let is_same_month = is_same_year
-                    && previous_commit.time.date_naive().month0()
-                        == current_commit.time.date_naive().month0();
-
-                let is_same_week = is_same_month
-                    && previous_commit.time.date_naive().iso_week()
-                        == current_commit.time.date_naive().iso_week();
-
-                let is_same_day = is_same_week
-                    && previous_commit.time.date_naive().day0()
-                        == current_commit.time.date_naive().day0();
-
-                let is_same_hour =
-                    is_same_day && previous_commit.time.hour() == current_commit.time.hour();
-
-                match metric_config.frequency {
-                    Frequency::PerCommit => false,
-                    Frequency::Yearly => is_same_year,
-                    Frequency::Monthly => is_same_month,
-                    Frequency::Weekly => is_same_week,
-                    Frequency::Daily => is_same_day,
-                    Frequency::Hourly => is_same_hour,
-                }
-            } else {
-                false
-            };
+    for (_, metric_config) in sorted_metrics {
+        let sampled = sample_commits(
+            commits_of_metric(metric_config),
+            &metric_config.frequency,
+            metric_config.align_to_calendar.unwrap_or(false),
+            metric_config.phase_offset.unwrap_or_default(),
+            force_latest_commit,
+        );
 
-            if skipped {
-                distance += 1;
-                continue;
-            }
+        for (index, (current_commit, _)) in sampled.iter().enumerate() {
+            let mut distance = 0;
+            let previous_commits: Vec<(&CommitHash, usize)> = sampled[..index]
+                .iter()
+                .zip(&sampled[1..=index])
+                .rev()
+                .map(|((previous_commit, _), (_, previous_distance))| {
+                    distance += previous_distance;
+                    (&previous_commit.id, distance)
+                })
+                .collect();
 
             add_task(
                 &mut graph,
                 &mut created_tasks,
                 &metric_config.collector,
-                current_commit_hash,
-                previous_commit.map(|c| &c.id),
-                distance,
+                &current_commit.id,
+                &previous_commits,
             );
-
-            previous_commit = Some(current_commit);
         }
     }
 
@@ -226,9 +528,11 @@ pub fn build_collection_execution_graph(
 
 #[cfg(test)]
 mod test {
-    use std::collections::HashSet;
+    use std::collections::{BTreeMap, HashSet};
 
     use chrono::{DateTime, Utc};
+    use petgraph::visit::EdgeRef;
+    use proptest::prelude::*;
 
     use crate::git::Author;
 
@@ -246,6 +550,8 @@ mod test {
             committer: dummy_author.clone(),
             message: None,
             time: time.parse::<DateTime<Utc>>().unwrap(),
+            offset_minutes: 0,
+            parent_count: 1,
         }
     }
 
@@ -268,6 +574,632 @@ mod test {
         assert_eq!(node_commit_hashes, expected_node_commit_hashes);
     }
 
+    fn add_node(
+        graph: &mut Graph<CollectionTask, CollectionGraphEdge>,
+        collector_config: CollectorConfig,
+        commit_hash: &str,
+    ) -> NodeIndex {
+        graph.add_node(CollectionTask {
+            commit_hash: CommitHash(commit_hash.to_string()),
+            collector_config,
+        })
+    }
+
+    /// Commits spanning several hours, days, weeks and months, so every frequency samples a
+    /// different subset of them
+    fn create_snapshot_commits() -> Vec<CommitInfo> {
+        vec![
+            create_dummy_commit("c1", "2023-01-02T10:00:00Z"),
+            create_dummy_commit("c2", "2023-01-02T10:30:00Z"),
+            create_dummy_commit("c3", "2023-01-02T15:00:00Z"),
+            create_dummy_commit("c4", "2023-01-04T09:00:00Z"),
+            create_dummy_commit("c5", "2023-01-10T09:00:00Z"),
+            create_dummy_commit("c6", "2023-02-01T12:00:00Z"),
+            create_dummy_commit("c7", "2023-02-01T12:10:00Z"),
+            create_dummy_commit("c8", "2023-03-15T08:00:00Z"),
+        ]
+    }
+
+    /// Sorted lines describing all tasks and dependencies, independent of the insertion order
+    fn render_graph(graph: &CollectionExecutionGraph) -> String {
+        let task = |idx: NodeIndex| {
+            let task = &graph.graph[idx];
+            format!(
+                "{} {}",
+                task.commit_hash,
+                serde_json::to_string(&task.collector_config).unwrap()
+            )
+        };
+
+        let mut nodes: Vec<String> = graph.graph.node_indices().map(task).collect();
+        nodes.sort();
+
+        let mut edges: Vec<String> = graph
+            .graph
+            .edge_indices()
+            .map(|idx| {
+                let (from, to) = graph.graph.edge_endpoints(idx).unwrap();
+                format!(
+                    "{} -> {} (distance {})",
+                    task(from),
+                    task(to),
+                    graph.graph[idx].distance
+                )
+            })
+            .collect();
+        edges.sort();
+
+        format!(
+            "# nodes\n{}\n\n# edges\n{}\n",
+            nodes.join("\n"),
+            edges.join("\n")
+        )
+    }
+
+    /// Configs of existing users have to keep working and collect the same tasks. Run with
+    /// `UPDATE_SNAPSHOTS=1` to accept intended changes of the graphs.
+    #[test]
+    fn test_config_snapshots() {
+        #[derive(serde::Deserialize)]
+        struct SnapshotConfig {
+            reference: crate::GitRepository,
+            metrics: HashMap<String, MetricConfig>,
+        }
+
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/configs");
+        let update = std::env::var_os("UPDATE_SNAPSHOTS").is_some();
+
+        let mut config_paths: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+            .collect();
+        config_paths.sort();
+        assert!(!config_paths.is_empty());
+
+        for config_path in config_paths {
+            let config: SnapshotConfig =
+                toml::from_str(&std::fs::read_to_string(&config_path).unwrap())
+                    .unwrap_or_else(|e| panic!("{} no longer parses: {e}", config_path.display()));
+
+            let commits = config.reference.select_commits(create_snapshot_commits());
+
+            let graph = build_collection_execution_graph(&config.metrics, &commits, true);
+            graph.validate().unwrap();
+            let rendered = render_graph(&graph);
+
+            let snapshot_path = config_path.with_extension("graph");
+
+            if update {
+                std::fs::write(&snapshot_path, &rendered).unwrap();
+                continue;
+            }
+
+            let snapshot = std::fs::read_to_string(&snapshot_path)
+                .unwrap_or_else(|e| panic!("Missing snapshot {}: {e}", snapshot_path.display()));
+
+            assert_eq!(
+                snapshot,
+                rendered,
+                "Execution graph of {} changed",
+                config_path.display()
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_built_graph() {
+        let mut metrics = HashMap::new();
+
+        metrics.insert(
+            "test_metric".to_string(),
+            MetricConfig {
+                frequency: crate::Frequency::PerCommit,
+                branch: None,
+                cache: None,
+                thresholds: None,
+                campaign: None,
+                align_to_calendar: None,
+                phase_offset: None,
+                max_concurrency: None,
+                collector: CollectorConfig::TotalLoc {
+                    scope: Box::default(),
+                },
+            },
+        );
+
+        let commits = vec![
+            create_dummy_commit("1", "2012-12-12T00:00:00Z"),
+            create_dummy_commit("2", "2012-12-13T00:00:00Z"),
+        ];
+
+        let result = build_collection_execution_graph(&metrics, &commits, true);
+
+        assert!(result.validate().is_ok());
+    }
+
+    #[test]
+    fn test_metrics_of_different_branches_share_tasks() {
+        let metric = |branch: Option<&str>| MetricConfig {
+            frequency: crate::Frequency::PerCommit,
+            branch: branch.map(ToString::to_string),
+            cache: None,
+            thresholds: None,
+            campaign: None,
+            align_to_calendar: None,
+            phase_offset: None,
+            max_concurrency: None,
+            collector: CollectorConfig::CumulativeDiffStat {
+                scope: Box::default(),
+            },
+        };
+
+        let metrics = HashMap::from([
+            ("churn".to_string(), metric(None)),
+            ("release-churn".to_string(), metric(Some("release"))),
+        ]);
+
+        let main = vec![
+            create_dummy_commit("c1", "2023-01-01T00:00:00Z"),
+            create_dummy_commit("c2", "2023-01-02T00:00:00Z"),
+            create_dummy_commit("c3", "2023-01-04T00:00:00Z"),
+        ];
+        let release = vec![
+            main[0].clone(),
+            main[1].clone(),
+            create_dummy_commit("r3", "2023-01-03T00:00:00Z"),
+        ];
+
+        let result = build_collection_execution_graph_with(
+            &metrics,
+            |metric| match metric.branch {
+                Some(_) => &release,
+                None => &main,
+            },
+            false,
+        );
+
+        assert!(result.validate().is_ok());
+        assert_node_commit_hashes(&result.graph, &["c1", "c2", "c3", "r3"]);
+
+        // Both branches continue from the last shared commit
+        let predecessor = |commit_hash: &str| {
+            let node_idx = result
+                .graph
+                .node_indices()
+                .find(|idx| result.graph[*idx].commit_hash.0 == commit_hash)
+                .unwrap();
+
+            result
+                .graph
+                .neighbors_directed(node_idx, Direction::Incoming)
+                .map(|idx| result.graph[idx].commit_hash.0.clone())
+                .filter(|predecessor| predecessor != commit_hash)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(predecessor("c3"), vec!["c2"]);
+        assert_eq!(predecessor("r3"), vec!["c2"]);
+    }
+
+    #[test]
+    fn test_window_dependencies() {
+        let collector_config = CollectorConfig::RollingDiffStat {
+            window: 3,
+            scope: Box::default(),
+        };
+
+        let metrics = HashMap::from([(
+            "rolling-churn".to_string(),
+            MetricConfig {
+                frequency: crate::Frequency::Weekly,
+                branch: None,
+                cache: None,
+                thresholds: None,
+                campaign: None,
+                align_to_calendar: None,
+                phase_offset: None,
+                max_concurrency: None,
+                collector: collector_config.clone(),
+            },
+        )]);
+
+        let commits = vec![
+            create_dummy_commit("c1", "2023-01-02T00:00:00Z"),
+            create_dummy_commit("c2", "2023-01-09T00:00:00Z"),
+            create_dummy_commit("c3", "2023-01-10T00:00:00Z"),
+            create_dummy_commit("c4", "2023-01-16T00:00:00Z"),
+            create_dummy_commit("c5", "2023-01-23T00:00:00Z"),
+        ];
+
+        let result = build_collection_execution_graph(&metrics, &commits, false);
+
+        assert!(result.validate().is_ok());
+        assert!(requires_preceding_values(&collector_config));
+
+        let window = |commit_hash: &str| {
+            let node_idx = result
+                .graph
+                .node_indices()
+                .find(|idx| {
+                    let task = &result.graph[*idx];
+                    task.commit_hash.0 == commit_hash && task.collector_config == collector_config
+                })
+                .unwrap();
+
+            let mut window: Vec<(usize, String)> = result
+                .graph
+                .edges_directed(node_idx, Direction::Incoming)
+                .filter(|edge| result.graph[edge.source()].collector_config != collector_config)
+                .map(|edge| {
+                    (
+                        edge.weight().offset,
+                        result.graph[edge.source()].commit_hash.0.clone(),
+                    )
+                })
+                .collect();
+            window.sort();
+            window
+        };
+
+        assert_eq!(window("c1"), vec![(0, "c1".to_string())]);
+        assert_eq!(
+            window("c5"),
+            vec![
+                (0, "c5".to_string()),
+                (1, "c4".to_string()),
+                (2, "c2".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sequential_tasks() {
+        let rolling_churn = CollectorConfig::RollingDiffStat {
+            window: 3,
+            scope: Box::default(),
+        };
+        let expression = CollectorConfig::Expression {
+            expression: "rolling-churn.insertions * 2".to_string(),
+            operands: BTreeMap::from([("rolling-churn".to_string(), rolling_churn.clone())]),
+        };
+
+        let metric = |collector: &CollectorConfig| MetricConfig {
+            frequency: crate::Frequency::Weekly,
+            branch: None,
+            cache: None,
+            thresholds: None,
+            campaign: None,
+            align_to_calendar: None,
+            phase_offset: None,
+            max_concurrency: None,
+            collector: collector.clone(),
+        };
+
+        let metrics = HashMap::from([
+            ("rolling-churn".to_string(), metric(&rolling_churn)),
+            ("doubled-churn".to_string(), metric(&expression)),
+        ]);
+
+        let commits = vec![
+            create_dummy_commit("c1", "2023-01-02T00:00:00Z"),
+            create_dummy_commit("c2", "2023-01-09T00:00:00Z"),
+            create_dummy_commit("c3", "2023-01-16T00:00:00Z"),
+        ];
+
+        let result = build_collection_execution_graph(&metrics, &commits, false);
+        let sequential_tasks = result.sequential_tasks();
+
+        // The expression does not require preceding values itself, but its operand does
+        assert!(!requires_preceding_values(&expression));
+
+        for node_idx in result.graph.node_indices() {
+            let task = &result.graph[node_idx];
+            let is_sequential =
+                task.collector_config == rolling_churn || task.collector_config == expression;
+
+            assert_eq!(
+                sequential_tasks.contains(&node_idx),
+                is_sequential,
+                "{:?} at {}",
+                task.collector_config,
+                task.commit_hash
+            );
+        }
+    }
+
+    #[test]
+    fn test_tree_readers_skip_the_checkout() {
+        for collector_config in [
+            CollectorConfig::TotalCargoDeps,
+            CollectorConfig::TotalCargoFeatures,
+            CollectorConfig::CognitiveComplexity,
+        ] {
+            assert!(reads_tree(&collector_config));
+            assert!(!requires_checkout(&collector_config));
+        }
+
+        assert!(!reads_tree(&CollectorConfig::TotalWorkspaceMembers));
+        assert!(requires_checkout(&CollectorConfig::TotalWorkspaceMembers));
+    }
+
+    #[test]
+    fn test_validate_cycle() {
+        let mut graph = Graph::new();
+        let a = add_node(
+            &mut graph,
+            CollectorConfig::Loc {
+                scope: Box::default(),
+            },
+            "1",
+        );
+        let b = add_node(
+            &mut graph,
+            CollectorConfig::Loc {
+                scope: Box::default(),
+            },
+            "2",
+        );
+        graph.add_edge(
+            a,
+            b,
+            CollectionGraphEdge {
+                distance: 0,
+                offset: 0,
+            },
+        );
+        graph.add_edge(
+            b,
+            a,
+            CollectionGraphEdge {
+                distance: 0,
+                offset: 0,
+            },
+        );
+
+        let result = CollectionExecutionGraph { graph }.validate();
+
+        assert!(matches!(result, Err(GraphValidationError::Cycle { .. })));
+    }
+
+    #[test]
+    fn test_validate_duplicate_task() {
+        let mut graph = Graph::new();
+        add_node(
+            &mut graph,
+            CollectorConfig::Loc {
+                scope: Box::default(),
+            },
+            "1",
+        );
+        add_node(
+            &mut graph,
+            CollectorConfig::Loc {
+                scope: Box::default(),
+            },
+            "1",
+        );
+
+        let result = CollectionExecutionGraph { graph }.validate();
+
+        assert!(matches!(
+            result,
+            Err(GraphValidationError::DuplicateTask { .. })
+        ));
+    }
+
+    #[test]
+    fn test_validate_missing_dependency() {
+        let mut graph = Graph::new();
+        let loc = add_node(
+            &mut graph,
+            CollectorConfig::Loc {
+                scope: Box::default(),
+            },
+            "1",
+        );
+        let total_loc = add_node(
+            &mut graph,
+            CollectorConfig::TotalLoc {
+                scope: Box::default(),
+            },
+            "2",
+        );
+        graph.add_edge(
+            loc,
+            total_loc,
+            CollectionGraphEdge {
+                distance: 0,
+                offset: 0,
+            },
+        );
+
+        let result = CollectionExecutionGraph { graph }.validate();
+
+        assert!(matches!(
+            result,
+            Err(GraphValidationError::MissingDependency { dependency, .. })
+                if *dependency == CollectorConfig::Loc { scope: Box::default() }
+        ));
+    }
+
+    #[test]
+    fn test_prune() {
+        let mut graph = Graph::new();
+        let loc_1 = add_node(
+            &mut graph,
+            CollectorConfig::Loc {
+                scope: Box::default(),
+            },
+            "1",
+        );
+        let total_loc_1 = add_node(
+            &mut graph,
+            CollectorConfig::TotalLoc {
+                scope: Box::default(),
+            },
+            "1",
+        );
+        let loc_2 = add_node(
+            &mut graph,
+            CollectorConfig::Loc {
+                scope: Box::default(),
+            },
+            "2",
+        );
+        let total_loc_2 = add_node(
+            &mut graph,
+            CollectorConfig::TotalLoc {
+                scope: Box::default(),
+            },
+            "2",
+        );
+        graph.add_edge(
+            loc_1,
+            total_loc_1,
+            CollectionGraphEdge {
+                distance: 0,
+                offset: 0,
+            },
+        );
+        graph.add_edge(
+            loc_2,
+            total_loc_2,
+            CollectionGraphEdge {
+                distance: 0,
+                offset: 0,
+            },
+        );
+        graph.add_edge(
+            loc_1,
+            loc_2,
+            CollectionGraphEdge {
+                distance: 0,
+                offset: 0,
+            },
+        );
+        graph.add_edge(
+            total_loc_1,
+            total_loc_2,
+            CollectionGraphEdge {
+                distance: 0,
+                offset: 0,
+            },
+        );
+
+        let graph = CollectionExecutionGraph { graph };
+
+        // Only the total of the second commit is missing
+        let result = graph.prune(|task| {
+            task.commit_hash.0 == "1"
+                || task.collector_config
+                    == CollectorConfig::Loc {
+                        scope: Box::default(),
+                    }
+        });
+
+        let remaining: HashSet<_> = result
+            .graph
+            .raw_nodes()
+            .iter()
+            .map(|n| {
+                (
+                    n.weight.collector_config.clone(),
+                    n.weight.commit_hash.0.clone(),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            remaining,
+            HashSet::from([
+                (
+                    CollectorConfig::Loc {
+                        scope: Box::default()
+                    },
+                    "2".to_string()
+                ),
+                (
+                    CollectorConfig::TotalLoc {
+                        scope: Box::default()
+                    },
+                    "1".to_string()
+                ),
+                (
+                    CollectorConfig::TotalLoc {
+                        scope: Box::default()
+                    },
+                    "2".to_string()
+                ),
+            ])
+        );
+        assert_eq!(result.graph.edge_count(), 2);
+    }
+
+    #[test]
+    fn test_exclude() {
+        let metric = |collector: &str| -> MetricConfig {
+            toml::from_str(&format!(
+                "collector = \"{collector}\"\nfrequency = \"per-commit\""
+            ))
+            .unwrap()
+        };
+
+        let metrics = HashMap::from([
+            ("loc".to_string(), metric("total-loc")),
+            ("churn".to_string(), metric("cumulative-diff-stat")),
+        ]);
+
+        let commits = vec![
+            create_dummy_commit("c1", "2023-01-01T00:00:00Z"),
+            create_dummy_commit("c2", "2023-01-02T00:00:00Z"),
+            create_dummy_commit("c3", "2023-01-03T00:00:00Z"),
+        ];
+
+        let graph = build_collection_execution_graph(&metrics, &commits, false);
+
+        let result = graph.exclude(|task| {
+            task.commit_hash.0 == "c2"
+                && matches!(
+                    task.collector_config,
+                    CollectorConfig::Loc { .. } | CollectorConfig::CumulativeDiffStat { .. }
+                )
+        });
+
+        let remaining: HashSet<_> = result
+            .graph
+            .raw_nodes()
+            .iter()
+            .map(|n| {
+                let collector = serde_json::to_value(&n.weight.collector_config).unwrap();
+                format!("{} {}", collector["collector"], n.weight.commit_hash)
+            })
+            .collect();
+
+        // The total of the same commit and all later cumulative values depend on the excluded
+        // tasks, the values of the other commits don't
+        for task in [
+            r#""loc" c1"#,
+            r#""total-loc" c1"#,
+            r#""cumulative-diff-stat" c1"#,
+            r#""loc" c3"#,
+            r#""total-loc" c3"#,
+        ] {
+            assert!(remaining.contains(task), "{task} was removed");
+        }
+
+        for task in [
+            r#""loc" c2"#,
+            r#""total-loc" c2"#,
+            r#""cumulative-diff-stat" c2"#,
+            r#""cumulative-diff-stat" c3"#,
+        ] {
+            assert!(!remaining.contains(task), "{task} was kept");
+        }
+
+        result.validate().unwrap();
+    }
+
     #[test]
     fn test_build_collection_execution_graph_per_commit_without_force_latest() {
         let mut metrics = HashMap::new();
@@ -276,7 +1208,16 @@ mod test {
             "test_metric".to_string(),
             MetricConfig {
                 frequency: crate::Frequency::PerCommit,
-                collector: CollectorConfig::Loc,
+                branch: None,
+                cache: None,
+                thresholds: None,
+                campaign: None,
+                align_to_calendar: None,
+                phase_offset: None,
+                max_concurrency: None,
+                collector: CollectorConfig::Loc {
+                    scope: Box::default(),
+                },
             },
         );
 
@@ -301,7 +1242,16 @@ mod test {
             "test_metric".to_string(),
             MetricConfig {
                 frequency: crate::Frequency::Daily,
-                collector: CollectorConfig::Loc,
+                branch: None,
+                cache: None,
+                thresholds: None,
+                campaign: None,
+                align_to_calendar: None,
+                phase_offset: None,
+                max_concurrency: None,
+                collector: CollectorConfig::Loc {
+                    scope: Box::default(),
+                },
             },
         );
 
@@ -332,7 +1282,16 @@ mod test {
             "test_metric".to_string(),
             MetricConfig {
                 frequency: crate::Frequency::Weekly,
-                collector: CollectorConfig::Loc,
+                branch: None,
+                cache: None,
+                thresholds: None,
+                campaign: None,
+                align_to_calendar: None,
+                phase_offset: None,
+                max_concurrency: None,
+                collector: CollectorConfig::Loc {
+                    scope: Box::default(),
+                },
             },
         );
 
@@ -350,6 +1309,294 @@ mod test {
         assert_node_commit_hashes(&result.graph, &["1.0", "2.0", "3.0", "4.0"]);
     }
 
+    #[test]
+    fn test_sample_commits_weekly_with_force_latest() {
+        let commits = vec![
+            create_dummy_commit("1.0", "2012-12-12T00:00:00Z"),
+            create_dummy_commit("1.1", "2012-12-13T01:00:00Z"),
+            create_dummy_commit("2.0", "2012-12-18T12:10:00Z"),
+            create_dummy_commit("2.1", "2012-12-19T12:10:00Z"),
+        ];
+
+        let sampled: Vec<(&str, usize)> = sample_commits(
+            &commits,
+            &crate::Frequency::Weekly,
+            false,
+            PhaseOffset::default(),
+            true,
+        )
+        .into_iter()
+        .map(|(commit, distance)| (commit.id.0.as_str(), distance))
+        .collect();
+
+        assert_eq!(vec![("1.0", 0), ("2.0", 1), ("2.1", 1)], sampled);
+    }
+
+    #[test]
+    fn test_sample_commits_weekly_aligned_to_calendar() {
+        // The ISO week starting on 2023-01-30 spans two months
+        let commits = vec![
+            create_dummy_commit("1.0", "2023-01-30T00:00:00Z"),
+            create_dummy_commit("1.1", "2023-02-01T00:00:00Z"),
+            create_dummy_commit("2.0", "2023-02-06T00:00:00Z"),
+        ];
+
+        let sampled = |align_to_calendar: bool| -> Vec<&str> {
+            sample_commits(
+                &commits,
+                &Frequency::Weekly,
+                align_to_calendar,
+                PhaseOffset::default(),
+                false,
+            )
+            .into_iter()
+            .map(|(commit, _)| commit.id.0.as_str())
+            .collect()
+        };
+
+        assert_eq!(sampled(false), ["1.0", "1.1", "2.0"]);
+        assert_eq!(sampled(true), ["1.0", "2.0"]);
+    }
+
+    #[test]
+    fn test_sample_commits_with_phase_offset() {
+        // 2023-01-01 is a Sunday, 2023-01-02 the following Monday
+        let commits = vec![
+            create_dummy_commit("1.0", "2022-12-31T12:00:00Z"),
+            create_dummy_commit("2.0", "2023-01-01T12:00:00Z"),
+            create_dummy_commit("2.1", "2023-01-02T12:00:00Z"),
+            create_dummy_commit("2.2", "2023-01-14T12:00:00Z"),
+            create_dummy_commit("3.0", "2023-01-15T12:00:00Z"),
+        ];
+
+        let sampled = |frequency: Frequency, days: u32| -> Vec<&str> {
+            sample_commits(
+                &commits,
+                &frequency,
+                true,
+                PhaseOffset { days, hours: 0 },
+                false,
+            )
+            .into_iter()
+            .map(|(commit, _)| commit.id.0.as_str())
+            .collect()
+        };
+
+        // Weeks starting on Sunday
+        assert_eq!(sampled(Frequency::Weekly, 6), ["1.0", "2.0", "2.2", "3.0"]);
+
+        // Months starting on the 15th
+        assert_eq!(sampled(Frequency::Monthly, 14), ["1.0", "3.0"]);
+    }
+
+    #[test]
+    fn test_sample_commits_latest_only() {
+        let commits = vec![
+            create_dummy_commit("1.0", "2023-01-02T00:00:00Z"),
+            create_dummy_commit("1.1", "2023-03-03T00:00:00Z"),
+            create_dummy_commit("1.2", "2024-01-09T00:00:00Z"),
+        ];
+
+        let sampled: Vec<(&str, usize)> = sample_commits(
+            &commits,
+            &Frequency::LatestOnly,
+            false,
+            PhaseOffset::default(),
+            false,
+        )
+        .into_iter()
+        .map(|(commit, distance)| (commit.id.0.as_str(), distance))
+        .collect();
+
+        assert_eq!(vec![("1.2", 2)], sampled);
+    }
+
+    #[test]
+    fn test_sample_commits_cron() {
+        // Sprints starting on Tuesdays at 09:00
+        let commits = vec![
+            create_dummy_commit("1.0", "2023-01-02T00:00:00Z"),
+            create_dummy_commit("1.1", "2023-01-03T08:59:00Z"),
+            create_dummy_commit("2.0", "2023-01-03T09:00:00Z"),
+            create_dummy_commit("2.1", "2023-01-09T12:00:00Z"),
+            create_dummy_commit("3.0", "2023-01-24T10:00:00Z"),
+        ];
+
+        let sampled: Vec<(&str, usize)> = sample_commits(
+            &commits,
+            &Frequency::Cron("0 9 * * Tue".to_string()),
+            false,
+            PhaseOffset::default(),
+            false,
+        )
+        .into_iter()
+        .map(|(commit, distance)| (commit.id.0.as_str(), distance))
+        .collect();
+
+        assert_eq!(vec![("1.0", 0), ("2.0", 1), ("3.0", 2)], sampled);
+    }
+
+    /// Key of the period a commit falls into, independent of `is_same_period`. Only defined for
+    /// the frequencies of `frequency_strategy`.
+    fn bucket(
+        commit: &CommitInfo,
+        index: usize,
+        frequency: &Frequency,
+        align_to_calendar: bool,
+    ) -> String {
+        let date = commit.time.date_naive();
+
+        match frequency {
+            Frequency::PerCommit => index.to_string(),
+            Frequency::Yearly => format!("{}", date.year()),
+            Frequency::Monthly => format!("{}-{}", date.year(), date.month()),
+            Frequency::Weekly if align_to_calendar => {
+                format!("{}-{}", date.iso_week().year(), date.iso_week().week())
+            }
+            Frequency::Weekly => format!(
+                "{}-{}-{}",
+                date.year(),
+                date.month(),
+                date.iso_week().week()
+            ),
+            Frequency::Daily => date.to_string(),
+            Frequency::Hourly => format!("{date} {}", commit.time.hour()),
+            Frequency::Cron(_) | Frequency::LatestOnly => {
+                panic!("Frequency {frequency} has no fixed periods to bucket commits into")
+            }
+        }
+    }
+
+    fn frequency_strategy() -> impl Strategy<Value = Frequency> {
+        prop_oneof![
+            Just(Frequency::PerCommit),
+            Just(Frequency::Yearly),
+            Just(Frequency::Monthly),
+            Just(Frequency::Weekly),
+            Just(Frequency::Daily),
+            Just(Frequency::Hourly),
+        ]
+    }
+
+    /// Commits with unique times, newest first like the history walk returns them. The gaps
+    /// between them mix minutes, days and months so every frequency has buckets with several
+    /// commits as well as empty ones.
+    fn timeline_strategy() -> impl Strategy<Value = Vec<CommitInfo>> {
+        prop::collection::vec(
+            prop_oneof![1..3_600_i64, 1..3 * 86_400_i64, 1..60 * 86_400_i64],
+            1..60,
+        )
+        .prop_map(|gaps| {
+            let start = "2019-12-20T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
+
+            let mut offset = 0;
+            let mut commits: Vec<CommitInfo> = gaps
+                .into_iter()
+                .enumerate()
+                .map(|(index, gap)| {
+                    offset += gap;
+
+                    let mut commit =
+                        create_dummy_commit(&index.to_string(), "2000-01-01T00:00:00Z");
+                    commit.time = start + chrono::Duration::seconds(offset);
+                    commit
+                })
+                .collect();
+
+            commits.reverse();
+            commits
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn test_build_collection_execution_graph_sampling_invariants(
+            commits in timeline_strategy(),
+            frequency in frequency_strategy(),
+            force_latest_commit in any::<bool>(),
+            align_to_calendar in any::<bool>(),
+        ) {
+            let mut metrics = HashMap::new();
+            metrics.insert(
+                "test_metric".to_string(),
+                MetricConfig {
+                    frequency: frequency.clone(),
+                    branch: None,
+                    cache: None,
+                    thresholds: None,
+                    campaign: None,
+                    align_to_calendar: Some(align_to_calendar),
+                    phase_offset: None,
+                    max_concurrency: None,
+                    collector: CollectorConfig::ChangedFiles,
+                },
+            );
+
+            let graph = build_collection_execution_graph(&metrics, &commits, force_latest_commit);
+            prop_assert!(graph.validate().is_ok());
+
+            let mut sorted_commits = commits.clone();
+            sorted_commits.sort_by(|a, b| a.time.cmp(&b.time));
+
+            // The first commit of every non-empty bucket is sampled, later ones only if they are
+            // the forced latest commit
+            let mut expected: Vec<&CommitHash> = vec![];
+            let mut seen_buckets = HashSet::new();
+            for (index, commit) in sorted_commits.iter().enumerate() {
+                let is_latest = index == sorted_commits.len() - 1;
+
+                if seen_buckets.insert(bucket(commit, index, &frequency, align_to_calendar))
+                    || (force_latest_commit && is_latest)
+                {
+                    expected.push(&commit.id);
+                }
+            }
+
+            let position = |hash: &CommitHash| {
+                sorted_commits.iter().position(|c| &c.id == hash).unwrap()
+            };
+
+            let mut sampled: Vec<&CommitHash> = graph
+                .graph
+                .node_weights()
+                .map(|task| &task.commit_hash)
+                .collect();
+            sampled.sort_by_key(|hash| position(hash));
+
+            prop_assert_eq!(&expected, &sampled);
+
+            if force_latest_commit {
+                prop_assert_eq!(sampled.last(), Some(&&sorted_commits.last().unwrap().id));
+            }
+
+            // Consecutive samples are connected, the distances never decrease
+            let mut edges: Vec<(usize, usize, usize)> = graph
+                .graph
+                .edge_indices()
+                .map(|idx| {
+                    let (from, to) = graph.graph.edge_endpoints(idx).unwrap();
+                    (
+                        position(&graph.graph[from].commit_hash),
+                        position(&graph.graph[to].commit_hash),
+                        graph.graph[idx].distance,
+                    )
+                })
+                .collect();
+            edges.sort_unstable();
+
+            prop_assert_eq!(edges.len(), sampled.len() - 1);
+
+            for (edge, pair) in edges.iter().zip(sampled.windows(2)) {
+                prop_assert_eq!((edge.0, edge.1), (position(pair[0]), position(pair[1])));
+                prop_assert!(edge.2 < sorted_commits.len());
+            }
+
+            for pair in edges.windows(2) {
+                prop_assert!(pair[0].2 <= pair[1].2);
+            }
+        }
+    }
+
     #[test]
     fn test_build_collection_execution_graph_monthly_without_force_latest() {
         let mut metrics = HashMap::new();
@@ -358,7 +1605,16 @@ mod test {
             "test_metric".to_string(),
             MetricConfig {
                 frequency: crate::Frequency::Monthly,
-                collector: CollectorConfig::Loc,
+                branch: None,
+                cache: None,
+                thresholds: None,
+                campaign: None,
+                align_to_calendar: None,
+                phase_offset: None,
+                max_concurrency: None,
+                collector: CollectorConfig::Loc {
+                    scope: Box::default(),
+                },
             },
         );
 
@@ -385,7 +1641,16 @@ mod test {
             "test_metric".to_string(),
             MetricConfig {
                 frequency: crate::Frequency::Yearly,
-                collector: CollectorConfig::Loc,
+                branch: None,
+                cache: None,
+                thresholds: None,
+                campaign: None,
+                align_to_calendar: None,
+                phase_offset: None,
+                max_concurrency: None,
+                collector: CollectorConfig::Loc {
+                    scope: Box::default(),
+                },
             },
         );
 
@@ -412,7 +1677,16 @@ mod test {
             "test_metric".to_string(),
             MetricConfig {
                 frequency: crate::Frequency::PerCommit,
-                collector: CollectorConfig::Loc,
+                branch: None,
+                cache: None,
+                thresholds: None,
+                campaign: None,
+                align_to_calendar: None,
+                phase_offset: None,
+                max_concurrency: None,
+                collector: CollectorConfig::Loc {
+                    scope: Box::default(),
+                },
             },
         );
 
@@ -437,7 +1711,16 @@ mod test {
             "test_metric".to_string(),
             MetricConfig {
                 frequency: crate::Frequency::Daily,
-                collector: CollectorConfig::Loc,
+                branch: None,
+                cache: None,
+                thresholds: None,
+                campaign: None,
+                align_to_calendar: None,
+                phase_offset: None,
+                max_concurrency: None,
+                collector: CollectorConfig::Loc {
+                    scope: Box::default(),
+                },
             },
         );
 
@@ -468,7 +1751,16 @@ mod test {
             "test_metric".to_string(),
             MetricConfig {
                 frequency: crate::Frequency::Weekly,
-                collector: CollectorConfig::Loc,
+                branch: None,
+                cache: None,
+                thresholds: None,
+                campaign: None,
+                align_to_calendar: None,
+                phase_offset: None,
+                max_concurrency: None,
+                collector: CollectorConfig::Loc {
+                    scope: Box::default(),
+                },
             },
         );
 
@@ -495,7 +1787,16 @@ mod test {
             "test_metric".to_string(),
             MetricConfig {
                 frequency: crate::Frequency::Monthly,
-                collector: CollectorConfig::Loc,
+                branch: None,
+                cache: None,
+                thresholds: None,
+                campaign: None,
+                align_to_calendar: None,
+                phase_offset: None,
+                max_concurrency: None,
+                collector: CollectorConfig::Loc {
+                    scope: Box::default(),
+                },
             },
         );
 
@@ -522,7 +1823,16 @@ mod test {
             "test_metric".to_string(),
             MetricConfig {
                 frequency: crate::Frequency::Yearly,
-                collector: CollectorConfig::Loc,
+                branch: None,
+                cache: None,
+                thresholds: None,
+                campaign: None,
+                align_to_calendar: None,
+                phase_offset: None,
+                max_concurrency: None,
+                collector: CollectorConfig::Loc {
+                    scope: Box::default(),
+                },
             },
         );
 
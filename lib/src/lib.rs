@@ -1,70 +1,246 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::{self};
 use std::path::PathBuf;
-use std::sync::Arc;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
 
-use collectors::{BaseCollector, Collector, DerivedCollector};
+use chrono::{DateTime, Utc};
+use collectors::{BaseCollector, Collector, DerivedCollector, SkipReason, SkippedValue};
 use dashmap::DashMap;
 use git::GitError;
 use graph::CollectionExecutionGraph;
 use nanoid::nanoid;
 use object_pool::Pool;
 use petgraph::graph::NodeIndex;
-use petgraph::visit::Walker;
+use petgraph::visit::{EdgeRef, Walker};
+use petgraph::Direction;
 #[cfg(feature = "rayon")]
 use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use ssh_key::PrivateKey;
 use thiserror::Error;
-use tracing::{debug, span, Level};
+use tracing::{debug, span, warn, Level};
 
-use crate::git::clone_repository;
-use crate::graph::build_collection_execution_graph;
+use crate::git::{clone_repository, update_object_store, WorktreeHandle};
+use crate::graph::{
+    build_collection_execution_graph_with, checkout_scope, reads_tree, reads_whole_tree,
+    requires_checkout, requires_preceding_values, CollectionTask,
+};
 
 mod cache;
+mod campaigns;
 mod collectors;
 mod config;
+mod events;
+#[cfg(feature = "fuzzing")]
+#[doc(hidden)]
+pub mod fuzzing;
 mod git;
 mod graph;
-
-pub use cache::{Cache, FileCache};
-pub use collectors::{
-    ChangedFilesLocValue, ChangedFilesValue, CollectorValue, FileListValue, LocValue,
-    PatternOccurencesValue, TotalCargoDependenciesValue, TotalDiffStatValue, TotalFileCountValue,
-    TotalLocValue, TotalPatternOccurencesValue,
+mod output;
+mod run;
+mod thresholds;
+
+pub use cache::{exclude_paths_id, BranchCache, Cache, CacheError, FileCache};
+pub use campaigns::{CampaignError, CampaignProjection};
+pub use collectors::CollectorValue;
+pub use config::{
+    validate_metrics, Campaign, CollectorConfig, FileScope, Frequency, GitRepository,
+    MatchRedaction, MergeCommits, MetricConfig, MetricConfigError, PhaseOffset, Quarantine,
+    RetryPolicy, Threshold, Thresholds, TreeLimits, DEFAULT_EXCLUDE_PATHS,
 };
-pub use config::{CollectorConfig, Frequency, GitRepository, MetricConfig};
-pub use git::{CloneProgress, CommitHash, CommitInfo, CommitTagInfo, RepositoryHandle};
+pub use events::{attribute_events, read_events, Event, EventsError};
+pub use globset::Glob;
+pub use graph::sample_commits;
+pub use output::{load_output, CollectedData, LoadOutputError, COLLECTOR_METADATA_KEY};
+pub use run::{RunConfig, RunError};
+pub use thresholds::{ThresholdBound, ThresholdError, ThresholdLevel, ThresholdViolation};
+
+pub use git::{
+    Author, CloneProgress, CloneSummary, CommitHash, CommitInfo, CommitTagInfo, HttpsCredentials,
+    TreeStats,
+};
+
+/// Only used by the CLI to inspect existing repositories, not covered by the API stability
+/// guarantees
+#[doc(hidden)]
+pub use git::RepositoryHandle;
+
+/// Values of the individual collectors, wrapped by [`CollectorValue`]
+pub mod values {
+    pub use crate::collectors::{
+        AuthorDomainValue, AuthorDomainsValue, BuildWarningsValue, ChangedFilesLocValue,
+        ChangedFilesValue, CiWorkflowsValue, CognitiveComplexityValue, CollectorValueCastError,
+        CommentDensityValue, ConfigFilesValue, ContainerConfigValue, CoverageReportValue,
+        CumulativeDiffStatValue, ExpressionSyntaxError, ExpressionValue, FileLengthValue,
+        FileListValue, FormattingChangesValue, HighEntropyStringsValue, ImportCouplingValue,
+        LocValue, MaintainabilityIndexValue, PartialGrepText, PartialMatchData,
+        PartialMatchDataSubmatch, PatternOccurencesValue, RollingDiffStatValue, SecretFinding,
+        SecretsValue, ShellcheckValue, SkipReason, SkippedValue, TotalCargoDependenciesValue,
+        TotalCargoFeaturesValue, TotalDiffStatValue, TotalFileCountValue, TotalLocValue,
+        TotalPatternOccurencesValue, TotalSymlinksAndSubmodulesValue, TotalWorkspaceMembersValue,
+    };
+}
+
+/// Everything needed to configure and run a collection, `use myaku::prelude::*;`
+pub mod prelude {
+    pub use crate::{
+        Cache, CollectionProcess, CollectionProcessError, CollectorConfig, CollectorValue,
+        CommitHash, CommitInfo, CommitTagInfo, ExecutionProgressCallbackState, FileCache,
+        Frequency, GitRepository, Initial, MetricConfig, PostCollection,
+    };
+}
 
 #[derive(Error, Debug)]
+#[non_exhaustive]
 pub enum CollectionProcessError {
     #[error("No metrics configured")]
     NoMetrics,
 
+    #[error("Invalid {} of metric {metric_name}", source.field())]
+    InvalidMetric {
+        metric_name: String,
+        #[source]
+        source: MetricConfigError,
+    },
+
     #[error("No commits found")]
     NoCommits,
 
     #[error("Repository URL in reference directory does not match the one in the config file")]
     MismatchedRepositoryUrl,
 
-    #[error("{0}")]
-    BaseCollectorError(#[from] collectors::BaseCollectorError),
+    #[error(
+        "No task for collector {collector_config:?} at commit {commit_hash} in the execution graph"
+    )]
+    UnknownTask {
+        collector_config: CollectorConfig,
+        commit_hash: CommitHash,
+    },
+
+    #[error("Dependency {collector_config:?} at commit {commit_hash} has not been collected yet")]
+    MissingDependency {
+        collector_config: CollectorConfig,
+        commit_hash: CommitHash,
+    },
+
+    #[error(
+        "Metric {metric_name} is collected from branch {branch}, whose commits were not collected"
+    )]
+    UncollectedBranch { metric_name: String, branch: String },
+
+    #[error("Branch {branch} moved from {expected} to {actual} during the collection")]
+    HeadMoved {
+        branch: String,
+        expected: CommitHash,
+        actual: CommitHash,
+    },
+
+    #[error(transparent)]
+    InvalidGraph(#[from] graph::GraphValidationError),
 
-    #[error("{0}")]
-    DerivedCollectorError(#[from] collectors::DerivedCollectorError),
+    #[error("Failed to collect {collector_id} at commit {commit_hash}")]
+    Task {
+        collector_id: String,
+        commit_hash: CommitHash,
+        #[source]
+        source: Box<TaskError>,
+    },
 
-    #[error("{0}")]
+    #[error(transparent)]
     Cache(#[from] cache::CacheError),
 
-    #[error("{0}")]
-    IO(#[from] std::io::Error),
+    #[error("Could not create directory {}", path.display())]
+    IO {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
 
-    #[error("{0}")]
+    #[error(transparent)]
     Git(#[from] git::GitError),
 
-    #[error("{0}")]
+    #[error(transparent)]
     Send(#[from] std::sync::mpsc::SendError<ExecutionProgressCallbackState>),
 }
 
+impl CollectionProcessError {
+    /// Stable, machine-readable identifier of the kind of failure
+    #[must_use]
+    pub fn code(&self) -> &'static str {
+        match self {
+            CollectionProcessError::NoMetrics => "no_metrics",
+            CollectionProcessError::InvalidMetric { .. } => "invalid_config",
+            CollectionProcessError::NoCommits => "no_commits",
+            CollectionProcessError::MismatchedRepositoryUrl => "mismatched_repository_url",
+            CollectionProcessError::UnknownTask { .. } => "unknown_task",
+            CollectionProcessError::MissingDependency { .. } => "missing_dependency",
+            CollectionProcessError::UncollectedBranch { .. } => "uncollected_branch",
+            CollectionProcessError::HeadMoved { .. } => "head_moved",
+            CollectionProcessError::InvalidGraph(_) => "invalid_graph",
+            CollectionProcessError::Task { source, .. } => source.code(),
+            CollectionProcessError::Cache(_) => "cache",
+            CollectionProcessError::IO { .. } => "io",
+            CollectionProcessError::Git(_) => "git",
+            CollectionProcessError::Send(_) => "progress_channel_closed",
+        }
+    }
+
+    /// Message of the error followed by the messages of all of its sources
+    #[must_use]
+    pub fn report(&self) -> String {
+        error_chain(self)
+    }
+}
+
+/// Failure of a single task of the execution graph
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum TaskError {
+    #[error(transparent)]
+    BaseCollector(#[from] collectors::BaseCollectorError),
+
+    #[error(transparent)]
+    DerivedCollector(#[from] collectors::DerivedCollectorError),
+
+    #[error("Could not check out the commit")]
+    Checkout(#[source] git::GitError),
+}
+
+impl TaskError {
+    /// Stable, machine-readable identifier of the kind of failure
+    #[must_use]
+    pub fn code(&self) -> &'static str {
+        match self {
+            TaskError::BaseCollector(_) | TaskError::DerivedCollector(_) => "collector_failed",
+            TaskError::Checkout(_) => "checkout_failed",
+        }
+    }
+
+    /// Whether collecting the task again may succeed, see [`RetryPolicy`]
+    #[must_use]
+    pub fn is_transient(&self) -> bool {
+        match self {
+            TaskError::BaseCollector(e) => e.is_transient(),
+            TaskError::DerivedCollector(_) => false,
+            TaskError::Checkout(e) => e.is_transient(),
+        }
+    }
+}
+
+fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+
+    while let Some(error) = source {
+        message.push_str(": ");
+        message.push_str(&error.to_string());
+        source = error.source();
+    }
+
+    message
+}
+
 pub struct Initial {
     pub metrics: HashMap<String, MetricConfig>,
 
@@ -72,10 +248,33 @@ pub struct Initial {
     pub repository_path: PathBuf,
     pub ssh_key: Option<PrivateKey>,
 
+    /// Token used to clone and fetch repositories over HTTPS
+    pub https_credentials: Option<HttpsCredentials>,
+
+    /// Bare mirror of the repository shared between clones of it, e.g. by several configs
+    /// tracking different branches. Only used when the repository is cloned.
+    pub object_store_path: Option<PathBuf>,
+
+    /// Files hidden from all collectors, `DEFAULT_EXCLUDE_PATHS` if unset
+    pub exclude_paths: Option<Vec<Glob>>,
+
     pub cache: Box<dyn Cache>,
 
     pub disable_cache: bool,
 
+    /// Retries of tasks failing for likely transient reasons
+    pub retry_policy: RetryPolicy,
+
+    /// Tasks which are skipped, e.g. because they failed in previous runs and will never succeed
+    pub quarantine: Quarantine,
+
+    /// Limits of the commit trees above which collectors going through all files are skipped
+    pub tree_limits: TreeLimits,
+
+    /// Reports the size of the files read by every task going through all files of its commit,
+    /// at the cost of walking the tree of these commits once more
+    pub measure_scanned_bytes: bool,
+
     /// If true, do not attempt to perform any network operations (clone, fetch, etc.)
     pub offline: bool,
 }
@@ -86,9 +285,16 @@ pub struct ReadyForClone {
 
     reference: GitRepository,
     ssh_key: Option<PrivateKey>,
+    https_credentials: Option<HttpsCredentials>,
+    object_store_path: Option<PathBuf>,
+    exclude_paths: Vec<Glob>,
 
     cache: Box<dyn Cache>,
     disable_cache: bool,
+    retry_policy: RetryPolicy,
+    quarantine: Quarantine,
+    tree_limits: TreeLimits,
+    measure_scanned_bytes: bool,
 
     /// If true, do not attempt to perform any network operations (clone, fetch, etc.)
     offline: bool,
@@ -99,9 +305,15 @@ pub struct ReadyForFetch {
 
     repo: RepositoryHandle,
     reference: GitRepository,
+    ssh_key: Option<PrivateKey>,
+    https_credentials: Option<HttpsCredentials>,
 
     cache: Box<dyn Cache>,
     disable_cache: bool,
+    retry_policy: RetryPolicy,
+    quarantine: Quarantine,
+    tree_limits: TreeLimits,
+    measure_scanned_bytes: bool,
 
     /// If true, do not attempt to perform any network operations (clone, fetch, etc.)
     offline: bool,
@@ -111,23 +323,41 @@ pub struct IdleWithoutCommits {
     pub metrics: HashMap<String, MetricConfig>,
 
     repo: RepositoryHandle,
-    branch: Option<String>,
+    reference: GitRepository,
 
     cache: Box<dyn Cache>,
     disable_cache: bool,
+    retry_policy: RetryPolicy,
+    quarantine: Quarantine,
+    tree_limits: TreeLimits,
+    measure_scanned_bytes: bool,
 
     /// If true, do not attempt to perform any network operations (clone, fetch, etc.)
     offline: bool,
+
+    /// Only set if the repository was just cloned
+    pub clone_summary: Option<CloneSummary>,
 }
 
 pub struct IdleWithCommits {
     pub metrics: HashMap<String, MetricConfig>,
 
     repo: RepositoryHandle,
-    branch: Option<String>,
+
+    /// Commit the branch of the reference pointed to when the commits were collected, or the end
+    /// of the range given by `to`. All later steps use this commit instead of resolving the branch
+    /// again.
+    pub head: CommitHash,
+
+    /// Branches the commits were collected from
+    pub branches: Branches,
 
     cache: Box<dyn Cache>,
     disable_cache: bool,
+    retry_policy: RetryPolicy,
+    quarantine: Quarantine,
+    tree_limits: TreeLimits,
+    measure_scanned_bytes: bool,
 
     /// If true, do not attempt to perform any network operations (clone, fetch, etc.)
     offline: bool,
@@ -143,29 +373,175 @@ pub struct ReadyForCollection {
     repo: RepositoryHandle,
     collection_execution_graph: CollectionExecutionGraph,
 
+    /// Graph as built from the sampled commits, before quarantined and known tasks are removed
+    planned_execution_graph: CollectionExecutionGraph,
+
+    /// Commit the branch of the reference pointed to when the commits were collected
+    pub head: CommitHash,
+    pub branches: Branches,
+
     cache: Box<dyn Cache>,
     disable_cache: bool,
+    retry_policy: RetryPolicy,
+    measure_scanned_bytes: bool,
 
     pub commits: Vec<CommitInfo>,
     pub tags: Option<Vec<CommitTagInfo>>,
     pub storage: DashMap<(CollectorConfig, CommitHash), CollectorValue>,
 
     pub latest_commit: CommitHash,
+
+    /// Latest commit of every branch, whose values are reported once a metric is finished
+    latest_commits: BTreeMap<String, CommitHash>,
+
+    /// Number of tasks pruned from the execution graph because their values were already known
+    precomputed_task_count: usize,
+
+    /// Number of tasks removed from the execution graph because they or their dependencies are
+    /// quarantined
+    quarantined_task_count: usize,
+
+    /// Number of tasks removed from the execution graph because their commit exceeds the tree
+    /// limits
+    skipped_task_count: usize,
 }
 
 pub struct PostCollection {
     pub metrics: HashMap<String, MetricConfig>,
 
-    collection_execution_graph: CollectionExecutionGraph,
-
     cache: Box<dyn Cache>,
     disable_cache: bool,
 
+    /// Files hidden from the collectors, see [`cache::exclude_paths_id`]
+    exclude_paths_id: Option<String>,
+
+    /// Graph as built from the sampled commits, whose edges tell how many commits each sampled
+    /// one stands for
+    planned_execution_graph: CollectionExecutionGraph,
+
     pub commits: Vec<CommitInfo>,
     pub tags: Option<Vec<CommitTagInfo>>,
     pub storage: DashMap<(CollectorConfig, CommitHash), CollectorValue>,
 
     pub latest_commit: CommitHash,
+
+    /// Commit the branch of the reference pointed to when the commits were collected
+    pub head: CommitHash,
+    pub branches: Branches,
+}
+
+/// Overview of the collected commits, e.g. to check the branch detection and frequency settings
+/// before collecting
+#[derive(Debug, Clone)]
+pub struct PreflightSummary {
+    pub commit_count: usize,
+
+    /// Time of the oldest and the newest commit, `None` if there are no commits
+    pub date_range: Option<(DateTime<Utc>, DateTime<Utc>)>,
+
+    /// Number of commits each metric is collected for, including commits whose values are
+    /// already cached
+    pub sampled_commits: BTreeMap<String, usize>,
+}
+
+/// Sampled commit of a metric a commit of its branch is represented by, see
+/// [`PostCollection::commit_samples`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitSample {
+    pub metric: String,
+    pub commit: CommitHash,
+    pub sampled_commit: CommitHash,
+}
+
+/// Task of an [`ExecutionPlan`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedTask {
+    pub collector_config: CollectorConfig,
+    pub commit_hash: CommitHash,
+
+    /// Whether the task checks its commit out instead of reading from the object database
+    pub requires_checkout: bool,
+
+    /// Whether the value is already cached, so the task is pruned when collecting
+    pub cached: bool,
+}
+
+/// Tasks of the execution graph of a set of metrics, before cached, quarantined or too large
+/// tasks are removed from it
+#[derive(Debug, Clone)]
+pub struct ExecutionPlan {
+    pub tasks: Vec<PlannedTask>,
+}
+
+impl ExecutionPlan {
+    /// Tasks which only one of the plans contains, e.g. to review the cost of a config change
+    #[must_use]
+    pub fn diff(&self, new: &ExecutionPlan) -> PlanDiff {
+        let keys = |plan: &ExecutionPlan| -> HashSet<(CollectorConfig, CommitHash)> {
+            plan.tasks
+                .iter()
+                .map(|task| (task.collector_config.clone(), task.commit_hash.clone()))
+                .collect()
+        };
+
+        let (old_keys, new_keys) = (keys(self), keys(new));
+        let only_in = |plan: &ExecutionPlan, other: &HashSet<(CollectorConfig, CommitHash)>| {
+            plan.tasks
+                .iter()
+                .filter(|task| {
+                    !other.contains(&(task.collector_config.clone(), task.commit_hash.clone()))
+                })
+                .cloned()
+                .collect()
+        };
+
+        PlanDiff {
+            added: only_in(new, &old_keys),
+            removed: only_in(self, &new_keys),
+        }
+    }
+}
+
+/// Difference between the execution plans of two sets of metrics for the same commits
+#[derive(Debug, Clone)]
+pub struct PlanDiff {
+    pub added: Vec<PlannedTask>,
+    pub removed: Vec<PlannedTask>,
+}
+
+impl PlanDiff {
+    /// Added tasks which are not cached yet and have to be collected
+    pub fn uncached_added(&self) -> impl Iterator<Item = &PlannedTask> {
+        self.added.iter().filter(|task| !task.cached)
+    }
+}
+
+/// Commits of the branches the metrics are collected from
+#[derive(Debug, Clone)]
+pub struct Branches {
+    /// Branch of the reference, collected by all metrics without a branch of their own
+    pub default: String,
+
+    /// Commit every branch pointed to when the commits were collected
+    pub heads: BTreeMap<String, CommitHash>,
+
+    /// Commits of every branch, after applying the commit selection of the reference
+    pub commits: BTreeMap<String, HashSet<CommitHash>>,
+}
+
+impl Branches {
+    #[must_use]
+    pub fn branch_of<'a>(&'a self, metric: &'a MetricConfig) -> &'a str {
+        metric.branch.as_deref().unwrap_or(&self.default)
+    }
+
+    /// Whether the commit is on the branch the metric is collected from
+    #[must_use]
+    pub fn contains(&self, metric: &MetricConfig, commit_hash: &CommitHash) -> bool {
+        self.commits
+            .get(self.branch_of(metric))
+            .is_some_and(|commits| commits.contains(commit_hash))
+    }
 }
 
 pub enum CollectionProcess {
@@ -191,19 +567,74 @@ pub enum CollectionProcess {
     PostCollection(PostCollection),
 }
 
-#[derive(Debug)]
+/// Progress of the collection, serialized with the variant name as snake case `type` field.
+///
+/// Tasks reference their collector by [`CollectorConfig::id`] instead of the full config.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[non_exhaustive]
 pub enum ExecutionProgressCallbackState {
     Initial {
         metric_count: usize,
+
+        /// Number of tasks which will be processed, each one is reported as new, reused or failed
         task_count: usize,
+
+        /// Number of tasks which were resolved from the cache before the execution and are
+        /// therefore not processed at all
+        precomputed_task_count: usize,
+
+        /// Number of tasks which are skipped because they or their dependencies are quarantined
+        quarantined_task_count: usize,
+
+        /// Number of tasks which are skipped because their commit exceeds the tree limits
+        skipped_task_count: usize,
     },
     New {
-        collector_config: CollectorConfig,
+        collector_id: String,
         commit_hash: CommitHash,
+
+        /// Time spent on the task, including checking out its commit and failed attempts
+        duration: Duration,
+
+        /// Size of the files at the commit, if the task goes through all of them and
+        /// [`Initial::measure_scanned_bytes`] is set
+        scanned_bytes: Option<u64>,
     },
     Reused {
-        collector_config: CollectorConfig,
+        collector_id: String,
+        commit_hash: CommitHash,
+    },
+    /// A task failed for a likely transient reason and is attempted again
+    TaskRetried {
+        collector_id: String,
         commit_hash: CommitHash,
+
+        /// Number of the failed attempt, starting at 1
+        attempt: u32,
+
+        /// Stable, machine-readable identifier of the kind of failure, see [`TaskError::code`]
+        code: &'static str,
+
+        /// Message of the error and all of its sources
+        error: String,
+    },
+    TaskFailed {
+        collector_id: String,
+        commit_hash: CommitHash,
+
+        /// Stable, machine-readable identifier of the kind of failure, see [`TaskError::code`]
+        code: &'static str,
+
+        /// Message of the error and all of its sources
+        error: String,
+    },
+    /// All tasks of a metric have been processed
+    MetricFinished {
+        metric_name: String,
+
+        /// Value of the metric at the latest commit, if it could be collected
+        latest_value: Option<CollectorValue>,
     },
     Finished,
 }
@@ -211,19 +642,97 @@ pub enum ExecutionProgressCallbackState {
 impl Initial {
     #[tracing::instrument(level = "trace", skip(self))]
     pub fn initialize(
-        self,
+        mut self,
         ignore_mismatched_repo_url: bool,
     ) -> Result<CollectionProcess, CollectionProcessError> {
         if self.metrics.is_empty() {
             return Err(CollectionProcessError::NoMetrics);
         }
 
+        // Fail before cloning instead of once for every commit the collector runs at
+        let mut metric_names: Vec<&String> = self.metrics.keys().collect();
+        metric_names.sort();
+
+        for metric_name in metric_names {
+            for error in self.metrics[metric_name].validate() {
+                if error.is_warning() {
+                    warn!("Metric {}: {}", metric_name, error);
+                    continue;
+                }
+
+                return Err(CollectionProcessError::InvalidMetric {
+                    metric_name: metric_name.clone(),
+                    source: error,
+                });
+            }
+        }
+
+        // Every explicitly listed commit is collected, which no frequency other than per-commit
+        // guarantees
+        if self.reference.commits.is_some() {
+            let mut metric_names: Vec<&String> = self.metrics.keys().collect();
+            metric_names.sort();
+
+            for metric_name in metric_names {
+                let metric = &self.metrics[metric_name];
+
+                if metric.frequency != Frequency::PerCommit {
+                    warn!(
+                        "Metric {}: Frequency {} is ignored, every listed commit is collected",
+                        metric_name, metric.frequency
+                    );
+                }
+
+                if metric.align_to_calendar.is_some() {
+                    warn!(
+                        "Metric {}: Calendar alignment is ignored, every listed commit is collected",
+                        metric_name
+                    );
+                }
+            }
+
+            for metric in self.metrics.values_mut() {
+                metric.frequency = Frequency::PerCommit;
+                metric.align_to_calendar = None;
+            }
+        }
+
+        // Metrics sharing collectors are collected at the commits of each of them
+        for (metric_name, error) in validate_metrics(&self.metrics) {
+            warn!("Metric {}: {}", metric_name, error);
+        }
+
+        // Expressions refer to other metrics by name, their collectors become the operands
+        collectors::expression::resolve(&mut self.metrics).map_err(|(metric_name, source)| {
+            CollectionProcessError::InvalidMetric {
+                metric_name,
+                source,
+            }
+        })?;
+
         let reference_dir = &self.repository_path;
 
-        fs::create_dir_all(reference_dir)?;
+        fs::create_dir_all(reference_dir).map_err(|source| CollectionProcessError::IO {
+            path: reference_dir.clone(),
+            source,
+        })?;
+
+        let exclude_paths = match self.exclude_paths {
+            Some(exclude_paths) => exclude_paths,
+            None => DEFAULT_EXCLUDE_PATHS
+                .iter()
+                .map(|glob| Glob::new(glob))
+                .collect::<Result<_, _>>()
+                .map_err(GitError::from)?,
+        };
+
+        match RepositoryHandle::open(reference_dir) {
+            Result::Ok(mut repo) => {
+                repo.set_excluded_paths(&exclude_paths)?;
+                repo.set_credentials(self.ssh_key.as_ref(), self.https_credentials.as_ref());
+                repo.set_submodules(self.reference.submodules.unwrap_or(false));
+                repo.set_first_parent(self.reference.first_parent.unwrap_or(false));
 
-        return match RepositoryHandle::open(reference_dir) {
-            Result::Ok(repo) => {
                 let remote_url = repo.remote_url()?;
 
                 if remote_url != self.reference.url && !ignore_mismatched_repo_url {
@@ -231,14 +740,23 @@ impl Initial {
                 }
 
                 if self.offline {
+                    if repo.is_shallow()? {
+                        warn!("Repository is shallow, metrics only cover the fetched history");
+                    }
+
                     // Skip fetch and clone if offline
                     return Ok(CollectionProcess::IdleWithoutCommits(IdleWithoutCommits {
                         repo,
-                        branch: self.reference.branch,
+                        reference: self.reference,
                         metrics: self.metrics,
                         cache: self.cache,
                         disable_cache: self.disable_cache,
+                        retry_policy: self.retry_policy,
+                        quarantine: self.quarantine,
+                        tree_limits: self.tree_limits,
+                        measure_scanned_bytes: self.measure_scanned_bytes,
                         offline: self.offline,
+                        clone_summary: None,
                     }));
                 }
 
@@ -246,8 +764,14 @@ impl Initial {
                     repo,
                     metrics: self.metrics,
                     reference: self.reference,
+                    ssh_key: self.ssh_key,
+                    https_credentials: self.https_credentials,
                     cache: self.cache,
                     disable_cache: self.disable_cache,
+                    retry_policy: self.retry_policy,
+                    quarantine: self.quarantine,
+                    tree_limits: self.tree_limits,
+                    measure_scanned_bytes: self.measure_scanned_bytes,
                     offline: self.offline,
                 }))
             }
@@ -261,25 +785,54 @@ impl Initial {
                     reference: self.reference,
                     repository_path: self.repository_path,
                     ssh_key: self.ssh_key,
+                    https_credentials: self.https_credentials,
+                    object_store_path: self.object_store_path,
+                    exclude_paths,
                     cache: self.cache,
                     disable_cache: self.disable_cache,
+                    retry_policy: self.retry_policy,
+                    quarantine: self.quarantine,
+                    tree_limits: self.tree_limits,
+                    measure_scanned_bytes: self.measure_scanned_bytes,
                     offline: self.offline,
                 }))
             }
-        };
+        }
     }
 }
 
 impl ReadyForFetch {
-    #[tracing::instrument(level = "trace", skip(self))]
-    pub fn fetch(self) -> Result<IdleWithoutCommits, CollectionProcessError> {
+    #[tracing::instrument(level = "trace", skip(self, callback))]
+    pub fn fetch(
+        self,
+        callback: impl Fn(&CloneProgress) + Send + Sync,
+    ) -> Result<IdleWithoutCommits, CollectionProcessError> {
+        self.repo.fetch(
+            callback,
+            self.ssh_key.as_ref(),
+            self.https_credentials.as_ref(),
+        )?;
+
+        deepen_history(
+            &self.repo,
+            &self.reference,
+            &self.metrics,
+            self.ssh_key.as_ref(),
+            self.https_credentials.as_ref(),
+        )?;
+
         Ok(IdleWithoutCommits {
             metrics: self.metrics,
             repo: self.repo,
-            branch: self.reference.branch,
+            reference: self.reference,
             cache: self.cache,
             disable_cache: self.disable_cache,
+            retry_policy: self.retry_policy,
+            quarantine: self.quarantine,
+            tree_limits: self.tree_limits,
+            measure_scanned_bytes: self.measure_scanned_bytes,
             offline: self.offline,
+            clone_summary: None,
         })
     }
 }
@@ -288,23 +841,54 @@ impl ReadyForClone {
     #[tracing::instrument(level = "trace", skip(self, callback))]
     pub fn clone(
         self,
-        callback: impl Fn(&CloneProgress),
+        callback: impl Fn(&CloneProgress) + Send + Sync,
     ) -> Result<IdleWithoutCommits, CollectionProcessError> {
-        let repo = clone_repository(
+        if let Some(object_store_path) = &self.object_store_path {
+            update_object_store(
+                &self.reference.url,
+                object_store_path,
+                self.ssh_key.as_ref(),
+                self.https_credentials.as_ref(),
+            )?;
+        }
+
+        let (mut repo, clone_summary) = clone_repository(
             &self.reference.url,
             &self.repository_path,
             callback,
             self.ssh_key.as_ref(),
+            self.https_credentials.as_ref(),
+            self.object_store_path.as_deref(),
+            self.reference.clone_depth,
+            self.reference.partial_clone.unwrap_or(false),
         )
         .map_err(|e| CollectionProcessError::Git(GitError::CloneError(e)))?;
+        repo.set_excluded_paths(&self.exclude_paths)?;
+        repo.set_submodules(self.reference.submodules.unwrap_or(false));
+        repo.set_first_parent(self.reference.first_parent.unwrap_or(false));
+
+        deepen_history(
+            &repo,
+            &self.reference,
+            &self.metrics,
+            self.ssh_key.as_ref(),
+            self.https_credentials.as_ref(),
+        )?;
+
+        debug!("{:?}", clone_summary);
 
         Ok(IdleWithoutCommits {
             repo,
             metrics: self.metrics,
-            branch: self.reference.branch,
+            reference: self.reference,
             cache: self.cache,
             disable_cache: self.disable_cache,
+            retry_policy: self.retry_policy,
+            quarantine: self.quarantine,
+            tree_limits: self.tree_limits,
+            measure_scanned_bytes: self.measure_scanned_bytes,
             offline: self.offline,
+            clone_summary: Some(clone_summary),
         })
     }
 }
@@ -317,17 +901,107 @@ impl IdleWithoutCommits {
 
     #[tracing::instrument(level = "trace", skip(self))]
     pub fn collect_commits(self) -> Result<IdleWithCommits, CollectionProcessError> {
-        let branch = match &self.branch {
+        let branch = match &self.reference.branch {
             Some(branch) => branch.clone(),
             None => self.repo.find_main_branch()?,
         };
 
-        self.repo.reset_hard(&format!("origin/{branch}"))?;
+        let tip = self
+            .repo
+            .resolve_revision(&self.repo.branch_revision(&branch))?;
+
+        // The history is walked from the end of the range, the tip is still recorded as the head
+        // of the branch so a moved branch is noticed before collecting
+        let head = match &self.reference.to {
+            Some(to) => resolve_reference_revision(&self.repo, to)?,
+            None => tip.clone(),
+        };
+        self.repo.reset_hard(&head.0)?;
+
+        let from = self
+            .reference
+            .from
+            .as_ref()
+            .map(|from| resolve_reference_revision(&self.repo, from))
+            .transpose()?;
+
+        let mut branches = Branches {
+            default: branch.clone(),
+            heads: BTreeMap::new(),
+            commits: BTreeMap::new(),
+        };
+
+        let mut other_branches: Vec<&String> = self
+            .metrics
+            .values()
+            .filter_map(|metric| metric.branch.as_ref())
+            .filter(|metric_branch| **metric_branch != branch)
+            .collect();
+        other_branches.sort();
+        other_branches.dedup();
+
+        // Commits of the reference branch come first, in the order of the history walk
+        let mut commits: Vec<CommitInfo> = Vec::new();
+        let mut seen: HashSet<CommitHash> = HashSet::new();
+
+        for branch in std::iter::once(&branch).chain(other_branches) {
+            let (branch_tip, branch_head) = if *branch == branches.default {
+                (tip.clone(), head.clone())
+            } else {
+                let branch_tip = self
+                    .repo
+                    .resolve_revision(&self.repo.branch_revision(branch))?;
+                (branch_tip.clone(), branch_tip)
+            };
+
+            // Only the head is sampled if all metrics are latest-only, unless the filters of the
+            // reference leave it out, the head has to change some of its paths or lie in its range
+            let mut branch_commits = if is_latest_only(&self.metrics)
+                && self.reference.path_filter().is_none()
+                && from.is_none()
+            {
+                self.reference
+                    .select_commits(vec![self.repo.get_commit(&branch_head)?])
+            } else {
+                Vec::new()
+            };
+
+            if branch_commits.is_empty() {
+                branch_commits = self.reference.select_commits(reachable_commits(
+                    &self.repo,
+                    &self.reference,
+                    &branch_head,
+                    from.as_ref(),
+                )?);
+            }
+
+            if branch_commits.is_empty() {
+                return Err(CollectionProcessError::NoCommits);
+            }
 
-        let commits = self.repo.get_all_commits()?;
+            branches.heads.insert(branch.clone(), branch_tip);
+            branches.commits.insert(
+                branch.clone(),
+                branch_commits
+                    .iter()
+                    .map(|commit| commit.id.clone())
+                    .collect(),
+            );
+
+            commits.extend(
+                branch_commits
+                    .into_iter()
+                    .filter(|commit| seen.insert(commit.id.clone())),
+            );
+        }
 
-        if commits.is_empty() {
-            return Err(CollectionProcessError::NoCommits);
+        for listed in self.reference.commits.iter().flatten() {
+            if !commits
+                .iter()
+                .any(|commit| commit.id.0.starts_with(&listed.0))
+            {
+                warn!("Commit {} is not part of the collected history", listed);
+            }
         }
 
         Ok(IdleWithCommits {
@@ -336,9 +1010,14 @@ impl IdleWithoutCommits {
             storage: DashMap::new(),
             metrics: self.metrics,
             repo: self.repo,
-            branch: self.branch,
+            branches,
+            head,
             cache: self.cache,
             disable_cache: self.disable_cache,
+            retry_policy: self.retry_policy,
+            quarantine: self.quarantine,
+            tree_limits: self.tree_limits,
+            measure_scanned_bytes: self.measure_scanned_bytes,
             offline: self.offline,
         })
     }
@@ -347,12 +1026,7 @@ impl IdleWithoutCommits {
 impl IdleWithCommits {
     #[tracing::instrument(level = "trace", skip(self))]
     pub fn collect_tags(self) -> Result<IdleWithCommits, CollectionProcessError> {
-        let branch = match &self.branch {
-            Some(branch) => branch.clone(),
-            None => self.repo.find_main_branch()?,
-        };
-
-        self.repo.reset_hard(&format!("origin/{branch}"))?;
+        self.repo.reset_hard(&self.head.0)?;
 
         let tags = self.repo.get_all_commit_tags()?;
 
@@ -362,30 +1036,248 @@ impl IdleWithCommits {
             repo: self.repo,
             commits: self.commits,
             storage: self.storage,
-            branch: self.branch,
+            branches: self.branches,
+            head: self.head,
             cache: self.cache,
             disable_cache: self.disable_cache,
+            retry_policy: self.retry_policy,
+            quarantine: self.quarantine,
+            tree_limits: self.tree_limits,
+            measure_scanned_bytes: self.measure_scanned_bytes,
             offline: self.offline,
         })
     }
 
+    #[must_use]
+    /// Commits of every branch, sorted by time
+    fn sorted_commits_by_branch(&self) -> BTreeMap<&str, Vec<CommitInfo>> {
+        let mut sorted_commits = self.commits.clone();
+        sorted_commits.sort_by(|a, b| a.time.cmp(&b.time));
+
+        self.branches
+            .commits
+            .iter()
+            .map(|(branch, branch_commits)| {
+                let commits = sorted_commits
+                    .iter()
+                    .filter(|commit| branch_commits.contains(&commit.id))
+                    .cloned()
+                    .collect();
+
+                (branch.as_str(), commits)
+            })
+            .collect()
+    }
+
+    #[must_use]
+    pub fn preflight_summary(&self, force_latest_commit: bool) -> PreflightSummary {
+        let mut sorted_commits = self.commits.clone();
+        sorted_commits.sort_by(|a, b| a.time.cmp(&b.time));
+
+        let commits_by_branch = self.sorted_commits_by_branch();
+
+        let date_range = sorted_commits
+            .first()
+            .zip(sorted_commits.last())
+            .map(|(first, last)| (first.time, last.time));
+
+        let sampled_commits = self
+            .metrics
+            .iter()
+            .map(|(metric_name, metric_config)| {
+                let count = sample_commits(
+                    &commits_by_branch[self.branches.branch_of(metric_config)],
+                    &metric_config.frequency,
+                    metric_config.align_to_calendar.unwrap_or(false),
+                    metric_config.phase_offset.unwrap_or_default(),
+                    force_latest_commit,
+                )
+                .len();
+
+                (metric_name.clone(), count)
+            })
+            .collect();
+
+        PreflightSummary {
+            commit_count: sorted_commits.len(),
+            date_range,
+            sampled_commits,
+        }
+    }
+
+    /// Plans the execution graph of the given metrics for the collected commits, e.g. to compare
+    /// two configs without collecting them. The metrics have to be collected from branches the
+    /// metrics of the collection are collected from.
+    pub fn execution_plan(
+        &self,
+        metrics: &HashMap<String, MetricConfig>,
+        force_latest_commit: bool,
+    ) -> Result<ExecutionPlan, CollectionProcessError> {
+        let mut metrics = metrics.clone();
+        collectors::expression::resolve(&mut metrics).map_err(|(metric_name, source)| {
+            CollectionProcessError::InvalidMetric {
+                metric_name,
+                source,
+            }
+        })?;
+
+        let commits_by_branch = self.sorted_commits_by_branch();
+
+        let mut metric_names: Vec<&String> = metrics.keys().collect();
+        metric_names.sort();
+
+        for metric_name in metric_names {
+            let branch = self.branches.branch_of(&metrics[metric_name]);
+            if !commits_by_branch.contains_key(branch) {
+                return Err(CollectionProcessError::UncollectedBranch {
+                    metric_name: metric_name.clone(),
+                    branch: branch.to_string(),
+                });
+            }
+        }
+
+        let collection_execution_graph = build_collection_execution_graph_with(
+            &metrics,
+            |metric| &commits_by_branch[self.branches.branch_of(metric)],
+            force_latest_commit,
+        );
+
+        collection_execution_graph.validate()?;
+
+        let uncached = uncached_collectors(&metrics);
+
+        let mut tasks = vec![];
+        for task in collection_execution_graph.graph.node_weights() {
+            let key = (task.collector_config.clone(), task.commit_hash.clone());
+
+            let cached = !self.disable_cache
+                && !uncached.contains(&task.collector_config)
+                && (self.storage.contains_key(&key)
+                    || self
+                        .cache
+                        .lookup(
+                            &task.collector_config,
+                            self.repo.excluded_paths_id(),
+                            &task.commit_hash,
+                        )?
+                        .is_some());
+
+            tasks.push(PlannedTask {
+                requires_checkout: requires_worktree(&self.repo, &task.collector_config),
+                collector_config: key.0,
+                commit_hash: key.1,
+                cached,
+            });
+        }
+
+        Ok(ExecutionPlan { tasks })
+    }
+
+    /// Removes the tasks going through all files of commits whose tree exceeds the limits,
+    /// together with the tasks depending on them, and stores a skipped value for the ones at the
+    /// oversized commits instead
+    fn skip_too_large_commits(
+        &self,
+        collection_execution_graph: &CollectionExecutionGraph,
+    ) -> Result<CollectionExecutionGraph, CollectionProcessError> {
+        let mut tree_stats: HashMap<CommitHash, TreeStats> = HashMap::new();
+        for task in collection_execution_graph.graph.node_weights() {
+            if !reads_whole_tree(&task.collector_config)
+                || tree_stats.contains_key(&task.commit_hash)
+            {
+                continue;
+            }
+
+            let stats = self.repo.tree_stats(&task.commit_hash)?;
+            tree_stats.insert(task.commit_hash.clone(), stats);
+        }
+
+        tree_stats.retain(|commit_hash, stats| {
+            let too_large = self.tree_limits.exceeded_by(stats);
+            if too_large {
+                warn!(
+                    "Skipping commit {} with {} files and {} bytes, it exceeds the tree limits",
+                    commit_hash.0, stats.file_count, stats.total_bytes
+                );
+            }
+            too_large
+        });
+
+        let skipped_tasks = collection_execution_graph.dependants(|task| {
+            reads_whole_tree(&task.collector_config) && tree_stats.contains_key(&task.commit_hash)
+        });
+
+        for node_idx in &skipped_tasks {
+            let task = &collection_execution_graph.graph[*node_idx];
+
+            // Later tasks requiring the previous value are dropped without a value of their own
+            let Some(stats) = tree_stats.get(&task.commit_hash) else {
+                continue;
+            };
+
+            self.storage.insert(
+                (task.collector_config.clone(), task.commit_hash.clone()),
+                SkippedValue {
+                    reason: SkipReason::TooLarge,
+                    file_count: stats.file_count,
+                    total_bytes: stats.total_bytes,
+                }
+                .into(),
+            );
+        }
+
+        Ok(collection_execution_graph.remove(&skipped_tasks))
+    }
+
     #[tracing::instrument(level = "trace", skip(self))]
     pub fn prepare_for_collection(
         self,
         force_latest_commit: bool,
     ) -> Result<ReadyForCollection, CollectionProcessError> {
-        let collection_execution_graph =
-            build_collection_execution_graph(&self.metrics, &self.commits, force_latest_commit);
+        let commits_by_branch = self.sorted_commits_by_branch();
+
+        let collection_execution_graph = build_collection_execution_graph_with(
+            &self.metrics,
+            |metric| &commits_by_branch[self.branches.branch_of(metric)],
+            force_latest_commit,
+        );
+
+        collection_execution_graph.validate()?;
+
+        let planned_execution_graph = collection_execution_graph.clone();
+        let task_count = collection_execution_graph.graph.node_count();
+
+        let collection_execution_graph = if self.quarantine.is_empty() {
+            collection_execution_graph
+        } else {
+            collection_execution_graph.exclude(|task| {
+                self.quarantine
+                    .contains(&task.collector_config.id(), &task.commit_hash)
+            })
+        };
+
+        let quarantined_task_count = task_count - collection_execution_graph.graph.node_count();
+        debug!(
+            "quarantined {} of {} tasks",
+            quarantined_task_count, task_count
+        );
+
+        let uncached = uncached_collectors(&self.metrics);
 
         if !self.disable_cache {
             // Fill storage from cache
             for nx in collection_execution_graph.graph.node_indices() {
                 let task = &collection_execution_graph.graph[nx];
 
-                if let Some(value) = self
-                    .cache
-                    .lookup(&task.collector_config, &task.commit_hash)?
-                {
+                if uncached.contains(&task.collector_config) {
+                    continue;
+                }
+
+                if let Some(value) = self.cache.lookup(
+                    &task.collector_config,
+                    self.repo.excluded_paths_id(),
+                    &task.commit_hash,
+                )? {
                     self.storage.insert(
                         (task.collector_config.clone(), task.commit_hash.clone()),
                         value,
@@ -394,16 +1286,57 @@ impl IdleWithCommits {
             }
         }
 
-        let latest_commit = self
-            .commits
+        let task_count = collection_execution_graph.graph.node_count();
+
+        let collection_execution_graph = if self.disable_cache {
+            collection_execution_graph
+        } else {
+            collection_execution_graph.prune(|task| {
+                !uncached.contains(&task.collector_config)
+                    && self
+                        .storage
+                        .contains_key(&(task.collector_config.clone(), task.commit_hash.clone()))
+            })
+        };
+
+        let precomputed_task_count = task_count - collection_execution_graph.graph.node_count();
+        debug!("pruned {} of {} tasks", precomputed_task_count, task_count);
+
+        let task_count = collection_execution_graph.graph.node_count();
+
+        let collection_execution_graph = if self.tree_limits.is_unlimited() {
+            collection_execution_graph
+        } else {
+            self.skip_too_large_commits(&collection_execution_graph)?
+        };
+
+        let skipped_task_count = task_count - collection_execution_graph.graph.node_count();
+        debug!("skipped {} of {} tasks", skipped_task_count, task_count);
+
+        let latest_commits: BTreeMap<String, CommitHash> = commits_by_branch
             .iter()
-            .max_by(|a, b| a.time.cmp(&b.time))
-            .map(|c| c.id.clone())
+            .filter_map(|(branch, commits)| {
+                commits
+                    .last()
+                    .map(|commit| ((*branch).to_string(), commit.id.clone()))
+            })
+            .collect();
+
+        let latest_commit = latest_commits
+            .get(&self.branches.default)
+            .cloned()
             .ok_or(CollectionProcessError::NoCommits)?;
 
         Ok(ReadyForCollection {
             collection_execution_graph,
+            planned_execution_graph,
             latest_commit,
+            latest_commits,
+            precomputed_task_count,
+            quarantined_task_count,
+            skipped_task_count,
+            branches: self.branches,
+            head: self.head,
             metrics: self.metrics,
             repo: self.repo,
             commits: self.commits,
@@ -411,14 +1344,288 @@ impl IdleWithCommits {
             storage: self.storage,
             cache: self.cache,
             disable_cache: self.disable_cache,
+            retry_policy: self.retry_policy,
+            measure_scanned_bytes: self.measure_scanned_bytes,
         })
     }
 }
 
-impl ReadyForCollection {
-    #[tracing::instrument(level = "trace", skip(self, channel))]
-    pub fn collect_metrics(
-        self,
+/// Whether only the latest commit is collected for all metrics, so their history isn't needed
+fn is_latest_only(metrics: &HashMap<String, MetricConfig>) -> bool {
+    metrics
+        .values()
+        .all(|metric| metric.frequency == Frequency::LatestOnly)
+}
+
+/// Fetches the history of a shallow repository the collection needs. Every frequency but
+/// latest-only samples the whole history, so it is fetched completely unless the reference only
+/// selects recent commits with `since` or `max_commits`.
+fn deepen_history(
+    repo: &RepositoryHandle,
+    reference: &GitRepository,
+    metrics: &HashMap<String, MetricConfig>,
+    ssh_key: Option<&PrivateKey>,
+    https_credentials: Option<&HttpsCredentials>,
+) -> Result<(), CollectionProcessError> {
+    if is_latest_only(metrics) || !repo.is_shallow()? {
+        return Ok(());
+    }
+
+    // The start of a range can lie anywhere in the history
+    if reference.commits.is_some()
+        || reference.from.is_some()
+        || reference.to.is_some()
+        || (reference.since.is_none() && reference.max_commits.is_none())
+    {
+        debug!("Repository is shallow, fetching the remaining history");
+        repo.unshallow(ssh_key, https_credentials)?;
+        return Ok(());
+    }
+
+    // All commits on or after the day are fetched, `max_commits` selects from them
+    if let Some(since) = reference.since {
+        debug!(
+            "Repository is shallow, fetching the history since {}",
+            since
+        );
+        repo.deepen_since(since, ssh_key, https_credentials)?;
+        return Ok(());
+    }
+
+    let Some(max_commits) = reference.max_commits else {
+        return Ok(());
+    };
+
+    let branch = match &reference.branch {
+        Some(branch) => branch.clone(),
+        None => repo.find_main_branch()?,
+    };
+
+    // Filters of the reference may leave commits out, so the history is deepened until enough
+    // of them are selected, doubling the fetched commits every time
+    let mut commits = max_commits.max(1);
+    while repo.is_shallow()? {
+        let head = repo.resolve_revision(&repo.branch_revision(&branch))?;
+        let selected = reference
+            .select_commits(reachable_commits(repo, reference, &head, None)?)
+            .len();
+
+        if selected >= max_commits {
+            break;
+        }
+
+        debug!(
+            "Repository is shallow, fetching {} more commits of the history",
+            commits
+        );
+        repo.deepen(commits, ssh_key, https_credentials)?;
+        commits *= 2;
+    }
+
+    Ok(())
+}
+
+/// The commits reachable from the head but not from `from`, only the ones changing the `paths` of
+/// the reference if it has any
+fn reachable_commits(
+    repo: &RepositoryHandle,
+    reference: &GitRepository,
+    head: &CommitHash,
+    from: Option<&CommitHash>,
+) -> Result<Vec<CommitInfo>, GitError> {
+    match (reference.path_filter(), from) {
+        (Some(paths), from) => repo.get_commits_touching_paths(head, from, paths),
+        (None, Some(from)) => repo.get_commits_between(from, head),
+        (None, None) => repo.get_commits_reachable_from(head),
+    }
+}
+
+/// Resolves the `from` or `to` revision of the reference, e.g. a tag or hash. Branch names are
+/// looked up among the branches of the remote as well, like the branch of the reference.
+fn resolve_reference_revision(
+    repo: &RepositoryHandle,
+    revision: &str,
+) -> Result<CommitHash, GitError> {
+    repo.resolve_revision(revision)
+        .or_else(|_| repo.resolve_revision(&repo.branch_revision(revision)))
+}
+
+/// Returns whether the tasks of the collector need a worktree with the commit checked out. The
+/// files of submodules are only read from their repositories in a checkout, so collectors reading
+/// the files from the object database need one as well if submodules are enabled.
+fn requires_worktree(repo: &RepositoryHandle, collector_config: &CollectorConfig) -> bool {
+    requires_checkout(collector_config) || (repo.submodules && reads_tree(collector_config))
+}
+
+/// Checks the commit out for a task of the collector. Collectors scoped to some files which don't
+/// need the other ones only get those, which saves most of the checkout in large repositories.
+/// Otherwise the files left behind by a previous attempt are removed if `clean` is set.
+fn check_out(
+    worktree: &WorktreeHandle,
+    collector_config: &CollectorConfig,
+    commit_hash: &CommitHash,
+    clean: bool,
+) -> Result<(), GitError> {
+    if let Some(scope) = checkout_scope(collector_config) {
+        let scope = scope.matcher()?;
+        return worktree.checkout_matching(&commit_hash.0, |path| scope.is_match(path));
+    }
+
+    if clean {
+        worktree.reset_clean(&commit_hash.0)
+    } else {
+        worktree.reset_hard(&commit_hash.0)
+    }
+}
+
+/// Collectors of metrics which opted out of the cache. Other metrics sharing such a collector
+/// are collected again as well, as they share its values.
+fn uncached_collectors(metrics: &HashMap<String, MetricConfig>) -> HashSet<CollectorConfig> {
+    metrics
+        .values()
+        .filter(|metric| !metric.is_cached())
+        .map(|metric| metric.collector.clone())
+        .collect()
+}
+
+/// Maximum number of concurrently running tasks of the collectors of metrics which limit it.
+/// Metrics sharing a collector share the lowest of their limits.
+fn concurrency_limits(metrics: &HashMap<String, MetricConfig>) -> HashMap<CollectorConfig, usize> {
+    let mut limits: HashMap<CollectorConfig, usize> = HashMap::new();
+
+    for metric in metrics.values() {
+        if let Some(max_concurrency) = metric.max_concurrency {
+            limits
+                .entry(metric.collector.clone())
+                .and_modify(|limit| *limit = (*limit).min(max_concurrency))
+                .or_insert(max_concurrency);
+        }
+    }
+
+    limits
+}
+
+/// Number of running tasks of every collector with a concurrency limit
+struct RunningTasks {
+    limits: HashMap<CollectorConfig, usize>,
+    counts: Mutex<HashMap<CollectorConfig, usize>>,
+    finished: Condvar,
+}
+
+/// Frees the slot of a running task once it is dropped
+struct RunningTask<'a> {
+    running_tasks: &'a RunningTasks,
+    collector_config: &'a CollectorConfig,
+}
+
+impl RunningTasks {
+    fn new(limits: HashMap<CollectorConfig, usize>) -> Self {
+        Self {
+            limits,
+            counts: Mutex::new(HashMap::new()),
+            finished: Condvar::new(),
+        }
+    }
+
+    /// Blocks until fewer tasks of the collector than its limit are running
+    fn start<'a>(&'a self, collector_config: &'a CollectorConfig) -> Option<RunningTask<'a>> {
+        let limit = *self.limits.get(collector_config)?;
+
+        let mut counts = self
+            .finished
+            .wait_while(
+                self.counts.lock().expect("Failed to lock running tasks"),
+                |counts| {
+                    counts
+                        .get(collector_config)
+                        .is_some_and(|count| *count >= limit)
+                },
+            )
+            .expect("Failed to lock running tasks");
+        *counts.entry(collector_config.clone()).or_default() += 1;
+
+        Some(RunningTask {
+            running_tasks: self,
+            collector_config,
+        })
+    }
+}
+
+impl Drop for RunningTask<'_> {
+    fn drop(&mut self) {
+        let mut counts = self
+            .running_tasks
+            .counts
+            .lock()
+            .expect("Failed to lock running tasks");
+
+        if let Some(count) = counts.get_mut(self.collector_config) {
+            *count -= 1;
+        }
+
+        self.running_tasks.finished.notify_all();
+    }
+}
+
+fn send_metric_finished_events(
+    channel: Option<&std::sync::mpsc::Sender<ExecutionProgressCallbackState>>,
+    metrics: &HashMap<String, MetricConfig>,
+    storage: &DashMap<(CollectorConfig, CommitHash), CollectorValue>,
+    branches: &Branches,
+    latest_commits: &BTreeMap<String, CommitHash>,
+    collector_config: &CollectorConfig,
+) -> Result<(), CollectionProcessError> {
+    let Some(channel) = channel else {
+        return Ok(());
+    };
+
+    for (metric_name, metric_config) in metrics {
+        if &metric_config.collector == collector_config {
+            let latest_value = latest_commits
+                .get(branches.branch_of(metric_config))
+                .and_then(|latest_commit| {
+                    storage.get(&(collector_config.clone(), latest_commit.clone()))
+                })
+                .map(|value| value.clone());
+
+            channel.send(ExecutionProgressCallbackState::MetricFinished {
+                metric_name: metric_name.clone(),
+                latest_value,
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+impl ReadyForCollection {
+    /// Ensures the branches still point to the commits they pointed to when the commits were
+    /// collected, e.g. because the reference repository was fetched by another process.
+    fn verify_head(&self) -> Result<(), CollectionProcessError> {
+        for (branch, head) in &self.branches.heads {
+            let actual = self
+                .repo
+                .resolve_revision(&self.repo.branch_revision(branch))?;
+
+            if actual != *head {
+                return Err(CollectionProcessError::HeadMoved {
+                    branch: branch.clone(),
+                    expected: head.clone(),
+                    actual,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// # Panics
+    ///
+    /// Panics if a temporary worktree can not be created.
+    #[allow(clippy::too_many_lines, clippy::needless_pass_by_value)]
+    #[tracing::instrument(level = "trace", skip(self, channel))]
+    pub fn collect_metrics(
+        self,
         channel: Option<std::sync::mpsc::Sender<ExecutionProgressCallbackState>>,
         worktree_path: PathBuf,
     ) -> Result<PostCollection, CollectionProcessError> {
@@ -426,7 +1633,12 @@ impl ReadyForCollection {
             '1', '2', '3', '4', '5', '6', '7', '8', '9', '0', 'a', 'b', 'c', 'd', 'e', 'f',
         ];
 
-        fs::create_dir_all(&worktree_path)?;
+        self.verify_head()?;
+
+        fs::create_dir_all(&worktree_path).map_err(|source| CollectionProcessError::IO {
+            path: worktree_path.clone(),
+            source,
+        })?;
 
         let available_cpus = num_cpus::get();
 
@@ -441,10 +1653,16 @@ impl ReadyForCollection {
             handle
         }));
 
-        // Grouped task by commit, in order of topologial sort
+        let sequential_tasks = self.collection_execution_graph.sequential_tasks();
         let visitor = petgraph::visit::Topo::new(&self.collection_execution_graph.graph);
-        let node_indices: Vec<Vec<NodeIndex>> = visitor
-            .iter(&self.collection_execution_graph.graph)
+        let (sequential_node_indices, parallel_node_indices): (Vec<NodeIndex>, Vec<NodeIndex>) =
+            visitor
+                .iter(&self.collection_execution_graph.graph)
+                .partition(|idx| sequential_tasks.contains(idx));
+
+        // Grouped task by commit, in order of topologial sort
+        let node_indices: Vec<Vec<NodeIndex>> = parallel_node_indices
+            .into_iter()
             .fold(indexmap::IndexMap::new(), |mut acc, current| {
                 let task = &self.collection_execution_graph.graph[current];
                 let entry: &mut Vec<NodeIndex> = acc.entry(task.commit_hash.clone()).or_default();
@@ -461,96 +1679,444 @@ impl ReadyForCollection {
         let iter = node_indices.par_iter();
 
         let disable_cache = self.disable_cache;
+        let uncached = uncached_collectors(&self.metrics);
+        let retry_policy = self.retry_policy;
+        let running_tasks = RunningTasks::new(concurrency_limits(&self.metrics));
+
+        // Several collectors usually go through the files of the same commit
+        let scanned_bytes_of_commits: DashMap<CommitHash, u64> = DashMap::new();
+        let scanned_bytes = |task: &CollectionTask| -> Option<u64> {
+            if !self.measure_scanned_bytes || !reads_whole_tree(&task.collector_config) {
+                return None;
+            }
+
+            if let Some(bytes) = scanned_bytes_of_commits.get(&task.commit_hash) {
+                return Some(*bytes);
+            }
+
+            match self.repo.tree_stats(&task.commit_hash) {
+                Ok(stats) => {
+                    scanned_bytes_of_commits.insert(task.commit_hash.clone(), stats.total_bytes);
+                    Some(stats.total_bytes)
+                }
+                Err(e) => {
+                    warn!("Could not measure the tree of {}: {}", task.commit_hash, e);
+                    None
+                }
+            }
+        };
 
         if let Some(channel) = &channel {
             channel.send(ExecutionProgressCallbackState::Initial {
                 metric_count: self.metrics.len(),
                 task_count: self.collection_execution_graph.graph.node_count(),
+                precomputed_task_count: self.precomputed_task_count,
+                quarantined_task_count: self.quarantined_task_count,
+                skipped_task_count: self.skipped_task_count,
             })?;
         }
 
-        let _: Vec<Result<(), CollectionProcessError>> = iter
-            .cloned()
-            .map(|task_indices| -> Result<(), CollectionProcessError> {
-                for task_idx in task_indices {
-                    let task = &self.collection_execution_graph.graph[task_idx];
+        // Number of unprocessed tasks per collector used by a metric
+        let mut remaining_tasks: HashMap<CollectorConfig, usize> = self
+            .metrics
+            .values()
+            .map(|metric| (metric.collector.clone(), 0))
+            .collect();
 
-                    let _enter =
-                        span!(Level::TRACE, "processing task", idx = ?task_idx, commit = ?task.commit_hash).entered();
+        for task in self.collection_execution_graph.graph.node_weights() {
+            if let Some(count) = remaining_tasks.get_mut(&task.collector_config) {
+                *count += 1;
+            }
+        }
 
-                    let is_in_storage = self
-                        .storage
-                        .contains_key(&(task.collector_config.clone(), task.commit_hash.clone()));
+        // Metrics which were resolved from the cache entirely are finished right away
+        for (collector_config, count) in &remaining_tasks {
+            if *count == 0 {
+                send_metric_finished_events(
+                    channel.as_ref(),
+                    &self.metrics,
+                    &self.storage,
+                    &self.branches,
+                    &self.latest_commits,
+                    collector_config,
+                )?;
+            }
+        }
 
-                    if is_in_storage && !disable_cache {
-                        debug!("reusing value from storage");
-                        if let Some(channel) = &channel {
-                            channel.send(ExecutionProgressCallbackState::Reused {
-                                collector_config: task.collector_config.clone(),
-                                commit_hash: task.commit_hash.clone(),
-                            })?;
-                        }
-                    } else {
-                        let collector: Collector = (&task.collector_config).into();
+        let remaining_tasks = Mutex::new(remaining_tasks);
 
-                        let output = match collector {
-                            Collector::Base(collector) => {
-                                let mut worktree = loop {
-                                    if let Some(worktree) = worktree_pool.try_pull() {
-                                        break worktree;
-                                    }
-                                };
+        let process_task = |task_idx: NodeIndex| -> Result<(), CollectionProcessError> {
+            let task = &self.collection_execution_graph.graph[task_idx];
 
-                                let worktree = worktree.as_mut();
+            let _enter =
+                span!(Level::TRACE, "processing task", idx = ?task_idx, commit = ?task.commit_hash)
+                    .entered();
 
-                                worktree.reset_hard(&task.commit_hash.0)?;
-                                collector.collect(
+            let is_in_storage = self
+                .storage
+                .contains_key(&(task.collector_config.clone(), task.commit_hash.clone()));
+
+            if is_in_storage && !disable_cache && !uncached.contains(&task.collector_config) {
+                debug!("reusing value from storage");
+                if let Some(channel) = &channel {
+                    channel.send(ExecutionProgressCallbackState::Reused {
+                        collector_id: task.collector_config.id(),
+                        commit_hash: task.commit_hash.clone(),
+                    })?;
+                }
+            } else {
+                let running_task = running_tasks.start(&task.collector_config);
+
+                let started = Instant::now();
+                let mut attempt = 1;
+
+                let output = loop {
+                    let collector: Collector = (&task.collector_config).into();
+
+                    let output: Result<CollectorValue, TaskError> = match collector {
+                        Collector::Base(collector)
+                            if !requires_worktree(&self.repo, &task.collector_config) =>
+                        {
+                            // Nothing is read from the working directory, so the main worktree
+                            // can be used without resetting it
+                            collector
+                                .collect(
+                                    &self.storage,
+                                    &mut self.repo.main_worktree(),
+                                    &self.collection_execution_graph,
+                                    task_idx,
+                                )
+                                .map_err(TaskError::from)
+                        }
+                        Collector::Base(collector) => {
+                            let mut worktree = loop {
+                                if let Some(worktree) = worktree_pool.try_pull() {
+                                    break worktree;
+                                }
+                            };
+
+                            let worktree = worktree.as_mut();
+
+                            // Files left behind by a failed attempt must not affect the retry
+                            let reset = check_out(
+                                worktree,
+                                &task.collector_config,
+                                &task.commit_hash,
+                                attempt > 1,
+                            );
+
+                            reset.map_err(TaskError::Checkout).and_then(|()| {
+                                Ok(collector.collect(
                                     &self.storage,
                                     worktree,
                                     &self.collection_execution_graph,
                                     task_idx,
-                                )?
+                                )?)
+                            })
+                        }
+                        Collector::Derived(collector) => collector
+                            .collect(&self.storage, &self.collection_execution_graph, task_idx)
+                            .map_err(TaskError::from),
+                    };
+
+                    match output {
+                        Err(source)
+                            if attempt <= retry_policy.max_retries && source.is_transient() =>
+                        {
+                            warn!(
+                                "Attempt {} of task failed, retrying: {}",
+                                attempt,
+                                error_chain(&source)
+                            );
+
+                            if let Some(channel) = &channel {
+                                channel.send(ExecutionProgressCallbackState::TaskRetried {
+                                    collector_id: task.collector_config.id(),
+                                    commit_hash: task.commit_hash.clone(),
+                                    attempt,
+                                    code: source.code(),
+                                    error: error_chain(&source),
+                                })?;
                             }
-                            Collector::Derived(collector) => collector.collect(
-                                &self.storage,
-                                &self.collection_execution_graph,
-                                task_idx,
-                            )?,
-                        };
 
-                        self.storage.insert(
-                            (task.collector_config.clone(), task.commit_hash.clone()),
-                            output.clone(),
-                        );
+                            attempt += 1;
+                        }
+                        output => break output,
+                    }
+                };
+                drop(running_task);
 
+                let output = match output {
+                    Ok(output) => output,
+                    Err(source) => {
                         if let Some(channel) = &channel {
-                            channel.send(ExecutionProgressCallbackState::New {
-                                collector_config: task.collector_config.clone(),
+                            channel.send(ExecutionProgressCallbackState::TaskFailed {
+                                collector_id: task.collector_config.id(),
                                 commit_hash: task.commit_hash.clone(),
+                                code: source.code(),
+                                error: error_chain(&source),
                             })?;
                         }
+
+                        return Err(CollectionProcessError::Task {
+                            collector_id: task.collector_config.id(),
+                            commit_hash: task.commit_hash.clone(),
+                            source: Box::new(source),
+                        });
                     }
+                };
+
+                self.storage.insert(
+                    (task.collector_config.clone(), task.commit_hash.clone()),
+                    output.clone(),
+                );
+
+                if let Some(channel) = &channel {
+                    channel.send(ExecutionProgressCallbackState::New {
+                        collector_id: task.collector_config.id(),
+                        commit_hash: task.commit_hash.clone(),
+                        duration: started.elapsed(),
+                        scanned_bytes: scanned_bytes(task),
+                    })?;
+                }
+            }
+
+            let is_last_task_of_metric = remaining_tasks
+                .lock()
+                .expect("Failed to lock remaining tasks")
+                .get_mut(&task.collector_config)
+                .is_some_and(|count| {
+                    *count -= 1;
+                    *count == 0
+                });
+
+            if is_last_task_of_metric {
+                send_metric_finished_events(
+                    channel.as_ref(),
+                    &self.metrics,
+                    &self.storage,
+                    &self.branches,
+                    &self.latest_commits,
+                    &task.collector_config,
+                )?;
+            }
+
+            Ok(())
+        };
+
+        iter.cloned()
+            .map(|task_indices| -> Result<(), CollectionProcessError> {
+                for task_idx in task_indices {
+                    process_task(task_idx)?;
                 }
 
                 Ok(())
             })
-            .collect();
+            .collect::<Result<Vec<()>, CollectionProcessError>>()?;
+
+        // Tasks requiring values at previous commits have to wait for them, so they and the tasks
+        // depending on them are run one after another in topological order once all other tasks
+        // are done
+        for task_idx in sequential_node_indices {
+            process_task(task_idx)?;
+        }
 
         drop(worktree_pool);
 
+        // All tasks reference exact commits, so the values are still consistent with each other,
+        // but they might not include the newest commits anymore
+        if let Err(e) = self.verify_head() {
+            warn!("{}", e);
+        }
+
         if let Some(channel) = &channel {
             channel.send(ExecutionProgressCallbackState::Finished)?;
         }
 
         Ok(PostCollection {
             metrics: self.metrics,
-            collection_execution_graph: self.collection_execution_graph,
             commits: self.commits,
             tags: self.tags,
             storage: self.storage,
             latest_commit: self.latest_commit,
+            head: self.head,
+            branches: self.branches,
+            cache: self.cache,
+            disable_cache: self.disable_cache,
+            exclude_paths_id: self.repo.excluded_paths_id().map(ToString::to_string),
+            planned_execution_graph: self.planned_execution_graph,
+        })
+    }
+
+    /// Executes exactly one task of the execution graph, e.g. when an external orchestrator
+    /// distributes the tasks itself.
+    ///
+    /// All dependencies of the task have to be available in the storage already, which usually
+    /// means they were collected by earlier invocations and loaded from the cache. Unlike
+    /// `collect_metrics`, the collected value is written to the cache right away.
+    #[tracing::instrument(level = "trace", skip(self))]
+    #[allow(clippy::too_many_lines)]
+    pub fn collect_task(
+        self,
+        collector_config: &CollectorConfig,
+        commit_hash: &CommitHash,
+        worktree_path: &PathBuf,
+    ) -> Result<PostCollection, CollectionProcessError> {
+        self.verify_head()?;
+
+        let is_in_storage = self
+            .storage
+            .contains_key(&(collector_config.clone(), commit_hash.clone()));
+
+        let is_cached =
+            !self.disable_cache && !uncached_collectors(&self.metrics).contains(collector_config);
+
+        if is_in_storage && is_cached {
+            debug!("reusing value from storage");
+        } else {
+            let graph = &self.collection_execution_graph.graph;
+
+            let task_idx = graph
+                .node_indices()
+                .find(|idx| {
+                    let task = &graph[*idx];
+                    &task.collector_config == collector_config && &task.commit_hash == commit_hash
+                })
+                .ok_or_else(|| CollectionProcessError::UnknownTask {
+                    collector_config: collector_config.clone(),
+                    commit_hash: commit_hash.clone(),
+                })?;
+
+            // Values of the same collector at previous commits are usually optional, dependencies
+            // at the same commit are not
+            for dependency_idx in graph.neighbors_directed(task_idx, petgraph::Direction::Incoming)
+            {
+                let dependency = &graph[dependency_idx];
+
+                let is_required = &dependency.commit_hash == commit_hash
+                    || requires_preceding_values(collector_config);
+
+                if is_required
+                    && !self.storage.contains_key(&(
+                        dependency.collector_config.clone(),
+                        dependency.commit_hash.clone(),
+                    ))
+                {
+                    return Err(CollectionProcessError::MissingDependency {
+                        collector_config: dependency.collector_config.clone(),
+                        commit_hash: dependency.commit_hash.clone(),
+                    });
+                }
+            }
+
+            fs::create_dir_all(worktree_path).map_err(|source| CollectionProcessError::IO {
+                path: worktree_path.clone(),
+                source,
+            })?;
+
+            let mut attempt = 1;
+
+            // Created by the first attempt of a base collector which needs one, the retries reuse
+            // it and remove the files left behind by the failed attempt. It is removed once the
+            // task is done.
+            let mut temp_worktree = None;
+
+            let output = loop {
+                let collector: Collector = collector_config.into();
+
+                let output: Result<CollectorValue, TaskError> = match collector {
+                    Collector::Base(collector)
+                        if !requires_worktree(&self.repo, collector_config) =>
+                    {
+                        collector
+                            .collect(
+                                &self.storage,
+                                &mut self.repo.main_worktree(),
+                                &self.collection_execution_graph,
+                                task_idx,
+                            )
+                            .map_err(TaskError::from)
+                    }
+                    Collector::Base(collector) => {
+                        let worktree = match &mut temp_worktree {
+                            Some(worktree) => worktree,
+                            slot @ None => {
+                                let alphabet: [char; 16] = [
+                                    '1', '2', '3', '4', '5', '6', '7', '8', '9', '0', 'a', 'b',
+                                    'c', 'd', 'e', 'f',
+                                ];
+                                let id = nanoid!(10, &alphabet);
+
+                                slot.insert(
+                                    self.repo
+                                        .create_temp_worktree(&id, &worktree_path.join(&id))?,
+                                )
+                            }
+                        };
+                        let worktree = worktree.as_mut();
+
+                        check_out(worktree, collector_config, commit_hash, attempt > 1)
+                            .map_err(TaskError::Checkout)
+                            .and_then(|()| {
+                                Ok(collector.collect(
+                                    &self.storage,
+                                    worktree,
+                                    &self.collection_execution_graph,
+                                    task_idx,
+                                )?)
+                            })
+                    }
+                    Collector::Derived(collector) => collector
+                        .collect(&self.storage, &self.collection_execution_graph, task_idx)
+                        .map_err(TaskError::from),
+                };
+
+                match output {
+                    Err(source)
+                        if attempt <= self.retry_policy.max_retries && source.is_transient() =>
+                    {
+                        warn!(
+                            "Attempt {} of task failed, retrying: {}",
+                            attempt,
+                            error_chain(&source)
+                        );
+                        attempt += 1;
+                    }
+                    output => break output,
+                }
+            };
+
+            let output = output.map_err(|source| CollectionProcessError::Task {
+                collector_id: collector_config.id(),
+                commit_hash: commit_hash.clone(),
+                source: Box::new(source),
+            })?;
+
+            if is_cached {
+                self.cache.store(
+                    collector_config,
+                    self.repo.excluded_paths_id(),
+                    commit_hash,
+                    &output,
+                )?;
+            }
+
+            self.storage
+                .insert((collector_config.clone(), commit_hash.clone()), output);
+        }
+
+        Ok(PostCollection {
+            metrics: self.metrics,
+            commits: self.commits,
+            tags: self.tags,
+            storage: self.storage,
+            latest_commit: self.latest_commit,
+            head: self.head,
+            branches: self.branches,
             cache: self.cache,
             disable_cache: self.disable_cache,
+            exclude_paths_id: self.repo.excluded_paths_id().map(ToString::to_string),
+            planned_execution_graph: self.planned_execution_graph,
         })
     }
 }
@@ -559,19 +2125,583 @@ impl PostCollection {
     #[tracing::instrument(level = "trace", skip(self))]
     pub fn write_to_cache(self) -> Result<PostCollection, CollectionProcessError> {
         if !self.disable_cache {
-            for nx in self.collection_execution_graph.graph.node_indices() {
-                let task = &self.collection_execution_graph.graph[nx];
+            let uncached = uncached_collectors(&self.metrics);
 
-                if let Some(value) = self
-                    .storage
-                    .get(&(task.collector_config.clone(), task.commit_hash.clone()))
-                {
-                    self.cache
-                        .store(&task.collector_config, &task.commit_hash, &value)?;
+            // Iterates the storage instead of the graph, which no longer contains pruned tasks
+            for entry in &self.storage {
+                let (collector_config, commit_hash) = entry.key();
+
+                if uncached.contains(collector_config) {
+                    continue;
                 }
+
+                // Skipped values depend on the limits of the current run
+                if matches!(entry.value(), CollectorValue::Skipped(_)) {
+                    continue;
+                }
+
+                self.cache.store(
+                    collector_config,
+                    self.exclude_paths_id.as_deref(),
+                    commit_hash,
+                    entry.value(),
+                )?;
             }
         }
 
         Ok(self)
     }
+
+    /// Checks the latest values of the metrics against their thresholds
+    pub fn threshold_violations(&self) -> Result<Vec<ThresholdViolation>, ThresholdError> {
+        thresholds::evaluate(&self.metrics, &self.commits, &self.branches, &self.storage)
+    }
+
+    /// Projects when the metrics with a campaign reach its target
+    pub fn campaign_projections(&self) -> Result<Vec<CampaignProjection>, CampaignError> {
+        campaigns::project(&self.metrics, &self.commits, &self.branches, &self.storage)
+    }
+
+    /// Commits of the branch of the metric sorted by time, together with the sampled ones and
+    /// the number of commits skipped since the previously sampled one
+    fn sampled_branch_commits(
+        &self,
+        metric: &MetricConfig,
+    ) -> (Vec<CommitInfo>, Vec<(CommitHash, usize)>) {
+        let mut branch_commits: Vec<CommitInfo> = self
+            .commits
+            .iter()
+            .filter(|commit| self.branches.contains(metric, &commit.id))
+            .cloned()
+            .collect();
+        branch_commits.sort_by(|a, b| a.time.cmp(&b.time));
+
+        let positions: HashMap<&CommitHash, usize> = branch_commits
+            .iter()
+            .enumerate()
+            .map(|(position, commit)| (&commit.id, position))
+            .collect();
+
+        let graph = &self.planned_execution_graph.graph;
+
+        let mut tasks: Vec<(usize, NodeIndex)> = graph
+            .node_indices()
+            .filter(|idx| graph[*idx].collector_config == metric.collector)
+            .filter_map(|idx| {
+                positions
+                    .get(&graph[idx].commit_hash)
+                    .map(|position| (*position, idx))
+            })
+            .collect();
+        tasks.sort_unstable();
+
+        // The edge from the previously sampled commit counts the commits skipped since the start
+        // of the history, so the ones skipped in between are the difference to the distance of the
+        // previous sample. All commits before the first sample are skipped.
+        let mut previous_distance = 0;
+        let sampled = tasks
+            .into_iter()
+            .map(|(position, idx)| {
+                let distance = graph
+                    .edges_directed(idx, Direction::Incoming)
+                    .find(|edge| {
+                        edge.weight().offset == 1
+                            && graph[edge.source()].collector_config == metric.collector
+                    })
+                    .map_or(position, |edge| edge.weight().distance);
+
+                let skipped = distance.saturating_sub(previous_distance);
+                previous_distance = distance;
+
+                (graph[idx].commit_hash.clone(), skipped)
+            })
+            .collect();
+
+        (branch_commits, sampled)
+    }
+
+    /// Number of commits each sampled commit of the metric stands for, the commit itself and the
+    /// ones skipped since the previously sampled commit, e.g. to weight its values
+    #[must_use]
+    pub fn commits_represented(&self, metric_name: &str) -> HashMap<CommitHash, usize> {
+        let Some(metric) = self.metrics.get(metric_name) else {
+            return HashMap::new();
+        };
+
+        let (_, sampled) = self.sampled_branch_commits(metric);
+
+        sampled
+            .into_iter()
+            .map(|(commit, skipped)| (commit, skipped + 1))
+            .collect()
+    }
+
+    /// Sampled commit representing every commit of the branch of each metric, the next sampled
+    /// commit at or after it, e.g. to join data of single commits with the sampled series.
+    /// Sorted by metric name and commit time.
+    #[must_use]
+    pub fn commit_samples(&self) -> Vec<CommitSample> {
+        let mut metric_names: Vec<&String> = self.metrics.keys().collect();
+        metric_names.sort();
+
+        let mut samples = vec![];
+        for metric_name in metric_names {
+            let (branch_commits, sampled) = self.sampled_branch_commits(&self.metrics[metric_name]);
+
+            // Every sampled commit follows the commits skipped before it
+            let mut remaining = branch_commits.iter();
+            for (sampled_commit, skipped) in sampled {
+                for commit in remaining.by_ref().take(skipped + 1) {
+                    samples.push(CommitSample {
+                        metric: metric_name.clone(),
+                        commit: commit.id.clone(),
+                        sampled_commit: sampled_commit.clone(),
+                    });
+                }
+            }
+        }
+
+        samples
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn test_running_tasks_respect_concurrency_limit() {
+        let collector = CollectorConfig::ChangedFiles;
+        let running_tasks = RunningTasks::new(HashMap::from([(collector.clone(), 2)]));
+
+        let running = AtomicUsize::new(0);
+        let max_running = AtomicUsize::new(0);
+
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    let _task = running_tasks.start(&collector);
+
+                    let now_running = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_running.fetch_max(now_running, Ordering::SeqCst);
+                    std::thread::sleep(Duration::from_millis(10));
+                    running.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+
+        assert_eq!(max_running.load(Ordering::SeqCst), 2);
+        assert!(running_tasks
+            .start(&CollectorConfig::TotalDiffStat {
+                scope: Box::default()
+            })
+            .is_none());
+    }
+
+    #[test]
+    fn test_plan_diff() {
+        let task = |collector_config: CollectorConfig, commit: &str, cached: bool| PlannedTask {
+            requires_checkout: requires_checkout(&collector_config),
+            collector_config,
+            commit_hash: CommitHash(commit.to_string()),
+            cached,
+        };
+        let total_loc = CollectorConfig::TotalLoc {
+            scope: Box::default(),
+        };
+
+        let old = ExecutionPlan {
+            tasks: vec![
+                task(CollectorConfig::ChangedFiles, "a", true),
+                task(CollectorConfig::ChangedFiles, "b", true),
+            ],
+        };
+        let new = ExecutionPlan {
+            tasks: vec![
+                task(CollectorConfig::ChangedFiles, "b", true),
+                task(total_loc.clone(), "a", true),
+                task(total_loc.clone(), "b", false),
+            ],
+        };
+
+        let diff = old.diff(&new);
+        assert_eq!(
+            diff.removed,
+            vec![task(CollectorConfig::ChangedFiles, "a", true)]
+        );
+        assert_eq!(diff.added.len(), 2);
+        assert_eq!(
+            diff.uncached_added().collect::<Vec<_>>(),
+            vec![&task(total_loc, "b", false)]
+        );
+    }
+
+    #[test]
+    fn test_commit_samples() {
+        let author = Author {
+            name: None,
+            email: None,
+        };
+        let commits: Vec<CommitInfo> = [
+            ("1.0", "2012-12-12T00:00:00Z"),
+            ("1.1", "2012-12-13T01:00:00Z"),
+            ("2.0", "2012-12-18T12:10:00Z"),
+            ("2.1", "2012-12-19T12:10:00Z"),
+        ]
+        .iter()
+        .map(|(hash, time)| CommitInfo {
+            id: CommitHash((*hash).to_string()),
+            author: author.clone(),
+            committer: author.clone(),
+            message: None,
+            time: time.parse::<DateTime<Utc>>().unwrap(),
+            offset_minutes: 0,
+            parent_count: 1,
+        })
+        .collect();
+
+        let metric: MetricConfig =
+            toml::from_str("collector = \"total-loc\"\nfrequency = \"weekly\"").unwrap();
+        let metrics = HashMap::from([("loc".to_string(), metric)]);
+
+        let mut process = PostCollection {
+            planned_execution_graph: graph::build_collection_execution_graph(
+                &metrics, &commits, true,
+            ),
+            metrics,
+            cache: Box::new(FileCache::new(&std::env::temp_dir())),
+            disable_cache: true,
+            exclude_paths_id: None,
+            tags: None,
+            storage: DashMap::new(),
+            latest_commit: CommitHash("2.1".to_string()),
+            head: CommitHash("2.1".to_string()),
+            branches: Branches {
+                default: "main".to_string(),
+                heads: BTreeMap::new(),
+                commits: BTreeMap::from([(
+                    "main".to_string(),
+                    commits.iter().map(|commit| commit.id.clone()).collect(),
+                )]),
+            },
+            commits,
+        };
+
+        // The latest commit is sampled in addition to the first one of every week
+        let samples: Vec<(String, String)> = process
+            .commit_samples()
+            .into_iter()
+            .map(|sample| (sample.commit.0, sample.sampled_commit.0))
+            .collect();
+        assert_eq!(
+            samples,
+            [
+                ("1.0", "1.0"),
+                ("1.1", "2.0"),
+                ("2.0", "2.0"),
+                ("2.1", "2.1")
+            ]
+            .map(|(commit, sampled)| (commit.to_string(), sampled.to_string()))
+        );
+
+        let represented = process.commits_represented("loc");
+        assert_eq!(
+            represented,
+            HashMap::from(
+                [("1.0", 1), ("2.0", 2), ("2.1", 1)]
+                    .map(|(commit, count)| (CommitHash(commit.to_string()), count))
+            )
+        );
+
+        // Without forcing the latest commit, only the commits up to the last sample are covered
+        process.planned_execution_graph =
+            graph::build_collection_execution_graph(&process.metrics, &process.commits, false);
+
+        let represented = process.commits_represented("loc");
+        assert_eq!(
+            represented,
+            HashMap::from(
+                [("1.0", 1), ("2.0", 2)]
+                    .map(|(commit, count)| (CommitHash(commit.to_string()), count))
+            )
+        );
+        assert_eq!(process.commit_samples().len(), 3);
+    }
+
+    /// Creates a repository on the `main` branch with one commit per file and day, each adding the
+    /// file
+    fn create_source_repository(files: &[&str]) -> (PathBuf, Vec<CommitHash>) {
+        let path = std::env::temp_dir().join(format!("myaku-source-{}", nanoid!()));
+        let git2_repo = git2::Repository::init(&path).unwrap();
+        git2_repo.set_head("refs/heads/main").unwrap();
+
+        let mut index = git2_repo.index().unwrap();
+        let mut commits: Vec<CommitHash> = vec![];
+        for (day, file) in (0..).zip(files) {
+            let time = git2::Time::new(1_700_000_000 + day * 86_400, 0);
+            let signature = git2::Signature::new("Dummy", "dummy@test.com", &time).unwrap();
+            fs::write(path.join(file), file).unwrap();
+            index.add_path(std::path::Path::new(file)).unwrap();
+            index.write().unwrap();
+
+            let tree = git2_repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let parent = commits
+                .last()
+                .map(|commit| git2_repo.find_commit(git2::Oid::from_str(&commit.0).unwrap()))
+                .transpose()
+                .unwrap();
+            let parents: Vec<&git2::Commit> = parent.iter().collect();
+            let id = git2_repo
+                .commit(Some("HEAD"), &signature, &signature, file, &tree, &parents)
+                .unwrap();
+            commits.push(CommitHash(id.to_string()));
+        }
+
+        (path, commits)
+    }
+
+    /// Clones the repository and collects the metrics at every commit of the reference
+    fn collect(
+        metrics: HashMap<String, MetricConfig>,
+        reference: GitRepository,
+    ) -> Result<PostCollection, CollectionProcessError> {
+        let work_path = std::env::temp_dir().join(format!("myaku-work-{}", nanoid!()));
+
+        let initial = Initial {
+            metrics,
+            reference,
+            repository_path: work_path.join("repository"),
+            ssh_key: None,
+            https_credentials: None,
+            object_store_path: None,
+            exclude_paths: None,
+            cache: Box::new(FileCache::new(&work_path.join("cache"))),
+            disable_cache: true,
+            retry_policy: RetryPolicy::default(),
+            quarantine: Quarantine::default(),
+            tree_limits: TreeLimits::default(),
+            measure_scanned_bytes: false,
+            offline: false,
+        };
+
+        let CollectionProcess::ReadyForClone(process) = initial.initialize(false)? else {
+            panic!("Repository should not exist yet");
+        };
+
+        process
+            .clone(|_| {})?
+            .collect_commits()?
+            .prepare_for_collection(false)?
+            .collect_metrics(None, work_path.join("worktrees"))
+    }
+
+    #[test]
+    fn test_collect_task_removes_its_worktree() {
+        let (source_path, commits) = create_source_repository(&["a.txt", "b.txt"]);
+        let work_path = std::env::temp_dir().join(format!("myaku-work-{}", nanoid!()));
+
+        let metric: MetricConfig = toml::from_str(
+            "collector = \"total-symlinks-and-submodules\"\nfrequency = \"per-commit\"",
+        )
+        .unwrap();
+        let reference: GitRepository = toml::from_str(&format!(
+            "url = \"file://{}\"\nbranch = \"main\"",
+            source_path.display()
+        ))
+        .unwrap();
+
+        let initial = Initial {
+            metrics: HashMap::from([("links".to_string(), metric.clone())]),
+            reference,
+            repository_path: work_path.join("repository"),
+            ssh_key: None,
+            https_credentials: None,
+            object_store_path: None,
+            exclude_paths: None,
+            cache: Box::new(FileCache::new(&work_path.join("cache"))),
+            disable_cache: true,
+            retry_policy: RetryPolicy::default(),
+            quarantine: Quarantine::default(),
+            tree_limits: TreeLimits::default(),
+            measure_scanned_bytes: false,
+            offline: false,
+        };
+
+        let CollectionProcess::ReadyForClone(process) = initial.initialize(false).unwrap() else {
+            panic!("Repository should not exist yet");
+        };
+
+        let worktree_path = work_path.join("worktrees");
+        let process = process
+            .clone(|_| {})
+            .unwrap()
+            .collect_commits()
+            .unwrap()
+            .prepare_for_collection(false)
+            .unwrap()
+            .collect_task(&metric.collector, &commits[0], &worktree_path)
+            .unwrap();
+
+        assert!(process
+            .storage
+            .contains_key(&(metric.collector.clone(), commits[0].clone())));
+        assert_eq!(fs::read_dir(&worktree_path).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_collect_range_ending_before_branch_tip() {
+        let (source_path, commits) = create_source_repository(&["a.txt", "b.txt", "c.txt"]);
+
+        let metric: MetricConfig =
+            toml::from_str("collector = \"total-file-count\"\nfrequency = \"per-commit\"").unwrap();
+        let reference: GitRepository = toml::from_str(&format!(
+            "url = \"file://{}\"\nbranch = \"main\"\nto = \"{}\"",
+            source_path.display(),
+            commits[1]
+        ))
+        .unwrap();
+
+        let process = collect(
+            HashMap::from([("files".to_string(), metric.clone())]),
+            reference,
+        )
+        .unwrap();
+
+        assert_eq!(process.head, commits[1]);
+        assert_eq!(process.branches.heads["main"], commits[2]);
+
+        let mut collected: Vec<CommitHash> = process
+            .storage
+            .iter()
+            .filter(|entry| entry.key().0 == metric.collector)
+            .map(|entry| entry.key().1.clone())
+            .collect();
+        collected.sort_by_key(|commit| commits.iter().position(|c| c == commit));
+        assert_eq!(collected, commits[..2]);
+    }
+
+    #[test]
+    fn test_collect_expression_over_cumulative_metric() {
+        let (source_path, commits) = create_source_repository(&["a.txt", "b.txt", "c.txt"]);
+
+        let metrics: HashMap<String, MetricConfig> = toml::from_str(
+            r#"
+            [churn]
+            collector = "cumulative-diff-stat"
+            frequency = "per-commit"
+
+            [additions]
+            collector = "expression"
+            expression = "churn.insertions"
+            frequency = "per-commit"
+            "#,
+        )
+        .unwrap();
+        let reference: GitRepository = toml::from_str(&format!(
+            "url = \"file://{}\"\nbranch = \"main\"",
+            source_path.display()
+        ))
+        .unwrap();
+
+        // The expression has to wait for the cumulative values of its commits
+        let process = collect(metrics, reference).unwrap();
+
+        let collector = &process.metrics["additions"].collector;
+        let additions: Vec<Option<f64>> = commits
+            .iter()
+            .map(|commit| {
+                match &*process
+                    .storage
+                    .get(&(collector.clone(), commit.clone()))
+                    .unwrap()
+                {
+                    CollectorValue::Expression(value) => value.value,
+                    value => panic!("Unexpected value {value:?}"),
+                }
+            })
+            .collect();
+
+        assert_eq!(additions, vec![Some(1.0), Some(2.0), Some(3.0)]);
+    }
+
+    #[test]
+    fn test_collect_author_domains_counts_skipped_commits() {
+        // Tuesday to Sunday, then Monday of the following week
+        let (source_path, commits) = create_source_repository(&[
+            "a.txt", "b.txt", "c.txt", "d.txt", "e.txt", "f.txt", "g.txt",
+        ]);
+
+        let metric: MetricConfig =
+            toml::from_str("collector = \"author-domains\"\nfrequency = \"weekly\"\nwindow = 1")
+                .unwrap();
+        let reference: GitRepository = toml::from_str(&format!(
+            "url = \"file://{}\"\nbranch = \"main\"",
+            source_path.display()
+        ))
+        .unwrap();
+
+        let process = collect(
+            HashMap::from([("domains".to_string(), metric.clone())]),
+            reference,
+        )
+        .unwrap();
+
+        let value = |commit: &CommitHash| match &*process
+            .storage
+            .get(&(metric.collector.clone(), commit.clone()))
+            .unwrap()
+        {
+            CollectorValue::AuthorDomains(value) => value.clone(),
+            value => panic!("Unexpected value {value:?}"),
+        };
+
+        assert_eq!(value(&commits[0]).commits, 1);
+
+        // The commits skipped since the first week's sample are counted with the second one
+        let value = value(&commits[6]);
+        assert_eq!(value.commits, 6);
+        assert_eq!(value.commits_by_domain["test.com"], 6);
+    }
+
+    #[test]
+    fn test_run_config_returns_results_as_json() {
+        let (source_path, commits) = create_source_repository(&["a.txt", "b.txt", "c.txt"]);
+        let work_path = std::env::temp_dir().join(format!("myaku-work-{}", nanoid!()));
+
+        let config: RunConfig = serde_json::from_value(serde_json::json!({
+            "reference": {"url": format!("file://{}", source_path.display()), "branch": "main"},
+            "metrics": {"files": {"collector": "total-file-count", "frequency": "per-commit"}},
+            "repository_path": work_path.join("repository"),
+            "cache_path": work_path.join("cache"),
+            "worktree_path": work_path.join("worktrees"),
+        }))
+        .unwrap();
+
+        let process = config.run(None).unwrap();
+        let results: serde_json::Value =
+            serde_json::from_str(&process.results_json().unwrap()).unwrap();
+
+        assert_eq!(results["head"], commits[2].0);
+        assert_eq!(results["commits"].as_array().unwrap().len(), 3);
+        assert_eq!(results["metrics"]["files"].as_array().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_run_config_rejects_conflicting_https_credentials() {
+        let config: RunConfig = serde_json::from_value(serde_json::json!({
+            "reference": {"url": "https://example.com/repo.git"},
+            "metrics": {"files": {"collector": "total-file-count", "frequency": "per-commit"}},
+            "repository_path": "unused",
+            "https_token": "token",
+            "credential_helper": "store",
+        }))
+        .unwrap();
+
+        assert!(matches!(
+            config.run(None),
+            Err(RunError::ConflictingHttpsCredentials)
+        ));
+    }
 }
@@ -0,0 +1,595 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+
+use arrow::{
+    array::{AsArray, RecordBatch},
+    compute::concat_batches,
+    datatypes::UInt64Type,
+};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use serde::de::DeserializeOwned;
+use thiserror::Error;
+use walkdir::WalkDir;
+
+use crate::{
+    collectors::CollectorValue,
+    events::Event,
+    git::{CommitHash, CommitInfo, CommitTagInfo},
+};
+
+#[derive(Error, Debug)]
+pub enum LoadOutputError {
+    #[error("No output found in {}", path.display())]
+    Missing { path: PathBuf },
+
+    #[error(
+        "Metric table {} does not record its collector, only tables in the wide layout without \
+         transforms can be loaded",
+        path.display()
+    )]
+    UnsupportedFormat { path: PathBuf },
+
+    #[error("Could not read output file {}", path.display())]
+    IO {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("Could not parse output file {}", path.display())]
+    SerdeJson {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    #[error("Could not read parquet file {}", path.display())]
+    Parquet {
+        path: PathBuf,
+        #[source]
+        source: parquet::errors::ParquetError,
+    },
+
+    #[error("Could not read parquet file {}", path.display())]
+    Arrow {
+        path: PathBuf,
+        #[source]
+        source: arrow::error::ArrowError,
+    },
+
+    #[error("Could not convert parquet file {}", path.display())]
+    SerdeArrow {
+        path: PathBuf,
+        #[source]
+        source: serde_arrow::Error,
+    },
+}
+
+/// Key of the schema metadata of parquet metric tables naming the collector of their values, so
+/// they can be loaded again
+pub const COLLECTOR_METADATA_KEY: &str = "myaku:collector";
+
+/// Commits, tags and metric values of a previously written output, like [`crate::PostCollection`]
+/// provides them after a collection
+#[derive(Debug)]
+pub struct CollectedData {
+    pub commits: Vec<CommitInfo>,
+
+    /// Tags of the commits, `None` if the output contains none
+    pub tags: Option<Vec<CommitTagInfo>>,
+
+    /// External events written next to the metrics, `None` if the output contains none
+    pub events: Option<Vec<Event>>,
+
+    /// Values of the metrics by their name, including the ones of skipped commits
+    pub metrics: HashMap<String, HashMap<CommitHash, CollectorValue>>,
+
+    /// Number of commits the sampled commits of every metric stand for, see
+    /// [`crate::PostCollection::commits_represented`], empty for outputs written before it was
+    /// recorded
+    pub commits_represented: HashMap<String, HashMap<CommitHash, usize>>,
+}
+
+impl CollectedData {
+    /// Values of a metric in the order of the commits, commits without a value are left out
+    #[must_use]
+    pub fn series(&self, metric_name: &str) -> Vec<(&CommitInfo, &CollectorValue)> {
+        let Some(values) = self.metrics.get(metric_name) else {
+            return Vec::new();
+        };
+
+        self.commits
+            .iter()
+            .filter_map(|commit| values.get(&commit.id).map(|value| (commit, value)))
+            .collect()
+    }
+}
+
+fn read_json<T: DeserializeOwned>(path: &Path) -> Result<T, LoadOutputError> {
+    let contents = fs::read_to_string(path).map_err(|source| LoadOutputError::IO {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    serde_json::from_str(&contents).map_err(|source| LoadOutputError::SerdeJson {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// Reads an output directory written in the JSON or parquet format, so its values can be used
+/// without collecting them again
+pub fn load_output(dir: &Path) -> Result<CollectedData, LoadOutputError> {
+    if dir.join("commits.json").exists() {
+        load_json_output(dir)
+    } else if dir.join("commits.parquet").exists() {
+        load_parquet_output(dir)
+    } else {
+        Err(LoadOutputError::Missing {
+            path: dir.to_path_buf(),
+        })
+    }
+}
+
+/// Name of a metric from the path of its directory below `base`, metric names may contain slashes
+fn metric_name(base: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(base).ok()?;
+
+    Some(
+        relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/"),
+    )
+}
+
+fn load_json_output(dir: &Path) -> Result<CollectedData, LoadOutputError> {
+    let commits = read_json(&dir.join("commits.json"))?;
+
+    let tags_file = dir.join("commit_tags.json");
+    let tags = if tags_file.exists() {
+        Some(read_json(&tags_file)?)
+    } else {
+        None
+    };
+
+    let events_file = dir.join("events.json");
+    let events = if events_file.exists() {
+        Some(read_json(&events_file)?)
+    } else {
+        None
+    };
+
+    // Values are written to `metrics/<metric name>/<commit>.json`, where metric names may
+    // contain slashes
+    let metrics_dir = dir.join("metrics");
+    let mut metrics: HashMap<String, HashMap<CommitHash, CollectorValue>> = HashMap::new();
+
+    if metrics_dir.exists() {
+        for entry in WalkDir::new(&metrics_dir).min_depth(2) {
+            let entry = entry.map_err(|e| LoadOutputError::IO {
+                path: e.path().unwrap_or(&metrics_dir).to_path_buf(),
+                source: e.into(),
+            })?;
+
+            let path = entry.path();
+            if !entry.file_type().is_file() || path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+
+            let (Some(metric_dir), Some(commit)) = (path.parent(), path.file_stem()) else {
+                continue;
+            };
+            let Some(metric_name) = metric_name(&metrics_dir, metric_dir) else {
+                continue;
+            };
+
+            metrics.entry(metric_name).or_default().insert(
+                CommitHash(commit.to_string_lossy().to_string()),
+                read_json(path)?,
+            );
+        }
+    }
+
+    // Written to `commits_represented/<metric name>.json`
+    let commits_represented_dir = dir.join("commits_represented");
+    let mut commits_represented = HashMap::new();
+
+    if commits_represented_dir.exists() {
+        for entry in WalkDir::new(&commits_represented_dir).min_depth(1) {
+            let entry = entry.map_err(|e| LoadOutputError::IO {
+                path: e.path().unwrap_or(&commits_represented_dir).to_path_buf(),
+                source: e.into(),
+            })?;
+
+            let path = entry.path();
+            if !entry.file_type().is_file() || path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+
+            let Some(metric_name) = metric_name(&commits_represented_dir, &path.with_extension(""))
+            else {
+                continue;
+            };
+
+            let counts: HashMap<String, usize> = read_json(path)?;
+            commits_represented.insert(
+                metric_name,
+                counts
+                    .into_iter()
+                    .map(|(commit, count)| (CommitHash(commit), count))
+                    .collect(),
+            );
+        }
+    }
+
+    Ok(CollectedData {
+        commits,
+        tags,
+        events,
+        metrics,
+        commits_represented,
+    })
+}
+
+/// All rows of a parquet file as a single batch
+fn read_parquet(path: &Path) -> Result<RecordBatch, LoadOutputError> {
+    let parquet_error = |source| LoadOutputError::Parquet {
+        path: path.to_path_buf(),
+        source,
+    };
+    let arrow_error = |source| LoadOutputError::Arrow {
+        path: path.to_path_buf(),
+        source,
+    };
+
+    let file = File::open(path).map_err(|source| LoadOutputError::IO {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    // The schema of the reader lacks the metadata of the file's schema
+    let builder = ParquetRecordBatchReaderBuilder::try_new(file).map_err(parquet_error)?;
+    let schema = builder.schema().clone();
+    let reader = builder.build().map_err(parquet_error)?;
+
+    let batches = reader
+        .collect::<Result<Vec<RecordBatch>, _>>()
+        .map_err(arrow_error)?;
+
+    concat_batches(&schema, &batches).map_err(arrow_error)
+}
+
+fn from_record_batch<T: DeserializeOwned>(
+    path: &Path,
+    batch: &RecordBatch,
+) -> Result<T, LoadOutputError> {
+    serde_arrow::from_record_batch(batch).map_err(|source| LoadOutputError::SerdeArrow {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// Reads the values of a metric table with the type of the collector it records
+macro_rules! values_from_record_batch {
+    ($path:expr, $batch:expr, $collector:expr, [$($variant:ident),* $(,)?]) => {
+        match $collector {
+            $(stringify!($variant) => from_record_batch::<Vec<_>>($path, $batch)?
+                .into_iter()
+                .map(CollectorValue::$variant)
+                .collect::<Vec<CollectorValue>>(),)*
+            _ => {
+                return Err(LoadOutputError::UnsupportedFormat {
+                    path: $path.to_path_buf(),
+                })
+            }
+        }
+    };
+}
+
+/// Values of a metric table together with their commits and the number of commits they represent.
+/// Tables reference the commits either by hash or by the ids of `commits.parquet`.
+#[allow(clippy::type_complexity)]
+fn read_metric_table(
+    path: &Path,
+    commit_ids: &HashMap<u64, CommitHash>,
+) -> Result<Vec<(CommitHash, CollectorValue, Option<usize>)>, LoadOutputError> {
+    let batch = read_parquet(path)?;
+
+    let unsupported = || LoadOutputError::UnsupportedFormat {
+        path: path.to_path_buf(),
+    };
+
+    let collector = batch
+        .schema()
+        .metadata()
+        .get(COLLECTOR_METADATA_KEY)
+        .cloned()
+        .ok_or_else(unsupported)?;
+
+    let commits: Vec<CommitHash> = if let Some(column) = batch.column_by_name("commit") {
+        column
+            .as_string_opt::<i32>()
+            .ok_or_else(unsupported)?
+            .iter()
+            .map(|hash| hash.map(|hash| CommitHash(hash.to_string())))
+            .collect::<Option<_>>()
+            .ok_or_else(unsupported)?
+    } else {
+        batch
+            .column_by_name("commit_id")
+            .and_then(|column| column.as_primitive_opt::<UInt64Type>())
+            .ok_or_else(unsupported)?
+            .iter()
+            .map(|id| id.and_then(|id| commit_ids.get(&id).cloned()))
+            .collect::<Option<_>>()
+            .ok_or_else(unsupported)?
+    };
+
+    let represented: Vec<Option<usize>> = match batch
+        .column_by_name("commits_represented")
+        .and_then(|column| column.as_primitive_opt::<UInt64Type>())
+    {
+        Some(column) => column
+            .iter()
+            .map(|count| count.and_then(|count| usize::try_from(count).ok()))
+            .collect(),
+        None => vec![None; batch.num_rows()],
+    };
+
+    let values = values_from_record_batch!(
+        path,
+        &batch,
+        collector.as_str(),
+        [
+            ChangedFiles,
+            Loc,
+            PatternOccurences,
+            TotalCargoDependencies,
+            TotalCargoFeatures,
+            TotalDiffStat,
+            TotalLoc,
+            TotalPatternOccurences,
+            FileList,
+            TotalFileCount,
+            ChangedFilesLoc,
+            TotalWorkspaceMembers,
+            CiWorkflows,
+            ContainerConfig,
+            TotalSymlinksAndSubmodules,
+            CoverageReport,
+            FileLength,
+            CumulativeDiffStat,
+            RollingDiffStat,
+            BuildWarnings,
+            FormattingChanges,
+            ImportCoupling,
+            ConfigFiles,
+            Shellcheck,
+            CognitiveComplexity,
+            CommentDensity,
+            MaintainabilityIndex,
+            Expression,
+            AuthorDomain,
+            AuthorDomains,
+            Secrets,
+            HighEntropyStrings,
+            Skipped,
+        ]
+    );
+
+    Ok(commits
+        .into_iter()
+        .zip(values)
+        .zip(represented)
+        .map(|((commit, value), represented)| (commit, value, represented))
+        .collect())
+}
+
+fn load_parquet_output(dir: &Path) -> Result<CollectedData, LoadOutputError> {
+    let commits_file = dir.join("commits.parquet");
+    let commits_batch = read_parquet(&commits_file)?;
+    let commits: Vec<CommitInfo> = from_record_batch(&commits_file, &commits_batch)?;
+
+    // Only written with `commit_ids` enabled, in the order of the commits
+    let commit_ids: HashMap<u64, CommitHash> = commits_batch
+        .column_by_name("commit_id")
+        .and_then(|column| column.as_primitive_opt::<UInt64Type>())
+        .map(|ids| {
+            ids.iter()
+                .zip(&commits)
+                .filter_map(|(id, commit)| id.map(|id| (id, commit.id.clone())))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let tags_file = dir.join("commit_tags.parquet");
+    let tags = if tags_file.exists() {
+        Some(from_record_batch(&tags_file, &read_parquet(&tags_file)?)?)
+    } else {
+        None
+    };
+
+    let events_file = dir.join("events.parquet");
+    let events = if events_file.exists() {
+        Some(from_record_batch(
+            &events_file,
+            &read_parquet(&events_file)?,
+        )?)
+    } else {
+        None
+    };
+
+    // Values are written to `metrics/<metric name>/data.parquet`, the ones of skipped commits to
+    // `skipped.parquet` next to it
+    let metrics_dir = dir.join("metrics");
+    let mut metrics: HashMap<String, HashMap<CommitHash, CollectorValue>> = HashMap::new();
+    let mut commits_represented: HashMap<String, HashMap<CommitHash, usize>> = HashMap::new();
+
+    if metrics_dir.exists() {
+        for entry in WalkDir::new(&metrics_dir).min_depth(2) {
+            let entry = entry.map_err(|e| LoadOutputError::IO {
+                path: e.path().unwrap_or(&metrics_dir).to_path_buf(),
+                source: e.into(),
+            })?;
+
+            let path = entry.path();
+            let is_table = path
+                .file_name()
+                .is_some_and(|name| name == "data.parquet" || name == "skipped.parquet");
+            if !entry.file_type().is_file() || !is_table {
+                continue;
+            }
+
+            let Some(metric_name) = path
+                .parent()
+                .and_then(|metric_dir| metric_name(&metrics_dir, metric_dir))
+            else {
+                continue;
+            };
+
+            for (commit, value, represented) in read_metric_table(path, &commit_ids)? {
+                if let Some(represented) = represented {
+                    commits_represented
+                        .entry(metric_name.clone())
+                        .or_default()
+                        .insert(commit.clone(), represented);
+                }
+
+                metrics
+                    .entry(metric_name.clone())
+                    .or_default()
+                    .insert(commit, value);
+            }
+        }
+    }
+
+    Ok(CollectedData {
+        commits,
+        tags,
+        events,
+        metrics,
+        commits_represented,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        sync::Arc,
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    use arrow::{
+        array::{ArrayRef, Float64Array, StringArray},
+        datatypes::FieldRef,
+    };
+    use parquet::arrow::ArrowWriter;
+    use serde_arrow::schema::{SchemaLike, TracingOptions};
+
+    use super::*;
+
+    #[test]
+    fn test_load_output() {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .subsec_nanos();
+        let root = std::env::temp_dir().join(format!("myaku-load-output-{nanos}"));
+
+        let author = r#"{"name":null,"email":null}"#;
+        let commit = |id: &str, time: i64| {
+            format!(
+                r#"{{"id":"{id}","author":{author},"committer":{author},"message":null,"time":{time}}}"#
+            )
+        };
+
+        fs::create_dir_all(root.join("metrics/rust/loc")).unwrap();
+        fs::write(
+            root.join("commits.json"),
+            format!("[{},{}]", commit("b", 2), commit("a", 1)),
+        )
+        .unwrap();
+        fs::write(
+            root.join("metrics/rust/loc/a.json"),
+            r#"{"collector":"TotalLoc","loc":10}"#,
+        )
+        .unwrap();
+        fs::create_dir_all(root.join("commits_represented/rust")).unwrap();
+        fs::write(root.join("commits_represented/rust/loc.json"), r#"{"a":2}"#).unwrap();
+
+        let data = load_output(&root).unwrap();
+
+        assert!(data.tags.is_none());
+        assert!(data.events.is_none());
+        assert_eq!(
+            data.commits_represented["rust/loc"][&CommitHash("a".to_string())],
+            2
+        );
+
+        let series: Vec<(&str, &CollectorValue)> = data
+            .series("rust/loc")
+            .into_iter()
+            .map(|(commit, value)| (commit.id.0.as_str(), value))
+            .collect();
+        assert!(matches!(
+            series.as_slice(),
+            [("a", CollectorValue::TotalLoc(value))] if value.loc == 10
+        ));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_load_output_rejects_parquet_tables_without_collector() {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .subsec_nanos();
+        let root = std::env::temp_dir().join(format!("myaku-load-parquet-{nanos}"));
+
+        let write = |path: &Path, batch: &RecordBatch| {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            let mut writer =
+                ArrowWriter::try_new(File::create(path).unwrap(), batch.schema(), None).unwrap();
+            writer.write(batch).unwrap();
+            writer.close().unwrap();
+        };
+
+        let commits: Vec<CommitInfo> = serde_json::from_str(
+            r#"[{"id":"a","author":{"name":null,"email":null},"committer":{"name":null,"email":null},"message":null,"time":1}]"#,
+        )
+        .unwrap();
+        let fields = Vec::<FieldRef>::from_type::<CommitInfo>(TracingOptions::default()).unwrap();
+        write(
+            &root.join("commits.parquet"),
+            &serde_arrow::to_record_batch(&fields, &commits).unwrap(),
+        );
+
+        // Like a table written in the long layout
+        let values = RecordBatch::try_from_iter([
+            ("commit", Arc::new(StringArray::from(vec!["a"])) as ArrayRef),
+            (
+                "field",
+                Arc::new(StringArray::from(vec!["loc"])) as ArrayRef,
+            ),
+            (
+                "value",
+                Arc::new(Float64Array::from(vec![10.0])) as ArrayRef,
+            ),
+        ])
+        .unwrap();
+        write(&root.join("metrics/loc/data.parquet"), &values);
+
+        assert!(matches!(
+            load_output(&root),
+            Err(LoadOutputError::UnsupportedFormat { path }) if path.ends_with("data.parquet")
+        ));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
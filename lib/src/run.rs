@@ -0,0 +1,253 @@
+use std::{collections::HashMap, path::PathBuf, sync::mpsc::Sender};
+
+use serde::{Deserialize, Serialize};
+use ssh_key::PrivateKey;
+use thiserror::Error;
+
+use crate::{
+    error_chain, Cache, CollectionProcess, CollectionProcessError, CollectorValue, CommitHash,
+    CommitInfo, CommitTagInfo, ExecutionProgressCallbackState, FileCache, GitRepository, Glob,
+    HttpsCredentials, Initial, MetricConfig, PostCollection, Quarantine, RetryPolicy, TreeLimits,
+};
+
+/// Username sent together with `https_token` unless configured
+const DEFAULT_HTTPS_USERNAME: &str = "x-access-token";
+
+/// Config of a whole collection, as passed to the bindings embedding the library. Mirrors the
+/// config file of the CLI, with `repository_path` being required.
+#[derive(Deserialize, Debug)]
+pub struct RunConfig {
+    pub reference: GitRepository,
+    pub metrics: HashMap<String, MetricConfig>,
+
+    pub repository_path: PathBuf,
+    pub object_store_path: Option<PathBuf>,
+    pub exclude_paths: Option<Vec<Glob>>,
+
+    /// `.myaku/cache` if unset
+    pub cache_path: Option<PathBuf>,
+
+    /// `.myaku/worktree` if unset
+    pub worktree_path: Option<PathBuf>,
+
+    /// Private key in the OpenSSH format used to clone and fetch the repository
+    pub ssh_key_path: Option<PathBuf>,
+    pub ssh_key_passphrase: Option<String>,
+
+    /// Token used to clone and fetch the repository over HTTPS
+    pub https_token: Option<String>,
+    pub https_username: Option<String>,
+
+    /// Credential helper asked instead of the ones of the git config, in the format of git's
+    /// `credential.helper` option
+    pub credential_helper: Option<String>,
+
+    #[serde(default)]
+    pub no_cache: bool,
+
+    #[serde(default)]
+    pub offline: bool,
+
+    #[serde(default)]
+    pub retry_policy: RetryPolicy,
+
+    /// Commits skipped per collector, keyed by collector id
+    #[serde(default)]
+    pub quarantine: Quarantine,
+
+    #[serde(default)]
+    pub tree_limits: TreeLimits,
+}
+
+#[derive(Error, Debug)]
+#[non_exhaustive]
+pub enum RunError {
+    #[error("Could not read SSH key {}", path.display())]
+    SshKey {
+        path: PathBuf,
+        #[source]
+        source: ssh_key::Error,
+    },
+
+    #[error("Only one of https_token and credential_helper can be set")]
+    ConflictingHttpsCredentials,
+
+    #[error(transparent)]
+    Collection(#[from] CollectionProcessError),
+}
+
+impl RunError {
+    /// Message of the error followed by the messages of all of its sources
+    #[must_use]
+    pub fn report(&self) -> String {
+        error_chain(self)
+    }
+}
+
+impl RunConfig {
+    fn ssh_key(&self) -> Result<Option<PrivateKey>, RunError> {
+        let Some(path) = &self.ssh_key_path else {
+            return Ok(None);
+        };
+
+        let read_error = |source| RunError::SshKey {
+            path: path.clone(),
+            source,
+        };
+
+        let key = PrivateKey::read_openssh_file(path).map_err(read_error)?;
+
+        match &self.ssh_key_passphrase {
+            Some(passphrase) if key.is_encrypted() => {
+                key.decrypt(passphrase).map(Some).map_err(read_error)
+            }
+            _ => Ok(Some(key)),
+        }
+    }
+
+    fn https_credentials(&self) -> Result<Option<HttpsCredentials>, RunError> {
+        match (&self.https_token, &self.credential_helper) {
+            (Some(_), Some(_)) => Err(RunError::ConflictingHttpsCredentials),
+            (Some(token), None) => Ok(Some(HttpsCredentials::Token {
+                username: self
+                    .https_username
+                    .clone()
+                    .unwrap_or(DEFAULT_HTTPS_USERNAME.to_string()),
+                token: token.clone(),
+            })),
+            (None, Some(helper)) => Ok(Some(HttpsCredentials::Helper(helper.clone()))),
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// Runs the whole collection, see [`Initial::run`]
+    pub fn run(
+        self,
+        progress: Option<Sender<ExecutionProgressCallbackState>>,
+    ) -> Result<PostCollection, RunError> {
+        // Keys and credentials are only needed to clone and fetch
+        let (ssh_key, https_credentials) = if self.offline {
+            (None, None)
+        } else {
+            (self.ssh_key()?, self.https_credentials()?)
+        };
+
+        let cache_path = self
+            .cache_path
+            .unwrap_or_else(|| PathBuf::from(".myaku/cache"));
+
+        let worktree_path = self
+            .worktree_path
+            .unwrap_or_else(|| PathBuf::from(".myaku/worktree"));
+
+        let cache: Box<dyn Cache> = Box::new(FileCache::new(&cache_path));
+
+        let process = Initial {
+            metrics: self.metrics,
+            reference: self.reference,
+            repository_path: self.repository_path,
+            ssh_key,
+            https_credentials,
+            object_store_path: self.object_store_path,
+            exclude_paths: self.exclude_paths,
+            cache,
+            disable_cache: self.no_cache,
+            offline: self.offline,
+            retry_policy: self.retry_policy,
+            quarantine: self.quarantine,
+            tree_limits: self.tree_limits,
+            measure_scanned_bytes: false,
+        };
+
+        Ok(process.run(worktree_path, progress)?)
+    }
+}
+
+impl Initial {
+    /// Runs all steps of the collection one after another, cloning or fetching the repository
+    /// first unless offline, and writes the collected values to the cache. Progress of the
+    /// collection of the metrics is sent to the channel, if given.
+    pub fn run(
+        self,
+        worktree_path: PathBuf,
+        progress: Option<Sender<ExecutionProgressCallbackState>>,
+    ) -> Result<PostCollection, CollectionProcessError> {
+        let process = match self.initialize(false)? {
+            CollectionProcess::ReadyForClone(process) => process.clone(|_| {})?,
+            CollectionProcess::ReadyForFetch(process) => process.fetch(|_| {})?,
+            CollectionProcess::IdleWithoutCommits(process) => process,
+            _ => unreachable!("initialize only returns clone, fetch or idle states"),
+        };
+
+        process
+            .collect_commits()?
+            .collect_tags()?
+            .prepare_for_collection(true)?
+            .collect_metrics(progress, worktree_path)?
+            .write_to_cache()
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct MetricRow<'a> {
+    commit: &'a CommitHash,
+    value: &'a CollectorValue,
+}
+
+#[derive(Serialize, Debug)]
+struct CollectionResult<'a> {
+    /// Commit the collected branch pointed to, identifying the exact snapshot of the results
+    head: &'a CommitHash,
+    commits: &'a [CommitInfo],
+    tags: Option<&'a [CommitTagInfo]>,
+    metrics: HashMap<&'a str, Vec<MetricRow<'a>>>,
+}
+
+impl PostCollection {
+    /// Values of every metric at the commits of its branch, see [`PostCollection::results_json`]
+    #[must_use]
+    pub fn metric_values(&self) -> HashMap<&str, Vec<(CommitHash, CollectorValue)>> {
+        let mut metrics: HashMap<&str, Vec<(CommitHash, CollectorValue)>> = HashMap::new();
+        for (metric_name, metric_config) in &self.metrics {
+            let values = self
+                .storage
+                .iter()
+                .filter(|e| {
+                    let (collector, commit) = e.key();
+                    collector == &metric_config.collector
+                        && self.branches.contains(metric_config, commit)
+                })
+                .map(|e| (e.key().1.clone(), e.value().clone()))
+                .collect();
+
+            metrics.insert(metric_name.as_str(), values);
+        }
+
+        metrics
+    }
+
+    /// Head, commits, tags and the values of every metric as a JSON object, the results the
+    /// bindings and the workers return
+    pub fn results_json(&self) -> Result<String, serde_json::Error> {
+        let values = self.metric_values();
+
+        let metrics = values
+            .iter()
+            .map(|(metric_name, values)| {
+                let rows = values
+                    .iter()
+                    .map(|(commit, value)| MetricRow { commit, value })
+                    .collect();
+
+                (*metric_name, rows)
+            })
+            .collect();
+
+        serde_json::to_string(&CollectionResult {
+            head: &self.head,
+            commits: &self.commits,
+            tags: self.tags.as_deref(),
+            metrics,
+        })
+    }
+}
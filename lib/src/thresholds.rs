@@ -0,0 +1,464 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Months, Utc};
+use dashmap::DashMap;
+use thiserror::Error;
+
+use crate::{
+    collectors::{
+        expression::{self, ExpressionError},
+        CollectorValue,
+    },
+    config::{CollectorConfig, MetricConfig, Threshold},
+    git::{CommitHash, CommitInfo},
+    graph::sample_commits,
+    Branches,
+};
+
+#[derive(Error, Debug)]
+pub enum ThresholdError {
+    #[error("Value of metric {metric} is not a number")]
+    NotNumeric { metric: String },
+
+    #[error(
+        "Value of metric {metric} has several numbers, select one of {} as the field of its thresholds",
+        fields.join(", ")
+    )]
+    AmbiguousValue { metric: String, fields: Vec<String> },
+}
+
+impl ThresholdError {
+    fn from_expression(metric: &str, error: ExpressionError) -> Self {
+        match error {
+            ExpressionError::AmbiguousOperand { fields, .. } => ThresholdError::AmbiguousValue {
+                metric: metric.to_string(),
+                fields,
+            },
+            ExpressionError::NotNumeric { operand } => {
+                ThresholdError::NotNumeric { metric: operand }
+            }
+            _ => ThresholdError::NotNumeric {
+                metric: metric.to_string(),
+            },
+        }
+    }
+}
+
+/// Violations of `fail` thresholds are meant to fail a CI run, `warn` ones only to be reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ThresholdLevel {
+    Warn,
+    Fail,
+}
+
+/// Bound of a [`Threshold`] together with its limit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThresholdBound {
+    Min(f64),
+    Max(f64),
+    MaxIncrease(f64),
+    MaxDecrease(f64),
+    MinMonthlyDecrease(f64),
+}
+
+/// Latest value of a metric which is outside of a bound of its thresholds
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThresholdViolation {
+    pub metric: String,
+    pub level: ThresholdLevel,
+    pub bound: ThresholdBound,
+
+    /// Latest sampled commit with a value of the metric
+    pub commit: CommitHash,
+    pub value: f64,
+
+    /// Value of the sampled commit before it, if there is one
+    pub previous_value: Option<f64>,
+
+    /// Value of the last sampled commit at least a month before it, if there is one
+    pub month_ago_value: Option<f64>,
+}
+
+impl std::fmt::Display for ThresholdViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            metric,
+            commit,
+            value,
+            ..
+        } = self;
+
+        let change = self.previous_value.map_or(0.0, |previous_value| {
+            relative_change(previous_value, *value)
+        });
+
+        match self.bound {
+            ThresholdBound::Min(min) => {
+                write!(
+                    f,
+                    "{metric} is {value} at {commit}, below the minimum of {min}"
+                )
+            }
+            ThresholdBound::Max(max) => {
+                write!(
+                    f,
+                    "{metric} is {value} at {commit}, above the maximum of {max}"
+                )
+            }
+            ThresholdBound::MaxIncrease(limit) => write!(
+                f,
+                "{metric} increased by {change:.1}% to {value} at {commit}, more than {limit}%"
+            ),
+            ThresholdBound::MaxDecrease(limit) => write!(
+                f,
+                "{metric} decreased by {:.1}% to {value} at {commit}, more than {limit}%",
+                -change
+            ),
+            ThresholdBound::MinMonthlyDecrease(limit) => write!(
+                f,
+                "{metric} changed by {:+.1}% within a month to {value} at {commit}, instead of decreasing by at least {limit}%",
+                self.month_ago_value.map_or(0.0, |month_ago_value| {
+                    relative_change(month_ago_value, *value)
+                })
+            ),
+        }
+    }
+}
+
+/// Change from the previous value in percent
+fn relative_change(previous_value: f64, value: f64) -> f64 {
+    let difference = value - previous_value;
+    if difference == 0.0 {
+        return 0.0;
+    }
+
+    difference / previous_value.abs() * 100.0
+}
+
+fn violated_bounds(
+    threshold: &Threshold,
+    value: f64,
+    previous_value: Option<f64>,
+    month_ago_value: Option<f64>,
+) -> Vec<ThresholdBound> {
+    let change = previous_value.map(|previous_value| relative_change(previous_value, value));
+
+    let mut violated = vec![];
+    if let Some(min) = threshold.min.filter(|min| value < *min) {
+        violated.push(ThresholdBound::Min(min));
+    }
+    if let Some(max) = threshold.max.filter(|max| value > *max) {
+        violated.push(ThresholdBound::Max(max));
+    }
+    if let Some(limit) = threshold
+        .max_increase
+        .filter(|limit| change.is_some_and(|change| change > *limit))
+    {
+        violated.push(ThresholdBound::MaxIncrease(limit));
+    }
+    if let Some(limit) = threshold
+        .max_decrease
+        .filter(|limit| change.is_some_and(|change| -change > *limit))
+    {
+        violated.push(ThresholdBound::MaxDecrease(limit));
+    }
+    if let Some(limit) = threshold.min_monthly_decrease.filter(|limit| {
+        month_ago_value.is_some_and(|month_ago_value| {
+            value != 0.0 && -relative_change(month_ago_value, value) < *limit
+        })
+    }) {
+        violated.push(ThresholdBound::MinMonthlyDecrease(limit));
+    }
+
+    violated
+}
+
+/// Checks the latest value of every metric with thresholds, relative bounds against the value of
+/// the sampled commit before it and monthly ones against the value of the last sampled commit at
+/// least a month earlier. Commits without a value, e.g. skipped ones, are passed over.
+pub(crate) fn evaluate(
+    metrics: &HashMap<String, MetricConfig>,
+    commits: &[CommitInfo],
+    branches: &Branches,
+    storage: &DashMap<(CollectorConfig, CommitHash), CollectorValue>,
+) -> Result<Vec<ThresholdViolation>, ThresholdError> {
+    let mut sorted_commits = commits.to_vec();
+    sorted_commits.sort_by(|a, b| a.time.cmp(&b.time));
+
+    let mut metric_names: Vec<&String> = metrics.keys().collect();
+    metric_names.sort();
+
+    let mut violations = vec![];
+    for metric_name in metric_names {
+        let metric = &metrics[metric_name];
+        let Some(thresholds) = &metric.thresholds else {
+            continue;
+        };
+
+        let branch_commits: Vec<CommitInfo> = sorted_commits
+            .iter()
+            .filter(|commit| branches.contains(metric, &commit.id))
+            .cloned()
+            .collect();
+
+        let monthly = [&thresholds.warn, &thresholds.fail]
+            .into_iter()
+            .flatten()
+            .any(|threshold| threshold.min_monthly_decrease.is_some());
+
+        // The latest two values and with monthly bounds the ones going back a month, newest first
+        let mut values: Vec<(CommitHash, DateTime<Utc>, f64)> = vec![];
+        for (commit, _) in sample_commits(
+            &branch_commits,
+            &metric.frequency,
+            metric.align_to_calendar.unwrap_or(false),
+            metric.phase_offset.unwrap_or_default(),
+            true,
+        )
+        .into_iter()
+        .rev()
+        {
+            let Some(value) = storage.get(&(metric.collector.clone(), commit.id.clone())) else {
+                continue;
+            };
+
+            if matches!(value.value(), CollectorValue::Skipped(_)) {
+                continue;
+            }
+
+            let number =
+                expression::number(value.value(), metric_name, thresholds.field.as_deref())
+                    .map_err(|error| ThresholdError::from_expression(metric_name, error))?;
+
+            if let Some(number) = number {
+                values.push((commit.id.clone(), commit.time, number));
+            }
+
+            let reaches_back_a_month = || {
+                let (_, latest_time, _) = &values[0];
+                let (_, time, _) = &values[values.len() - 1];
+                latest_time
+                    .checked_sub_months(Months::new(1))
+                    .is_some_and(|month_ago| *time <= month_ago)
+            };
+            if values.len() >= 2 && (!monthly || reaches_back_a_month()) {
+                break;
+            }
+        }
+
+        let Some((commit, latest_time, value)) = values.first().cloned() else {
+            continue;
+        };
+        let previous_value = values.get(1).map(|(_, _, value)| *value);
+        let month_ago_value = latest_time
+            .checked_sub_months(Months::new(1))
+            .and_then(|month_ago| values.iter().find(|(_, time, _)| *time <= month_ago))
+            .map(|(_, _, value)| *value);
+
+        for (level, threshold) in [
+            (ThresholdLevel::Warn, &thresholds.warn),
+            (ThresholdLevel::Fail, &thresholds.fail),
+        ] {
+            let Some(threshold) = threshold else {
+                continue;
+            };
+
+            for bound in violated_bounds(threshold, value, previous_value, month_ago_value) {
+                violations.push(ThresholdViolation {
+                    metric: metric_name.clone(),
+                    level,
+                    bound,
+                    commit: commit.clone(),
+                    value,
+                    previous_value,
+                    month_ago_value,
+                });
+            }
+        }
+    }
+
+    Ok(violations)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::{BTreeMap, HashSet};
+
+    use chrono::{DateTime, Utc};
+
+    use super::*;
+    use crate::{
+        collectors::{SkipReason, SkippedValue, TotalLocValue},
+        config::{Frequency, Thresholds},
+        git::Author,
+    };
+
+    fn commits(commits: &[(&str, &str)]) -> (Vec<CommitInfo>, Branches) {
+        let author = Author {
+            name: None,
+            email: None,
+        };
+        let commits: Vec<CommitInfo> = commits
+            .iter()
+            .map(|(hash, time)| CommitInfo {
+                id: CommitHash((*hash).to_string()),
+                author: author.clone(),
+                committer: author.clone(),
+                message: None,
+                time: time.parse::<DateTime<Utc>>().unwrap(),
+                offset_minutes: 0,
+                parent_count: 1,
+            })
+            .collect();
+
+        let branches = Branches {
+            default: "main".to_string(),
+            heads: BTreeMap::new(),
+            commits: BTreeMap::from([(
+                "main".to_string(),
+                commits
+                    .iter()
+                    .map(|commit| commit.id.clone())
+                    .collect::<HashSet<_>>(),
+            )]),
+        };
+
+        (commits, branches)
+    }
+
+    #[test]
+    fn test_thresholds_are_checked_against_the_previous_value() {
+        let (commits, branches) = commits(&[
+            ("a", "2024-01-01T00:00:00Z"),
+            ("b", "2024-02-01T00:00:00Z"),
+            ("c", "2024-03-01T00:00:00Z"),
+        ]);
+
+        let collector = CollectorConfig::TotalLoc {
+            scope: Box::default(),
+        };
+        let metric = MetricConfig {
+            collector: collector.clone(),
+            frequency: Frequency::Monthly,
+            branch: None,
+            cache: None,
+            thresholds: Some(Thresholds {
+                field: None,
+                warn: Some(Threshold {
+                    max_increase: Some(10.0),
+                    ..Threshold::default()
+                }),
+                fail: Some(Threshold {
+                    max: Some(200.0),
+                    ..Threshold::default()
+                }),
+            }),
+            campaign: None,
+            align_to_calendar: None,
+            phase_offset: None,
+            max_concurrency: None,
+        };
+        let metrics = HashMap::from([("loc".to_string(), metric)]);
+
+        // The latest commit was skipped, so its predecessor is the latest one with a value
+        let storage = DashMap::new();
+        for (hash, value) in [("a", 100), ("b", 150)] {
+            storage.insert(
+                (collector.clone(), CommitHash(hash.to_string())),
+                TotalLocValue { loc: value }.into(),
+            );
+        }
+        storage.insert(
+            (collector.clone(), CommitHash("c".to_string())),
+            SkippedValue {
+                reason: SkipReason::TooLarge,
+                file_count: 0,
+                total_bytes: 0,
+            }
+            .into(),
+        );
+
+        let violations = evaluate(&metrics, &commits, &branches, &storage).unwrap();
+        assert_eq!(
+            violations,
+            vec![ThresholdViolation {
+                metric: "loc".to_string(),
+                level: ThresholdLevel::Warn,
+                bound: ThresholdBound::MaxIncrease(10.0),
+                commit: CommitHash("b".to_string()),
+                value: 150.0,
+                previous_value: Some(100.0),
+                month_ago_value: Some(100.0),
+            }]
+        );
+        assert_eq!(
+            violations[0].to_string(),
+            "loc increased by 50.0% to 150 at b, more than 10%"
+        );
+    }
+
+    #[test]
+    fn test_monthly_decrease() {
+        let (commits, branches) = commits(&[
+            ("a", "2023-12-01T00:00:00Z"),
+            ("b", "2024-01-10T00:00:00Z"),
+            ("c", "2024-02-10T00:00:00Z"),
+            ("d", "2024-02-15T00:00:00Z"),
+        ]);
+
+        let collector = CollectorConfig::TotalLoc {
+            scope: Box::default(),
+        };
+        let metric = |min_monthly_decrease| MetricConfig {
+            collector: collector.clone(),
+            frequency: Frequency::PerCommit,
+            branch: None,
+            cache: None,
+            thresholds: Some(Thresholds {
+                field: None,
+                warn: None,
+                fail: Some(Threshold {
+                    min_monthly_decrease: Some(min_monthly_decrease),
+                    ..Threshold::default()
+                }),
+            }),
+            campaign: None,
+            align_to_calendar: None,
+            phase_offset: None,
+            max_concurrency: None,
+        };
+
+        let storage = DashMap::new();
+        for (hash, value) in [("a", 400), ("b", 200), ("c", 196), ("d", 190)] {
+            storage.insert(
+                (collector.clone(), CommitHash(hash.to_string())),
+                TotalLocValue { loc: value }.into(),
+            );
+        }
+
+        // Compared against `b`, the last commit at least a month before `d`
+        let metrics = HashMap::from([("loc".to_string(), metric(5.0))]);
+        assert!(evaluate(&metrics, &commits, &branches, &storage)
+            .unwrap()
+            .is_empty());
+
+        let metrics = HashMap::from([("loc".to_string(), metric(10.0))]);
+        let violations = evaluate(&metrics, &commits, &branches, &storage).unwrap();
+        assert_eq!(
+            violations,
+            vec![ThresholdViolation {
+                metric: "loc".to_string(),
+                level: ThresholdLevel::Fail,
+                bound: ThresholdBound::MinMonthlyDecrease(10.0),
+                commit: CommitHash("d".to_string()),
+                value: 190.0,
+                previous_value: Some(196.0),
+                month_ago_value: Some(200.0),
+            }]
+        );
+        assert_eq!(
+            violations[0].to_string(),
+            "loc changed by -5.0% within a month to 190 at d, instead of decreasing by at least 10%"
+        );
+    }
+}
@@ -0,0 +1,22 @@
+//! Changes of the public API have to be deliberate, see "Library API" in the README.
+//!
+//! Requires a nightly toolchain, run with `UPDATE_EXPECT=1` to accept intended changes.
+
+#[test]
+fn public_api() {
+    let rustdoc_json = rustdoc_json::Builder::default()
+        .toolchain("nightly")
+        .manifest_path(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml"))
+        .target_dir(concat!(env!("CARGO_TARGET_TMPDIR"), "/public-api"))
+        .quiet(true)
+        .build()
+        .unwrap();
+
+    // Auto trait and derived impls stay, e.g. losing `Send` breaks embedders as well
+    let public_api = public_api::Builder::from_rustdoc_json(rustdoc_json)
+        .omit_blanket_impls(true)
+        .build()
+        .unwrap();
+
+    expect_test::expect_file!["public-api.txt"].assert_eq(&public_api.to_string());
+}
@@ -0,0 +1,122 @@
+"""Notebook-friendly access to the myaku collection engine."""
+
+import json
+from dataclasses import dataclass
+from pathlib import Path
+from typing import Any, Dict, List, Optional
+
+from . import _myaku
+
+__all__ = ["Collection", "run_collection", "read_cache", "read_output"]
+
+
+@dataclass
+class Collection:
+    commits: Any
+    tags: Optional[Any]
+    metrics: Dict[str, Any]
+    events: Optional[Any] = None
+
+
+def _frame(rows: List[Dict[str, Any]], backend: str):
+    if backend == "pandas":
+        import pandas
+
+        return pandas.DataFrame(rows)
+
+    if backend == "polars":
+        import polars
+
+        return polars.DataFrame(rows)
+
+    raise ValueError(f"Unknown backend: {backend}")
+
+
+def _flatten(row: Dict[str, Any]) -> Dict[str, Any]:
+    value = dict(row["value"])
+    value.pop("collector", None)
+    return {"commit": row["commit"], **value}
+
+
+def run_collection(config: Dict[str, Any], backend: str = "pandas") -> Collection:
+    """Run a collection for the given config and return the results as data frames.
+
+    The config mirrors the CLI config file, with `repository_path` being required.
+    """
+    raw = json.loads(_myaku.run_collection(json.dumps(config, default=str)))
+
+    tags = raw["tags"]
+
+    return Collection(
+        commits=_frame(raw["commits"], backend),
+        tags=_frame(tags, backend) if tags is not None else None,
+        metrics={
+            name: _frame([_flatten(row) for row in rows], backend)
+            for name, rows in raw["metrics"].items()
+        },
+    )
+
+
+def read_cache(
+    cache_path,
+    collector: Dict[str, Any],
+    commit: str,
+    exclude_paths: Optional[List[str]] = None,
+) -> Optional[Dict[str, Any]]:
+    """Read a single cached value, e.g. `read_cache(path, {"collector": "total-loc"}, hash)`.
+
+    Values are cached per set of excluded files, `exclude_paths` defaults to the ones excluded
+    unless configured otherwise.
+    """
+    value = _myaku.read_cache(str(cache_path), json.dumps(collector), commit, exclude_paths)
+    return json.loads(value) if value is not None else None
+
+
+def read_output(path, backend: str = "pandas") -> Collection:
+    """Read a previously written output directory (parquet or json) into data frames."""
+    path = Path(path)
+
+    def read_parquet(file: Path):
+        if backend == "pandas":
+            import pandas
+
+            return pandas.read_parquet(file)
+
+        if backend == "polars":
+            import polars
+
+            return polars.read_parquet(file)
+
+        raise ValueError(f"Unknown backend: {backend}")
+
+    def read_table(name: str):
+        if (path / f"{name}.parquet").exists():
+            return read_parquet(path / f"{name}.parquet")
+
+        if (path / f"{name}.json").exists():
+            return _frame(json.loads((path / f"{name}.json").read_text()), backend)
+
+        return None
+
+    metrics = {}
+    metrics_dir = path / "metrics"
+    if metrics_dir.exists():
+        for metric_dir in sorted(p for p in metrics_dir.iterdir() if p.is_dir()):
+            data = metric_dir / "data.parquet"
+
+            if data.exists():
+                metrics[metric_dir.name] = read_parquet(data)
+                continue
+
+            rows = [
+                _flatten({"commit": file.stem, "value": json.loads(file.read_text())})
+                for file in sorted(metric_dir.glob("*.json"))
+            ]
+            metrics[metric_dir.name] = _frame(rows, backend)
+
+    return Collection(
+        commits=read_table("commits"),
+        tags=read_table("commit_tags"),
+        metrics=metrics,
+        events=read_table("events"),
+    )
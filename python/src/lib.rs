@@ -0,0 +1,68 @@
+use std::path::Path;
+
+use myaku::{
+    exclude_paths_id, Cache, CollectorConfig, CommitHash, FileCache, Glob, RunConfig,
+    DEFAULT_EXCLUDE_PATHS,
+};
+use pyo3::{exceptions::PyRuntimeError, exceptions::PyValueError, prelude::*};
+
+/// Runs a full collection for the given JSON encoded config and returns the results as JSON.
+#[pyfunction]
+fn run_collection(py: Python<'_>, config: &str) -> PyResult<String> {
+    let config: RunConfig =
+        serde_json::from_str(config).map_err(|e| PyValueError::new_err(e.to_string()))?;
+
+    py.allow_threads(|| {
+        let process = config
+            .run(None)
+            .map_err(|e| PyRuntimeError::new_err(e.report()))?;
+
+        process
+            .results_json()
+            .map_err(|e| PyRuntimeError::new_err(e.to_string()))
+    })
+}
+
+/// Looks up a single value in a file cache, returning it as JSON if present. Values are looked up
+/// among the ones collected with the given excluded files, `DEFAULT_EXCLUDE_PATHS` if unset.
+#[pyfunction]
+#[pyo3(signature = (cache_path, collector, commit, exclude_paths=None))]
+fn read_cache(
+    cache_path: &str,
+    collector: &str,
+    commit: &str,
+    exclude_paths: Option<Vec<String>>,
+) -> PyResult<Option<String>> {
+    let collector: CollectorConfig =
+        serde_json::from_str(collector).map_err(|e| PyValueError::new_err(e.to_string()))?;
+
+    let exclude_paths = match exclude_paths {
+        Some(exclude_paths) => exclude_paths,
+        None => DEFAULT_EXCLUDE_PATHS.map(ToString::to_string).to_vec(),
+    }
+    .iter()
+    .map(|glob| Glob::new(glob))
+    .collect::<Result<Vec<Glob>, _>>()
+    .map_err(|e| PyValueError::new_err(e.to_string()))?;
+
+    let cache = FileCache::new(Path::new(cache_path));
+    let value = cache
+        .lookup(
+            &collector,
+            exclude_paths_id(&exclude_paths).as_deref(),
+            &CommitHash(commit.to_string()),
+        )
+        .map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+
+    value
+        .map(|v| serde_json::to_string(&v))
+        .transpose()
+        .map_err(|e| PyRuntimeError::new_err(e.to_string()))
+}
+
+#[pymodule]
+fn _myaku(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(run_collection, m)?)?;
+    m.add_function(wrap_pyfunction!(read_cache, m)?)?;
+    Ok(())
+}